@@ -0,0 +1,564 @@
+//! Assembler for the textual disassembly of AVM1 buffers.
+//!
+//! The input uses the format produced by `disasm`: one action per line, optionally prefixed by its offset, and
+//! `label:` lines. Offsets and comments are ignored. Jump offsets and the sizes of function bodies, `try` blocks and
+//! `with` bodies are computed from the labels, so assembling a disassembly produces the original buffer.
+
+use crate::avm1::raw::{
+  self, Action, CatchTarget, DefineFunction, DefineFunction2, GetUrl2, PushValue, Try, DEFINE_FUNCTION2_FLAGS,
+};
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug)]
+pub(crate) struct AssembleError {
+  /// Line of the input where the error occurred (starting at 1)
+  pub line: usize,
+  pub message: String,
+}
+
+impl fmt::Display for AssembleError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "line {}: {}", self.line, self.message)
+  }
+}
+
+/// Jump target or end of a nested code region.
+pub(crate) enum Target {
+  Label(String),
+  /// Raw operand value, relative to the end of the action (or of the previous `try` block)
+  Raw(i64),
+}
+
+/// Action whose jump offset or nested sizes are resolved from its targets when linking.
+pub(crate) struct Instruction {
+  /// Line of the source, used in error messages
+  pub line: usize,
+  /// Action to emit, its jump offset and nested sizes are ignored
+  pub action: Action,
+  /// Targets of the action, see `disasm::action_targets`
+  pub targets: Vec<Target>,
+}
+
+pub(crate) enum Item {
+  Label(String),
+  Instruction(Instruction),
+}
+
+/// Assembles the textual disassembly `text` into an AVM1 buffer.
+pub(crate) fn assemble(text: &str) -> Result<Vec<u8>, AssembleError> {
+  let mut items: Vec<Item> = Vec::new();
+  let mut labels: HashSet<String> = HashSet::new();
+  for (index, content) in text.lines().enumerate() {
+    let line = index + 1;
+    let error = |message: String| AssembleError { line, message };
+    let mut tokens = tokenize(content).map_err(error)?;
+    if let Some(Token::Word(word)) = tokens.first() {
+      if let Some(offset) = word.strip_suffix(':') {
+        if !offset.is_empty() && offset.bytes().all(|c| c.is_ascii_digit()) {
+          tokens.remove(0);
+        } else if tokens.len() == 1 {
+          if !labels.insert(offset.to_string()) {
+            return Err(error(format!("duplicate label: {}", offset)));
+          }
+          items.push(Item::Label(offset.to_string()));
+          continue;
+        }
+      }
+    }
+    if tokens.is_empty() {
+      continue;
+    }
+    let (action, targets) = parse_instruction(tokens).map_err(error)?;
+    items.push(Item::Instruction(Instruction { line, action, targets }));
+  }
+  link(items)
+}
+
+/// Emits the instructions, resolving their targets. The labels must be unique.
+pub(crate) fn link(items: Vec<Item>) -> Result<Vec<u8>, AssembleError> {
+  // The size of an action does not depend on its targets, so the layout can be computed before resolving them.
+  let mut instructions: Vec<Instruction> = Vec::new();
+  let mut labels: HashMap<String, i64> = HashMap::new();
+  let mut offset: usize = 0;
+  for item in items {
+    match item {
+      Item::Label(name) => {
+        labels.insert(name, offset as i64);
+      }
+      Item::Instruction(instruction) => {
+        let mut bytes = Vec::new();
+        raw::write_action(&mut bytes, &instruction.action);
+        offset += bytes.len();
+        instructions.push(instruction);
+      }
+    }
+  }
+
+  let mut out: Vec<u8> = Vec::with_capacity(offset);
+  for mut instruction in instructions {
+    let mut bytes = Vec::new();
+    raw::write_action(&mut bytes, &instruction.action);
+    let end = (out.len() + bytes.len()) as i64;
+    resolve_targets(&mut instruction, end, &labels).map_err(|message| AssembleError {
+      line: instruction.line,
+      message,
+    })?;
+    raw::write_action(&mut out, &instruction.action);
+  }
+  Ok(out)
+}
+
+/// Resolves the targets of the instruction and updates its relative offsets and sizes.
+fn resolve_targets(instruction: &mut Instruction, end: i64, labels: &HashMap<String, i64>) -> Result<(), String> {
+  let resolve = |target: &Target, base: i64| -> Result<i64, String> {
+    match target {
+      Target::Label(label) => match labels.get(label) {
+        Some(offset) => Ok(offset - base),
+        None => Err(format!("undefined label: {}", label)),
+      },
+      Target::Raw(value) => Ok(*value),
+    }
+  };
+  let targets = &instruction.targets;
+  match &mut instruction.action {
+    Action::Jump(offset) | Action::If(offset) => *offset = to_int(resolve(&targets[0], end)?, "jump offset")?,
+    Action::DefineFunction(action) => action.size = to_int(resolve(&targets[0], end)?, "function size")?,
+    Action::DefineFunction2(action) => action.size = to_int(resolve(&targets[0], end)?, "function size")?,
+    Action::With { size } => *size = to_int(resolve(&targets[0], end)?, "with size")?,
+    Action::Try(action) => {
+      let try_size = resolve(&targets[0], end)?;
+      let catch_size = resolve(&targets[1], end + try_size)?;
+      let finally_size = resolve(&targets[2], end + try_size + catch_size)?;
+      action.try_size = to_int(try_size, "try size")?;
+      action.catch_size = to_int(catch_size, "catch size")?;
+      action.finally_size = to_int(finally_size, "finally size")?;
+    }
+    _ => {}
+  }
+  Ok(())
+}
+
+fn to_int<T: TryFrom<i64>>(value: i64, name: &str) -> Result<T, String> {
+  T::try_from(value).map_err(|_| format!("{} out of range: {}", name, value))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+  String(Vec<u8>),
+  Word(String),
+  Punct(char),
+}
+
+/// Splits a line into tokens, stopping at the first comment.
+fn tokenize(line: &str) -> Result<Vec<Token>, String> {
+  let mut tokens = Vec::new();
+  let mut chars = line.chars().peekable();
+  while let Some(c) = chars.next() {
+    match c {
+      ';' => break,
+      c if c.is_whitespace() => {}
+      ',' | '(' | ')' | '=' => tokens.push(Token::Punct(c)),
+      '"' => {
+        let mut value: Vec<u8> = Vec::new();
+        loop {
+          match chars.next() {
+            None => return Err(String::from("unterminated string")),
+            Some('"') => break,
+            Some('\\') => match chars.next() {
+              Some('"') => value.push(b'"'),
+              Some('\\') => value.push(b'\\'),
+              Some('n') => value.push(b'\n'),
+              Some('r') => value.push(b'\r'),
+              Some('t') => value.push(b'\t'),
+              Some('x') => {
+                let digits: String = chars.by_ref().take(2).collect();
+                let byte = u8::from_str_radix(&digits, 16).map_err(|_| format!("invalid escape: \\x{}", digits))?;
+                value.push(byte);
+              }
+              Some(c) => return Err(format!("invalid escape: \\{}", c)),
+              None => return Err(String::from("unterminated string")),
+            },
+            Some(c) => value.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+          }
+        }
+        tokens.push(Token::String(value));
+      }
+      c => {
+        let mut word = String::new();
+        word.push(c);
+        while let Some(&c) = chars.peek() {
+          if c.is_whitespace() || [';', ',', '(', ')', '=', '"'].contains(&c) {
+            break;
+          }
+          word.push(c);
+          chars.next();
+        }
+        tokens.push(Token::Word(word));
+      }
+    }
+  }
+  Ok(tokens)
+}
+
+/// Operands of an instruction.
+struct Operands {
+  tokens: std::vec::IntoIter<Token>,
+}
+
+impl Operands {
+  fn peek(&self) -> Option<&Token> {
+    self.tokens.as_slice().first()
+  }
+
+  fn next(&mut self) -> Result<Token, String> {
+    self.tokens.next().ok_or_else(|| String::from("missing operand"))
+  }
+
+  fn string(&mut self) -> Result<Vec<u8>, String> {
+    match self.next()? {
+      Token::String(value) => Ok(value),
+      token => Err(format!("expected string, found {}", format_token(&token))),
+    }
+  }
+
+  fn word(&mut self) -> Result<String, String> {
+    match self.next()? {
+      Token::Word(value) => Ok(value),
+      token => Err(format!("expected operand, found {}", format_token(&token))),
+    }
+  }
+
+  fn punct(&mut self, expected: char) -> Result<(), String> {
+    match self.next()? {
+      Token::Punct(c) if c == expected => Ok(()),
+      token => Err(format!("expected `{}`, found {}", expected, format_token(&token))),
+    }
+  }
+
+  /// Consumes the punctuation if it is the next token.
+  fn eat(&mut self, expected: char) -> bool {
+    if self.peek() == Some(&Token::Punct(expected)) {
+      self.tokens.next();
+      true
+    } else {
+      false
+    }
+  }
+
+  /// Reads a comma-separated list, until the end of the operands or a closing parenthesis.
+  fn list<T>(&mut self, mut item: impl FnMut(&mut Self) -> Result<T, String>) -> Result<Vec<T>, String> {
+    let mut items = Vec::new();
+    while self.peek().is_some() && self.peek() != Some(&Token::Punct(')')) {
+      items.push(item(self)?);
+      if !self.eat(',') {
+        break;
+      }
+    }
+    Ok(items)
+  }
+
+  /// Reads the remaining `key=value` operands.
+  fn options(&mut self) -> Result<Options, String> {
+    let mut options = Vec::new();
+    while self.peek().is_some() {
+      let key = self.word()?;
+      self.punct('=')?;
+      options.push((key, self.next()?));
+    }
+    Ok(Options(options))
+  }
+}
+
+struct Options(Vec<(String, Token)>);
+
+impl Options {
+  fn take(&mut self, key: &str) -> Option<Token> {
+    let index = self.0.iter().position(|(k, _)| k == key)?;
+    Some(self.0.remove(index).1)
+  }
+
+  fn take_word(&mut self, key: &str) -> Result<Option<String>, String> {
+    match self.take(key) {
+      Some(Token::Word(value)) => Ok(Some(value)),
+      Some(token) => Err(format!("invalid value for `{}`: {}", key, format_token(&token))),
+      None => Ok(None),
+    }
+  }
+
+  fn require_word(&mut self, key: &str) -> Result<String, String> {
+    self.take_word(key)?.ok_or_else(|| format!("missing `{}`", key))
+  }
+
+  fn finish(self) -> Result<(), String> {
+    match self.0.first() {
+      Some((key, _)) => Err(format!("unexpected option: {}", key)),
+      None => Ok(()),
+    }
+  }
+}
+
+fn format_token(token: &Token) -> String {
+  match token {
+    Token::String(value) => crate::avm1::disasm::format_string(value),
+    Token::Word(value) => value.clone(),
+    Token::Punct(c) => format!("`{}`", c),
+  }
+}
+
+fn parse_operand<T: FromStr>(value: &str, name: &str) -> Result<T, String> {
+  value.parse().map_err(|_| format!("invalid {}: {}", name, value))
+}
+
+fn parse_register(value: &str) -> Result<u8, String> {
+  match value.strip_prefix("r:") {
+    Some(register) => parse_operand(register, "register"),
+    None => Err(format!("expected register, found {}", value)),
+  }
+}
+
+fn parse_target(value: &str) -> Result<Target, String> {
+  match value.strip_prefix('#') {
+    Some(raw) => Ok(Target::Raw(parse_operand(raw, "offset")?)),
+    None => Ok(Target::Label(value.to_string())),
+  }
+}
+
+/// Parses a `|`-separated list of flags into a bit set, using the index of each name as its bit.
+fn parse_flags(value: Option<String>, names: &[&str]) -> Result<u16, String> {
+  let mut flags: u16 = 0;
+  for flag in value.iter().flat_map(|value| value.split('|')) {
+    match names.iter().position(|name| *name == flag) {
+      Some(bit) => flags |= 1 << bit,
+      None => return Err(format!("unknown flag: {}", flag)),
+    }
+  }
+  Ok(flags)
+}
+
+fn parse_hex(value: &str) -> Result<Vec<u8>, String> {
+  match value.strip_prefix("hex:") {
+    Some(data) => hex::decode(data).map_err(|_| format!("invalid hex data: {}", data)),
+    None => Err(format!("expected hex data, found {}", value)),
+  }
+}
+
+fn parse_instruction(tokens: Vec<Token>) -> Result<(Action, Vec<Target>), String> {
+  let mut operands = Operands {
+    tokens: tokens.into_iter(),
+  };
+  let ops = &mut operands;
+  let mnemonic = ops.word()?;
+  let mut targets: Vec<Target> = Vec::new();
+  let action = match mnemonic.as_str() {
+    ".action" => {
+      let code = ops.word()?;
+      let code = match code.strip_prefix("0x") {
+        Some(hex) => u8::from_str_radix(hex, 16).map_err(|_| format!("invalid action code: {}", code))?,
+        None => return Err(format!("invalid action code: {}", code)),
+      };
+      let data = match ops.peek() {
+        Some(_) => Some(parse_hex(&ops.word()?)?),
+        None => None,
+      };
+      match (code < 0x80, data) {
+        (true, None) => Action::Unknown { code, data: None },
+        (false, Some(data)) => Action::Unknown { code, data: Some(data) },
+        (true, Some(_)) => return Err(String::from("actions with a code below 0x80 have no data")),
+        (false, None) => return Err(String::from("missing action data")),
+      }
+    }
+    ".bytes" => Action::Truncated(parse_hex(&ops.word()?)?),
+    mnemonic => {
+      let code = raw::code(mnemonic).ok_or_else(|| format!("unknown mnemonic: {}", mnemonic))?;
+      match code {
+        code if code < 0x80 => Action::Simple(code),
+        0x81 => Action::GotoFrame(parse_operand(&ops.word()?, "frame")?),
+        0x83 => {
+          let url = ops.string()?;
+          ops.punct(',')?;
+          let target = ops.string()?;
+          Action::GetUrl { url, target }
+        }
+        0x87 => Action::StoreRegister(parse_register(&ops.word()?)?),
+        0x88 => Action::ConstantPool(ops.list(|ops| ops.string())?),
+        0x89 => Action::StrictMode(parse_operand(&ops.word()?, "boolean")?),
+        0x8a => {
+          let frame = parse_operand(&ops.word()?, "frame")?;
+          let mut options = ops.options()?;
+          let skip_count = parse_operand(&options.require_word("skip")?, "skip count")?;
+          options.finish()?;
+          Action::WaitForFrame { frame, skip_count }
+        }
+        0x8b => Action::SetTarget(ops.string()?),
+        0x8c => Action::GotoLabel(ops.string()?),
+        0x8d => {
+          let mut options = ops.options()?;
+          let skip_count = parse_operand(&options.require_word("skip")?, "skip count")?;
+          options.finish()?;
+          Action::WaitForFrame2 { skip_count }
+        }
+        0x8e => {
+          let name = ops.string()?;
+          ops.punct('(')?;
+          let parameters = ops.list(|ops| match ops.next()? {
+            Token::String(name) => Ok((0, name)),
+            Token::Word(register) => {
+              let register = parse_register(&register)?;
+              ops.punct('=')?;
+              Ok((register, ops.string()?))
+            }
+            token => Err(format!("expected parameter, found {}", format_token(&token))),
+          })?;
+          ops.punct(')')?;
+          let mut options = ops.options()?;
+          let register_count = parse_operand(&options.require_word("registers")?, "register count")?;
+          let flags = parse_flags(options.take_word("flags")?, &DEFINE_FUNCTION2_FLAGS)?;
+          targets.push(parse_target(&options.require_word("end")?)?);
+          options.finish()?;
+          Action::DefineFunction2(DefineFunction2 {
+            name,
+            register_count,
+            flags,
+            parameters,
+            size: 0,
+          })
+        }
+        0x8f => {
+          let mut options = ops.options()?;
+          let catch_target = match options.take("target") {
+            Some(Token::String(name)) => CatchTarget::Variable(name),
+            Some(Token::Word(register)) => CatchTarget::Register(parse_register(&register)?),
+            _ => return Err(String::from("missing `target`")),
+          };
+          let flags = parse_flags(options.take_word("flags")?, &["catch", "finally"])?;
+          for key in ["try_end", "catch_end", "finally_end"].iter() {
+            targets.push(parse_target(&options.require_word(key)?)?);
+          }
+          options.finish()?;
+          Action::Try(Try {
+            catch_target,
+            has_catch: flags & 0x01 != 0,
+            has_finally: flags & 0x02 != 0,
+            try_size: 0,
+            catch_size: 0,
+            finally_size: 0,
+          })
+        }
+        0x94 => {
+          let mut options = ops.options()?;
+          targets.push(parse_target(&options.require_word("end")?)?);
+          options.finish()?;
+          Action::With { size: 0 }
+        }
+        0x96 => Action::Push(ops.list(|ops| parse_push_value(ops.next()?))?),
+        0x99 | 0x9d => {
+          targets.push(parse_target(&ops.word()?)?);
+          if code == 0x99 {
+            Action::Jump(0)
+          } else {
+            Action::If(0)
+          }
+        }
+        0x9a => {
+          let mut options = ops.options()?;
+          let method = match options.require_word("method")?.as_str() {
+            "none" => 0,
+            "get" => 1,
+            "post" => 2,
+            method => return Err(format!("unknown method: {}", method)),
+          };
+          let flags = parse_flags(options.take_word("flags")?, &["load_target", "load_variables"])?;
+          options.finish()?;
+          Action::GetUrl2(GetUrl2 {
+            method,
+            load_target: flags & 0x01 != 0,
+            load_variables: flags & 0x02 != 0,
+          })
+        }
+        0x9b => {
+          let name = ops.string()?;
+          ops.punct('(')?;
+          let parameters = ops.list(|ops| ops.string())?;
+          ops.punct(')')?;
+          let mut options = ops.options()?;
+          targets.push(parse_target(&options.require_word("end")?)?);
+          options.finish()?;
+          Action::DefineFunction(DefineFunction {
+            name,
+            parameters,
+            size: 0,
+          })
+        }
+        0x9e => Action::Call,
+        0x9f => {
+          let mut options = ops.options()?;
+          let play = parse_flags(options.take_word("flags")?, &["play"])? != 0;
+          let scene_bias = match options.take_word("scene_bias")? {
+            Some(scene_bias) => Some(parse_operand(&scene_bias, "scene bias")?),
+            None => None,
+          };
+          options.finish()?;
+          Action::GotoFrame2 { play, scene_bias }
+        }
+        code => return Err(format!("unsupported action: 0x{:02x}", code)),
+      }
+    }
+  };
+  if let Some(token) = ops.peek() {
+    return Err(format!("unexpected operand: {}", format_token(token)));
+  }
+  Ok((action, targets))
+}
+
+fn parse_push_value(token: Token) -> Result<PushValue, String> {
+  let word = match token {
+    Token::String(value) => return Ok(PushValue::String(value)),
+    Token::Word(word) => word,
+    token => return Err(format!("expected value, found {}", format_token(&token))),
+  };
+  let value = match word.as_str() {
+    "null" => PushValue::Null,
+    "undefined" => PushValue::Undefined,
+    "true" => PushValue::Boolean(true),
+    "false" => PushValue::Boolean(false),
+    "NaN" => PushValue::Float64(f64::NAN),
+    "Infinity" => PushValue::Float64(f64::INFINITY),
+    "-Infinity" => PushValue::Float64(f64::NEG_INFINITY),
+    word => {
+      if let Some(register) = word.strip_prefix("r:") {
+        PushValue::Register(parse_operand(register, "register")?)
+      } else if let Some(index) = word.strip_prefix("c16:") {
+        PushValue::Constant16(parse_operand(index, "constant index")?)
+      } else if let Some(index) = word.strip_prefix("c:") {
+        match parse_operand::<u16>(index, "constant index")? {
+          index if index <= u16::from(u8::MAX) => PushValue::Constant8(index as u8),
+          index => PushValue::Constant16(index),
+        }
+      } else if let Some(value) = word.strip_prefix("f32:") {
+        PushValue::Float32(match value {
+          "NaN" => f32::NAN,
+          "Infinity" => f32::INFINITY,
+          "-Infinity" => f32::NEG_INFINITY,
+          value => match value.strip_prefix("0x") {
+            Some(bits) => {
+              f32::from_bits(u32::from_str_radix(bits, 16).map_err(|_| format!("invalid float: {}", word))?)
+            }
+            None => parse_operand(value, "float")?,
+          },
+        })
+      } else if let Some(bits) = word.strip_prefix("f64:0x") {
+        PushValue::Float64(f64::from_bits(
+          u64::from_str_radix(bits, 16).map_err(|_| format!("invalid float: {}", word))?,
+        ))
+      } else if word.contains(&['.', 'e', 'E'][..]) {
+        PushValue::Float64(parse_operand(word, "float")?)
+      } else {
+        PushValue::Sint32(parse_operand(word, "integer")?)
+      }
+    }
+  };
+  Ok(value)
+}
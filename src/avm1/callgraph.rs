@@ -0,0 +1,420 @@
+//! Call graph of the AVM1 buffers of a movie.
+//!
+//! Functions are indexed by the `DefineFunction` and `DefineFunction2` actions of all the buffers. Anonymous functions
+//! are named after the variables and members they are assigned to (`_global.Foo.prototype.bar = function () {}`).
+//!
+//! Call sites are found by a symbolic evaluation of the stack of each block: `CallFunction` and `CallMethod` are
+//! recorded when the name of the callee is a constant string (and the object of a method is a variable path). Callees
+//! are resolved to the functions with the same name, or else to the functions with the same last name segment
+//! (`this.bar()` calls `Foo.prototype.bar`).
+
+use crate::avm1::dot::escape;
+use crate::avm1::{block_actions, block_children, block_label, block_offsets, stack_effect};
+use crate::dump::Avm1Location;
+use avm1_types::cfg::{Cfg, CfgAction, CfgLabel};
+use avm1_types::Value;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+#[derive(Debug, Serialize)]
+pub(crate) struct CallGraph {
+  pub buffers: Vec<BufferInfo>,
+  pub functions: Vec<FunctionInfo>,
+  pub calls: Vec<CallSite>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct BufferInfo {
+  pub location: String,
+  /// Events triggering the buffer, for clip actions
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  pub events: Vec<&'static str>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct FunctionInfo {
+  /// Unique id: `<location>@<label of the entry block of the body>`
+  pub id: String,
+  /// Name of the definition, then the variables and members the function is assigned to
+  pub names: Vec<String>,
+  pub parameters: Vec<String>,
+  /// Number of registers, only for `DefineFunction2`
+  pub register_count: Option<u8>,
+  pub location: String,
+  /// Label of the block containing the definition
+  pub label: String,
+  pub offset: Option<usize>,
+  /// Id of the enclosing function
+  pub parent: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct CallSite {
+  /// Id of the calling function, or the location of the buffer for top-level code
+  pub caller: String,
+  pub location: String,
+  /// Label of the block containing the call
+  pub label: String,
+  pub offset: Option<usize>,
+  /// `function` (`CallFunction`) or `method` (`CallMethod`)
+  pub kind: &'static str,
+  pub callee: String,
+  /// Ids of the functions the callee resolves to
+  pub targets: Vec<String>,
+}
+
+/// Builds the call graph of the buffers.
+pub(crate) fn call_graph(buffers: &[(Avm1Location, &[u8], &Cfg)]) -> CallGraph {
+  let mut graph = CallGraph {
+    buffers: Vec::new(),
+    functions: Vec::new(),
+    calls: Vec::new(),
+  };
+  for (location, bytes, cfg) in buffers {
+    graph.buffers.push(BufferInfo {
+      location: location.to_string(),
+      events: match location {
+        Avm1Location::RootClipAction { events, .. } | Avm1Location::SpriteClipAction { events, .. } => events.names(),
+        _ => Vec::new(),
+      },
+    });
+    let mut indexer = Indexer {
+      graph: &mut graph,
+      location: location.to_string(),
+      caller: location.to_string(),
+      constant_pool: Vec::new(),
+      offsets: block_offsets(bytes, cfg),
+    };
+    indexer.cfg(cfg, &mut HashMap::new());
+  }
+  resolve_calls(&mut graph);
+  graph
+}
+
+/// Symbolic stack value.
+#[derive(Clone, Debug)]
+enum Sym {
+  String(String),
+  /// Variable or member path, such as `_root.menu`
+  Path(String),
+  /// Function, by index in `CallGraph::functions`
+  Function(usize),
+  Undefined,
+  Unknown,
+}
+
+struct Indexer<'a> {
+  graph: &'a mut CallGraph,
+  location: String,
+  /// Id of the function being indexed, or the location for top-level code
+  caller: String,
+  /// Constant pool defined by the last `ConstantPool` action
+  constant_pool: Vec<String>,
+  /// Offsets of the blocks in the buffer
+  offsets: HashMap<CfgLabel, usize>,
+}
+
+impl<'a> Indexer<'a> {
+  /// Indexes a CFG, `registers` are the values of the registers of the enclosing function.
+  fn cfg(&mut self, cfg: &Cfg, registers: &mut HashMap<u8, Sym>) {
+    for block in cfg.blocks.iter() {
+      let label = block_label(block);
+      let mut stack: Vec<Sym> = Vec::new();
+      for action in block_actions(block) {
+        self.action(action, label, &mut stack, registers);
+      }
+      for child in block_children(block) {
+        self.cfg(child, registers);
+      }
+    }
+  }
+
+  fn action(&mut self, action: &CfgAction, label: &CfgLabel, stack: &mut Vec<Sym>, registers: &mut HashMap<u8, Sym>) {
+    match action {
+      CfgAction::ConstantPool(action) => self.constant_pool = action.constant_pool.clone(),
+      CfgAction::Push(action) => {
+        for value in action.values.iter() {
+          let value = match value {
+            Value::String(value) => Sym::String(value.clone()),
+            Value::Constant(index) => match self.constant_pool.get(usize::from(*index)) {
+              Some(value) => Sym::String(value.clone()),
+              None => Sym::Unknown,
+            },
+            Value::Register(register) => registers.get(register).cloned().unwrap_or(Sym::Unknown),
+            Value::Undefined => Sym::Undefined,
+            _ => Sym::Unknown,
+          };
+          stack.push(value);
+        }
+      }
+      CfgAction::DefineFunction(action) => {
+        let mut registers = HashMap::new();
+        let index = self.function(
+          &action.name,
+          action.parameters.clone(),
+          None,
+          label,
+          &action.body,
+          &mut registers,
+        );
+        if action.name.is_empty() {
+          stack.push(Sym::Function(index));
+        }
+      }
+      CfgAction::DefineFunction2(action) => {
+        let mut registers = HashMap::new();
+        let preloaded = [
+          (action.preload_this, "this"),
+          (action.preload_arguments, "arguments"),
+          (action.preload_super, "super"),
+          (action.preload_root, "_root"),
+          (action.preload_parent, "_parent"),
+          (action.preload_global, "_global"),
+        ];
+        let mut next_register: u8 = 1;
+        for (preload, name) in preloaded.iter() {
+          if *preload {
+            registers.insert(next_register, Sym::Path(name.to_string()));
+            next_register = next_register.saturating_add(1);
+          }
+        }
+        for parameter in action.parameters.iter() {
+          if parameter.register != 0 {
+            registers.insert(parameter.register, Sym::Path(parameter.name.clone()));
+          }
+        }
+        let parameters = action.parameters.iter().map(|p| p.name.clone()).collect();
+        let index = self.function(
+          &action.name,
+          parameters,
+          Some(action.register_count),
+          label,
+          &action.body,
+          &mut registers,
+        );
+        if action.name.is_empty() {
+          stack.push(Sym::Function(index));
+        }
+      }
+      CfgAction::GetVariable => {
+        let value = match pop(stack) {
+          Sym::String(name) => Sym::Path(name),
+          _ => Sym::Unknown,
+        };
+        stack.push(value);
+      }
+      CfgAction::GetMember => {
+        let key = pop(stack);
+        let object = pop(stack);
+        stack.push(member(&object, &key).map_or(Sym::Unknown, Sym::Path));
+      }
+      CfgAction::SetVariable | CfgAction::DefineLocal => {
+        let value = pop(stack);
+        let name = pop(stack);
+        if let (Sym::Function(index), Sym::String(name)) = (value, name) {
+          self.graph.functions[index].names.push(name);
+        }
+      }
+      CfgAction::SetMember => {
+        let value = pop(stack);
+        let key = pop(stack);
+        let object = pop(stack);
+        if let (Sym::Function(index), Some(name)) = (value, member(&object, &key)) {
+          self.graph.functions[index].names.push(name);
+        }
+      }
+      CfgAction::StoreRegister(action) => {
+        let value = stack.last().cloned().unwrap_or(Sym::Unknown);
+        registers.insert(action.register, value);
+      }
+      CfgAction::PushDuplicate => {
+        let value = pop(stack);
+        stack.push(value.clone());
+        stack.push(value);
+      }
+      CfgAction::StackSwap => {
+        let top = pop(stack);
+        let second = pop(stack);
+        stack.push(top);
+        stack.push(second);
+      }
+      CfgAction::CallFunction => {
+        let name = pop(stack);
+        if let Sym::String(name) = name {
+          self.call(label, "function", name);
+        }
+        // The arguments are unknown: the stack is reset
+        stack.clear();
+        stack.push(Sym::Unknown);
+      }
+      CfgAction::CallMethod => {
+        let name = pop(stack);
+        let object = pop(stack);
+        let callee = match (&object, &name) {
+          (Sym::Path(object), Sym::Undefined) => Some(object.clone()),
+          (Sym::Path(object), Sym::String(name)) if name.is_empty() => Some(object.clone()),
+          _ => member(&object, &name),
+        };
+        if let Some(callee) = callee {
+          self.call(label, "method", callee);
+        }
+        stack.clear();
+        stack.push(Sym::Unknown);
+      }
+      action => match stack_effect(action) {
+        Some((pops, pushes)) => {
+          for _ in 0..pops {
+            pop(stack);
+          }
+          stack.resize(stack.len() + pushes, Sym::Unknown);
+        }
+        None => stack.clear(),
+      },
+    }
+  }
+
+  /// Indexes a function and its body, returns its index.
+  fn function(
+    &mut self,
+    name: &str,
+    parameters: Vec<String>,
+    register_count: Option<u8>,
+    label: &CfgLabel,
+    body: &Cfg,
+    registers: &mut HashMap<u8, Sym>,
+  ) -> usize {
+    let id = format!("{}@{}", self.location, block_label(body.blocks.first()).0);
+    let index = self.graph.functions.len();
+    self.graph.functions.push(FunctionInfo {
+      id: id.clone(),
+      names: if name.is_empty() {
+        Vec::new()
+      } else {
+        vec![name.to_string()]
+      },
+      parameters,
+      register_count,
+      location: self.location.clone(),
+      label: label.0.clone(),
+      offset: self.offsets.get(label).copied(),
+      parent: if self.caller == self.location {
+        None
+      } else {
+        Some(self.caller.clone())
+      },
+    });
+    let constant_pool = self.constant_pool.clone();
+    let caller = std::mem::replace(&mut self.caller, id);
+    self.cfg(body, registers);
+    self.caller = caller;
+    self.constant_pool = constant_pool;
+    index
+  }
+
+  fn call(&mut self, label: &CfgLabel, kind: &'static str, callee: String) {
+    self.graph.calls.push(CallSite {
+      caller: self.caller.clone(),
+      location: self.location.clone(),
+      label: label.0.clone(),
+      offset: self.offsets.get(label).copied(),
+      kind,
+      callee,
+      targets: Vec::new(),
+    });
+  }
+}
+
+fn pop(stack: &mut Vec<Sym>) -> Sym {
+  stack.pop().unwrap_or(Sym::Unknown)
+}
+
+/// Returns the path of a member of a path.
+fn member(object: &Sym, key: &Sym) -> Option<String> {
+  match (object, key) {
+    (Sym::Path(object), Sym::String(key)) => Some(format!("{}.{}", object, key)),
+    _ => None,
+  }
+}
+
+/// Returns the last segment of a name (`bar` for `Foo.prototype.bar`).
+fn last_segment(name: &str) -> &str {
+  name.rsplit(&['.', '/', ':'][..]).next().unwrap_or(name)
+}
+
+fn resolve_calls(graph: &mut CallGraph) {
+  let mut by_name: HashMap<&str, Vec<&str>> = HashMap::new();
+  let mut by_segment: HashMap<&str, Vec<&str>> = HashMap::new();
+  for function in graph.functions.iter() {
+    for name in function.names.iter() {
+      by_name.entry(name).or_default().push(&function.id);
+      by_segment.entry(last_segment(name)).or_default().push(&function.id);
+    }
+  }
+  for call in graph.calls.iter_mut() {
+    let targets = by_name
+      .get(call.callee.as_str())
+      .or_else(|| by_segment.get(last_segment(&call.callee)));
+    if let Some(targets) = targets {
+      call.targets = targets.iter().map(|id| id.to_string()).collect();
+      call.targets.dedup();
+    }
+  }
+}
+
+/// Returns the DOT graph of the resolved calls. Buffers are drawn as boxes, grouped with the functions they define.
+pub(crate) fn call_graph_to_dot(graph: &CallGraph) -> String {
+  let mut out = String::new();
+  writeln!(out, "digraph calls {{").unwrap();
+  writeln!(out, "  node [fontname=\"monospace\"];").unwrap();
+  writeln!(out, "  edge [fontname=\"monospace\"];").unwrap();
+
+  for (i, buffer) in graph.buffers.iter().enumerate() {
+    writeln!(out, "  subgraph \"cluster_{}\" {{", i).unwrap();
+    let label = if buffer.events.is_empty() {
+      buffer.location.clone()
+    } else {
+      format!("{} ({})", buffer.location, buffer.events.join(","))
+    };
+    writeln!(
+      out,
+      "    \"{}\" [shape=box, label=\"{}\"];",
+      escape(&buffer.location),
+      escape(&label)
+    )
+    .unwrap();
+    for function in graph.functions.iter().filter(|f| f.location == buffer.location) {
+      let name = match function.names.first() {
+        Some(name) => name.as_str(),
+        None => "(anonymous)",
+      };
+      let label = format!("{}({})", name, function.parameters.join(", "));
+      writeln!(out, "    \"{}\" [label=\"{}\"];", escape(&function.id), escape(&label)).unwrap();
+    }
+    writeln!(out, "  }}").unwrap();
+  }
+
+  // Calls are counted per caller and target
+  let mut edges: BTreeMap<(&str, &str), usize> = BTreeMap::new();
+  for call in graph.calls.iter() {
+    for target in call.targets.iter() {
+      *edges.entry((&call.caller, target)).or_default() += 1;
+    }
+  }
+  for ((caller, target), count) in edges {
+    if count > 1 {
+      writeln!(
+        out,
+        "  \"{}\" -> \"{}\" [label=\"{}\"];",
+        escape(caller),
+        escape(target),
+        count
+      )
+      .unwrap();
+    } else {
+      writeln!(out, "  \"{}\" -> \"{}\";", escape(caller), escape(target)).unwrap();
+    }
+  }
+  writeln!(out, "}}").unwrap();
+  out
+}
@@ -0,0 +1,335 @@
+//! Recovery of ActionScript 2 classes from `DoInitAction` buffers (`#initclip` blocks).
+//!
+//! AS2 classes are compiled to the assignment of their constructor to a `_global` path (guarded by
+//! `if (!_global.pkg.Name)`), followed by `extends` and `implements` actions and assignments to the members of the
+//! constructor (static members) and of its prototype (methods and instance properties). The buffers are decompiled and
+//! these statements are collected, following the registers holding the constructor and its prototype.
+//!
+//! Interfaces are compiled to empty constructors: classes implemented by another class and without members are
+//! reported as interfaces.
+
+use crate::avm1::decompiler::ast::{print_expr, Expr, Stmt};
+use crate::avm1::decompiler::decompile_statements;
+use crate::dump::Avm1Location;
+use crate::source_paths::source_path;
+use avm1_types::cfg::Cfg;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Write;
+use std::path::PathBuf;
+
+#[derive(Debug, Serialize)]
+pub(crate) struct Class {
+  /// Full name, such as `com.example.Circle`
+  pub name: String,
+  /// `class` or `interface`
+  pub kind: &'static str,
+  pub location: String,
+  pub superclass: Option<String>,
+  pub interfaces: Vec<String>,
+  /// Parameters of the constructor
+  pub parameters: Vec<String>,
+  pub methods: Vec<Method>,
+  pub static_methods: Vec<Method>,
+  pub properties: Vec<Property>,
+  pub static_properties: Vec<Property>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct Method {
+  pub name: String,
+  pub parameters: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct Property {
+  pub name: String,
+  /// Initial value, if it is a literal
+  pub value: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct Package<'a> {
+  /// Name of the package, empty for the top-level package
+  pub name: &'a str,
+  pub classes: Vec<&'a Class>,
+}
+
+impl Class {
+  /// Returns the package of the class (empty for top-level classes) and its short name.
+  pub(crate) fn split_name(&self) -> (&str, &str) {
+    match self.name.rfind('.') {
+      Some(i) => (&self.name[..i], &self.name[i + 1..]),
+      None => ("", &self.name),
+    }
+  }
+
+  /// Returns the path of the stub source file, relative to the root of the package tree.
+  pub(crate) fn stub_path(&self) -> PathBuf {
+    source_path(&self.name)
+  }
+
+  /// Returns the ActionScript 2 declaration of the class, with empty method bodies.
+  pub(crate) fn stub(&self) -> String {
+    let mut out = String::new();
+    writeln!(out, "// {}", self.location).unwrap();
+    write!(out, "{} {}", self.kind, self.name).unwrap();
+    if let Some(superclass) = &self.superclass {
+      write!(out, " extends {}", superclass).unwrap();
+    }
+    if !self.interfaces.is_empty() {
+      write!(out, " implements {}", self.interfaces.join(", ")).unwrap();
+    }
+    out.push_str(" {\n");
+    let mut sections: Vec<String> = Vec::new();
+    let properties: Vec<String> = self
+      .static_properties
+      .iter()
+      .map(|property| ("static ", property))
+      .chain(self.properties.iter().map(|property| ("", property)))
+      .map(|(modifier, property)| match &property.value {
+        Some(value) => format!("  {}var {} = {};\n", modifier, property.name, value),
+        None => format!("  {}var {};\n", modifier, property.name),
+      })
+      .collect();
+    if !properties.is_empty() {
+      sections.push(properties.concat());
+    }
+    if self.kind == "class" {
+      let (_, name) = self.split_name();
+      sections.push(format!(
+        "  function {}({}) {{\n  }}\n",
+        name,
+        self.parameters.join(", ")
+      ));
+    }
+    let methods = self
+      .static_methods
+      .iter()
+      .map(|method| ("static ", method))
+      .chain(self.methods.iter().map(|method| ("", method)));
+    for (modifier, method) in methods {
+      sections.push(format!(
+        "  {}function {}({}) {{\n  }}\n",
+        modifier,
+        method.name,
+        method.parameters.join(", ")
+      ));
+    }
+    out.push_str(&sections.join("\n"));
+    out.push_str("}\n");
+    out
+  }
+}
+
+/// Returns the classes defined by the buffers, by name.
+pub(crate) fn find_classes(buffers: &[(Avm1Location, &[u8], &Cfg)]) -> Vec<Class> {
+  let mut classes: BTreeMap<String, Class> = BTreeMap::new();
+  for (location, bytes, cfg) in buffers {
+    let mut scanner = Scanner {
+      classes: &mut classes,
+      location: location.to_string(),
+      paths: HashMap::new(),
+      functions: HashMap::new(),
+    };
+    scanner.statements(&decompile_statements(bytes, cfg));
+  }
+
+  let implemented: HashSet<String> = classes
+    .values()
+    .flat_map(|class| class.interfaces.iter().cloned())
+    .collect();
+  let mut classes: Vec<Class> = classes.into_values().collect();
+  for class in classes.iter_mut() {
+    let is_empty = class.superclass.is_none()
+      && class.methods.is_empty()
+      && class.static_methods.is_empty()
+      && class.properties.is_empty()
+      && class.static_properties.is_empty();
+    if is_empty && implemented.contains(&class.name) {
+      class.kind = "interface";
+    }
+  }
+  classes
+}
+
+/// Groups the classes by package.
+pub(crate) fn packages(classes: &[Class]) -> Vec<Package<'_>> {
+  let mut packages: BTreeMap<&str, Vec<&Class>> = BTreeMap::new();
+  for class in classes {
+    packages.entry(class.split_name().0).or_default().push(class);
+  }
+  packages
+    .into_iter()
+    .map(|(name, classes)| Package { name, classes })
+    .collect()
+}
+
+struct Scanner<'a> {
+  classes: &'a mut BTreeMap<String, Class>,
+  location: String,
+  /// Paths held by variables and registers (such as `_r2 = _global.Foo.prototype`)
+  paths: HashMap<String, String>,
+  /// Parameters of the functions held by variables and registers
+  functions: HashMap<String, Vec<String>>,
+}
+
+impl<'a> Scanner<'a> {
+  fn statements(&mut self, statements: &[Stmt]) {
+    for statement in statements {
+      self.statement(statement);
+    }
+  }
+
+  fn statement(&mut self, statement: &Stmt) {
+    match statement {
+      Stmt::Assign(Expr::Var(name), value) => {
+        self.paths.remove(name);
+        self.functions.remove(name);
+        if let Expr::Function(function) = value {
+          self.functions.insert(name.clone(), function.parameters.clone());
+        } else if let Some(path) = self.path(value) {
+          self.paths.insert(name.clone(), path);
+        }
+      }
+      Stmt::Assign(target, value) => {
+        let path = match self.path(target) {
+          Some(path) => path,
+          None => return,
+        };
+        let parameters = match value {
+          Expr::Function(function) => Some(function.parameters.clone()),
+          Expr::Var(name) => {
+            let parameters = self.functions.get(name).cloned();
+            if parameters.is_some() {
+              // The register holding the constructor is used to define its members
+              self.paths.insert(name.clone(), path.clone());
+            }
+            parameters
+          }
+          _ => None,
+        };
+        self.assign(path, parameters, value);
+      }
+      Stmt::Extends(subclass, superclass) => {
+        if let (Some(subclass), Some(superclass)) = (self.path(subclass), self.path(superclass)) {
+          if let Some(class) = class_name(&subclass).and_then(|name| self.classes.get_mut(name)) {
+            class.superclass = Some(type_name(&superclass).to_string());
+          }
+        }
+      }
+      Stmt::Implements(constructor, interfaces) => {
+        let interfaces: Vec<String> = interfaces
+          .iter()
+          .filter_map(|interface| self.path(interface))
+          .map(|path| type_name(&path).to_string())
+          .collect();
+        if let Some(constructor) = self.path(constructor) {
+          if let Some(class) = class_name(&constructor).and_then(|name| self.classes.get_mut(name)) {
+            class.interfaces.extend(interfaces);
+          }
+        }
+      }
+      Stmt::If(_, then, r#else) => {
+        self.statements(then);
+        self.statements(r#else);
+      }
+      Stmt::While(_, body) | Stmt::DoWhile(body, _) | Stmt::With(_, body) => self.statements(body),
+      Stmt::Try { body, catches, finally } => {
+        self.statements(body);
+        for (_, catch) in catches {
+          self.statements(catch);
+        }
+        if let Some(finally) = finally {
+          self.statements(finally);
+        }
+      }
+      _ => {}
+    }
+  }
+
+  /// Records the assignment of a function (with its `parameters`) or another value to a path.
+  fn assign(&mut self, path: String, parameters: Option<Vec<String>>, value: &Expr) {
+    if let Some(i) = path.rfind(".prototype.") {
+      let name = path[i + ".prototype.".len()..].to_string();
+      if let Some(class) = class_name(&path[..i]).map(|name| self.class(name)) {
+        match parameters {
+          Some(parameters) => class.methods.push(Method { name, parameters }),
+          None => class.properties.push(Property {
+            name,
+            value: literal(value),
+          }),
+        }
+      }
+      return;
+    }
+    if let Some(i) = path.rfind('.') {
+      if let Some(class) = class_name(&path[..i]).and_then(|name| self.classes.get_mut(name)) {
+        let name = path[i + 1..].to_string();
+        match parameters {
+          Some(parameters) => class.static_methods.push(Method { name, parameters }),
+          None => class.static_properties.push(Property {
+            name,
+            value: literal(value),
+          }),
+        }
+        return;
+      }
+    }
+    if let (Some(name), Some(parameters)) = (class_name(&path), parameters) {
+      self.class(name).parameters = parameters;
+    }
+  }
+
+  /// Returns the class with the provided name, creating it if needed.
+  fn class(&mut self, name: &str) -> &mut Class {
+    let location = &self.location;
+    self.classes.entry(name.to_string()).or_insert_with(|| Class {
+      name: name.to_string(),
+      kind: "class",
+      location: location.clone(),
+      superclass: None,
+      interfaces: Vec::new(),
+      parameters: Vec::new(),
+      methods: Vec::new(),
+      static_methods: Vec::new(),
+      properties: Vec::new(),
+      static_properties: Vec::new(),
+    })
+  }
+
+  /// Returns the path of an expression, such as `_global.com.example.Circle.prototype`.
+  fn path(&self, expr: &Expr) -> Option<String> {
+    let path = match expr {
+      Expr::Eval(name) => match name.as_ref() {
+        Expr::String(name) => name.clone(),
+        _ => return None,
+      },
+      Expr::Var(name) => self.paths.get(name)?.clone(),
+      Expr::Member(object, key) => match key.as_ref() {
+        Expr::String(key) => format!("{}.{}", self.path(object)?, key),
+        _ => return None,
+      },
+      _ => return None,
+    };
+    Some(path)
+  }
+}
+
+/// Returns the name of the class defined at `path`, if it is a member of `_global`.
+fn class_name(path: &str) -> Option<&str> {
+  path.strip_prefix("_global.")
+}
+
+/// Returns the name of a type referenced by `path` (built-in classes are not members of `_global`).
+fn type_name(path: &str) -> &str {
+  class_name(path).unwrap_or(path)
+}
+
+/// Returns the source of a literal value.
+fn literal(value: &Expr) -> Option<String> {
+  match value {
+    Expr::Boolean(_) | Expr::Null | Expr::Number(_) | Expr::String(_) => Some(print_expr(value)),
+    _ => None,
+  }
+}
@@ -0,0 +1,495 @@
+//! ActionScript 2 syntax tree and printer.
+
+use std::fmt::Write;
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Expr {
+  Undefined,
+  Null,
+  Boolean(bool),
+  Number(f64),
+  String(String),
+  /// Variable, register or stack temporary
+  Var(String),
+  /// Dynamic variable lookup: `eval(name)`
+  Eval(Box<Expr>),
+  Member(Box<Expr>, Box<Expr>),
+  Call(Box<Expr>, Vec<Expr>),
+  New(Box<Expr>, Vec<Expr>),
+  Unary(&'static str, Box<Expr>),
+  Binary(&'static str, Box<Expr>, Box<Expr>),
+  Array(Vec<Expr>),
+  Object(Vec<(Expr, Expr)>),
+  Ternary(Box<Expr>, Box<Expr>, Box<Expr>),
+  Function(Box<Function>),
+  /// Global function or property (`getTimer`, `_x`, ...)
+  Builtin(&'static str),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Function {
+  pub name: String,
+  pub parameters: Vec<String>,
+  pub body: Vec<Stmt>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Stmt {
+  Expr(Expr),
+  /// `var name;` or `var name = value;`
+  Var(Expr, Option<Expr>),
+  /// Assignment to a variable (`Eval`), register, temporary (`Var`) or member
+  Assign(Expr, Expr),
+  Function(Function),
+  Return(Option<Expr>),
+  Throw(Expr),
+  If(Expr, Vec<Stmt>, Vec<Stmt>),
+  While(Expr, Vec<Stmt>),
+  DoWhile(Vec<Stmt>, Expr),
+  Break,
+  Continue,
+  With(Expr, Vec<Stmt>),
+  Try {
+    body: Vec<Stmt>,
+    /// Catch clauses, with their variable (and type, in ActionScript 3)
+    catches: Vec<(String, Vec<Stmt>)>,
+    finally: Option<Vec<Stmt>>,
+  },
+  /// `subclass extends superclass;`, emitted by the `Extends` action
+  Extends(Expr, Expr),
+  /// `constructor implements interfaces;`, emitted by the `ImplementsOp` action
+  Implements(Expr, Vec<Expr>),
+  Label(String),
+  Goto(String),
+  Comment(String),
+}
+
+impl Expr {
+  pub(crate) fn call(name: &'static str, args: Vec<Expr>) -> Self {
+    Expr::Call(Box::new(Expr::Builtin(name)), args)
+  }
+
+  pub(crate) fn unary(op: &'static str, value: Expr) -> Self {
+    Expr::Unary(op, Box::new(value))
+  }
+
+  pub(crate) fn binary(op: &'static str, left: Expr, right: Expr) -> Self {
+    Expr::Binary(op, Box::new(left), Box::new(right))
+  }
+
+  /// Tests if evaluating the expression may have an observable effect.
+  pub(crate) fn has_side_effects(&self) -> bool {
+    match self {
+      Expr::Call(..) | Expr::New(..) | Expr::Eval(_) => true,
+      Expr::Unary(op, value) => *op == "delete" || value.has_side_effects(),
+      Expr::Binary(_, left, right) => left.has_side_effects() || right.has_side_effects(),
+      Expr::Member(object, key) => object.has_side_effects() || key.has_side_effects(),
+      Expr::Array(items) => items.iter().any(Expr::has_side_effects),
+      Expr::Ternary(condition, then, r#else) => {
+        condition.has_side_effects() || then.has_side_effects() || r#else.has_side_effects()
+      }
+      Expr::Object(properties) => properties
+        .iter()
+        .any(|(key, value)| key.has_side_effects() || value.has_side_effects()),
+      _ => false,
+    }
+  }
+
+  /// Returns the logical negation of the expression, simplifying double negations and comparisons.
+  pub(crate) fn not(self) -> Self {
+    match self {
+      Expr::Unary("!", value) => *value,
+      Expr::Boolean(value) => Expr::Boolean(!value),
+      Expr::Binary(op, left, right) => {
+        let inverse = match op {
+          "==" => Some("!="),
+          "!=" => Some("=="),
+          "===" => Some("!=="),
+          "!==" => Some("==="),
+          _ => None,
+        };
+        match inverse {
+          Some(inverse) => Expr::Binary(inverse, left, right),
+          None => Expr::unary("!", Expr::Binary(op, left, right)),
+        }
+      }
+      value => Expr::unary("!", value),
+    }
+  }
+
+  fn precedence(&self) -> u8 {
+    match self {
+      Expr::Function(_) => 1,
+      Expr::Ternary(..) => 2,
+      Expr::Binary(op, ..) => binary_precedence(op),
+      Expr::Unary(..) => 14,
+      Expr::New(..) => 16,
+      Expr::Call(..) | Expr::Member(..) => 17,
+      _ => 18,
+    }
+  }
+}
+
+fn binary_precedence(op: &str) -> u8 {
+  match op {
+    "||" | "or" => 3,
+    "&&" | "and" => 4,
+    "|" => 5,
+    "^" => 6,
+    "&" => 7,
+    "==" | "!=" | "===" | "!==" | "eq" | "ne" => 8,
+    "<" | ">" | "<=" | ">=" | "instanceof" | "in" | "is" | "as" | "lt" | "gt" | "le" | "ge" => 9,
+    "<<" | ">>" | ">>>" => 10,
+    "+" | "-" | "add" => 11,
+    _ => 12,
+  }
+}
+
+/// Tests if the string can be used as an identifier.
+pub(crate) fn is_identifier(name: &str) -> bool {
+  let mut chars = name.chars();
+  match chars.next() {
+    Some(c) if c.is_alphabetic() || c == '_' || c == '$' => {}
+    _ => return false,
+  }
+  chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+}
+
+/// Tests if the string can be used as a variable path (`a`, `_root.a`, `/a:b`).
+fn is_path(name: &str) -> bool {
+  name.split(&['.', ':', '/'][..]).all(is_identifier)
+}
+
+pub(crate) fn quote(value: &str) -> String {
+  let mut out = String::with_capacity(value.len() + 2);
+  out.push('"');
+  for c in value.chars() {
+    match c {
+      '"' => out.push_str("\\\""),
+      '\\' => out.push_str("\\\\"),
+      '\n' => out.push_str("\\n"),
+      '\r' => out.push_str("\\r"),
+      '\t' => out.push_str("\\t"),
+      c if (c as u32) < 0x20 => write!(out, "\\x{:02x}", c as u32).unwrap(),
+      c => out.push(c),
+    }
+  }
+  out.push('"');
+  out
+}
+
+fn format_number(value: f64) -> String {
+  if value.is_nan() {
+    "NaN".to_string()
+  } else if value.is_infinite() {
+    if value > 0.0 { "Infinity" } else { "-Infinity" }.to_string()
+  } else if value == value.trunc() && value.abs() < 1e15 {
+    if value == 0.0 && value.is_sign_negative() {
+      "-0".to_string()
+    } else {
+      format!("{}", value as i64)
+    }
+  } else {
+    format!("{}", value)
+  }
+}
+
+/// Prints a list of statements, with the provided initial indentation level.
+pub(crate) fn print(statements: &[Stmt], indent: usize) -> String {
+  let mut printer = Printer {
+    out: String::new(),
+    indent,
+  };
+  printer.statements(statements);
+  printer.out
+}
+
+/// Prints an expression.
+pub(crate) fn print_expr(expr: &Expr) -> String {
+  let mut printer = Printer {
+    out: String::new(),
+    indent: 0,
+  };
+  printer.expr(expr, 0);
+  printer.out
+}
+
+struct Printer {
+  out: String,
+  indent: usize,
+}
+
+impl Printer {
+  fn line_start(&mut self) {
+    for _ in 0..self.indent {
+      self.out.push_str("  ");
+    }
+  }
+
+  fn statements(&mut self, statements: &[Stmt]) {
+    for statement in statements {
+      self.statement(statement);
+    }
+  }
+
+  fn block(&mut self, statements: &[Stmt]) {
+    self.out.push_str("{\n");
+    self.indent += 1;
+    self.statements(statements);
+    self.indent -= 1;
+    self.line_start();
+    self.out.push('}');
+  }
+
+  fn statement(&mut self, statement: &Stmt) {
+    if let Stmt::Label(label) = statement {
+      // Labels are outdented to stand out
+      self.indent = self.indent.saturating_sub(1);
+      self.line_start();
+      self.indent += 1;
+      self.out.push_str(label);
+      self.out.push_str(":\n");
+      return;
+    }
+    self.line_start();
+    match statement {
+      Stmt::Expr(expr) => {
+        self.expr(expr, 1);
+        self.out.push(';');
+      }
+      Stmt::Var(name, value) => {
+        self.out.push_str("var ");
+        match name {
+          Expr::String(name) => self.out.push_str(name),
+          name => self.expr(name, 1),
+        }
+        if let Some(value) = value {
+          self.out.push_str(" = ");
+          self.expr(value, 1);
+        }
+        self.out.push(';');
+      }
+      Stmt::Assign(target, value) => {
+        if self.target(target) {
+          self.out.push_str(" = ");
+          self.expr(value, 1);
+        } else {
+          // Dynamic variable name
+          self.out.push_str("set(");
+          match target {
+            Expr::Eval(name) => self.expr(name, 1),
+            target => self.expr(target, 1),
+          }
+          self.out.push_str(", ");
+          self.expr(value, 1);
+          self.out.push(')');
+        }
+        self.out.push(';');
+      }
+      Stmt::Function(function) => self.function(function),
+      Stmt::Return(None) => self.out.push_str("return;"),
+      Stmt::Return(Some(value)) => {
+        self.out.push_str("return ");
+        self.expr(value, 0);
+        self.out.push(';');
+      }
+      Stmt::Throw(value) => {
+        self.out.push_str("throw ");
+        self.expr(value, 0);
+        self.out.push(';');
+      }
+      Stmt::If(condition, then, r#else) => {
+        self.out.push_str("if (");
+        self.expr(condition, 0);
+        self.out.push_str(") ");
+        self.block(then);
+        let mut r#else = r#else;
+        while !r#else.is_empty() {
+          self.out.push_str(" else ");
+          match r#else.as_slice() {
+            [Stmt::If(condition, then, next)] => {
+              self.out.push_str("if (");
+              self.expr(condition, 0);
+              self.out.push_str(") ");
+              self.block(then);
+              r#else = next;
+            }
+            _ => {
+              self.block(r#else);
+              break;
+            }
+          }
+        }
+      }
+      Stmt::While(condition, body) => {
+        self.out.push_str("while (");
+        self.expr(condition, 0);
+        self.out.push_str(") ");
+        self.block(body);
+      }
+      Stmt::DoWhile(body, condition) => {
+        self.out.push_str("do ");
+        self.block(body);
+        self.out.push_str(" while (");
+        self.expr(condition, 0);
+        self.out.push_str(");");
+      }
+      Stmt::Break => self.out.push_str("break;"),
+      Stmt::Continue => self.out.push_str("continue;"),
+      Stmt::With(object, body) => {
+        self.out.push_str("with (");
+        self.expr(object, 0);
+        self.out.push_str(") ");
+        self.block(body);
+      }
+      Stmt::Try { body, catches, finally } => {
+        self.out.push_str("try ");
+        self.block(body);
+        for (name, body) in catches {
+          write!(self.out, " catch ({}) ", name).unwrap();
+          self.block(body);
+        }
+        if let Some(body) = finally {
+          self.out.push_str(" finally ");
+          self.block(body);
+        }
+      }
+      Stmt::Extends(subclass, superclass) => {
+        self.expr(subclass, 15);
+        self.out.push_str(" extends ");
+        self.expr(superclass, 15);
+        self.out.push(';');
+      }
+      Stmt::Implements(constructor, interfaces) => {
+        self.expr(constructor, 15);
+        self.out.push_str(" implements ");
+        self.list(interfaces);
+        self.out.push(';');
+      }
+      Stmt::Goto(label) => write!(self.out, "goto {};", label).unwrap(),
+      Stmt::Comment(comment) => write!(self.out, "// {}", comment).unwrap(),
+      Stmt::Label(_) => unreachable!(),
+    }
+    self.out.push('\n');
+  }
+
+  fn function(&mut self, function: &Function) {
+    self.out.push_str("function ");
+    self.out.push_str(&function.name);
+    write!(self.out, "({}) ", function.parameters.join(", ")).unwrap();
+    self.block(&function.body);
+  }
+
+  /// Prints the left-hand side of an assignment, returns `false` if it is not a valid target.
+  fn target(&mut self, target: &Expr) -> bool {
+    match target {
+      Expr::Eval(name) => match name.as_ref() {
+        Expr::String(name) if is_path(name) => self.out.push_str(name),
+        _ => return false,
+      },
+      Expr::Var(_) | Expr::Member(..) => self.expr(target, 17),
+      _ => return false,
+    }
+    true
+  }
+
+  fn list(&mut self, items: &[Expr]) {
+    for (i, item) in items.iter().enumerate() {
+      if i > 0 {
+        self.out.push_str(", ");
+      }
+      self.expr(item, 1);
+    }
+  }
+
+  fn expr(&mut self, expr: &Expr, min_precedence: u8) {
+    let parenthesize = expr.precedence() < min_precedence;
+    if parenthesize {
+      self.out.push('(');
+    }
+    match expr {
+      Expr::Undefined => self.out.push_str("undefined"),
+      Expr::Null => self.out.push_str("null"),
+      Expr::Boolean(value) => write!(self.out, "{}", value).unwrap(),
+      Expr::Number(value) => self.out.push_str(&format_number(*value)),
+      Expr::String(value) => self.out.push_str(&quote(value)),
+      Expr::Var(name) => self.out.push_str(name),
+      Expr::Builtin(name) => self.out.push_str(name),
+      Expr::Eval(name) => match name.as_ref() {
+        Expr::String(name) if is_path(name) => self.out.push_str(name),
+        name => {
+          self.out.push_str("eval(");
+          self.expr(name, 1);
+          self.out.push(')');
+        }
+      },
+      Expr::Member(object, key) => {
+        self.expr(object, 17);
+        match key.as_ref() {
+          Expr::String(key) if is_identifier(key) => write!(self.out, ".{}", key).unwrap(),
+          key => {
+            self.out.push('[');
+            self.expr(key, 0);
+            self.out.push(']');
+          }
+        }
+      }
+      Expr::Call(callee, args) => {
+        self.expr(callee, 17);
+        self.out.push('(');
+        self.list(args);
+        self.out.push(')');
+      }
+      Expr::New(constructor, args) => {
+        self.out.push_str("new ");
+        self.expr(constructor, 17);
+        self.out.push('(');
+        self.list(args);
+        self.out.push(')');
+      }
+      Expr::Unary(op, value) => {
+        self.out.push_str(op);
+        if op.chars().all(char::is_alphabetic) {
+          self.out.push(' ');
+        }
+        self.expr(value, 14);
+      }
+      Expr::Binary(op, left, right) => {
+        let precedence = binary_precedence(op);
+        self.expr(left, precedence);
+        write!(self.out, " {} ", op).unwrap();
+        // Binary operators are left-associative
+        self.expr(right, precedence + 1);
+      }
+      Expr::Array(items) => {
+        self.out.push('[');
+        self.list(items);
+        self.out.push(']');
+      }
+      Expr::Object(properties) => {
+        self.out.push('{');
+        for (i, (key, value)) in properties.iter().enumerate() {
+          self.out.push_str(if i > 0 { ", " } else { " " });
+          match key {
+            Expr::String(key) if is_identifier(key) => self.out.push_str(key),
+            key => self.expr(key, 18),
+          }
+          self.out.push_str(": ");
+          self.expr(value, 1);
+        }
+        self.out.push_str(if properties.is_empty() { "}" } else { " }" });
+      }
+      Expr::Ternary(condition, then, r#else) => {
+        self.expr(condition, 3);
+        self.out.push_str(" ? ");
+        self.expr(then, 2);
+        self.out.push_str(" : ");
+        self.expr(r#else, 2);
+      }
+      Expr::Function(function) => self.function(function),
+    }
+    if parenthesize {
+      self.out.push(')');
+    }
+  }
+}
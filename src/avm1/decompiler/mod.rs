@@ -0,0 +1,870 @@
+//! ActionScript 2 decompiler.
+//!
+//! The stack operations of each CFG block are converted to statements and expressions, then the control flow
+//! between blocks is recovered with `crate::structure`. Stack values flowing between blocks are stored in `_sN`
+//! temporaries, and jumps without a structured equivalent are kept as labeled `goto` statements.
+
+pub(crate) mod ast;
+pub(crate) mod simplify;
+
+use self::ast::{Expr, Function, Stmt};
+use crate::avm1::{block_actions, block_children, block_label, block_offsets, cfg_labels};
+use crate::structure::{self, Structured, Target, Terminator};
+use avm1_types::cfg::{Cfg, CfgAction, CfgBlock, CfgLabel};
+use avm1_types::{CatchTarget, GetUrl2Method, Value};
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// Names of the properties used by `GetProperty` and `SetProperty`, by index.
+const PROPERTY_NAMES: [&str; 22] = [
+  "_x",
+  "_y",
+  "_xscale",
+  "_yscale",
+  "_currentframe",
+  "_totalframes",
+  "_alpha",
+  "_visible",
+  "_width",
+  "_height",
+  "_rotation",
+  "_target",
+  "_framesloaded",
+  "_name",
+  "_droptarget",
+  "_url",
+  "_highquality",
+  "_focusrect",
+  "_soundbuftime",
+  "_quality",
+  "_xmouse",
+  "_ymouse",
+];
+
+/// Decompiles AVM1 bytecode to ActionScript 2 source code, `cfg` is the result of `parse_cfg` for `bytes`.
+pub(crate) fn decompile(bytes: &[u8], cfg: &Cfg) -> String {
+  ast::print(&decompile_statements(bytes, cfg), 0)
+}
+
+/// Decompiles AVM1 bytecode to ActionScript 2 statements, `cfg` is the result of `parse_cfg` for `bytes`.
+pub(crate) fn decompile_statements(bytes: &[u8], cfg: &Cfg) -> Vec<Stmt> {
+  let offsets = block_offsets(bytes, cfg);
+  let mut decompiler = Decompiler {
+    registers: HashMap::new(),
+    constant_pool: Vec::new(),
+    offsets: &offsets,
+  };
+  decompiler.body(cfg)
+}
+
+struct Decompiler<'a> {
+  /// Names of the registers of the current function
+  registers: HashMap<u8, String>,
+  /// Constant pool defined by the last `ConstantPool` action
+  constant_pool: Vec<String>,
+  /// Offsets of the blocks in the buffer
+  offsets: &'a HashMap<CfgLabel, usize>,
+}
+
+/// Statements of a block, before structuring.
+struct Lifted {
+  statements: Vec<Stmt>,
+  /// Condition of the conditional jump ending the block
+  condition: Option<Expr>,
+}
+
+/// Decompiled CFG, with its blocks indexed by position.
+struct Region<'a> {
+  labels: Vec<&'a CfgLabel>,
+  lifted: Vec<Lifted>,
+  /// Outer labels targeted from the region, `None` is the end of the code
+  exits: Vec<Option<CfgLabel>>,
+  /// Exits reached when the region completes normally
+  follows: Vec<Option<CfgLabel>>,
+}
+
+impl<'a> Decompiler<'a> {
+  /// Decompiles the body of a function (or the top-level code).
+  fn body(&mut self, cfg: &Cfg) -> Vec<Stmt> {
+    let mut statements = self.region(cfg, &[None]);
+    simplify::simplify_body(&mut statements);
+    statements
+  }
+
+  /// Decompiles a CFG; `follows` are the outer labels where the execution continues when it reaches the end of the
+  /// region (`None` for the end of the code).
+  fn region(&mut self, cfg: &Cfg, follows: &[Option<CfgLabel>]) -> Vec<Stmt> {
+    let blocks: Vec<&CfgBlock> = cfg.blocks.iter().collect();
+    let indexes: HashMap<&CfgLabel, usize> = blocks
+      .iter()
+      .enumerate()
+      .map(|(i, block)| (block_label(block), i))
+      .collect();
+    let mut exits: Vec<Option<CfgLabel>> = Vec::new();
+    let mut resolve = |label: Option<&CfgLabel>| -> Target {
+      if let Some(index) = label.and_then(|label| indexes.get(label)) {
+        return Target::Node(*index);
+      }
+      let exit = label.cloned();
+      match exits.iter().position(|e| *e == exit) {
+        Some(position) => Target::Exit(position),
+        None => {
+          exits.push(exit);
+          Target::Exit(exits.len() - 1)
+        }
+      }
+    };
+
+    let mut terminators = Vec::with_capacity(blocks.len());
+    let mut block_follows = Vec::with_capacity(blocks.len());
+    for block in blocks.iter() {
+      let block_follow = nested_follow(block, self.offsets);
+      let terminator = match block {
+        CfgBlock::Error(_) | CfgBlock::Return(_) | CfgBlock::Throw(_) => Terminator::End,
+        CfgBlock::If(block) => Terminator::Branch {
+          if_true: resolve(block.if_true.as_ref()),
+          if_false: resolve(block.if_false.as_ref()),
+        },
+        CfgBlock::Simple(block) => Terminator::Jump(resolve(block.next.as_ref())),
+        CfgBlock::WaitForFrame(block) => Terminator::Branch {
+          if_true: resolve(block.if_loaded.as_ref()),
+          if_false: resolve(block.if_not_loaded.as_ref()),
+        },
+        CfgBlock::WaitForFrame2(block) => Terminator::Branch {
+          if_true: resolve(block.if_loaded.as_ref()),
+          if_false: resolve(block.if_not_loaded.as_ref()),
+        },
+        CfgBlock::Try(_) | CfgBlock::With(_) => match &block_follow {
+          Some(label) => Terminator::Jump(resolve(label.as_ref())),
+          None => Terminator::End,
+        },
+      };
+      terminators.push(terminator);
+      block_follows.push(block_follow);
+    }
+
+    let mut predecessors: Vec<Vec<usize>> = vec![Vec::new(); blocks.len()];
+    for (i, terminator) in terminators.iter().enumerate() {
+      for target in structure::successors(terminator) {
+        if let Target::Node(successor) = target {
+          predecessors[successor].push(i);
+        }
+      }
+    }
+
+    // Blocks are in bytecode order, so the stack at the start of a block is usually known before it is lifted
+    let mut entry_stacks: Vec<Vec<Expr>> = Vec::with_capacity(blocks.len());
+    let mut exit_stacks: Vec<Vec<Expr>> = Vec::with_capacity(blocks.len());
+    let mut lifted = Vec::with_capacity(blocks.len());
+    for (i, block) in blocks.iter().enumerate() {
+      let entry_stack = entry_stack(&predecessors[i], &exit_stacks);
+      let (block_lifted, exit_stack) = self.lift(block, entry_stack.clone(), block_follows[i].as_ref());
+      entry_stacks.push(entry_stack);
+      exit_stacks.push(exit_stack);
+      lifted.push(block_lifted);
+    }
+    // Store the values expected in temporaries by the successors
+    for (i, exit_stack) in exit_stacks.iter().enumerate() {
+      let mut slots = BTreeSet::new();
+      for target in structure::successors(&terminators[i]) {
+        if let Target::Node(successor) = target {
+          for (slot, value) in entry_stacks[successor].iter().enumerate() {
+            if *value == temporary(slot) && slot < exit_stack.len() && exit_stack[slot] != *value {
+              slots.insert(slot);
+            }
+          }
+        }
+      }
+      for slot in slots {
+        let value = exit_stack[slot].clone();
+        lifted[i].statements.push(Stmt::Assign(temporary(slot), value));
+      }
+    }
+
+    let structured = structure::structure(&terminators);
+    let mut region = Region {
+      labels: blocks.iter().map(|block| block_label(block)).collect(),
+      lifted,
+      exits,
+      follows: follows.to_vec(),
+    };
+    let mut statements = Vec::new();
+    region.convert(structured, true, &mut statements);
+    statements
+  }
+
+  /// Converts the actions of a block to statements, returns the values left on the stack.
+  fn lift(&mut self, block: &CfgBlock, stack: Vec<Expr>, follow: Option<&Option<CfgLabel>>) -> (Lifted, Vec<Expr>) {
+    let mut lifter = Lifter {
+      stack,
+      statements: Vec::new(),
+      underflow: false,
+    };
+    for action in block_actions(block) {
+      self.action(&mut lifter, action);
+    }
+    let mut condition = None;
+    // A nested region that never completes normally has no follow
+    let follows: Vec<Option<CfgLabel>> = follow.cloned().into_iter().collect();
+    match block {
+      CfgBlock::Error(block) => {
+        let error = block.error.as_deref().unwrap_or("invalid bytecode");
+        lifter.statements.push(Stmt::Comment(format!("error: {}", error)));
+      }
+      CfgBlock::If(_) => condition = Some(lifter.pop()),
+      CfgBlock::Return(_) => {
+        let value = match lifter.pop() {
+          Expr::Undefined => None,
+          value => Some(value),
+        };
+        lifter.statements.push(Stmt::Return(value));
+      }
+      CfgBlock::Simple(_) => {}
+      CfgBlock::Throw(_) => {
+        let value = lifter.pop();
+        lifter.statements.push(Stmt::Throw(value));
+      }
+      CfgBlock::Try(block) => {
+        // The `try` and `catch` blocks complete normally by running the `finally` block
+        let mut try_follows = follows.clone();
+        if let Some(finally) = &block.finally {
+          try_follows.push(Some(block_label(finally.blocks.first()).clone()));
+        }
+        let body = self.region(&block.r#try, &try_follows);
+        let catches = match &block.catch {
+          Some(catch) => {
+            let name = match &block.catch_target {
+              CatchTarget::Register(register) => self.register_name(*register),
+              CatchTarget::Variable(name) => name.clone(),
+            };
+            vec![(name, self.region(catch, &try_follows))]
+          }
+          None => Vec::new(),
+        };
+        let finally = block.finally.as_ref().map(|finally| self.region(finally, &follows));
+        lifter.statements.push(Stmt::Try { body, catches, finally });
+      }
+      CfgBlock::WaitForFrame(block) => {
+        condition = Some(Expr::call("ifFrameLoaded", vec![Expr::Number(block.frame as f64)]));
+      }
+      CfgBlock::WaitForFrame2(_) => {
+        let frame = lifter.pop();
+        condition = Some(Expr::call("ifFrameLoaded", vec![frame]));
+      }
+      CfgBlock::With(block) => {
+        let object = lifter.pop();
+        let body = self.region(&block.with, &follows);
+        lifter.statements.push(Stmt::With(object, body));
+      }
+    }
+    let mut statements = lifter.statements;
+    if lifter.underflow {
+      statements.insert(0, Stmt::Comment("stack underflow".to_string()));
+    }
+    (Lifted { statements, condition }, lifter.stack)
+  }
+
+  fn register_name(&self, register: u8) -> String {
+    match self.registers.get(&register) {
+      Some(name) => name.clone(),
+      None => format!("_r{}", register),
+    }
+  }
+
+  fn value(&self, value: &Value) -> Expr {
+    match value {
+      Value::Boolean(value) => Expr::Boolean(*value),
+      Value::Constant(index) => match self.constant_pool.get(usize::from(*index)) {
+        Some(value) => Expr::String(value.clone()),
+        None => Expr::Var(format!("_constant{}", index)),
+      },
+      Value::Float32(value) => Expr::Number(f64::from(*value)),
+      Value::Float64(value) => Expr::Number(*value),
+      Value::Null => Expr::Null,
+      Value::Register(register) => Expr::Var(self.register_name(*register)),
+      Value::String(value) => Expr::String(value.clone()),
+      Value::Sint32(value) => Expr::Number(f64::from(*value)),
+      Value::Undefined => Expr::Undefined,
+    }
+  }
+
+  /// Decompiles a nested function, registers are named after the parameters and preloaded variables.
+  fn function(&self, name: &str, parameters: Vec<String>, registers: HashMap<u8, String>, body: &Cfg) -> Function {
+    let mut decompiler = Decompiler {
+      registers,
+      constant_pool: self.constant_pool.clone(),
+      offsets: self.offsets,
+    };
+    Function {
+      name: name.to_string(),
+      parameters,
+      body: decompiler.body(body),
+    }
+  }
+
+  fn action(&mut self, lifter: &mut Lifter, action: &CfgAction) {
+    match action {
+      CfgAction::Add | CfgAction::Add2 => lifter.binary("+"),
+      CfgAction::And => lifter.binary("&&"),
+      CfgAction::AsciiToChar => lifter.builtin("chr", 1),
+      CfgAction::BitAnd => lifter.binary("&"),
+      CfgAction::BitLShift => lifter.binary("<<"),
+      CfgAction::BitOr => lifter.binary("|"),
+      CfgAction::BitRShift => lifter.binary(">>"),
+      CfgAction::BitURShift => lifter.binary(">>>"),
+      CfgAction::BitXor => lifter.binary("^"),
+      CfgAction::Call => {
+        let frame = lifter.pop();
+        lifter.statement(Expr::call("call", vec![frame]));
+      }
+      CfgAction::CallFunction => {
+        let name = lifter.pop();
+        let args = lifter.pop_args();
+        lifter.push(Expr::Call(Box::new(Expr::Eval(Box::new(name))), args));
+      }
+      CfgAction::CallMethod => {
+        let name = lifter.pop();
+        let object = lifter.pop();
+        let args = lifter.pop_args();
+        lifter.push(Expr::Call(Box::new(method(object, name)), args));
+      }
+      CfgAction::CastOp => {
+        let object = lifter.pop();
+        let constructor = lifter.pop();
+        lifter.push(Expr::Call(Box::new(constructor), vec![object]));
+      }
+      CfgAction::CharToAscii => lifter.builtin("ord", 1),
+      CfgAction::CloneSprite => {
+        let depth = lifter.pop();
+        let target = lifter.pop();
+        let source = lifter.pop();
+        lifter.statement(Expr::call("duplicateMovieClip", vec![source, target, depth]));
+      }
+      CfgAction::ConstantPool(action) => self.constant_pool = action.constant_pool.clone(),
+      CfgAction::Decrement => {
+        let value = lifter.pop();
+        lifter.push(Expr::binary("-", value, Expr::Number(1.0)));
+      }
+      CfgAction::DefineFunction(action) => {
+        let function = self.function(&action.name, action.parameters.clone(), HashMap::new(), &action.body);
+        lifter.function(function);
+      }
+      CfgAction::DefineFunction2(action) => {
+        let mut registers = HashMap::new();
+        let preloaded = [
+          (action.preload_this, "this"),
+          (action.preload_arguments, "arguments"),
+          (action.preload_super, "super"),
+          (action.preload_root, "_root"),
+          (action.preload_parent, "_parent"),
+          (action.preload_global, "_global"),
+        ];
+        let mut next_register: u8 = 1;
+        for (preload, name) in preloaded.iter() {
+          if *preload {
+            registers.insert(next_register, name.to_string());
+            next_register = next_register.saturating_add(1);
+          }
+        }
+        for parameter in action.parameters.iter() {
+          if parameter.register != 0 {
+            registers.insert(parameter.register, parameter.name.clone());
+          }
+        }
+        let parameters = action.parameters.iter().map(|p| p.name.clone()).collect();
+        let function = self.function(&action.name, parameters, registers, &action.body);
+        lifter.function(function);
+      }
+      CfgAction::DefineLocal => {
+        let value = lifter.pop();
+        let name = lifter.pop();
+        lifter.statements.push(Stmt::Var(name, Some(value)));
+      }
+      CfgAction::DefineLocal2 => {
+        let name = lifter.pop();
+        lifter.statements.push(Stmt::Var(name, None));
+      }
+      CfgAction::Delete => {
+        let name = lifter.pop();
+        let object = lifter.pop();
+        lifter.push(Expr::unary("delete", Expr::Member(Box::new(object), Box::new(name))));
+      }
+      CfgAction::Delete2 => {
+        let name = lifter.pop();
+        lifter.push(Expr::unary("delete", Expr::Eval(Box::new(name))));
+      }
+      CfgAction::Divide => lifter.binary("/"),
+      CfgAction::EndDrag => lifter.statement(Expr::call("stopDrag", Vec::new())),
+      CfgAction::Enumerate => {
+        let name = lifter.pop();
+        lifter.push(Expr::call("enumerate", vec![Expr::Eval(Box::new(name))]));
+      }
+      CfgAction::Enumerate2 => lifter.builtin("enumerate", 1),
+      CfgAction::Equals | CfgAction::Equals2 => lifter.binary("=="),
+      CfgAction::Extends => {
+        let superclass = lifter.pop();
+        let subclass = lifter.pop();
+        lifter.statements.push(Stmt::Extends(subclass, superclass));
+      }
+      CfgAction::FsCommand2 => {
+        let args = lifter.pop_args();
+        lifter.push(Expr::call("fscommand2", args));
+      }
+      CfgAction::GetMember => {
+        let key = lifter.pop();
+        let object = lifter.pop();
+        lifter.push(Expr::Member(Box::new(object), Box::new(key)));
+      }
+      CfgAction::GetProperty => {
+        let property = lifter.pop();
+        let target = lifter.pop();
+        lifter.push(Expr::call("getProperty", vec![target, property_name(property)]));
+      }
+      CfgAction::GetTime => lifter.builtin("getTimer", 0),
+      CfgAction::GetUrl(action) => {
+        let call = match action.url.strip_prefix("FSCommand:") {
+          Some(command) => Expr::call(
+            "fscommand",
+            vec![Expr::String(command.to_string()), Expr::String(action.target.clone())],
+          ),
+          None => Expr::call(
+            "getURL",
+            vec![Expr::String(action.url.clone()), Expr::String(action.target.clone())],
+          ),
+        };
+        lifter.statement(call);
+      }
+      CfgAction::GetUrl2(action) => {
+        let target = lifter.pop();
+        let url = lifter.pop();
+        let mut args = vec![url, target];
+        match action.send_vars_method {
+          GetUrl2Method::None => {}
+          GetUrl2Method::Get => args.push(Expr::String("GET".to_string())),
+          GetUrl2Method::Post => args.push(Expr::String("POST".to_string())),
+        }
+        let name = if action.load_variables {
+          "loadVariables"
+        } else if action.load_target {
+          "loadMovie"
+        } else {
+          "getURL"
+        };
+        lifter.statement(Expr::call(name, args));
+      }
+      CfgAction::GetVariable => {
+        let name = lifter.pop();
+        lifter.push(Expr::Eval(Box::new(name)));
+      }
+      CfgAction::GotoFrame(action) => {
+        let frame = Expr::Number(action.frame as f64 + 1.0);
+        lifter.statement(Expr::call("gotoAndStop", vec![frame]));
+      }
+      CfgAction::GotoFrame2(action) => {
+        let frame = lifter.pop();
+        if action.scene_bias != 0 {
+          lifter
+            .statements
+            .push(Stmt::Comment(format!("scene bias: {}", action.scene_bias)));
+        }
+        let name = if action.play { "gotoAndPlay" } else { "gotoAndStop" };
+        lifter.statement(Expr::call(name, vec![frame]));
+      }
+      CfgAction::GotoLabel(action) => {
+        lifter.statement(Expr::call("gotoAndStop", vec![Expr::String(action.label.clone())]));
+      }
+      CfgAction::Greater => lifter.binary(">"),
+      CfgAction::ImplementsOp => {
+        let constructor = lifter.pop();
+        let interfaces = lifter.pop_args();
+        lifter.statements.push(Stmt::Implements(constructor, interfaces));
+      }
+      CfgAction::Increment => {
+        let value = lifter.pop();
+        lifter.push(Expr::binary("+", value, Expr::Number(1.0)));
+      }
+      CfgAction::InitArray => {
+        let items = lifter.pop_args();
+        lifter.push(Expr::Array(items));
+      }
+      CfgAction::InitObject => {
+        let count = lifter.pop_count();
+        let mut properties = Vec::with_capacity(count);
+        for _ in 0..count {
+          let value = lifter.pop();
+          let key = lifter.pop();
+          properties.push((key, value));
+        }
+        properties.reverse();
+        lifter.push(Expr::Object(properties));
+      }
+      CfgAction::InstanceOf => lifter.binary("instanceof"),
+      CfgAction::Less | CfgAction::Less2 => lifter.binary("<"),
+      CfgAction::MbAsciiToChar => lifter.builtin("mbchr", 1),
+      CfgAction::MbCharToAscii => lifter.builtin("mbord", 1),
+      CfgAction::MbStringExtract => lifter.builtin("mbsubstring", 3),
+      CfgAction::MbStringLength => lifter.builtin("mblength", 1),
+      CfgAction::Modulo => lifter.binary("%"),
+      CfgAction::Multiply => lifter.binary("*"),
+      CfgAction::NewMethod => {
+        let name = lifter.pop();
+        let object = lifter.pop();
+        let args = lifter.pop_args();
+        lifter.push(Expr::New(Box::new(method(object, name)), args));
+      }
+      CfgAction::NewObject => {
+        let name = lifter.pop();
+        let args = lifter.pop_args();
+        lifter.push(Expr::New(Box::new(Expr::Eval(Box::new(name))), args));
+      }
+      CfgAction::NextFrame => lifter.statement(Expr::call("nextFrame", Vec::new())),
+      CfgAction::Not => {
+        let value = lifter.pop();
+        lifter.push(value.not());
+      }
+      CfgAction::Or => lifter.binary("||"),
+      CfgAction::Play => lifter.statement(Expr::call("play", Vec::new())),
+      CfgAction::Pop => {
+        let value = lifter.pop();
+        if value.has_side_effects() {
+          lifter.statements.push(Stmt::Expr(value));
+        }
+      }
+      CfgAction::PrevFrame => lifter.statement(Expr::call("prevFrame", Vec::new())),
+      CfgAction::Push(action) => {
+        for value in action.values.iter() {
+          lifter.push(self.value(value));
+        }
+      }
+      CfgAction::PushDuplicate => {
+        let value = lifter.pop();
+        lifter.push(value.clone());
+        lifter.push(value);
+      }
+      CfgAction::RandomNumber => lifter.builtin("random", 1),
+      CfgAction::RemoveSprite => {
+        let target = lifter.pop();
+        lifter.statement(Expr::call("removeMovieClip", vec![target]));
+      }
+      CfgAction::SetMember => {
+        let value = lifter.pop();
+        let key = lifter.pop();
+        let object = lifter.pop();
+        lifter
+          .statements
+          .push(Stmt::Assign(Expr::Member(Box::new(object), Box::new(key)), value));
+      }
+      CfgAction::SetProperty => {
+        let value = lifter.pop();
+        let property = lifter.pop();
+        let target = lifter.pop();
+        lifter.statement(Expr::call("setProperty", vec![target, property_name(property), value]));
+      }
+      CfgAction::SetTarget(action) => {
+        lifter.statement(Expr::call("setTarget", vec![Expr::String(action.target_name.clone())]));
+      }
+      CfgAction::SetTarget2 => {
+        let target = lifter.pop();
+        lifter.statement(Expr::call("setTarget", vec![target]));
+      }
+      CfgAction::SetVariable => {
+        let value = lifter.pop();
+        let name = lifter.pop();
+        lifter.statements.push(Stmt::Assign(Expr::Eval(Box::new(name)), value));
+      }
+      CfgAction::StackSwap => {
+        let top = lifter.pop();
+        let second = lifter.pop();
+        lifter.push(top);
+        lifter.push(second);
+      }
+      CfgAction::StartDrag => {
+        let target = lifter.pop();
+        let lock_center = lifter.pop();
+        let constrain = lifter.pop();
+        let mut args = vec![target, lock_center];
+        let constrained = match constrain {
+          Expr::Number(value) => Some(value != 0.0),
+          Expr::Boolean(value) => Some(value),
+          _ => None,
+        };
+        match constrained {
+          Some(false) => {}
+          Some(true) => {
+            let mut bounds: Vec<Expr> = (0..4).map(|_| lifter.pop()).collect();
+            bounds.reverse();
+            args.extend(bounds);
+          }
+          None => lifter
+            .statements
+            .push(Stmt::Comment("unknown drag constraint".to_string())),
+        }
+        lifter.statement(Expr::call("startDrag", args));
+      }
+      CfgAction::Stop => lifter.statement(Expr::call("stop", Vec::new())),
+      CfgAction::StopSounds => lifter.statement(Expr::call("stopAllSounds", Vec::new())),
+      CfgAction::StoreRegister(action) => {
+        let value = lifter.pop();
+        let register = Expr::Var(self.register_name(action.register));
+        if value != register {
+          lifter.statements.push(Stmt::Assign(register.clone(), value));
+        }
+        lifter.push(register);
+      }
+      CfgAction::StrictEquals => lifter.binary("==="),
+      CfgAction::StrictMode(action) => {
+        lifter
+          .statements
+          .push(Stmt::Comment(format!("strict mode: {}", action.is_strict)));
+      }
+      CfgAction::StringAdd => lifter.binary("add"),
+      CfgAction::StringEquals => lifter.binary("eq"),
+      CfgAction::StringExtract => lifter.builtin("substring", 3),
+      CfgAction::StringGreater => lifter.binary("gt"),
+      CfgAction::StringLength => lifter.builtin("length", 1),
+      CfgAction::StringLess => lifter.binary("lt"),
+      CfgAction::Subtract => lifter.binary("-"),
+      CfgAction::TargetPath => lifter.builtin("targetPath", 1),
+      CfgAction::ToggleQuality => lifter.statement(Expr::call("toggleHighQuality", Vec::new())),
+      CfgAction::ToInteger => lifter.builtin("int", 1),
+      CfgAction::ToNumber => lifter.builtin("Number", 1),
+      CfgAction::ToString => lifter.builtin("String", 1),
+      CfgAction::Trace => {
+        let value = lifter.pop();
+        lifter.statement(Expr::call("trace", vec![value]));
+      }
+      CfgAction::TypeOf => {
+        let value = lifter.pop();
+        lifter.push(Expr::unary("typeof", value));
+      }
+      CfgAction::Unknown(action) => {
+        lifter
+          .statements
+          .push(Stmt::Comment(format!("unknown action 0x{:02x}", action.code)));
+      }
+    }
+  }
+}
+
+/// Symbolic evaluation of the stack of a block.
+struct Lifter {
+  stack: Vec<Expr>,
+  statements: Vec<Stmt>,
+  underflow: bool,
+}
+
+impl Lifter {
+  fn push(&mut self, value: Expr) {
+    self.stack.push(value);
+  }
+
+  fn pop(&mut self) -> Expr {
+    match self.stack.pop() {
+      Some(value) => value,
+      None => {
+        self.underflow = true;
+        Expr::Undefined
+      }
+    }
+  }
+
+  /// Pops a count, then as many values (the first popped value is the first item).
+  fn pop_args(&mut self) -> Vec<Expr> {
+    let count = self.pop_count();
+    (0..count).map(|_| self.pop()).collect()
+  }
+
+  fn pop_count(&mut self) -> usize {
+    match self.pop() {
+      Expr::Number(count) if count >= 0.0 && count <= self.stack.len() as f64 => count as usize,
+      _ => {
+        self
+          .statements
+          .push(Stmt::Comment("unknown argument count".to_string()));
+        0
+      }
+    }
+  }
+
+  fn binary(&mut self, op: &'static str) {
+    let right = self.pop();
+    let left = self.pop();
+    self.push(Expr::binary(op, left, right));
+  }
+
+  /// Calls a global function with the `arity` top values of the stack (the top value is the last argument).
+  fn builtin(&mut self, name: &'static str, arity: usize) {
+    let mut args: Vec<Expr> = (0..arity).map(|_| self.pop()).collect();
+    args.reverse();
+    self.push(Expr::call(name, args));
+  }
+
+  /// Emits a statement for a call without result.
+  fn statement(&mut self, call: Expr) {
+    self.statements.push(Stmt::Expr(call));
+  }
+
+  fn function(&mut self, function: Function) {
+    if function.name.is_empty() {
+      self.push(Expr::Function(Box::new(function)));
+    } else {
+      self.statements.push(Stmt::Function(function));
+    }
+  }
+}
+
+/// Returns the callee of `CallMethod` and `NewMethod`: a missing name calls the object itself.
+fn method(object: Expr, name: Expr) -> Expr {
+  match name {
+    Expr::Undefined => object,
+    Expr::String(ref name) if name.is_empty() => object,
+    name => Expr::Member(Box::new(object), Box::new(name)),
+  }
+}
+
+fn property_name(property: Expr) -> Expr {
+  match property {
+    Expr::Number(index) if index >= 0.0 && index == index.trunc() => match PROPERTY_NAMES.get(index as usize) {
+      Some(name) => Expr::Builtin(name),
+      None => property,
+    },
+    property => property,
+  }
+}
+
+pub(crate) fn temporary(slot: usize) -> Expr {
+  Expr::Var(format!("_s{}", slot))
+}
+
+/// Tests if the expression is a constant, safe to propagate to other blocks.
+fn is_constant(expr: &Expr) -> bool {
+  match expr {
+    Expr::Undefined | Expr::Null | Expr::Boolean(_) | Expr::Number(_) | Expr::String(_) | Expr::Builtin(_) => true,
+    Expr::Unary(op, value) => *op != "delete" && is_constant(value),
+    Expr::Binary(_, left, right) => is_constant(left) && is_constant(right),
+    _ => false,
+  }
+}
+
+/// Returns the stack at the start of a block: constants shared by all the predecessors are propagated, the other
+/// values are read from temporaries. `exit_stacks` contains the stacks of the blocks lifted so far.
+pub(crate) fn entry_stack(predecessors: &[usize], exit_stacks: &[Vec<Expr>]) -> Vec<Expr> {
+  let known: Vec<&Vec<Expr>> = predecessors
+    .iter()
+    .filter_map(|predecessor| exit_stacks.get(*predecessor))
+    .collect();
+  let complete = known.len() == predecessors.len();
+  let depth = known.first().map_or(0, |stack| stack.len());
+  (0..depth)
+    .map(|slot| {
+      let value = &known[0][slot];
+      if complete && is_constant(value) && known.iter().all(|stack| stack.get(slot) == Some(value)) {
+        value.clone()
+      } else {
+        temporary(slot)
+      }
+    })
+    .collect()
+}
+
+/// Returns the label following a `try` or `with` block, `Some(None)` for the end of the code and `None` if the
+/// nested code never completes normally.
+fn nested_follow(block: &CfgBlock, offsets: &HashMap<CfgLabel, usize>) -> Option<Option<CfgLabel>> {
+  let children = block_children(block);
+  if children.is_empty() {
+    return None;
+  }
+  // Jumps between the `try`, `catch` and `finally` blocks stay inside the block
+  let defined: HashSet<&CfgLabel> = children.iter().flat_map(|child| cfg_labels(child)).collect();
+  let mut exits: Vec<Option<CfgLabel>> = Vec::new();
+  for child in children {
+    collect_exits(child, &defined, &mut exits);
+  }
+  // The code following a block is usually the closest label after it
+  let offset = offsets.get(block_label(block));
+  let after = exits
+    .iter()
+    .filter_map(|exit| exit.as_ref())
+    .filter(|label| offsets.get(label) > offset)
+    .min_by_key(|label| offsets.get(label));
+  match after {
+    Some(label) => Some(Some(label.clone())),
+    None => exits.into_iter().next(),
+  }
+}
+
+fn collect_exits(cfg: &Cfg, defined: &HashSet<&CfgLabel>, exits: &mut Vec<Option<CfgLabel>>) {
+  for block in cfg.blocks.iter() {
+    let targets = match block {
+      CfgBlock::If(block) => vec![&block.if_true, &block.if_false],
+      CfgBlock::Simple(block) => vec![&block.next],
+      CfgBlock::WaitForFrame(block) => vec![&block.if_loaded, &block.if_not_loaded],
+      CfgBlock::WaitForFrame2(block) => vec![&block.if_loaded, &block.if_not_loaded],
+      _ => Vec::new(),
+    };
+    for target in targets {
+      let is_exit = match target {
+        Some(label) => !defined.contains(label),
+        None => true,
+      };
+      if is_exit && !exits.contains(target) {
+        exits.push(target.clone());
+      }
+    }
+    for child in block_children(block) {
+      collect_exits(child, defined, exits);
+    }
+  }
+}
+
+impl<'a> Region<'a> {
+  /// Converts structured code to statements; `tail` indicates that the code completes the region.
+  fn convert(&mut self, items: Vec<Structured>, tail: bool, out: &mut Vec<Stmt>) {
+    let len = items.len();
+    for (i, item) in items.into_iter().enumerate() {
+      let tail = tail && i + 1 == len;
+      match item {
+        Structured::Node(node) => {
+          out.push(Stmt::Label(self.labels[node].0.clone()));
+          out.append(&mut self.lifted[node].statements);
+        }
+        Structured::If {
+          node,
+          negate,
+          then,
+          r#else,
+        } => {
+          let condition = self.lifted[node].condition.take().unwrap_or(Expr::Undefined);
+          let mut condition = if negate { condition.not() } else { condition };
+          let (mut then_statements, mut else_statements) = (Vec::new(), Vec::new());
+          self.convert(then, tail, &mut then_statements);
+          self.convert(r#else, tail, &mut else_statements);
+          if let Expr::Unary("!", _) = condition {
+            if !else_statements.is_empty() {
+              condition = condition.not();
+              std::mem::swap(&mut then_statements, &mut else_statements);
+            }
+          }
+          out.push(Stmt::If(condition, then_statements, else_statements));
+        }
+        Structured::Loop(body) => {
+          let mut statements = Vec::new();
+          self.convert(body, false, &mut statements);
+          out.push(Stmt::While(Expr::Boolean(true), statements));
+        }
+        Structured::Break => out.push(Stmt::Break),
+        Structured::Continue => out.push(Stmt::Continue),
+        Structured::Goto(node) => out.push(Stmt::Goto(self.labels[node].0.clone())),
+        Structured::Exit(exit) => {
+          let exit = &self.exits[exit];
+          if tail && self.follows.contains(exit) {
+            continue;
+          }
+          match exit {
+            Some(label) => out.push(Stmt::Goto(label.0.clone())),
+            None => out.push(Stmt::Return(None)),
+          }
+        }
+      }
+    }
+  }
+}
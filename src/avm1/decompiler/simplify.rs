@@ -0,0 +1,291 @@
+//! Simplifications of the decompiled statements.
+
+use super::ast::{Expr, Stmt};
+use std::collections::{HashMap, HashSet};
+
+/// Simplifies the statements of a function body.
+pub(crate) fn simplify_body(statements: &mut Vec<Stmt>) {
+  let mut targets = HashSet::new();
+  collect_gotos(statements, &mut targets);
+  remove_labels(statements, &targets);
+  simplify(statements);
+  let mut uses = HashMap::new();
+  count_temporaries(statements, &mut uses);
+  inline_temporaries(statements, &uses);
+  if statements.last() == Some(&Stmt::Return(None)) {
+    statements.pop();
+  }
+}
+
+/// Tests if the variable is a stack temporary (`_s0`, `_s1`, ...).
+fn is_temporary(name: &str) -> bool {
+  match name.strip_prefix("_s") {
+    Some(index) => !index.is_empty() && index.bytes().all(|c| c.is_ascii_digit()),
+    None => false,
+  }
+}
+
+/// Returns the statement lists nested in a statement (excluding function bodies).
+fn children(statement: &Stmt) -> Vec<&Vec<Stmt>> {
+  match statement {
+    Stmt::If(_, then, r#else) => vec![then, r#else],
+    Stmt::While(_, body) | Stmt::DoWhile(body, _) | Stmt::With(_, body) => vec![body],
+    Stmt::Try { body, catches, finally } => {
+      let mut children = vec![body];
+      children.extend(catches.iter().map(|(_, body)| body));
+      children.extend(finally.iter());
+      children
+    }
+    _ => Vec::new(),
+  }
+}
+
+fn children_mut(statement: &mut Stmt) -> Vec<&mut Vec<Stmt>> {
+  match statement {
+    Stmt::If(_, then, r#else) => vec![then, r#else],
+    Stmt::While(_, body) | Stmt::DoWhile(body, _) | Stmt::With(_, body) => vec![body],
+    Stmt::Try { body, catches, finally } => {
+      let mut children = vec![body];
+      children.extend(catches.iter_mut().map(|(_, body)| body));
+      children.extend(finally.iter_mut());
+      children
+    }
+    _ => Vec::new(),
+  }
+}
+
+/// Returns the expressions evaluated directly by a statement.
+fn expressions_mut(statement: &mut Stmt) -> Vec<&mut Expr> {
+  match statement {
+    Stmt::Expr(value) | Stmt::Throw(value) | Stmt::Return(Some(value)) => vec![value],
+    Stmt::Var(name, value) => std::iter::once(name).chain(value.iter_mut()).collect(),
+    Stmt::Assign(target, value) => vec![target, value],
+    Stmt::If(condition, ..) | Stmt::While(condition, _) | Stmt::DoWhile(_, condition) | Stmt::With(condition, _) => {
+      vec![condition]
+    }
+    Stmt::Extends(subclass, superclass) => vec![subclass, superclass],
+    Stmt::Implements(constructor, interfaces) => std::iter::once(constructor).chain(interfaces.iter_mut()).collect(),
+    _ => Vec::new(),
+  }
+}
+
+/// Returns the sub-expressions of an expression (excluding function bodies).
+fn operands_mut(expr: &mut Expr) -> Vec<&mut Expr> {
+  match expr {
+    Expr::Eval(value) | Expr::Unary(_, value) => vec![value],
+    Expr::Member(left, right) | Expr::Binary(_, left, right) => vec![left, right],
+    Expr::Call(callee, args) | Expr::New(callee, args) => {
+      std::iter::once(&mut **callee).chain(args.iter_mut()).collect()
+    }
+    Expr::Array(items) => items.iter_mut().collect(),
+    Expr::Object(properties) => properties
+      .iter_mut()
+      .flat_map(|(key, value)| vec![key, value])
+      .collect(),
+    Expr::Ternary(condition, then, r#else) => vec![condition, then, r#else],
+    _ => Vec::new(),
+  }
+}
+
+fn collect_gotos(statements: &[Stmt], targets: &mut HashSet<String>) {
+  for statement in statements {
+    if let Stmt::Goto(label) = statement {
+      targets.insert(label.clone());
+    }
+    for child in children(statement) {
+      collect_gotos(child, targets);
+    }
+  }
+}
+
+fn remove_labels(statements: &mut Vec<Stmt>, targets: &HashSet<String>) {
+  statements.retain(|statement| match statement {
+    Stmt::Label(label) => targets.contains(label),
+    _ => true,
+  });
+  for statement in statements.iter_mut() {
+    for child in children_mut(statement) {
+      remove_labels(child, targets);
+    }
+  }
+}
+
+/// Tests if the execution never continues after these statements.
+fn is_terminal(statements: &[Stmt]) -> bool {
+  match statements.last() {
+    Some(Stmt::Return(_)) | Some(Stmt::Throw(_)) | Some(Stmt::Break) | Some(Stmt::Continue) | Some(Stmt::Goto(_)) => {
+      true
+    }
+    Some(Stmt::If(_, then, r#else)) => is_terminal(then) && is_terminal(r#else),
+    _ => false,
+  }
+}
+
+/// Tests if the statements contain a `continue` for the enclosing loop.
+fn has_continue(statements: &[Stmt]) -> bool {
+  statements.iter().any(|statement| match statement {
+    Stmt::Continue => true,
+    Stmt::While(..) | Stmt::DoWhile(..) => false,
+    statement => children(statement).into_iter().any(|child| has_continue(child)),
+  })
+}
+
+/// Recovers conditional expressions, `while` and `do..while` loops, and moves `else` branches after the `if`
+/// statement when the other branch never completes.
+fn simplify(statements: &mut Vec<Stmt>) {
+  let mut i = 0;
+  while i < statements.len() {
+    for child in children_mut(&mut statements[i]) {
+      simplify(child);
+    }
+    let statement = &mut statements[i];
+    match statement {
+      Stmt::If(condition, then, r#else) => {
+        let swap = if then.is_empty() {
+          !r#else.is_empty()
+        } else {
+          !r#else.is_empty() && is_terminal(r#else) && !is_terminal(then)
+        };
+        if swap {
+          *condition = std::mem::replace(condition, Expr::Undefined).not();
+          std::mem::swap(then, r#else);
+        }
+        let ternary = match (then.as_slice(), r#else.as_slice()) {
+          ([Stmt::Assign(Expr::Var(left), then)], [Stmt::Assign(Expr::Var(right), r#else)])
+            if left == right && is_temporary(left) =>
+          {
+            Some((left.clone(), then.clone(), r#else.clone()))
+          }
+          _ => None,
+        };
+        if let Some((name, then, r#else)) = ternary {
+          let condition = std::mem::replace(condition, Expr::Undefined);
+          *statement = Stmt::Assign(
+            Expr::Var(name),
+            Expr::Ternary(Box::new(condition), Box::new(then), Box::new(r#else)),
+          );
+        } else if !r#else.is_empty() && is_terminal(then) {
+          let rest = std::mem::take(r#else);
+          statements.splice(i + 1..i + 1, rest);
+        }
+      }
+      Stmt::While(condition, body) if *condition == Expr::Boolean(true) => {
+        let exit = match body.first() {
+          Some(Stmt::If(exit, then, r#else)) if then.as_slice() == [Stmt::Break] && r#else.is_empty() => {
+            Some(exit.clone())
+          }
+          _ => None,
+        };
+        if let Some(exit) = exit {
+          *condition = exit.not();
+          body.remove(0);
+        } else {
+          let exit = match body.last() {
+            Some(Stmt::If(exit, then, r#else)) if then.as_slice() == [Stmt::Break] && r#else.is_empty() => {
+              Some(exit.clone())
+            }
+            _ => None,
+          };
+          if let Some(exit) = exit {
+            if !has_continue(body) {
+              body.pop();
+              *statement = Stmt::DoWhile(std::mem::take(body), exit.not());
+            }
+          }
+        }
+      }
+      _ => {}
+    }
+    i += 1;
+  }
+}
+
+fn count_temporaries(statements: &mut [Stmt], uses: &mut HashMap<String, usize>) {
+  for statement in statements.iter_mut() {
+    for expr in expressions_mut(statement) {
+      count_expr_temporaries(expr, uses);
+    }
+    for child in children_mut(statement) {
+      count_temporaries(child, uses);
+    }
+  }
+}
+
+fn count_expr_temporaries(expr: &mut Expr, uses: &mut HashMap<String, usize>) {
+  if let Expr::Var(name) = expr {
+    if is_temporary(name) {
+      *uses.entry(name.clone()).or_insert(0) += 1;
+    }
+  }
+  for operand in operands_mut(expr) {
+    count_expr_temporaries(operand, uses);
+  }
+}
+
+/// Replaces the first occurrence of the variable `name`, returns `false` if it was not found.
+fn replace_var(expr: &mut Expr, name: &str, value: &mut Option<Expr>) -> bool {
+  if let Expr::Var(var) = expr {
+    if var == name {
+      if let Some(value) = value.take() {
+        *expr = value;
+      }
+      return true;
+    }
+  }
+  operands_mut(expr)
+    .into_iter()
+    .any(|operand| replace_var(operand, name, value))
+}
+
+/// Replaces temporaries assigned once and used once by the next statement by their value.
+fn inline_temporaries(statements: &mut Vec<Stmt>, uses: &HashMap<String, usize>) {
+  let mut i = 0;
+  while i < statements.len() {
+    for child in children_mut(&mut statements[i]) {
+      inline_temporaries(child, uses);
+    }
+    let name = match &statements[i] {
+      Stmt::Assign(Expr::Var(name), _) if is_temporary(name) && uses.get(name) == Some(&2) => name.clone(),
+      _ => {
+        i += 1;
+        continue;
+      }
+    };
+    let next_is_pure = match statements.get(i + 1) {
+      Some(next) => {
+        let mut next = next.clone();
+        let mut placeholder = Some(Expr::Undefined);
+        expressions_mut(&mut next)
+          .into_iter()
+          .any(|expr| replace_var(expr, &name, &mut placeholder));
+        expressions_mut(&mut next).into_iter().all(|expr| match expr {
+          // The variable of an assignment is not evaluated
+          Expr::Eval(name) => !name.has_side_effects(),
+          expr => !expr.has_side_effects(),
+        })
+      }
+      None => false,
+    };
+    let value_is_pure = match &statements[i] {
+      Stmt::Assign(_, value) => !value.has_side_effects(),
+      _ => false,
+    };
+    if !(next_is_pure || value_is_pure) {
+      i += 1;
+      continue;
+    }
+    let value = match statements.get(i) {
+      Some(Stmt::Assign(_, value)) => value.clone(),
+      _ => unreachable!(),
+    };
+    let mut value = Some(value);
+    let replaced = expressions_mut(&mut statements[i + 1])
+      .into_iter()
+      .any(|expr| replace_var(expr, &name, &mut value));
+    if replaced {
+      statements.remove(i);
+    } else {
+      i += 1;
+    }
+  }
+}
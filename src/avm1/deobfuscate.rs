@@ -0,0 +1,561 @@
+//! Deobfuscation of AVM1 control flow graphs.
+//!
+//! The passes are repeated until the CFG no longer changes:
+//! - Constant folding: operations on values pushed in the same block are evaluated, conditional branches on a known
+//!   value (opaque predicates) become unconditional.
+//! - Jump chains: references to empty blocks that only jump to another block are redirected to the final target.
+//! - Unreachable blocks are removed.
+//! - Blocks only reachable by a jump from the previous block are merged into it.
+//!
+//! Folding assumes the semantics of SWF 5 and later, and only evaluates operations whose result does not depend on the
+//! SWF version. Values from the constant pool are only resolved when the buffer defines a single constant pool.
+
+use crate::avm1::{block_actions, block_actions_mut, block_children, block_label};
+use avm1_types::actions::Push;
+use avm1_types::cfg::{Cfg, CfgAction, CfgBlock, CfgLabel, CfgSimpleBlock};
+use avm1_types::Value;
+use std::collections::{HashMap, HashSet};
+use vec1::Vec1;
+
+/// Number of changes applied by each pass.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct Stats {
+  /// Folded actions
+  pub folded: usize,
+  /// Conditional branches replaced by a jump
+  pub branches: usize,
+  /// Jumps redirected to the end of their chain
+  pub jumps: usize,
+  /// Removed unreachable blocks
+  pub removed: usize,
+  /// Blocks merged into their predecessor
+  pub merged: usize,
+}
+
+/// Returns the deobfuscated CFG.
+pub(crate) fn deobfuscate(cfg: &Cfg) -> (Cfg, Stats) {
+  let pool = constant_pool(cfg);
+  let mut cfg = cfg.clone();
+  let mut stats = Stats::default();
+  loop {
+    let previous = stats.clone();
+    visit_cfgs_mut(&mut cfg, &mut |cfg| {
+      for block in cfg.blocks.iter_mut() {
+        fold_block(block, pool.as_deref(), &mut stats);
+      }
+    });
+    collapse_jumps(&mut cfg, &mut stats);
+    remove_unreachable(&mut cfg, &mut stats);
+    merge_blocks(&mut cfg, &mut stats);
+    if stats == previous {
+      return (cfg, stats);
+    }
+  }
+}
+
+/// Returns the constant pool of the buffer, if it is defined by a single `ConstantPool` action.
+fn constant_pool(cfg: &Cfg) -> Option<Vec<String>> {
+  fn collect<'a>(cfg: &'a Cfg, pools: &mut Vec<&'a Vec<String>>) {
+    for block in cfg.blocks.iter() {
+      for action in block_actions(block) {
+        match action {
+          CfgAction::ConstantPool(action) => pools.push(&action.constant_pool),
+          CfgAction::DefineFunction(action) => collect(&action.body, pools),
+          CfgAction::DefineFunction2(action) => collect(&action.body, pools),
+          _ => {}
+        }
+      }
+      for child in block_children(block) {
+        collect(child, pools);
+      }
+    }
+  }
+  let mut pools = Vec::new();
+  collect(cfg, &mut pools);
+  match pools.as_slice() {
+    [pool] => Some(pool.to_vec()),
+    _ => None,
+  }
+}
+
+/// Returns the CFGs nested in a block, including function bodies.
+fn nested_cfgs(block: &CfgBlock) -> Vec<&Cfg> {
+  let mut cfgs: Vec<&Cfg> = block_actions(block)
+    .iter()
+    .filter_map(|action| match action {
+      CfgAction::DefineFunction(action) => Some(&action.body),
+      CfgAction::DefineFunction2(action) => Some(&action.body),
+      _ => None,
+    })
+    .collect();
+  cfgs.extend(block_children(block));
+  cfgs
+}
+
+fn nested_cfgs_mut(block: &mut CfgBlock) -> Vec<&mut Cfg> {
+  let (actions, children): (&mut Vec<CfgAction>, Vec<&mut Cfg>) = match block {
+    CfgBlock::Try(block) => {
+      let mut children = vec![&mut block.r#try];
+      children.extend(block.catch.iter_mut());
+      children.extend(block.finally.iter_mut());
+      (&mut block.actions, children)
+    }
+    CfgBlock::With(block) => (&mut block.actions, vec![&mut block.with]),
+    block => (block_actions_mut(block), Vec::new()),
+  };
+  let mut cfgs: Vec<&mut Cfg> = actions
+    .iter_mut()
+    .filter_map(|action| match action {
+      CfgAction::DefineFunction(action) => Some(&mut action.body),
+      CfgAction::DefineFunction2(action) => Some(&mut action.body),
+      _ => None,
+    })
+    .collect();
+  cfgs.extend(children);
+  cfgs
+}
+
+/// Calls `f` on the CFG and on all the CFGs nested inside it, parents first.
+fn visit_cfgs_mut(cfg: &mut Cfg, f: &mut dyn FnMut(&mut Cfg)) {
+  f(cfg);
+  for block in cfg.blocks.iter_mut() {
+    for nested in nested_cfgs_mut(block) {
+      visit_cfgs_mut(nested, f);
+    }
+  }
+}
+
+/// Returns the explicit jump targets of a block.
+fn block_targets(block: &CfgBlock) -> Vec<&CfgLabel> {
+  let targets = match block {
+    CfgBlock::If(block) => vec![&block.if_true, &block.if_false],
+    CfgBlock::Simple(block) => vec![&block.next],
+    CfgBlock::WaitForFrame(block) => vec![&block.if_loaded, &block.if_not_loaded],
+    CfgBlock::WaitForFrame2(block) => vec![&block.if_loaded, &block.if_not_loaded],
+    _ => Vec::new(),
+  };
+  targets.into_iter().flatten().collect()
+}
+
+fn block_targets_mut(block: &mut CfgBlock) -> Vec<&mut Option<CfgLabel>> {
+  match block {
+    CfgBlock::If(block) => vec![&mut block.if_true, &mut block.if_false],
+    CfgBlock::Simple(block) => vec![&mut block.next],
+    CfgBlock::WaitForFrame(block) => vec![&mut block.if_loaded, &mut block.if_not_loaded],
+    CfgBlock::WaitForFrame2(block) => vec![&mut block.if_loaded, &mut block.if_not_loaded],
+    _ => Vec::new(),
+  }
+}
+
+/// Takes the blocks out of a CFG, they must be put back before the CFG is used.
+fn take_blocks(cfg: &mut Cfg) -> Vec<CfgBlock> {
+  let placeholder = CfgBlock::Simple(CfgSimpleBlock {
+    label: CfgLabel(String::new()),
+    actions: Vec::new(),
+    next: None,
+  });
+  std::mem::replace(&mut cfg.blocks, Vec1::new(placeholder)).into_vec()
+}
+
+/// Redirects jumps to empty blocks to the end of the chain, and replaces conditional branches to the same block by a
+/// jump.
+fn collapse_jumps(cfg: &mut Cfg, stats: &mut Stats) {
+  let mut redirects: HashMap<CfgLabel, CfgLabel> = HashMap::new();
+  visit_cfgs_mut(cfg, &mut |cfg| {
+    for block in cfg.blocks.iter() {
+      if let CfgBlock::Simple(CfgSimpleBlock {
+        label,
+        actions,
+        next: Some(next),
+      }) = block
+      {
+        if actions.is_empty() && next != label {
+          redirects.insert(label.clone(), next.clone());
+        }
+      }
+    }
+  });
+  let resolve = |label: &CfgLabel| -> Option<CfgLabel> {
+    let mut visited: HashSet<&CfgLabel> = HashSet::new();
+    let mut current = label;
+    while let Some(next) = redirects.get(current) {
+      if !visited.insert(current) {
+        // Infinite loop of empty blocks
+        return None;
+      }
+      current = next;
+    }
+    if current == label {
+      None
+    } else {
+      Some(current.clone())
+    }
+  };
+  visit_cfgs_mut(cfg, &mut |cfg| {
+    for block in cfg.blocks.iter_mut() {
+      for target in block_targets_mut(block) {
+        if let Some(resolved) = target.as_ref().and_then(&resolve) {
+          *target = Some(resolved);
+          stats.jumps += 1;
+        }
+      }
+      if let CfgBlock::If(if_block) = block {
+        if if_block.if_true == if_block.if_false {
+          let mut actions = std::mem::take(&mut if_block.actions);
+          actions.push(CfgAction::Pop);
+          *block = CfgBlock::Simple(CfgSimpleBlock {
+            label: if_block.label.clone(),
+            actions,
+            next: if_block.if_true.clone(),
+          });
+          stats.branches += 1;
+        }
+      }
+    }
+  });
+}
+
+/// Removes the blocks that can't be reached from the entry point of the buffer.
+fn remove_unreachable(cfg: &mut Cfg, stats: &mut Stats) {
+  let mut reachable: HashSet<CfgLabel> = HashSet::new();
+  {
+    fn index<'a>(cfg: &'a Cfg, blocks: &mut HashMap<&'a CfgLabel, &'a CfgBlock>) {
+      for block in cfg.blocks.iter() {
+        blocks.insert(block_label(block), block);
+        for nested in nested_cfgs(block) {
+          index(nested, blocks);
+        }
+      }
+    }
+    let mut blocks: HashMap<&CfgLabel, &CfgBlock> = HashMap::new();
+    index(cfg, &mut blocks);
+    let mut queue: Vec<&CfgLabel> = vec![block_label(cfg.blocks.first())];
+    while let Some(label) = queue.pop() {
+      if reachable.contains(label) {
+        continue;
+      }
+      reachable.insert(label.clone());
+      if let Some(block) = blocks.get(label) {
+        queue.extend(block_targets(block));
+        queue.extend(
+          nested_cfgs(block)
+            .into_iter()
+            .map(|nested| block_label(nested.blocks.first())),
+        );
+      }
+    }
+  }
+  visit_cfgs_mut(cfg, &mut |cfg| {
+    let blocks = take_blocks(cfg);
+    let count = blocks.len();
+    let blocks: Vec<CfgBlock> = blocks
+      .into_iter()
+      .enumerate()
+      .filter(|(i, block)| *i == 0 || reachable.contains(block_label(block)))
+      .map(|(_, block)| block)
+      .collect();
+    stats.removed += count - blocks.len();
+    cfg.blocks = Vec1::try_from_vec(blocks).expect("The entry block is always kept");
+  });
+}
+
+/// Merges blocks with a single predecessor into it, when the predecessor jumps unconditionally to the block.
+fn merge_blocks(cfg: &mut Cfg, stats: &mut Stats) {
+  let mut references: HashMap<CfgLabel, usize> = HashMap::new();
+  visit_cfgs_mut(cfg, &mut |cfg| {
+    for block in cfg.blocks.iter() {
+      for target in block_targets(block) {
+        *references.entry(target.clone()).or_insert(0) += 1;
+      }
+    }
+  });
+  visit_cfgs_mut(cfg, &mut |cfg| {
+    let mut blocks = take_blocks(cfg);
+    let mut i = 0;
+    while i < blocks.len() {
+      let successor = match &blocks[i] {
+        CfgBlock::Simple(CfgSimpleBlock { next: Some(next), .. }) if references.get(next) == Some(&1) => blocks
+          .iter()
+          .skip(1)
+          .position(|block| block_label(block) == next)
+          .map(|position| position + 1)
+          .filter(|position| *position != i),
+        _ => None,
+      };
+      let successor = match successor {
+        Some(successor) => successor,
+        None => {
+          i += 1;
+          continue;
+        }
+      };
+      let mut merged = blocks.remove(successor);
+      let index = if successor < i { i - 1 } else { i };
+      let label = block_label(&blocks[index]).clone();
+      let mut actions = std::mem::take(block_actions_mut(&mut blocks[index]));
+      actions.append(block_actions_mut(&mut merged));
+      *block_actions_mut(&mut merged) = actions;
+      set_block_label(&mut merged, label);
+      blocks[index] = merged;
+      stats.merged += 1;
+      // The merged block may itself end with a jump to a block that can be merged
+      i = index;
+    }
+    cfg.blocks = Vec1::try_from_vec(blocks).expect("Merging blocks never removes all the blocks");
+  });
+}
+
+fn set_block_label(block: &mut CfgBlock, label: CfgLabel) {
+  match block {
+    CfgBlock::Error(block) => block.label = label,
+    CfgBlock::If(block) => block.label = label,
+    CfgBlock::Return(block) => block.label = label,
+    CfgBlock::Simple(block) => block.label = label,
+    CfgBlock::Throw(block) => block.label = label,
+    CfgBlock::Try(block) => block.label = label,
+    CfgBlock::WaitForFrame(block) => block.label = label,
+    CfgBlock::WaitForFrame2(block) => block.label = label,
+    CfgBlock::With(block) => block.label = label,
+  }
+}
+
+/// Known primitive value.
+#[derive(Clone, Debug, PartialEq)]
+enum Constant {
+  Undefined,
+  Null,
+  Boolean(bool),
+  Number(f64),
+  String(String),
+}
+
+impl Constant {
+  fn from_value(value: &Value, pool: Option<&[String]>) -> Option<Constant> {
+    let constant = match value {
+      Value::Boolean(value) => Constant::Boolean(*value),
+      Value::Constant(index) => Constant::String(pool?.get(usize::from(*index))?.clone()),
+      Value::Float32(value) => Constant::Number(f64::from(*value)),
+      Value::Float64(value) => Constant::Number(*value),
+      Value::Null => Constant::Null,
+      Value::Register(_) => return None,
+      Value::String(value) => Constant::String(value.clone()),
+      Value::Sint32(value) => Constant::Number(f64::from(*value)),
+      Value::Undefined => Constant::Undefined,
+    };
+    Some(constant)
+  }
+
+  fn to_value(&self) -> Value {
+    match self {
+      Constant::Undefined => Value::Undefined,
+      Constant::Null => Value::Null,
+      Constant::Boolean(value) => Value::Boolean(*value),
+      Constant::Number(value) => {
+        let is_int = value.fract() == 0.0 && *value >= f64::from(i32::MIN) && *value <= f64::from(i32::MAX);
+        if is_int && !(*value == 0.0 && value.is_sign_negative()) {
+          Value::Sint32(*value as i32)
+        } else {
+          Value::Float64(*value)
+        }
+      }
+      Constant::String(value) => Value::String(value.clone()),
+    }
+  }
+
+  /// Converts the value to a boolean, `None` if the result depends on the SWF version.
+  fn to_boolean(&self) -> Option<bool> {
+    match self {
+      Constant::Undefined | Constant::Null => Some(false),
+      Constant::Boolean(value) => Some(*value),
+      Constant::Number(value) => Some(*value != 0.0 && !value.is_nan()),
+      Constant::String(_) => None,
+    }
+  }
+}
+
+/// Formats an integer as a string, `None` for other numbers (their format differs from Rust).
+fn integer_to_string(value: f64) -> Option<String> {
+  if value.fract() == 0.0 && value.abs() < 1e15 {
+    Some(format!("{}", value as i64))
+  } else {
+    None
+  }
+}
+
+pub(crate) fn to_int32(value: f64) -> i32 {
+  if value.is_finite() {
+    value.trunc().rem_euclid(4_294_967_296.0) as u32 as i32
+  } else {
+    0
+  }
+}
+
+/// Evaluates an action on known operands (the last operand is the top of the stack).
+fn evaluate(action: &CfgAction, operands: &[Constant]) -> Option<Constant> {
+  use Constant::{Boolean, Null, Number, Undefined};
+  let result = match (action, operands) {
+    (CfgAction::Add2, [Number(left), Number(right)]) => Number(left + right),
+    (CfgAction::Add2, [Constant::String(left), Constant::String(right)])
+    | (CfgAction::StringAdd, [Constant::String(left), Constant::String(right)]) => {
+      Constant::String(format!("{}{}", left, right))
+    }
+    (CfgAction::Add2, [Constant::String(left), Number(right)]) => {
+      Constant::String(format!("{}{}", left, integer_to_string(*right)?))
+    }
+    (CfgAction::Add2, [Number(left), Constant::String(right)]) => {
+      Constant::String(format!("{}{}", integer_to_string(*left)?, right))
+    }
+    (CfgAction::Subtract, [Number(left), Number(right)]) => Number(left - right),
+    (CfgAction::Multiply, [Number(left), Number(right)]) => Number(left * right),
+    (CfgAction::Divide, [Number(left), Number(right)]) if *right != 0.0 => Number(left / right),
+    (CfgAction::Modulo, [Number(left), Number(right)]) if *right != 0.0 => Number(left % right),
+    (CfgAction::Less2, [Number(left), Number(right)]) | (CfgAction::Greater, [Number(right), Number(left)]) => {
+      if left.is_nan() || right.is_nan() {
+        Undefined
+      } else {
+        Boolean(left < right)
+      }
+    }
+    (CfgAction::Less2, [Constant::String(left), Constant::String(right)])
+    | (CfgAction::Greater, [Constant::String(right), Constant::String(left)]) => {
+      Boolean(left.encode_utf16().lt(right.encode_utf16()))
+    }
+    (CfgAction::Equals2, [left, right]) => Boolean(match (left, right) {
+      (Number(left), Number(right)) => left == right,
+      (Constant::String(left), Constant::String(right)) => left == right,
+      (Boolean(left), Boolean(right)) => left == right,
+      (Null, Null) | (Null, Undefined) | (Undefined, Null) | (Undefined, Undefined) => true,
+      (Null, _) | (Undefined, _) | (_, Null) | (_, Undefined) => false,
+      _ => return None,
+    }),
+    // `NaN` is not equal to itself, as required
+    (CfgAction::StrictEquals, [left, right]) => Boolean(left == right),
+    (CfgAction::Not, [value]) => Boolean(!value.to_boolean()?),
+    (CfgAction::BitAnd, [Number(left), Number(right)]) => Number(f64::from(to_int32(*left) & to_int32(*right))),
+    (CfgAction::BitOr, [Number(left), Number(right)]) => Number(f64::from(to_int32(*left) | to_int32(*right))),
+    (CfgAction::BitXor, [Number(left), Number(right)]) => Number(f64::from(to_int32(*left) ^ to_int32(*right))),
+    (CfgAction::BitLShift, [Number(left), Number(right)]) => {
+      Number(f64::from(to_int32(*left) << (to_int32(*right) & 31)))
+    }
+    (CfgAction::BitRShift, [Number(left), Number(right)]) => {
+      Number(f64::from(to_int32(*left) >> (to_int32(*right) & 31)))
+    }
+    (CfgAction::BitURShift, [Number(left), Number(right)]) => {
+      Number(f64::from((to_int32(*left) as u32) >> (to_int32(*right) & 31)))
+    }
+    (CfgAction::Increment, [Number(value)]) => Number(value + 1.0),
+    (CfgAction::Decrement, [Number(value)]) => Number(value - 1.0),
+    (CfgAction::TypeOf, [value]) => Constant::String(String::from(match value {
+      Undefined => "undefined",
+      Null => "null",
+      Boolean(_) => "boolean",
+      Number(_) => "number",
+      Constant::String(_) => "string",
+    })),
+    _ => return None,
+  };
+  Some(result)
+}
+
+/// Number of operands of the actions that can be folded.
+fn arity(action: &CfgAction) -> usize {
+  match action {
+    CfgAction::Not | CfgAction::Increment | CfgAction::Decrement | CfgAction::TypeOf => 1,
+    CfgAction::Add2
+    | CfgAction::StringAdd
+    | CfgAction::Subtract
+    | CfgAction::Multiply
+    | CfgAction::Divide
+    | CfgAction::Modulo
+    | CfgAction::Less2
+    | CfgAction::Greater
+    | CfgAction::Equals2
+    | CfgAction::StrictEquals
+    | CfgAction::BitAnd
+    | CfgAction::BitOr
+    | CfgAction::BitXor
+    | CfgAction::BitLShift
+    | CfgAction::BitRShift
+    | CfgAction::BitURShift => 2,
+    _ => 0,
+  }
+}
+
+/// Folds the actions of a block.
+fn fold_block(block: &mut CfgBlock, pool: Option<&[String]>, stats: &mut Stats) {
+  // Values pushed since the last emitted action, with their value if it is known
+  let mut pending: Vec<(Value, Option<Constant>)> = Vec::new();
+  let mut actions: Vec<CfgAction> = Vec::new();
+  let flush = |pending: &mut Vec<(Value, Option<Constant>)>, actions: &mut Vec<CfgAction>| {
+    if !pending.is_empty() {
+      let values = pending.drain(..).map(|(value, _)| value).collect();
+      actions.push(CfgAction::Push(Push { values }));
+    }
+  };
+  for action in std::mem::take(block_actions_mut(block)) {
+    match action {
+      CfgAction::Push(push) => {
+        pending.extend(push.values.into_iter().map(|value| {
+          let constant = Constant::from_value(&value, pool);
+          (value, constant)
+        }));
+        continue;
+      }
+      CfgAction::Pop if !pending.is_empty() => {
+        pending.pop();
+        stats.folded += 1;
+        continue;
+      }
+      CfgAction::PushDuplicate if !pending.is_empty() => {
+        pending.push(pending.last().unwrap().clone());
+        stats.folded += 1;
+        continue;
+      }
+      CfgAction::StackSwap if pending.len() >= 2 => {
+        let len = pending.len();
+        pending.swap(len - 1, len - 2);
+        stats.folded += 1;
+        continue;
+      }
+      _ => {}
+    }
+    let arity = arity(&action);
+    if arity > 0 && pending.len() >= arity {
+      let start = pending.len() - arity;
+      let operands: Option<Vec<Constant>> = pending[start..].iter().map(|(_, constant)| constant.clone()).collect();
+      if let Some(result) = operands.and_then(|operands| evaluate(&action, &operands)) {
+        pending.truncate(start);
+        pending.push((result.to_value(), Some(result)));
+        stats.folded += 1;
+        continue;
+      }
+    }
+    flush(&mut pending, &mut actions);
+    actions.push(action);
+  }
+
+  if let CfgBlock::If(if_block) = block {
+    let condition = match pending.last() {
+      Some((_, Some(condition))) => condition.to_boolean(),
+      _ => None,
+    };
+    if let Some(condition) = condition {
+      pending.pop();
+      flush(&mut pending, &mut actions);
+      let next = if condition {
+        if_block.if_true.clone()
+      } else {
+        if_block.if_false.clone()
+      };
+      *block = CfgBlock::Simple(CfgSimpleBlock {
+        label: if_block.label.clone(),
+        actions,
+        next,
+      });
+      stats.branches += 1;
+      return;
+    }
+  }
+  flush(&mut pending, &mut actions);
+  *block_actions_mut(block) = actions;
+}
@@ -0,0 +1,498 @@
+//! Textual disassembly of AVM1 buffers.
+//!
+//! Each line contains the offset of the action followed by its mnemonic and operands. Jump targets and block
+//! boundaries are replaced by labels, using the names of the blocks of the CFG when possible.
+
+use crate::avm1::raw::{self, Action, CatchTarget, PushValue, RawAction, DEFINE_FUNCTION2_FLAGS};
+use crate::avm1::{block_offsets, cfg_labels, simple_code};
+use avm1_types::cfg::{Cfg, CfgAction, CfgDefineFunction2};
+use avm1_types::{GetUrl2Method, Value};
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, HashSet};
+use std::convert::TryFrom;
+use std::fmt::Write;
+
+/// Returns the disassembly of `bytes`, `cfg` is the result of `parse_cfg` for the same buffer.
+pub(crate) fn disassemble(bytes: &[u8], cfg: &Cfg) -> String {
+  let actions = raw::read_actions(bytes);
+  let labels = resolve_labels(bytes, &actions, cfg);
+
+  let mut out = String::new();
+  writeln!(out, "; AVM1 disassembly ({} bytes)", bytes.len()).unwrap();
+  let mut constant_pool: Option<&[Vec<u8>]> = None;
+  let mut region_ends: Vec<usize> = Vec::new();
+  for action in actions.iter() {
+    while let Some(&end) = region_ends.last() {
+      if end > action.offset {
+        break;
+      }
+      region_ends.pop();
+    }
+    if let Some(label) = labels.get(&action.offset) {
+      writeln!(out, "{}:", label).unwrap();
+    }
+    let mut line = format!("{:>6}: {}", action.offset, "  ".repeat(region_ends.len()));
+    write_action(&mut line, action, &labels);
+    match &action.action {
+      Action::ConstantPool(pool) => constant_pool = Some(pool),
+      Action::Push(values) => {
+        let constants: Vec<String> = values
+          .iter()
+          .filter_map(|value| match value {
+            PushValue::Constant8(index) => Some(usize::from(*index)),
+            PushValue::Constant16(index) => Some(usize::from(*index)),
+            _ => None,
+          })
+          .map(|index| match constant_pool.and_then(|pool| pool.get(index)) {
+            Some(value) => format_string(value),
+            None => String::from("?"),
+          })
+          .collect();
+        if !constants.is_empty() {
+          write!(line, "  ; {}", constants.join(", ")).unwrap();
+        }
+      }
+      Action::Malformed { error, .. } => write!(line, "  ; error: {}", error).unwrap(),
+      _ => {}
+    }
+    writeln!(out, "{}", line).unwrap();
+    let nested_size = action.action.nested_size();
+    if nested_size > 0 {
+      region_ends.push(action.end() + nested_size);
+    }
+  }
+  if let Some(label) = labels.get(&bytes.len()) {
+    writeln!(out, "{}:", label).unwrap();
+  }
+  out
+}
+
+/// Returns the absolute targets of the jumps and nested code of an action.
+pub(crate) fn action_targets(action: &RawAction) -> Vec<i64> {
+  let end = action.end() as i64;
+  match &action.action {
+    Action::Jump(offset) | Action::If(offset) => vec![end + i64::from(*offset)],
+    Action::Try(action) => {
+      let try_end = end + i64::from(action.try_size);
+      let catch_end = try_end + i64::from(action.catch_size);
+      let finally_end = catch_end + i64::from(action.finally_size);
+      vec![try_end, catch_end, finally_end]
+    }
+    action => {
+      let nested_size = action.nested_size();
+      if nested_size > 0 {
+        vec![end + nested_size as i64]
+      } else {
+        Vec::new()
+      }
+    }
+  }
+}
+
+/// Assigns a label to the CFG blocks and action targets that start on an action boundary.
+fn resolve_labels(bytes: &[u8], actions: &[RawAction], cfg: &Cfg) -> BTreeMap<usize, String> {
+  let boundaries: HashSet<usize> = actions
+    .iter()
+    .map(|action| action.offset)
+    .chain(std::iter::once(bytes.len()))
+    .collect();
+  let mut labels: BTreeMap<usize, String> = BTreeMap::new();
+  let mut names: HashSet<String> = HashSet::new();
+  let offsets = block_offsets(bytes, cfg);
+  for label in cfg_labels(cfg) {
+    if let Some(&offset) = offsets.get(label) {
+      if boundaries.contains(&offset) && !labels.contains_key(&offset) && !names.contains(&label.0) {
+        names.insert(label.0.clone());
+        labels.insert(offset, label.0.clone());
+      }
+    }
+  }
+  for action in actions.iter() {
+    for target in action_targets(action) {
+      if target < 0 || !boundaries.contains(&(target as usize)) {
+        continue;
+      }
+      if let Entry::Vacant(entry) = labels.entry(target as usize) {
+        let mut name = format!("loc_{}", target);
+        while names.contains(&name) {
+          name.push('_');
+        }
+        names.insert(name.clone());
+        entry.insert(name);
+      }
+    }
+  }
+  labels
+}
+
+/// Formats a jump target or the end of a nested code region: a label if possible, else the raw operand value.
+fn format_target(target: i64, raw_value: i64, labels: &BTreeMap<usize, String>) -> String {
+  if target >= 0 {
+    if let Some(label) = labels.get(&(target as usize)) {
+      return label.clone();
+    }
+  }
+  format!("#{}", raw_value)
+}
+
+fn write_action(out: &mut String, action: &RawAction, labels: &BTreeMap<usize, String>) {
+  let end = action.end() as i64;
+  match &action.action {
+    Action::Unknown { .. } | Action::Malformed { .. } | Action::Truncated(_) => {}
+    known => out.push_str(known.code().and_then(raw::mnemonic).unwrap_or_default()),
+  }
+  match &action.action {
+    Action::Simple(_) | Action::Call => {}
+    Action::GotoFrame(frame) => write!(out, " {}", frame).unwrap(),
+    Action::GetUrl { url, target } => write!(out, " {}, {}", format_string(url), format_string(target)).unwrap(),
+    Action::StoreRegister(register) => write!(out, " r:{}", register).unwrap(),
+    Action::ConstantPool(pool) => {
+      let pool: Vec<String> = pool.iter().map(|value| format_string(value)).collect();
+      if !pool.is_empty() {
+        write!(out, " {}", pool.join(", ")).unwrap();
+      }
+    }
+    Action::StrictMode(is_strict) => write!(out, " {}", is_strict).unwrap(),
+    Action::WaitForFrame { frame, skip_count } => write!(out, " {} skip={}", frame, skip_count).unwrap(),
+    Action::SetTarget(target) => write!(out, " {}", format_string(target)).unwrap(),
+    Action::GotoLabel(label) => write!(out, " {}", format_string(label)).unwrap(),
+    Action::WaitForFrame2 { skip_count } => write!(out, " skip={}", skip_count).unwrap(),
+    Action::DefineFunction2(action) => {
+      let parameters: Vec<String> = action
+        .parameters
+        .iter()
+        .map(|(register, name)| match register {
+          0 => format_string(name),
+          register => format!("r:{}={}", register, format_string(name)),
+        })
+        .collect();
+      write!(
+        out,
+        " {} ({}) registers={}",
+        format_string(&action.name),
+        parameters.join(", "),
+        action.register_count
+      )
+      .unwrap();
+      let flags: Vec<&str> = DEFINE_FUNCTION2_FLAGS
+        .iter()
+        .enumerate()
+        .filter(|(bit, _)| action.flags & (1 << bit) != 0)
+        .map(|(_, name)| *name)
+        .collect();
+      if !flags.is_empty() {
+        write!(out, " flags={}", flags.join("|")).unwrap();
+      }
+      let size = i64::from(action.size);
+      write!(out, " end={}", format_target(end + size, size, labels)).unwrap();
+    }
+    Action::Try(action) => {
+      match &action.catch_target {
+        CatchTarget::Register(register) => write!(out, " target=r:{}", register).unwrap(),
+        CatchTarget::Variable(name) => write!(out, " target={}", format_string(name)).unwrap(),
+      }
+      let mut flags: Vec<&str> = Vec::new();
+      if action.has_catch {
+        flags.push("catch");
+      }
+      if action.has_finally {
+        flags.push("finally");
+      }
+      if !flags.is_empty() {
+        write!(out, " flags={}", flags.join("|")).unwrap();
+      }
+      let try_size = i64::from(action.try_size);
+      let catch_size = i64::from(action.catch_size);
+      let finally_size = i64::from(action.finally_size);
+      let try_end = end + try_size;
+      let catch_end = try_end + catch_size;
+      let finally_end = catch_end + finally_size;
+      write!(
+        out,
+        " try_end={} catch_end={} finally_end={}",
+        format_target(try_end, try_size, labels),
+        format_target(catch_end, catch_size, labels),
+        format_target(finally_end, finally_size, labels)
+      )
+      .unwrap();
+    }
+    Action::With { size } => {
+      let size = i64::from(*size);
+      write!(out, " end={}", format_target(end + size, size, labels)).unwrap();
+    }
+    Action::Push(values) => {
+      let values: Vec<String> = values.iter().map(format_push_value).collect();
+      write!(out, " {}", values.join(", ")).unwrap();
+    }
+    Action::Jump(offset) | Action::If(offset) => {
+      let offset = i64::from(*offset);
+      write!(out, " {}", format_target(end + offset, offset, labels)).unwrap();
+    }
+    Action::GetUrl2(action) => {
+      let method = match action.method {
+        0 => "none",
+        1 => "get",
+        _ => "post",
+      };
+      write!(out, " method={}", method).unwrap();
+      let mut flags: Vec<&str> = Vec::new();
+      if action.load_target {
+        flags.push("load_target");
+      }
+      if action.load_variables {
+        flags.push("load_variables");
+      }
+      if !flags.is_empty() {
+        write!(out, " flags={}", flags.join("|")).unwrap();
+      }
+    }
+    Action::DefineFunction(action) => {
+      let parameters: Vec<String> = action.parameters.iter().map(|name| format_string(name)).collect();
+      let size = i64::from(action.size);
+      write!(
+        out,
+        " {} ({}) end={}",
+        format_string(&action.name),
+        parameters.join(", "),
+        format_target(end + size, size, labels)
+      )
+      .unwrap();
+    }
+    Action::GotoFrame2 { play, scene_bias } => {
+      if *play {
+        out.push_str(" flags=play");
+      }
+      if let Some(scene_bias) = scene_bias {
+        write!(out, " scene_bias={}", scene_bias).unwrap();
+      }
+    }
+    Action::Unknown { code, data } => {
+      write!(out, ".action 0x{:02x}", code).unwrap();
+      if let Some(data) = data {
+        write!(out, " hex:{}", hex::encode(data)).unwrap();
+      }
+    }
+    Action::Malformed { code, data, .. } => write!(out, ".action 0x{:02x} hex:{}", code, hex::encode(data)).unwrap(),
+    Action::Truncated(bytes) => write!(out, ".bytes hex:{}", hex::encode(bytes)).unwrap(),
+  }
+}
+
+/// Formats an action of a CFG, using the same syntax as the disassembly.
+///
+/// Function bodies are not included.
+pub(crate) fn format_cfg_action(action: &CfgAction) -> String {
+  let format_strings = |values: &[String]| -> String {
+    let values: Vec<String> = values.iter().map(|value| format_string(value.as_bytes())).collect();
+    values.join(", ")
+  };
+  match action {
+    CfgAction::ConstantPool(action) => format!("ConstantPool {}", format_strings(&action.constant_pool)),
+    CfgAction::DefineFunction(action) => format!(
+      "DefineFunction {} ({})",
+      format_string(action.name.as_bytes()),
+      format_strings(&action.parameters)
+    ),
+    CfgAction::DefineFunction2(action) => {
+      let parameters: Vec<String> = action
+        .parameters
+        .iter()
+        .map(|parameter| match parameter.register {
+          0 => format_string(parameter.name.as_bytes()),
+          register => format!("r:{}={}", register, format_string(parameter.name.as_bytes())),
+        })
+        .collect();
+      let flags = cfg_function2_flags(action);
+      let flags: Vec<&str> = DEFINE_FUNCTION2_FLAGS
+        .iter()
+        .enumerate()
+        .filter(|(bit, _)| flags & (1 << bit) != 0)
+        .map(|(_, name)| *name)
+        .collect();
+      let mut out = format!(
+        "DefineFunction2 {} ({}) registers={}",
+        format_string(action.name.as_bytes()),
+        parameters.join(", "),
+        action.register_count
+      );
+      if !flags.is_empty() {
+        write!(out, " flags={}", flags.join("|")).unwrap();
+      }
+      out
+    }
+    CfgAction::GetUrl(action) => format!(
+      "GetUrl {}, {}",
+      format_string(action.url.as_bytes()),
+      format_string(action.target.as_bytes())
+    ),
+    CfgAction::GetUrl2(action) => {
+      let method = match action.send_vars_method {
+        GetUrl2Method::None => "none",
+        GetUrl2Method::Get => "get",
+        GetUrl2Method::Post => "post",
+      };
+      let mut out = format!("GetUrl2 method={}", method);
+      let mut flags: Vec<&str> = Vec::new();
+      if action.load_target {
+        flags.push("load_target");
+      }
+      if action.load_variables {
+        flags.push("load_variables");
+      }
+      if !flags.is_empty() {
+        write!(out, " flags={}", flags.join("|")).unwrap();
+      }
+      out
+    }
+    CfgAction::GotoFrame(action) => format!("GotoFrame {}", action.frame),
+    CfgAction::GotoFrame2(action) => {
+      let mut out = String::from("GotoFrame2");
+      if action.play {
+        out.push_str(" flags=play");
+      }
+      if action.scene_bias != 0 {
+        write!(out, " scene_bias={}", action.scene_bias).unwrap();
+      }
+      out
+    }
+    CfgAction::GotoLabel(action) => format!("GotoLabel {}", format_string(action.label.as_bytes())),
+    CfgAction::Push(action) => {
+      let values: Vec<String> = action
+        .values
+        .iter()
+        .map(|value| format_push_value(&cfg_push_value(value)))
+        .collect();
+      format!("Push {}", values.join(", "))
+    }
+    CfgAction::SetTarget(action) => format!("SetTarget {}", format_string(action.target_name.as_bytes())),
+    CfgAction::StoreRegister(action) => format!("StoreRegister r:{}", action.register),
+    CfgAction::StrictMode(action) => format!("StrictMode {}", action.is_strict),
+    CfgAction::Unknown(action) => format!(".action 0x{:02x} hex:{}", action.code, hex::encode(&action.data)),
+    // The other actions have no operands
+    action => simple_code(action)
+      .and_then(raw::mnemonic)
+      .expect("Action without mnemonic")
+      .to_string(),
+  }
+}
+
+/// Returns the raw flags of a CFG `DefineFunction2` action, see `DEFINE_FUNCTION2_FLAGS`.
+pub(crate) fn cfg_function2_flags(action: &CfgDefineFunction2) -> u16 {
+  let flags = [
+    action.preload_this,
+    action.suppress_this,
+    action.preload_arguments,
+    action.suppress_arguments,
+    action.preload_super,
+    action.suppress_super,
+    action.preload_root,
+    action.preload_parent,
+    action.preload_global,
+  ];
+  flags
+    .iter()
+    .enumerate()
+    .filter(|(_, is_set)| **is_set)
+    .fold(0, |flags, (bit, _)| flags | (1 << bit))
+}
+
+/// Converts a value of a CFG `Push` action to the equivalent raw value.
+pub(crate) fn cfg_push_value(value: &Value) -> PushValue {
+  match value {
+    Value::Boolean(value) => PushValue::Boolean(*value),
+    Value::Constant(index) => match u8::try_from(*index) {
+      Ok(index) => PushValue::Constant8(index),
+      Err(_) => PushValue::Constant16(*index),
+    },
+    Value::Float32(value) => PushValue::Float32(*value),
+    Value::Float64(value) => PushValue::Float64(*value),
+    Value::Null => PushValue::Null,
+    Value::Register(register) => PushValue::Register(*register),
+    Value::String(value) => PushValue::String(value.as_bytes().to_vec()),
+    Value::Sint32(value) => PushValue::Sint32(*value),
+    Value::Undefined => PushValue::Undefined,
+  }
+}
+
+pub(crate) fn format_push_value(value: &PushValue) -> String {
+  match value {
+    PushValue::String(value) => format_string(value),
+    PushValue::Float32(value) => {
+      if value.is_nan() && value.to_bits() != f32::NAN.to_bits() {
+        format!("f32:0x{:08x}", value.to_bits())
+      } else if value.is_nan() || value.is_infinite() {
+        format!("f32:{}", format_f64(f64::from(*value)))
+      } else {
+        format!("f32:{:?}", value)
+      }
+    }
+    PushValue::Null => String::from("null"),
+    PushValue::Undefined => String::from("undefined"),
+    PushValue::Register(register) => format!("r:{}", register),
+    PushValue::Boolean(value) => format!("{}", value),
+    PushValue::Float64(value) => {
+      if value.is_nan() && value.to_bits() != f64::NAN.to_bits() {
+        format!("f64:0x{:016x}", value.to_bits())
+      } else {
+        format_f64(*value)
+      }
+    }
+    PushValue::Sint32(value) => format!("{}", value),
+    PushValue::Constant8(index) => format!("c:{}", index),
+    PushValue::Constant16(index) if *index > u16::from(u8::MAX) => format!("c:{}", index),
+    PushValue::Constant16(index) => format!("c16:{}", index),
+  }
+}
+
+/// Formats a double so it can't be confused with an integer and parses back to the same value.
+fn format_f64(value: f64) -> String {
+  if value.is_nan() {
+    String::from("NaN")
+  } else if value.is_infinite() {
+    String::from(if value > 0.0 { "Infinity" } else { "-Infinity" })
+  } else {
+    // The `Debug` representation always contains a decimal point or an exponent.
+    format!("{:?}", value)
+  }
+}
+
+/// Formats an AVM1 string as a quoted literal.
+///
+/// Invalid UTF-8 bytes and control characters are escaped as `\xNN`.
+pub(crate) fn format_string(value: &[u8]) -> String {
+  let mut out = String::with_capacity(value.len() + 2);
+  out.push('"');
+  let mut rest = value;
+  loop {
+    match std::str::from_utf8(rest) {
+      Ok(valid) => {
+        push_escaped(&mut out, valid);
+        break;
+      }
+      Err(e) => {
+        let (valid, invalid) = rest.split_at(e.valid_up_to());
+        push_escaped(&mut out, std::str::from_utf8(valid).unwrap());
+        let invalid_len = e.error_len().unwrap_or(invalid.len());
+        for byte in invalid[..invalid_len].iter() {
+          write!(out, "\\x{:02x}", byte).unwrap();
+        }
+        rest = &invalid[invalid_len..];
+      }
+    }
+  }
+  out.push('"');
+  out
+}
+
+fn push_escaped(out: &mut String, value: &str) {
+  for c in value.chars() {
+    match c {
+      '"' => out.push_str("\\\""),
+      '\\' => out.push_str("\\\\"),
+      '\n' => out.push_str("\\n"),
+      '\r' => out.push_str("\\r"),
+      '\t' => out.push_str("\\t"),
+      c if (c as u32) < 0x20 || c == '\x7f' => write!(out, "\\x{:02x}", c as u32).unwrap(),
+      c => out.push(c),
+    }
+  }
+}
@@ -0,0 +1,140 @@
+//! Graphviz (DOT) rendering of AVM1 control flow graphs.
+//!
+//! Each block is a node listing its actions. Edges are labeled by the kind of branch, and function bodies are drawn
+//! as subgraphs (clusters).
+
+use crate::avm1::disasm::{format_cfg_action, format_string};
+use crate::avm1::{block_actions, block_children, block_label};
+use avm1_types::cfg::{Cfg, CfgAction, CfgBlock, CfgLabel};
+use avm1_types::CatchTarget;
+use std::fmt::Write;
+
+/// Returns the DOT graph of the CFG.
+pub(crate) fn cfg_to_dot(cfg: &Cfg) -> String {
+  let mut writer = DotWriter {
+    out: String::new(),
+    clusters: 0,
+  };
+  writeln!(writer.out, "digraph cfg {{").unwrap();
+  writeln!(writer.out, "  node [shape=box, fontname=\"monospace\"];").unwrap();
+  writeln!(writer.out, "  edge [fontname=\"monospace\"];").unwrap();
+  writer.cfg(cfg, 1);
+  writeln!(writer.out, "}}").unwrap();
+  writer.out
+}
+
+struct DotWriter {
+  out: String,
+  /// Number of clusters written so far, used to generate their ids
+  clusters: usize,
+}
+
+impl DotWriter {
+  fn cfg(&mut self, cfg: &Cfg, indent: usize) {
+    for block in cfg.blocks.iter() {
+      self.block(block, indent);
+    }
+  }
+
+  fn block(&mut self, block: &CfgBlock, indent: usize) {
+    let label = block_label(block);
+    let mut lines: Vec<String> = vec![format!("{}:", label.0)];
+    lines.extend(block_actions(block).iter().map(format_cfg_action));
+    let mut edges: Vec<(Option<&CfgLabel>, &str)> = Vec::new();
+    match block {
+      CfgBlock::Error(block) => lines.push(match &block.error {
+        Some(error) => format!("Error ; {}", error),
+        None => String::from("Error"),
+      }),
+      CfgBlock::If(block) => {
+        lines.push(String::from("If"));
+        edges.push((block.if_true.as_ref(), "if-true"));
+        edges.push((block.if_false.as_ref(), "if-false"));
+      }
+      CfgBlock::Return(_) => lines.push(String::from("Return")),
+      CfgBlock::Simple(block) => match &block.next {
+        Some(next) => edges.push((Some(next), "jump")),
+        None => lines.push(String::from("End")),
+      },
+      CfgBlock::Throw(_) => lines.push(String::from("Throw")),
+      CfgBlock::Try(block) => {
+        lines.push(match &block.catch_target {
+          CatchTarget::Register(register) => format!("Try target=r:{}", register),
+          CatchTarget::Variable(name) => format!("Try target={}", format_string(name.as_bytes())),
+        });
+        edges.push((Some(entry_label(&block.r#try)), "try"));
+        edges.extend(block.catch.iter().map(|catch| (Some(entry_label(catch)), "catch")));
+        edges.extend(
+          block
+            .finally
+            .iter()
+            .map(|finally| (Some(entry_label(finally)), "finally")),
+        );
+      }
+      CfgBlock::WaitForFrame(block) => {
+        lines.push(format!("WaitForFrame {}", block.frame));
+        edges.push((block.if_loaded.as_ref(), "if-loaded"));
+        edges.push((block.if_not_loaded.as_ref(), "if-not-loaded"));
+      }
+      CfgBlock::WaitForFrame2(block) => {
+        lines.push(String::from("WaitForFrame2"));
+        edges.push((block.if_loaded.as_ref(), "if-loaded"));
+        edges.push((block.if_not_loaded.as_ref(), "if-not-loaded"));
+      }
+      CfgBlock::With(block) => {
+        lines.push(String::from("With"));
+        edges.push((Some(entry_label(&block.with)), "with"));
+      }
+    }
+
+    let pad = "  ".repeat(indent);
+    let text: String = lines.iter().map(|line| format!("{}\\l", escape(line))).collect();
+    writeln!(self.out, "{}\"{}\" [label=\"{}\"];", pad, escape(&label.0), text).unwrap();
+    for (target, kind) in edges {
+      if let Some(target) = target {
+        self.edge(indent, label, target, kind, false);
+      }
+    }
+
+    for action in block_actions(block) {
+      let body = match action {
+        CfgAction::DefineFunction(action) => &action.body,
+        CfgAction::DefineFunction2(action) => &action.body,
+        _ => continue,
+      };
+      let id = self.clusters;
+      self.clusters += 1;
+      writeln!(self.out, "{}subgraph \"cluster_{}\" {{", pad, id).unwrap();
+      writeln!(self.out, "{}  label=\"{}\";", pad, escape(&format_cfg_action(action))).unwrap();
+      self.cfg(body, indent + 1);
+      writeln!(self.out, "{}}}", pad).unwrap();
+      self.edge(indent, label, entry_label(body), "function", true);
+    }
+    for child in block_children(block) {
+      self.cfg(child, indent);
+    }
+  }
+
+  fn edge(&mut self, indent: usize, from: &CfgLabel, to: &CfgLabel, kind: &str, dashed: bool) {
+    let style = if dashed { ", style=dashed" } else { "" };
+    writeln!(
+      self.out,
+      "{}\"{}\" -> \"{}\" [label=\"{}\"{}];",
+      "  ".repeat(indent),
+      escape(&from.0),
+      escape(&to.0),
+      kind,
+      style
+    )
+    .unwrap();
+  }
+}
+
+fn entry_label(cfg: &Cfg) -> &CfgLabel {
+  block_label(cfg.blocks.first())
+}
+
+/// Escapes a string for a double-quoted DOT identifier.
+pub(crate) fn escape(value: &str) -> String {
+  value.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,254 @@
+//! Emission of AVM1 bytecode from control flow graphs.
+//!
+//! Blocks are emitted in the order of the CFG, with nested code (function bodies, `try` blocks, `with` bodies)
+//! following the action it belongs to. Jumps to the next emitted block are omitted.
+
+use crate::avm1::asm::{self, Instruction, Item, Target};
+use crate::avm1::disasm::{cfg_function2_flags, cfg_push_value};
+use crate::avm1::raw::{self, Action, DefineFunction, DefineFunction2, GetUrl2, Try};
+use crate::avm1::{block_actions, block_label, cfg_labels, simple_code};
+use avm1_types::cfg::{Cfg, CfgAction, CfgBlock, CfgLabel};
+use avm1_types::{CatchTarget, GetUrl2Method};
+use std::collections::HashSet;
+
+/// Returns the bytecode of the CFG, or an error if a jump or nested block is too large for the AVM1 format.
+pub(crate) fn emit(cfg: &Cfg) -> Result<Vec<u8>, String> {
+  let mut emitter = Emitter {
+    items: Vec::new(),
+    labels: 0,
+    defined: cfg_labels(cfg).into_iter().map(|label| label.0.clone()).collect(),
+  };
+  let end = emitter.new_label();
+  emitter.region(cfg, &end);
+  emitter.items.push(Item::Label(end));
+  emitter.instruction(Action::Simple(0x00), Vec::new());
+  remove_fallthrough_jumps(&mut emitter.items);
+  asm::link(emitter.items).map_err(|e| e.message)
+}
+
+struct Emitter {
+  items: Vec<Item>,
+  /// Number of labels generated so far
+  labels: usize,
+  /// Labels of the blocks of the CFG
+  defined: HashSet<String>,
+}
+
+impl Emitter {
+  /// Returns a label that can't be confused with the labels of the CFG.
+  fn new_label(&mut self) -> String {
+    self.labels += 1;
+    format!("~{}", self.labels)
+  }
+
+  fn instruction(&mut self, action: Action, targets: Vec<Target>) {
+    self.items.push(Item::Instruction(Instruction {
+      line: 0,
+      action,
+      targets,
+    }));
+  }
+
+  /// Emits a jump to `target`, `end` is the label at the end of the buffer or function.
+  fn jump(&mut self, target: &Option<CfgLabel>, end: &str) {
+    let target = self.target(target, end);
+    self.instruction(Action::Jump(0), vec![target]);
+  }
+
+  /// Resolves the target of a branch. Missing targets and labels without a block end the buffer or function.
+  fn target(&self, target: &Option<CfgLabel>, end: &str) -> Target {
+    match target {
+      Some(label) if self.defined.contains(&label.0) => Target::Label(label.0.clone()),
+      _ => Target::Label(end.to_string()),
+    }
+  }
+
+  fn region(&mut self, cfg: &Cfg, end: &str) {
+    for block in cfg.blocks.iter() {
+      self.items.push(Item::Label(block_label(block).0.clone()));
+      for action in block_actions(block) {
+        self.action(action);
+      }
+      match block {
+        // Invalid bytecode stops the execution
+        CfgBlock::Error(_) => self.instruction(Action::Simple(0x00), Vec::new()),
+        CfgBlock::If(block) => {
+          let if_true = self.target(&block.if_true, end);
+          self.instruction(Action::If(0), vec![if_true]);
+          self.jump(&block.if_false, end);
+        }
+        CfgBlock::Return(_) => self.instruction(Action::Simple(0x3e), Vec::new()),
+        CfgBlock::Simple(block) => self.jump(&block.next, end),
+        CfgBlock::Throw(_) => self.instruction(Action::Simple(0x2a), Vec::new()),
+        CfgBlock::Try(block) => {
+          let (try_end, catch_end, finally_end) = (self.new_label(), self.new_label(), self.new_label());
+          let catch_target = match &block.catch_target {
+            CatchTarget::Register(register) => raw::CatchTarget::Register(*register),
+            CatchTarget::Variable(name) => raw::CatchTarget::Variable(name.as_bytes().to_vec()),
+          };
+          self.instruction(
+            Action::Try(Try {
+              catch_target,
+              has_catch: block.catch.is_some(),
+              has_finally: block.finally.is_some(),
+              try_size: 0,
+              catch_size: 0,
+              finally_size: 0,
+            }),
+            vec![
+              Target::Label(try_end.clone()),
+              Target::Label(catch_end.clone()),
+              Target::Label(finally_end.clone()),
+            ],
+          );
+          self.region(&block.r#try, end);
+          self.items.push(Item::Label(try_end));
+          if let Some(catch) = &block.catch {
+            self.region(catch, end);
+          }
+          self.items.push(Item::Label(catch_end));
+          if let Some(finally) = &block.finally {
+            self.region(finally, end);
+          }
+          self.items.push(Item::Label(finally_end));
+        }
+        // When the frame is not loaded, the first jump is skipped
+        CfgBlock::WaitForFrame(block) => {
+          self.instruction(
+            Action::WaitForFrame {
+              frame: block.frame as u16,
+              skip_count: 1,
+            },
+            Vec::new(),
+          );
+          self.jump(&block.if_loaded, end);
+          self.jump(&block.if_not_loaded, end);
+        }
+        CfgBlock::WaitForFrame2(block) => {
+          self.instruction(Action::WaitForFrame2 { skip_count: 1 }, Vec::new());
+          self.jump(&block.if_loaded, end);
+          self.jump(&block.if_not_loaded, end);
+        }
+        CfgBlock::With(block) => {
+          let with_end = self.new_label();
+          self.instruction(Action::With { size: 0 }, vec![Target::Label(with_end.clone())]);
+          self.region(&block.with, end);
+          self.items.push(Item::Label(with_end));
+        }
+      }
+    }
+  }
+
+  fn action(&mut self, action: &CfgAction) {
+    let action = match action {
+      CfgAction::DefineFunction(action) => {
+        let function_end = self.new_label();
+        self.instruction(
+          Action::DefineFunction(DefineFunction {
+            name: action.name.as_bytes().to_vec(),
+            parameters: action.parameters.iter().map(|name| name.as_bytes().to_vec()).collect(),
+            size: 0,
+          }),
+          vec![Target::Label(function_end.clone())],
+        );
+        self.region(&action.body, &function_end);
+        self.items.push(Item::Label(function_end));
+        return;
+      }
+      CfgAction::DefineFunction2(action) => {
+        let function_end = self.new_label();
+        self.instruction(
+          Action::DefineFunction2(DefineFunction2 {
+            name: action.name.as_bytes().to_vec(),
+            register_count: action.register_count,
+            flags: cfg_function2_flags(action),
+            parameters: action
+              .parameters
+              .iter()
+              .map(|parameter| (parameter.register, parameter.name.as_bytes().to_vec()))
+              .collect(),
+            size: 0,
+          }),
+          vec![Target::Label(function_end.clone())],
+        );
+        self.region(&action.body, &function_end);
+        self.items.push(Item::Label(function_end));
+        return;
+      }
+      CfgAction::Call => Action::Call,
+      CfgAction::ConstantPool(action) => Action::ConstantPool(
+        action
+          .constant_pool
+          .iter()
+          .map(|value| value.as_bytes().to_vec())
+          .collect(),
+      ),
+      CfgAction::GetUrl(action) => Action::GetUrl {
+        url: action.url.as_bytes().to_vec(),
+        target: action.target.as_bytes().to_vec(),
+      },
+      CfgAction::GetUrl2(action) => Action::GetUrl2(GetUrl2 {
+        method: match action.send_vars_method {
+          GetUrl2Method::None => 0,
+          GetUrl2Method::Get => 1,
+          GetUrl2Method::Post => 2,
+        },
+        load_target: action.load_target,
+        load_variables: action.load_variables,
+      }),
+      CfgAction::GotoFrame(action) => Action::GotoFrame(action.frame as u16),
+      CfgAction::GotoFrame2(action) => Action::GotoFrame2 {
+        play: action.play,
+        scene_bias: if action.scene_bias != 0 {
+          Some(action.scene_bias)
+        } else {
+          None
+        },
+      },
+      CfgAction::GotoLabel(action) => Action::GotoLabel(action.label.as_bytes().to_vec()),
+      CfgAction::Push(action) => Action::Push(action.values.iter().map(cfg_push_value).collect()),
+      CfgAction::SetTarget(action) => Action::SetTarget(action.target_name.as_bytes().to_vec()),
+      CfgAction::StoreRegister(action) => Action::StoreRegister(action.register),
+      CfgAction::StrictMode(action) => Action::StrictMode(action.is_strict),
+      CfgAction::Unknown(action) => Action::Unknown {
+        code: action.code,
+        data: if action.code < 0x80 {
+          None
+        } else {
+          Some(action.data.clone())
+        },
+      },
+      // The other actions have no operands
+      action => match simple_code(action) {
+        Some(code) => Action::Simple(code),
+        None => unreachable!("Action with operands: {:?}", action),
+      },
+    };
+    self.instruction(action, Vec::new());
+  }
+}
+
+/// Removes the jumps to the label immediately following them, except after `WaitForFrame` actions (they skip the
+/// first jump).
+fn remove_fallthrough_jumps(items: &mut Vec<Item>) {
+  let mut keep = vec![true; items.len()];
+  let mut after_wait_for_frame = false;
+  for (i, item) in items.iter().enumerate() {
+    if let Item::Instruction(instruction) = item {
+      if let (Action::Jump(_), [Target::Label(target)]) = (&instruction.action, instruction.targets.as_slice()) {
+        if !after_wait_for_frame {
+          keep[i] = !items[i + 1..]
+            .iter()
+            .take_while(|item| matches!(item, Item::Label(_)))
+            .any(|item| matches!(item, Item::Label(label) if label == target));
+        }
+      }
+      after_wait_for_frame = matches!(
+        instruction.action,
+        Action::WaitForFrame { .. } | Action::WaitForFrame2 { .. }
+      );
+    }
+  }
+  let mut keep = keep.into_iter();
+  items.retain(|_| keep.next().unwrap());
+}
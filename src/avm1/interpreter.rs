@@ -0,0 +1,2440 @@
+//! Sandboxed AVM1 interpreter.
+//!
+//! Buffers are run without a Flash Player: the display list is a tree of stub movie clips, timeline actions (`play`,
+//! `gotoAndStop`, ...) do nothing and external calls (`getURL`, `loadMovie`, `fscommand`, ...) are recorded instead of
+//! being performed. Nothing is read from or written to the network or the file system, and the results only depend on
+//! the buffers (`getTimer` counts the executed actions and `Math.random` uses a fixed seed).
+
+use crate::avm1::decompiler::ast::quote;
+use crate::avm1::deobfuscate::to_int32;
+use crate::avm1::raw::{self, Action, CatchTarget, PushValue, DEFINE_FUNCTION2_FLAGS};
+use crate::dump::Avm1Location;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::io::Write;
+use std::rc::Rc;
+
+/// Properties of movie clips read and written by `GetProperty` and `SetProperty`, by index.
+const CLIP_PROPERTIES: [&str; 22] = [
+  "_x",
+  "_y",
+  "_xscale",
+  "_yscale",
+  "_currentframe",
+  "_totalframes",
+  "_alpha",
+  "_visible",
+  "_width",
+  "_height",
+  "_rotation",
+  "_target",
+  "_framesloaded",
+  "_name",
+  "_droptarget",
+  "_url",
+  "_highquality",
+  "_focusrect",
+  "_soundbuftime",
+  "_quality",
+  "_xmouse",
+  "_ymouse",
+];
+
+/// Maximum depth of nested function calls, as in Flash Player.
+const MAX_CALL_DEPTH: usize = 256;
+
+/// Maximum depth of the objects listed in the final variables.
+const MAX_VARIABLE_DEPTH: usize = 16;
+
+/// Maximum length of the arrays grown by index assignments.
+const MAX_ARRAY_LENGTH: usize = 1 << 20;
+
+/// Maximum length of the strings built by concatenation, in bytes.
+const MAX_STRING_LENGTH: usize = 1 << 24;
+
+pub(crate) struct Options {
+  /// Maximum number of actions to execute, over all the buffers
+  pub max_steps: usize,
+  pub swf_version: u8,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct Report {
+  /// `completed` or `step-limit`
+  pub status: &'static str,
+  /// Number of executed actions
+  pub steps: usize,
+  /// Errors stopping a buffer: uncaught exceptions, malformed actions, call stack overflows
+  pub errors: Vec<String>,
+  pub traces: Vec<String>,
+  pub calls: Vec<ExternalCall>,
+  /// Final variables of `_root` and `_global`, with objects flattened to their members
+  pub variables: Vec<Variable>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct ExternalCall {
+  pub location: String,
+  /// Offset of the action performing the call
+  pub offset: usize,
+  /// Function or action, such as `getURL` or `LoadVars.load`
+  pub kind: &'static str,
+  pub arguments: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct Variable {
+  pub name: String,
+  pub value: String,
+}
+
+/// Formats the call as `<location> @<offset>: <kind>(<arguments>)`, with quoted arguments.
+impl fmt::Display for ExternalCall {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let arguments: Vec<String> = self.arguments.iter().map(|argument| quote(argument)).collect();
+    write!(
+      f,
+      "{} @{}: {}({})",
+      self.location,
+      self.offset,
+      self.kind,
+      arguments.join(", ")
+    )
+  }
+}
+
+impl Report {
+  /// Writes the traces, external calls, errors and variables as text. Empty sections are omitted.
+  pub(crate) fn print(&self, writer: &mut impl Write) -> std::io::Result<()> {
+    if !self.traces.is_empty() {
+      writeln!(writer, "Traces:")?;
+      for trace in self.traces.iter() {
+        writeln!(writer, "  {}", trace)?;
+      }
+    }
+    if !self.calls.is_empty() {
+      writeln!(writer, "External calls:")?;
+      for call in self.calls.iter() {
+        writeln!(writer, "  {}", call)?;
+      }
+    }
+    if !self.errors.is_empty() {
+      writeln!(writer, "Errors:")?;
+      for error in self.errors.iter() {
+        writeln!(writer, "  {}", error)?;
+      }
+    }
+    if !self.variables.is_empty() {
+      writeln!(writer, "Variables:")?;
+      for variable in self.variables.iter() {
+        writeln!(writer, "  {} = {}", variable.name, variable.value)?;
+      }
+    }
+    Ok(())
+  }
+}
+
+/// Runs the buffers in order, sharing the same display list and globals.
+pub(crate) fn run(buffers: &[(Avm1Location, &[u8])], options: &Options) -> Report {
+  let mut interpreter = Interpreter::new(options);
+  let mut status = "completed";
+  let mut errors = Vec::new();
+  for (location, bytes) in buffers {
+    let code = Rc::new(Code {
+      location: location.to_string(),
+      bytes: bytes.to_vec(),
+    });
+    let error = match interpreter.run_buffer(code) {
+      Ok(()) => continue,
+      Err(Interrupt::StepLimit) => {
+        status = "step-limit";
+        break;
+      }
+      Err(Interrupt::Throw(value)) => format!("uncaught exception: {}", interpreter.display(&value)),
+      Err(Interrupt::Error(message)) => message,
+    };
+    let (code, offset) = &interpreter.position;
+    errors.push(format!("{} @{}: {}", code.location, offset, error));
+  }
+  Report {
+    status,
+    steps: interpreter.steps,
+    errors,
+    traces: interpreter.traces.split_off(0),
+    calls: interpreter.calls.split_off(0),
+    variables: interpreter.variables(),
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+  Undefined,
+  Null,
+  Boolean(bool),
+  Number(f64),
+  String(String),
+  /// Index of the object in `Interpreter::objects`
+  Object(usize),
+}
+
+struct Object {
+  properties: BTreeMap<String, Value>,
+  proto: Option<usize>,
+  kind: Kind,
+}
+
+enum Kind {
+  Plain,
+  Array(Vec<Value>),
+  Function(Rc<Function>),
+  Native(Native),
+  Clip { name: String, parent: Option<usize> },
+}
+
+struct Code {
+  location: String,
+  bytes: Vec<u8>,
+}
+
+struct Function {
+  code: Rc<Code>,
+  /// Range of the body in the buffer
+  start: usize,
+  end: usize,
+  /// Parameters, as `(register, name)` pairs (a register of `0` means that the parameter is a local variable)
+  parameters: Vec<(u8, String)>,
+  /// Register count and flags of `DefineFunction2`, `None` for `DefineFunction`
+  function2: Option<(u8, u16)>,
+  /// Scope chain at the definition of the function
+  scopes: Vec<usize>,
+  /// Timeline defining the function
+  target: usize,
+  constant_pool: Rc<Vec<String>>,
+}
+
+/// Built-in functions and methods.
+#[derive(Clone, Copy, Debug)]
+enum Native {
+  /// Function without effect, returning `undefined`
+  Noop,
+  Array,
+  Object,
+  Number,
+  String,
+  Boolean,
+  Escape,
+  Unescape,
+  ParseInt,
+  ParseFloat,
+  IsNaN,
+  IsFinite,
+  GetTimer,
+  Call,
+  Apply,
+  FromCharCode,
+  CharAt,
+  CharCodeAt,
+  IndexOf,
+  LastIndexOf,
+  Substr,
+  Substring,
+  Slice,
+  Split,
+  ToUpperCase,
+  ToLowerCase,
+  Concat,
+  ToString,
+  ValueOf,
+  Push,
+  Pop,
+  Shift,
+  Unshift,
+  Join,
+  Reverse,
+  ArraySlice,
+  ArrayConcat,
+  Math(fn(f64) -> f64),
+  Round,
+  Max,
+  Min,
+  Pow,
+  Atan2,
+  Random,
+  /// Creates a child clip named after the argument with the provided index (`createEmptyMovieClip`, `attachMovie`)
+  CreateClip(usize),
+  /// `getBytesLoaded` and `getBytesTotal`, everything is loaded
+  BytesLoaded,
+  /// Records a call to an external API
+  External(&'static str),
+  /// Records a load into the clip (`MovieClip.loadMovie` and `MovieClip.loadVariables`)
+  ClipLoad(&'static str),
+}
+
+struct Frame {
+  code: Rc<Code>,
+  stack: Vec<Value>,
+  registers: Vec<Value>,
+  /// `with` objects and activation objects, innermost last
+  scopes: Vec<usize>,
+  /// Activation object of the function, `None` for top-level code
+  activation: Option<usize>,
+  this: Value,
+  /// Timeline of the variables, changed by `SetTarget`
+  target: usize,
+  base_target: usize,
+  constant_pool: Rc<Vec<String>>,
+}
+
+impl Frame {
+  fn pop(&mut self) -> Value {
+    self.stack.pop().unwrap_or(Value::Undefined)
+  }
+
+  /// Pops up to `count` values, the first popped value first.
+  fn pop_values(&mut self, count: usize) -> Vec<Value> {
+    let count = count.min(self.stack.len());
+    (0..count).map(|_| self.pop()).collect()
+  }
+}
+
+/// Result of the execution of a range of actions.
+enum Flow {
+  /// The end of the range was reached
+  Done,
+  Return(Value),
+  /// A jump left the range
+  Jump(usize),
+}
+
+enum Interrupt {
+  Throw(Value),
+  StepLimit,
+  Error(String),
+}
+
+/// Prototypes of the built-in classes.
+struct Protos {
+  object: usize,
+  function: usize,
+  string: usize,
+  number: usize,
+  array: usize,
+  clip: usize,
+}
+
+struct Interpreter {
+  max_steps: usize,
+  swf_version: u8,
+  objects: Vec<Object>,
+  protos: Protos,
+  root: usize,
+  global: usize,
+  /// Built-in globals, looked up after the members of `_global`
+  builtins: HashMap<&'static str, Value>,
+  steps: usize,
+  depth: usize,
+  random: u32,
+  /// Buffer and offset of the action being executed
+  position: (Rc<Code>, usize),
+  traces: Vec<String>,
+  calls: Vec<ExternalCall>,
+}
+
+impl Interpreter {
+  fn new(options: &Options) -> Self {
+    let mut interpreter = Interpreter {
+      max_steps: options.max_steps,
+      swf_version: options.swf_version,
+      objects: Vec::new(),
+      protos: Protos {
+        object: 0,
+        function: 0,
+        string: 0,
+        number: 0,
+        array: 0,
+        clip: 0,
+      },
+      root: 0,
+      global: 0,
+      builtins: HashMap::new(),
+      steps: 0,
+      depth: 0,
+      random: 0x2545_f491,
+      position: (
+        Rc::new(Code {
+          location: String::new(),
+          bytes: Vec::new(),
+        }),
+        0,
+      ),
+      traces: Vec::new(),
+      calls: Vec::new(),
+    };
+    interpreter.init_builtins();
+    interpreter
+  }
+
+  fn init_builtins(&mut self) {
+    let object = self.new_object(Kind::Plain, None);
+    self.protos.object = object;
+    self.protos.function = self.new_object(Kind::Plain, Some(object));
+    self.protos.string = self.new_object(Kind::Plain, Some(object));
+    self.protos.number = self.new_object(Kind::Plain, Some(object));
+    self.protos.array = self.new_object(Kind::Plain, Some(object));
+    self.protos.clip = self.new_object(Kind::Plain, Some(object));
+    self.global = self.new_object(Kind::Plain, Some(object));
+    self.root = self.new_object(
+      Kind::Clip {
+        name: String::new(),
+        parent: None,
+      },
+      Some(self.protos.clip),
+    );
+
+    let function = self.protos.function;
+    self.define_natives(function, &[("call", Native::Call), ("apply", Native::Apply)]);
+    let string = self.protos.string;
+    self.define_natives(
+      string,
+      &[
+        ("charAt", Native::CharAt),
+        ("charCodeAt", Native::CharCodeAt),
+        ("concat", Native::Concat),
+        ("indexOf", Native::IndexOf),
+        ("lastIndexOf", Native::LastIndexOf),
+        ("slice", Native::Slice),
+        ("split", Native::Split),
+        ("substr", Native::Substr),
+        ("substring", Native::Substring),
+        ("toLowerCase", Native::ToLowerCase),
+        ("toString", Native::ToString),
+        ("toUpperCase", Native::ToUpperCase),
+        ("valueOf", Native::ValueOf),
+      ],
+    );
+    let number = self.protos.number;
+    self.define_natives(number, &[("toString", Native::ToString), ("valueOf", Native::ValueOf)]);
+    let array = self.protos.array;
+    self.define_natives(
+      array,
+      &[
+        ("concat", Native::ArrayConcat),
+        ("join", Native::Join),
+        ("pop", Native::Pop),
+        ("push", Native::Push),
+        ("reverse", Native::Reverse),
+        ("shift", Native::Shift),
+        ("slice", Native::ArraySlice),
+        ("toString", Native::Join),
+        ("unshift", Native::Unshift),
+      ],
+    );
+    let clip = self.protos.clip;
+    self.define_natives(
+      clip,
+      &[
+        ("attachMovie", Native::CreateClip(1)),
+        ("createEmptyMovieClip", Native::CreateClip(0)),
+        ("createTextField", Native::CreateClip(0)),
+        ("getBytesLoaded", Native::BytesLoaded),
+        ("getBytesTotal", Native::BytesLoaded),
+        ("getURL", Native::External("getURL")),
+        ("gotoAndPlay", Native::Noop),
+        ("gotoAndStop", Native::Noop),
+        ("loadMovie", Native::ClipLoad("loadMovie")),
+        ("loadVariables", Native::ClipLoad("loadVariables")),
+        ("nextFrame", Native::Noop),
+        ("play", Native::Noop),
+        ("prevFrame", Native::Noop),
+        ("removeMovieClip", Native::Noop),
+        ("stop", Native::Noop),
+        ("swapDepths", Native::Noop),
+        ("unloadMovie", Native::Noop),
+      ],
+    );
+
+    let globals: [(&'static str, Native); 16] = [
+      ("ASSetPropFlags", Native::Noop),
+      ("Array", Native::Array),
+      ("Boolean", Native::Boolean),
+      ("Number", Native::Number),
+      ("Object", Native::Object),
+      ("String", Native::String),
+      ("clearInterval", Native::Noop),
+      ("escape", Native::Escape),
+      ("getTimer", Native::GetTimer),
+      ("isFinite", Native::IsFinite),
+      ("isNaN", Native::IsNaN),
+      ("parseFloat", Native::ParseFloat),
+      ("parseInt", Native::ParseInt),
+      ("setInterval", Native::Noop),
+      ("setTimeout", Native::Noop),
+      ("unescape", Native::Unescape),
+    ];
+    for (name, native) in globals.iter() {
+      let value = self.new_native(*native);
+      self.builtins.insert(name, value);
+    }
+    let prototypes = [
+      ("Array", array),
+      ("Number", number),
+      ("Object", object),
+      ("String", string),
+    ];
+    for (name, proto) in prototypes.iter() {
+      let constructor = self.builtins[name].clone();
+      self.set_member(&constructor, "prototype", Value::Object(*proto));
+    }
+    let string_constructor = self.builtins["String"].clone();
+    let from_char_code = self.new_native(Native::FromCharCode);
+    self.set_member(&string_constructor, "fromCharCode", from_char_code);
+
+    let movie_clip = self.new_native(Native::Noop);
+    self.set_member(&movie_clip, "prototype", Value::Object(clip));
+    self.builtins.insert("MovieClip", movie_clip);
+    let classes: [(&'static str, &[(&'static str, Native)]); 5] = [
+      (
+        "LoadVars",
+        &[
+          ("load", Native::External("LoadVars.load")),
+          ("send", Native::External("LoadVars.send")),
+          ("sendAndLoad", Native::External("LoadVars.sendAndLoad")),
+        ],
+      ),
+      (
+        "LocalConnection",
+        &[
+          ("connect", Native::External("LocalConnection.connect")),
+          ("send", Native::External("LocalConnection.send")),
+        ],
+      ),
+      ("Sound", &[("loadSound", Native::External("Sound.loadSound"))]),
+      (
+        "XML",
+        &[
+          ("load", Native::External("XML.load")),
+          ("send", Native::External("XML.send")),
+          ("sendAndLoad", Native::External("XML.sendAndLoad")),
+        ],
+      ),
+      ("XMLSocket", &[("connect", Native::External("XMLSocket.connect"))]),
+    ];
+    for (name, methods) in classes.iter() {
+      let proto = self.new_object(Kind::Plain, Some(object));
+      self.define_natives(proto, methods);
+      let constructor = self.new_native(Native::Noop);
+      self.set_member(&constructor, "prototype", Value::Object(proto));
+      self.builtins.insert(name, constructor);
+    }
+
+    let math = self.new_object(Kind::Plain, Some(object));
+    self.define_natives(
+      math,
+      &[
+        ("abs", Native::Math(f64::abs)),
+        ("acos", Native::Math(f64::acos)),
+        ("asin", Native::Math(f64::asin)),
+        ("atan", Native::Math(f64::atan)),
+        ("atan2", Native::Atan2),
+        ("ceil", Native::Math(f64::ceil)),
+        ("cos", Native::Math(f64::cos)),
+        ("exp", Native::Math(f64::exp)),
+        ("floor", Native::Math(f64::floor)),
+        ("log", Native::Math(f64::ln)),
+        ("max", Native::Max),
+        ("min", Native::Min),
+        ("pow", Native::Pow),
+        ("random", Native::Random),
+        ("round", Native::Round),
+        ("sin", Native::Math(f64::sin)),
+        ("sqrt", Native::Math(f64::sqrt)),
+        ("tan", Native::Math(f64::tan)),
+      ],
+    );
+    let constants = [
+      ("E", std::f64::consts::E),
+      ("LN10", std::f64::consts::LN_10),
+      ("LN2", std::f64::consts::LN_2),
+      ("LOG10E", std::f64::consts::LOG10_E),
+      ("LOG2E", std::f64::consts::LOG2_E),
+      ("PI", std::f64::consts::PI),
+      ("SQRT1_2", std::f64::consts::FRAC_1_SQRT_2),
+      ("SQRT2", std::f64::consts::SQRT_2),
+    ];
+    for (name, value) in constants.iter() {
+      self.set_member(&Value::Object(math), name, Value::Number(*value));
+    }
+    self.builtins.insert("Math", Value::Object(math));
+
+    let external_interface = self.new_object(Kind::Plain, Some(object));
+    self.define_natives(
+      external_interface,
+      &[
+        ("addCallback", Native::Noop),
+        ("call", Native::External("ExternalInterface.call")),
+      ],
+    );
+    self
+      .builtins
+      .insert("ExternalInterface", Value::Object(external_interface));
+    let security = self.new_object(Kind::Plain, Some(object));
+    self.define_natives(
+      security,
+      &[
+        ("allowDomain", Native::Noop),
+        ("loadPolicyFile", Native::External("System.security.loadPolicyFile")),
+      ],
+    );
+    let system = self.new_object(Kind::Plain, Some(object));
+    self.set_member(&Value::Object(system), "security", Value::Object(security));
+    self.builtins.insert("System", Value::Object(system));
+    for name in ["Key", "Mouse", "Stage"].iter() {
+      let value = self.new_object(Kind::Plain, Some(object));
+      self.builtins.insert(name, Value::Object(value));
+    }
+    self.builtins.insert("NaN", Value::Number(f64::NAN));
+    self.builtins.insert("Infinity", Value::Number(f64::INFINITY));
+  }
+
+  fn new_object(&mut self, kind: Kind, proto: Option<usize>) -> usize {
+    self.objects.push(Object {
+      properties: BTreeMap::new(),
+      proto,
+      kind,
+    });
+    self.objects.len() - 1
+  }
+
+  fn new_native(&mut self, native: Native) -> Value {
+    let function = self.protos.function;
+    Value::Object(self.new_object(Kind::Native(native), Some(function)))
+  }
+
+  fn new_array(&mut self, elements: Vec<Value>) -> Value {
+    let array = self.protos.array;
+    Value::Object(self.new_object(Kind::Array(elements), Some(array)))
+  }
+
+  fn new_clip(&mut self, parent: usize, name: String) -> Value {
+    let clip = self.protos.clip;
+    let child = Value::Object(self.new_object(
+      Kind::Clip {
+        name: name.clone(),
+        parent: Some(parent),
+      },
+      Some(clip),
+    ));
+    self.set_member(&Value::Object(parent), &name, child.clone());
+    child
+  }
+
+  fn define_natives(&mut self, object: usize, natives: &[(&str, Native)]) {
+    for (name, native) in natives {
+      let value = self.new_native(*native);
+      self.objects[object].properties.insert(name.to_string(), value);
+    }
+  }
+
+  fn run_buffer(&mut self, code: Rc<Code>) -> Result<(), Interrupt> {
+    let end = code.bytes.len();
+    let mut frame = Frame {
+      code,
+      stack: Vec::new(),
+      registers: vec![Value::Undefined; 4],
+      scopes: Vec::new(),
+      activation: None,
+      this: Value::Object(self.root),
+      target: self.root,
+      base_target: self.root,
+      constant_pool: Rc::new(Vec::new()),
+    };
+    self.execute(&mut frame, 0, end).map(|_| ())
+  }
+
+  /// Executes the actions of `frame.code` in the range `start..end`.
+  fn execute(&mut self, frame: &mut Frame, start: usize, end: usize) -> Result<Flow, Interrupt> {
+    let code = Rc::clone(&frame.code);
+    let bytes = &code.bytes;
+    let end = end.min(bytes.len());
+    let mut pc = start;
+    loop {
+      if pc < start || pc >= end {
+        return Ok(if pc == end { Flow::Done } else { Flow::Jump(pc) });
+      }
+      if self.steps >= self.max_steps {
+        return Err(Interrupt::StepLimit);
+      }
+      self.steps += 1;
+      self.position = (Rc::clone(&code), pc);
+      let action = raw::read_action(bytes, pc);
+      let next = action.end();
+      pc = next;
+      match action.action {
+        Action::Simple(0x00) => return Ok(Flow::Done),
+        Action::Simple(0x3e) => return Ok(Flow::Return(frame.pop())),
+        Action::Simple(code) => self.simple(frame, code)?,
+        Action::GotoFrame(_) | Action::GotoLabel(_) | Action::StrictMode(_) | Action::WaitForFrame { .. } => {}
+        Action::Call | Action::WaitForFrame2 { .. } | Action::GotoFrame2 { .. } => {
+          frame.pop();
+        }
+        Action::GetUrl { url, target } => {
+          let (url, target) = (lossy(&url), lossy(&target));
+          match fscommand(&url) {
+            Some(command) => self.record("fscommand", vec![command.to_string(), target]),
+            None => self.record("getURL", vec![url, target]),
+          }
+        }
+        Action::GetUrl2(action) => {
+          let target = frame.pop();
+          let url = frame.pop();
+          let (url, target) = (self.to_string(&url), self.to_string(&target));
+          let mut arguments = vec![url.clone(), target.clone()];
+          match action.method {
+            1 => arguments.push(String::from("GET")),
+            2 => arguments.push(String::from("POST")),
+            _ => {}
+          }
+          let kind = if action.load_variables {
+            "loadVariables"
+          } else if action.load_target || target.starts_with("_level") {
+            "loadMovie"
+          } else {
+            "getURL"
+          };
+          match fscommand(&url) {
+            Some(command) => self.record("fscommand", vec![command.to_string(), target]),
+            None => self.record(kind, arguments),
+          }
+        }
+        Action::StoreRegister(register) => {
+          let value = frame.stack.last().cloned().unwrap_or(Value::Undefined);
+          if let Some(slot) = frame.registers.get_mut(usize::from(register)) {
+            *slot = value;
+          }
+        }
+        Action::ConstantPool(pool) => {
+          frame.constant_pool = Rc::new(pool.iter().map(|value| lossy(value)).collect());
+        }
+        Action::SetTarget(target) => {
+          let target = lossy(&target);
+          frame.target = if target.is_empty() {
+            frame.base_target
+          } else {
+            self.resolve_target(frame, &target).unwrap_or(frame.target)
+          };
+        }
+        Action::DefineFunction(action) => {
+          let body_end = next + usize::from(action.size);
+          let function = Function {
+            code: Rc::clone(&code),
+            start: next,
+            end: body_end,
+            parameters: action.parameters.iter().map(|name| (0, lossy(name))).collect(),
+            function2: None,
+            scopes: frame.scopes.clone(),
+            target: frame.target,
+            constant_pool: Rc::clone(&frame.constant_pool),
+          };
+          self.define_function(frame, &action.name, function);
+          pc = body_end;
+        }
+        Action::DefineFunction2(action) => {
+          let body_end = next + usize::from(action.size);
+          let function = Function {
+            code: Rc::clone(&code),
+            start: next,
+            end: body_end,
+            parameters: action
+              .parameters
+              .iter()
+              .map(|(register, name)| (*register, lossy(name)))
+              .collect(),
+            function2: Some((action.register_count, action.flags)),
+            scopes: frame.scopes.clone(),
+            target: frame.target,
+            constant_pool: Rc::clone(&frame.constant_pool),
+          };
+          self.define_function(frame, &action.name, function);
+          pc = body_end;
+        }
+        Action::With { size } => {
+          let body_end = next + usize::from(size);
+          let object = frame.pop();
+          let flow = match object {
+            Value::Object(object) => {
+              frame.scopes.push(object);
+              let flow = self.execute(frame, next, body_end);
+              frame.scopes.pop();
+              flow?
+            }
+            _ => Flow::Done,
+          };
+          pc = match resume(flow, body_end) {
+            Ok(pc) => pc,
+            Err(flow) => return Ok(flow),
+          };
+        }
+        Action::Try(action) => {
+          let catch_start = next + usize::from(action.try_size);
+          let finally_start = catch_start + usize::from(action.catch_size);
+          let finally_end = finally_start + usize::from(action.finally_size);
+          let mut result = self.execute(frame, next, catch_start);
+          if action.has_catch {
+            if let Err(Interrupt::Throw(value)) = result {
+              match &action.catch_target {
+                CatchTarget::Register(register) => {
+                  if let Some(slot) = frame.registers.get_mut(usize::from(*register)) {
+                    *slot = value;
+                  }
+                }
+                CatchTarget::Variable(name) => self.define_local(frame, &lossy(name), value),
+              }
+              result = self.execute(frame, catch_start, finally_start);
+            }
+          }
+          if action.has_finally {
+            match self.execute(frame, finally_start, finally_end)? {
+              Flow::Done => {}
+              flow => result = Ok(flow),
+            }
+          }
+          pc = match resume(result?, finally_end) {
+            Ok(pc) => pc,
+            Err(flow) => return Ok(flow),
+          };
+        }
+        Action::Push(values) => {
+          for value in values {
+            let value = match value {
+              PushValue::String(value) => Value::String(lossy(&value)),
+              PushValue::Float32(value) => Value::Number(f64::from(value)),
+              PushValue::Null => Value::Null,
+              PushValue::Undefined => Value::Undefined,
+              PushValue::Register(register) => frame
+                .registers
+                .get(usize::from(register))
+                .cloned()
+                .unwrap_or(Value::Undefined),
+              PushValue::Boolean(value) => Value::Boolean(value),
+              PushValue::Float64(value) => Value::Number(value),
+              PushValue::Sint32(value) => Value::Number(f64::from(value)),
+              PushValue::Constant8(index) => constant(&frame.constant_pool, usize::from(index)),
+              PushValue::Constant16(index) => constant(&frame.constant_pool, usize::from(index)),
+            };
+            frame.stack.push(value);
+          }
+        }
+        Action::Jump(offset) => pc = jump_target(next, offset),
+        Action::If(offset) => {
+          let condition = frame.pop();
+          if self.to_bool(&condition) {
+            pc = jump_target(next, offset);
+          }
+        }
+        // Flash Player skips unknown actions
+        Action::Unknown { .. } => {}
+        Action::Malformed { error, .. } => return Err(Interrupt::Error(format!("malformed action: {}", error))),
+        Action::Truncated(_) => return Err(Interrupt::Error(String::from("truncated action"))),
+      }
+    }
+  }
+
+  /// Executes an action without operands.
+  fn simple(&mut self, frame: &mut Frame, code: u8) -> Result<(), Interrupt> {
+    match code {
+      // Timeline actions
+      0x04..=0x09 | 0x28 => {}
+      0x0a | 0x0b | 0x0c | 0x0d | 0x3f => {
+        let right = frame.pop();
+        let left = frame.pop();
+        let (left, right) = (self.to_number(&left), self.to_number(&right));
+        let result = match code {
+          0x0a => left + right,
+          0x0b => left - right,
+          0x0c => left * right,
+          0x0d => left / right,
+          _ => left % right,
+        };
+        frame.stack.push(Value::Number(result));
+      }
+      // Equals, Less, And, Or
+      0x0e..=0x11 => {
+        let right = frame.pop();
+        let left = frame.pop();
+        let result = match code {
+          0x0e => self.to_number(&left) == self.to_number(&right),
+          0x0f => self.to_number(&left) < self.to_number(&right),
+          0x10 => self.to_bool(&left) && self.to_bool(&right),
+          _ => self.to_bool(&left) || self.to_bool(&right),
+        };
+        frame.stack.push(self.legacy_bool(result));
+      }
+      0x12 => {
+        let value = frame.pop();
+        let result = !self.to_bool(&value);
+        frame.stack.push(self.legacy_bool(result));
+      }
+      // StringEquals, StringLess, StringGreater
+      0x13 | 0x29 | 0x68 => {
+        let right = frame.pop();
+        let left = frame.pop();
+        let (left, right) = (self.to_string(&left), self.to_string(&right));
+        let result = match code {
+          0x13 => left == right,
+          0x29 => left < right,
+          _ => left > right,
+        };
+        frame.stack.push(if code == 0x13 {
+          self.legacy_bool(result)
+        } else {
+          Value::Boolean(result)
+        });
+      }
+      // StringLength, MbStringLength
+      0x14 | 0x31 => {
+        let value = frame.pop();
+        let length = self.to_string(&value).chars().count();
+        frame.stack.push(Value::Number(length as f64));
+      }
+      // StringExtract, MbStringExtract
+      0x15 | 0x35 => {
+        let count = frame.pop();
+        let index = frame.pop();
+        let value = frame.pop();
+        let (count, index) = (self.to_integer(&count), self.to_integer(&index));
+        let chars: Vec<char> = self.to_string(&value).chars().collect();
+        let start = (index - 1.0).max(0.0).min(chars.len() as f64) as usize;
+        let end = if count < 0.0 {
+          chars.len()
+        } else {
+          (start as f64 + count).min(chars.len() as f64) as usize
+        };
+        frame.stack.push(Value::String(chars[start..end].iter().collect()));
+      }
+      0x17 => {
+        frame.pop();
+      }
+      0x18 => {
+        let value = frame.pop();
+        let result = self.to_integer(&value);
+        frame.stack.push(Value::Number(result));
+      }
+      0x1c => {
+        let name = frame.pop();
+        let name = self.to_string(&name);
+        let value = self.get_variable(frame, &name);
+        frame.stack.push(value);
+      }
+      0x1d => {
+        let value = frame.pop();
+        let name = frame.pop();
+        let name = self.to_string(&name);
+        self.set_variable(frame, &name, value);
+      }
+      0x20 => {
+        let target = frame.pop();
+        frame.target = match &target {
+          Value::String(target) if target.is_empty() => frame.base_target,
+          target => self.target_of(frame, target).unwrap_or(frame.target),
+        };
+      }
+      0x21 => {
+        let right = frame.pop();
+        let left = frame.pop();
+        let result = self.to_string(&left) + &self.to_string(&right);
+        frame.stack.push(new_string(result)?);
+      }
+      0x22 => {
+        let index = frame.pop();
+        let target = frame.pop();
+        let index = self.to_number(&index);
+        let value = match (self.target_of(frame, &target), property_name(index)) {
+          (Some(clip), Some(name)) => self.get_member(&Value::Object(clip), name),
+          _ => Value::Undefined,
+        };
+        frame.stack.push(value);
+      }
+      0x23 => {
+        let value = frame.pop();
+        let index = frame.pop();
+        let target = frame.pop();
+        let index = self.to_number(&index);
+        if let (Some(clip), Some(name)) = (self.target_of(frame, &target), property_name(index)) {
+          self.set_member(&Value::Object(clip), name, value);
+        }
+      }
+      // CloneSprite
+      0x24 => {
+        frame.pop_values(3);
+      }
+      0x25 => {
+        frame.pop();
+      }
+      0x26 => {
+        let value = frame.pop();
+        let message = match value {
+          Value::Undefined => String::from("undefined"),
+          value => self.to_string(&value),
+        };
+        self.traces.push(message);
+      }
+      // StartDrag
+      0x27 => {
+        frame.pop();
+        frame.pop();
+        let constrain = frame.pop();
+        if self.to_bool(&constrain) {
+          frame.pop_values(4);
+        }
+      }
+      0x2a => return Err(Interrupt::Throw(frame.pop())),
+      0x2b => {
+        let object = frame.pop();
+        let constructor = frame.pop();
+        let result = if self.instance_of(&object, &constructor) {
+          object
+        } else {
+          Value::Null
+        };
+        frame.stack.push(result);
+      }
+      // ImplementsOp
+      0x2c => {
+        frame.pop();
+        let count = frame.pop();
+        let count = self.to_count(&count);
+        frame.pop_values(count);
+      }
+      0x2d => {
+        let count = frame.pop();
+        let count = self.to_count(&count);
+        let arguments = frame.pop_values(count);
+        let arguments = arguments.iter().map(|value| self.to_string(value)).collect();
+        self.record("fscommand", arguments);
+      }
+      0x30 => {
+        let max = frame.pop();
+        let max = f64::from(to_int32(self.to_number(&max)));
+        let result = (self.next_random() * max).floor();
+        frame.stack.push(Value::Number(result));
+      }
+      // CharToAscii, MbCharToAscii
+      0x32 | 0x36 => {
+        let value = frame.pop();
+        let result = match self.to_string(&value).chars().next() {
+          Some(c) => f64::from(c as u32),
+          None => f64::NAN,
+        };
+        frame.stack.push(Value::Number(result));
+      }
+      // AsciiToChar, MbAsciiToChar
+      0x33 | 0x37 => {
+        let value = frame.pop();
+        let code = to_int32(self.to_number(&value)) as u32;
+        let result = std::char::from_u32(code).map(String::from).unwrap_or_default();
+        frame.stack.push(Value::String(result));
+      }
+      0x34 => frame.stack.push(Value::Number(self.steps as f64)),
+      0x3a => {
+        let name = frame.pop();
+        let object = frame.pop();
+        let name = self.to_string(&name);
+        let result = self.delete_member(&object, &name);
+        frame.stack.push(Value::Boolean(result));
+      }
+      0x3b => {
+        let name = frame.pop();
+        let name = self.to_string(&name);
+        let mut result = false;
+        for object in frame.scopes.iter().rev().chain(std::iter::once(&frame.target)) {
+          if self.objects[*object].properties.contains_key(&name) {
+            result = self.delete_member(&Value::Object(*object), &name);
+            break;
+          }
+        }
+        frame.stack.push(Value::Boolean(result));
+      }
+      0x3c => {
+        let value = frame.pop();
+        let name = frame.pop();
+        let name = self.to_string(&name);
+        self.define_local(frame, &name, value);
+      }
+      0x3d => {
+        let name = frame.pop();
+        let count = frame.pop();
+        let name = self.to_string(&name);
+        let count = self.to_count(&count);
+        let arguments = frame.pop_values(count);
+        let function = self.get_variable(frame, &name);
+        let this = Value::Object(frame.target);
+        let result = self.call(&function, this, arguments)?;
+        frame.stack.push(result);
+      }
+      0x40 => {
+        let name = frame.pop();
+        let count = frame.pop();
+        let name = self.to_string(&name);
+        let count = self.to_count(&count);
+        let arguments = frame.pop_values(count);
+        let constructor = self.get_variable(frame, &name);
+        let result = self.construct(&constructor, arguments)?;
+        frame.stack.push(result);
+      }
+      0x41 => {
+        let name = frame.pop();
+        let name = self.to_string(&name);
+        let exists = match frame.activation {
+          Some(activation) => self.objects[activation].properties.contains_key(&name),
+          None => self.objects[frame.target].properties.contains_key(&name),
+        };
+        if !exists {
+          self.define_local(frame, &name, Value::Undefined);
+        }
+      }
+      0x42 => {
+        let count = frame.pop();
+        let count = self.to_count(&count);
+        let elements = frame.pop_values(count);
+        let array = self.new_array(elements);
+        frame.stack.push(array);
+      }
+      0x43 => {
+        let count = frame.pop();
+        let count = self.to_count(&count).min(frame.stack.len() / 2);
+        let proto = self.protos.object;
+        let object = Value::Object(self.new_object(Kind::Plain, Some(proto)));
+        for _ in 0..count {
+          let value = frame.pop();
+          let name = frame.pop();
+          let name = self.to_string(&name);
+          self.set_member(&object, &name, value);
+        }
+        frame.stack.push(object);
+      }
+      0x44 => {
+        let value = frame.pop();
+        let result = match &value {
+          Value::Undefined => "undefined",
+          Value::Null => "null",
+          Value::Boolean(_) => "boolean",
+          Value::Number(_) => "number",
+          Value::String(_) => "string",
+          Value::Object(object) => match self.objects[*object].kind {
+            Kind::Function(_) | Kind::Native(_) => "function",
+            Kind::Clip { .. } => "movieclip",
+            Kind::Plain | Kind::Array(_) => "object",
+          },
+        };
+        frame.stack.push(Value::String(result.to_string()));
+      }
+      0x45 => {
+        let value = frame.pop();
+        let result = match value {
+          Value::Object(object) if matches!(self.objects[object].kind, Kind::Clip { .. }) => {
+            Value::String(self.clip_path(object))
+          }
+          _ => Value::Undefined,
+        };
+        frame.stack.push(result);
+      }
+      // Enumerate, Enumerate2
+      0x46 | 0x55 => {
+        let object = frame.pop();
+        let object = if code == 0x46 {
+          let name = self.to_string(&object);
+          self.get_variable(frame, &name)
+        } else {
+          object
+        };
+        frame.stack.push(Value::Null);
+        if let Value::Object(object) = object {
+          for key in self.keys(object).into_iter().rev() {
+            frame.stack.push(Value::String(key));
+          }
+        }
+      }
+      0x47 => {
+        let right = frame.pop();
+        let left = frame.pop();
+        let result = if is_string_like(&left) || is_string_like(&right) {
+          new_string(self.to_string(&left) + &self.to_string(&right))?
+        } else {
+          Value::Number(self.to_number(&left) + self.to_number(&right))
+        };
+        frame.stack.push(result);
+      }
+      // Less2, Greater
+      0x48 | 0x67 => {
+        let right = frame.pop();
+        let left = frame.pop();
+        let (left, right) = if code == 0x48 { (left, right) } else { (right, left) };
+        let result = if is_string_like(&left) && is_string_like(&right) {
+          Value::Boolean(self.to_string(&left) < self.to_string(&right))
+        } else {
+          let (left, right) = (self.to_number(&left), self.to_number(&right));
+          if left.is_nan() || right.is_nan() {
+            Value::Undefined
+          } else {
+            Value::Boolean(left < right)
+          }
+        };
+        frame.stack.push(result);
+      }
+      0x49 => {
+        let right = frame.pop();
+        let left = frame.pop();
+        let result = self.loose_equals(&left, &right);
+        frame.stack.push(Value::Boolean(result));
+      }
+      0x4a => {
+        let value = frame.pop();
+        let result = self.to_number(&value);
+        frame.stack.push(Value::Number(result));
+      }
+      0x4b => {
+        let value = frame.pop();
+        let result = self.to_string(&value);
+        frame.stack.push(Value::String(result));
+      }
+      0x4c => {
+        let value = frame.stack.last().cloned().unwrap_or(Value::Undefined);
+        frame.stack.push(value);
+      }
+      0x4d => {
+        let right = frame.pop();
+        let left = frame.pop();
+        frame.stack.push(right);
+        frame.stack.push(left);
+      }
+      0x4e => {
+        let name = frame.pop();
+        let object = frame.pop();
+        let name = self.to_string(&name);
+        let value = self.get_member(&object, &name);
+        frame.stack.push(value);
+      }
+      0x4f => {
+        let value = frame.pop();
+        let name = frame.pop();
+        let object = frame.pop();
+        let name = self.to_string(&name);
+        self.set_member(&object, &name, value);
+      }
+      0x50 | 0x51 => {
+        let value = frame.pop();
+        let value = self.to_number(&value);
+        frame
+          .stack
+          .push(Value::Number(if code == 0x50 { value + 1.0 } else { value - 1.0 }));
+      }
+      0x52 | 0x53 => {
+        let name = frame.pop();
+        let object = frame.pop();
+        let count = frame.pop();
+        let count = self.to_count(&count);
+        let arguments = frame.pop_values(count);
+        let function = match &name {
+          Value::Undefined => object.clone(),
+          Value::String(name) if name.is_empty() => object.clone(),
+          name => {
+            let name = self.to_string(name);
+            self.get_member(&object, &name)
+          }
+        };
+        let result = if code == 0x52 {
+          self.call(&function, object, arguments)?
+        } else {
+          self.construct(&function, arguments)?
+        };
+        frame.stack.push(result);
+      }
+      0x54 => {
+        let constructor = frame.pop();
+        let object = frame.pop();
+        let result = self.instance_of(&object, &constructor);
+        frame.stack.push(Value::Boolean(result));
+      }
+      // BitAnd, BitOr, BitXor, BitLShift, BitRShift, BitURShift
+      0x60..=0x65 => {
+        let right = frame.pop();
+        let left = frame.pop();
+        let (left, right) = (to_int32(self.to_number(&left)), to_int32(self.to_number(&right)));
+        let shift = (right & 0x1f) as u32;
+        let result = match code {
+          0x60 => f64::from(left & right),
+          0x61 => f64::from(left | right),
+          0x62 => f64::from(left ^ right),
+          0x63 => f64::from(left.wrapping_shl(shift)),
+          0x64 => f64::from(left.wrapping_shr(shift)),
+          _ => f64::from((left as u32).wrapping_shr(shift)),
+        };
+        frame.stack.push(Value::Number(result));
+      }
+      0x66 => {
+        let right = frame.pop();
+        let left = frame.pop();
+        let result = match (&left, &right) {
+          (Value::Number(left), Value::Number(right)) => left == right,
+          (left, right) => left == right,
+        };
+        frame.stack.push(Value::Boolean(result));
+      }
+      0x69 => {
+        let superclass = frame.pop();
+        let subclass = frame.pop();
+        let proto = match self.get_member(&superclass, "prototype") {
+          Value::Object(proto) => proto,
+          _ => self.protos.object,
+        };
+        let object = self.new_object(Kind::Plain, Some(proto));
+        self.set_member(&subclass, "prototype", Value::Object(object));
+      }
+      _ => return Err(Interrupt::Error(format!("unsupported action: 0x{:02x}", code))),
+    }
+    Ok(())
+  }
+
+  fn define_function(&mut self, frame: &mut Frame, name: &[u8], function: Function) {
+    let proto = self.protos.function;
+    let value = Value::Object(self.new_object(Kind::Function(Rc::new(function)), Some(proto)));
+    if name.is_empty() {
+      frame.stack.push(value);
+    } else {
+      self.define_local(frame, &lossy(name), value);
+    }
+  }
+
+  /// Defines a variable in the activation of the current function, or in the timeline for top-level code.
+  fn define_local(&mut self, frame: &Frame, name: &str, value: Value) {
+    let object = frame.activation.unwrap_or(frame.target);
+    self.set_member(&Value::Object(object), name, value);
+  }
+
+  fn call(&mut self, function: &Value, this: Value, arguments: Vec<Value>) -> Result<Value, Interrupt> {
+    let object = match function {
+      Value::Object(object) => *object,
+      _ => return Ok(Value::Undefined),
+    };
+    let function = match &self.objects[object].kind {
+      Kind::Function(function) => Rc::clone(function),
+      Kind::Native(native) => return self.native(*native, this, arguments),
+      _ => return Ok(Value::Undefined),
+    };
+    if self.depth >= MAX_CALL_DEPTH {
+      return Err(Interrupt::Error(String::from("call stack overflow")));
+    }
+
+    let proto = self.protos.object;
+    let activation = self.new_object(Kind::Plain, Some(proto));
+    let mut scopes = function.scopes.clone();
+    scopes.push(activation);
+    let (register_count, flags) = function.function2.unwrap_or((4, 0));
+    let mut frame = Frame {
+      code: Rc::clone(&function.code),
+      stack: Vec::new(),
+      registers: vec![Value::Undefined; usize::from(register_count)],
+      scopes,
+      activation: Some(activation),
+      this: this.clone(),
+      target: function.target,
+      base_target: function.target,
+      constant_pool: Rc::clone(&function.constant_pool),
+    };
+    let arguments_array = self.new_array(arguments.clone());
+    if function.function2.is_some() {
+      let parent = self.get_member(&Value::Object(function.target), "_parent");
+      let preloaded = [
+        ("preload_this", this),
+        ("preload_arguments", arguments_array.clone()),
+        ("preload_super", Value::Undefined),
+        ("preload_root", Value::Object(self.root)),
+        ("preload_parent", parent),
+        ("preload_global", Value::Object(self.global)),
+      ];
+      let mut register = 1;
+      for (flag, value) in preloaded.iter() {
+        if has_flag(flags, flag) {
+          if let Some(slot) = frame.registers.get_mut(register) {
+            *slot = value.clone();
+          }
+          register += 1;
+        }
+      }
+    }
+    if !has_flag(flags, "suppress_arguments") && !has_flag(flags, "preload_arguments") {
+      self.objects[activation]
+        .properties
+        .insert(String::from("arguments"), arguments_array);
+    }
+    for (index, (register, name)) in function.parameters.iter().enumerate() {
+      let value = arguments.get(index).cloned().unwrap_or(Value::Undefined);
+      match frame.registers.get_mut(usize::from(*register)) {
+        Some(slot) if *register != 0 => *slot = value,
+        _ => {
+          self.objects[activation].properties.insert(name.clone(), value);
+        }
+      }
+    }
+
+    self.depth += 1;
+    let flow = self.execute(&mut frame, function.start, function.end);
+    self.depth -= 1;
+    match flow? {
+      Flow::Return(value) => Ok(value),
+      Flow::Done | Flow::Jump(_) => Ok(Value::Undefined),
+    }
+  }
+
+  fn construct(&mut self, constructor: &Value, arguments: Vec<Value>) -> Result<Value, Interrupt> {
+    let proto = match self.get_member(constructor, "prototype") {
+      Value::Object(proto) => proto,
+      _ => self.protos.object,
+    };
+    let object = Value::Object(self.new_object(Kind::Plain, Some(proto)));
+    let result = self.call(constructor, object.clone(), arguments)?;
+    // Built-in constructors create their own values (arrays, numbers, ...)
+    let is_native = matches!(constructor, Value::Object(id) if matches!(self.objects[*id].kind, Kind::Native(_)));
+    Ok(if is_native && result != Value::Undefined {
+      result
+    } else {
+      object
+    })
+  }
+
+  fn native(&mut self, native: Native, this: Value, arguments: Vec<Value>) -> Result<Value, Interrupt> {
+    let argument = |index: usize| arguments.get(index).cloned().unwrap_or(Value::Undefined);
+    let result = match native {
+      Native::Noop => Value::Undefined,
+      Native::Array => match arguments.as_slice() {
+        [Value::Number(length)] => {
+          let length = (length.max(0.0) as usize).min(MAX_ARRAY_LENGTH);
+          self.new_array(vec![Value::Undefined; length])
+        }
+        _ => self.new_array(arguments),
+      },
+      Native::Object => match this {
+        Value::Object(_) => this,
+        _ => {
+          let proto = self.protos.object;
+          Value::Object(self.new_object(Kind::Plain, Some(proto)))
+        }
+      },
+      Native::Number => Value::Number(if arguments.is_empty() {
+        0.0
+      } else {
+        self.to_number(&argument(0))
+      }),
+      Native::String => Value::String(self.to_string(&argument(0))),
+      Native::Boolean => Value::Boolean(self.to_bool(&argument(0))),
+      Native::Escape => Value::String(escape(&self.to_string(&argument(0)))),
+      Native::Unescape => Value::String(unescape(&self.to_string(&argument(0)))),
+      Native::ParseInt => {
+        let radix = match argument(1) {
+          Value::Undefined => None,
+          radix => Some(to_int32(self.to_number(&radix))),
+        };
+        Value::Number(parse_int(&self.to_string(&argument(0)), radix))
+      }
+      Native::ParseFloat => Value::Number(parse_float(&self.to_string(&argument(0)))),
+      Native::IsNaN => Value::Boolean(self.to_number(&argument(0)).is_nan()),
+      Native::IsFinite => Value::Boolean(self.to_number(&argument(0)).is_finite()),
+      Native::GetTimer => Value::Number(self.steps as f64),
+      Native::Call => return self.call(&this, argument(0), arguments.into_iter().skip(1).collect()),
+      Native::Apply => {
+        let arguments = match argument(1) {
+          Value::Object(array) => match &self.objects[array].kind {
+            Kind::Array(elements) => elements.clone(),
+            _ => Vec::new(),
+          },
+          _ => Vec::new(),
+        };
+        return self.call(&this, argument(0), arguments);
+      }
+      Native::FromCharCode => {
+        let mut result = String::new();
+        for value in arguments.iter() {
+          let code = to_int32(self.to_number(value)) as u32 & 0xffff;
+          result.extend(std::char::from_u32(code));
+        }
+        Value::String(result)
+      }
+      Native::CharAt | Native::CharCodeAt => {
+        let chars: Vec<char> = self.to_string(&this).chars().collect();
+        let index = self.to_integer(&argument(0));
+        let c = if index >= 0.0 { chars.get(index as usize) } else { None };
+        match (native, c) {
+          (Native::CharAt, c) => Value::String(c.map(|c| c.to_string()).unwrap_or_default()),
+          (_, Some(c)) => Value::Number(f64::from(*c as u32)),
+          (_, None) => Value::Number(f64::NAN),
+        }
+      }
+      Native::IndexOf | Native::LastIndexOf => {
+        let chars: Vec<char> = self.to_string(&this).chars().collect();
+        let needle: Vec<char> = self.to_string(&argument(0)).chars().collect();
+        let result = if needle.len() > chars.len() {
+          None
+        } else {
+          let last = chars.len() - needle.len();
+          let matches = |start: &usize| chars[*start..*start + needle.len()] == needle[..];
+          match (native, argument(1)) {
+            (Native::IndexOf, Value::Undefined) => (0..=last).find(matches),
+            (Native::IndexOf, from) => {
+              let from = self.to_integer(&from).max(0.0) as usize;
+              (from.min(last + 1)..=last).find(matches)
+            }
+            (_, Value::Undefined) => (0..=last).rev().find(matches),
+            (_, from) => {
+              let from = self.to_integer(&from);
+              if from < 0.0 {
+                None
+              } else {
+                (0..=last.min(from as usize)).rev().find(matches)
+              }
+            }
+          }
+        };
+        Value::Number(result.map(|index| index as f64).unwrap_or(-1.0))
+      }
+      Native::Substr | Native::Substring | Native::Slice => {
+        let chars: Vec<char> = self.to_string(&this).chars().collect();
+        let length = chars.len() as f64;
+        let start = self.to_integer(&argument(0));
+        let end = match argument(1) {
+          Value::Undefined => None,
+          end => Some(self.to_integer(&end)),
+        };
+        let relative = |index: f64| {
+          if index < 0.0 {
+            (length + index).max(0.0)
+          } else {
+            index.min(length)
+          }
+        };
+        let (start, end) = match native {
+          Native::Substr => {
+            let start = relative(start);
+            let end = match end {
+              Some(count) if count < 0.0 => start,
+              Some(count) => (start + count).min(length),
+              None => length,
+            };
+            (start, end)
+          }
+          Native::Substring => {
+            let clamp = |index: f64| index.max(0.0).min(length);
+            let (start, end) = (clamp(start), clamp(end.unwrap_or(length)));
+            if start <= end {
+              (start, end)
+            } else {
+              (end, start)
+            }
+          }
+          _ => {
+            let (start, end) = (relative(start), relative(end.unwrap_or(length)));
+            (start, end.max(start))
+          }
+        };
+        Value::String(chars[start as usize..end as usize].iter().collect())
+      }
+      Native::Split => {
+        let value = self.to_string(&this);
+        let parts: Vec<Value> = match argument(0) {
+          Value::Undefined => vec![Value::String(value)],
+          separator => {
+            let separator = self.to_string(&separator);
+            if separator.is_empty() {
+              value.chars().map(|c| Value::String(c.to_string())).collect()
+            } else {
+              value
+                .split(separator.as_str())
+                .map(|part| Value::String(part.to_string()))
+                .collect()
+            }
+          }
+        };
+        let limit = match argument(1) {
+          Value::Undefined => parts.len(),
+          limit => self.to_integer(&limit).max(0.0) as usize,
+        };
+        self.new_array(parts.into_iter().take(limit).collect())
+      }
+      Native::ToUpperCase => Value::String(self.to_string(&this).to_uppercase()),
+      Native::ToLowerCase => Value::String(self.to_string(&this).to_lowercase()),
+      Native::Concat => {
+        let mut result = self.to_string(&this);
+        for value in arguments.iter() {
+          result.push_str(&self.to_string(value));
+        }
+        new_string(result)?
+      }
+      Native::ToString => match (&this, argument(0)) {
+        (Value::Number(value), Value::Number(radix)) if radix != 10.0 => {
+          Value::String(format_radix(*value, to_int32(radix)))
+        }
+        (this, _) => Value::String(self.to_string(this)),
+      },
+      Native::ValueOf => this,
+      Native::Push | Native::Unshift => {
+        let length = match self.array_mut(&this) {
+          Some(elements) => {
+            if let Native::Push = native {
+              elements.extend(arguments);
+            } else {
+              elements.splice(0..0, arguments);
+            }
+            elements.len() as f64
+          }
+          None => return Ok(Value::Undefined),
+        };
+        Value::Number(length)
+      }
+      Native::Pop | Native::Shift => match self.array_mut(&this) {
+        Some(elements) if elements.is_empty() => Value::Undefined,
+        Some(elements) => match native {
+          Native::Pop => elements.pop().unwrap_or(Value::Undefined),
+          _ => elements.remove(0),
+        },
+        None => Value::Undefined,
+      },
+      Native::Join => {
+        let elements = match self.array_mut(&this) {
+          Some(elements) => elements.clone(),
+          None => return Ok(Value::Undefined),
+        };
+        let separator = match argument(0) {
+          Value::Undefined => String::from(","),
+          separator => self.to_string(&separator),
+        };
+        let parts: Vec<String> = elements.iter().map(|value| self.element_to_string(value)).collect();
+        new_string(parts.join(&separator))?
+      }
+      Native::Reverse => {
+        if let Some(elements) = self.array_mut(&this) {
+          elements.reverse();
+        }
+        this
+      }
+      Native::ArraySlice => {
+        let elements = match self.array_mut(&this) {
+          Some(elements) => elements.clone(),
+          None => return Ok(Value::Undefined),
+        };
+        let length = elements.len() as f64;
+        let relative = |index: f64| {
+          if index < 0.0 {
+            (length + index).max(0.0)
+          } else {
+            index.min(length)
+          }
+        };
+        let start = relative(self.to_integer(&argument(0)));
+        let end = match argument(1) {
+          Value::Undefined => length,
+          end => relative(self.to_integer(&end)),
+        };
+        let slice = elements[start as usize..end.max(start) as usize].to_vec();
+        self.new_array(slice)
+      }
+      Native::ArrayConcat => {
+        let mut elements = match self.array_mut(&this) {
+          Some(elements) => elements.clone(),
+          None => return Ok(Value::Undefined),
+        };
+        for value in arguments {
+          match self.array_mut(&value) {
+            Some(other) => elements.extend(other.iter().cloned()),
+            None => elements.push(value),
+          }
+        }
+        self.new_array(elements)
+      }
+      Native::Math(function) => Value::Number(function(self.to_number(&argument(0)))),
+      Native::Round => Value::Number((self.to_number(&argument(0)) + 0.5).floor()),
+      Native::Max | Native::Min => {
+        let mut result = if let Native::Max = native {
+          f64::NEG_INFINITY
+        } else {
+          f64::INFINITY
+        };
+        for value in arguments.iter() {
+          let value = self.to_number(value);
+          result = match native {
+            _ if value.is_nan() || result.is_nan() => f64::NAN,
+            Native::Max => result.max(value),
+            _ => result.min(value),
+          };
+        }
+        Value::Number(result)
+      }
+      Native::Pow => Value::Number(self.to_number(&argument(0)).powf(self.to_number(&argument(1)))),
+      Native::Atan2 => Value::Number(self.to_number(&argument(0)).atan2(self.to_number(&argument(1)))),
+      Native::Random => Value::Number(self.next_random()),
+      Native::CreateClip(index) => match this {
+        Value::Object(clip) if matches!(self.objects[clip].kind, Kind::Clip { .. }) => {
+          let name = self.to_string(&argument(index));
+          self.new_clip(clip, name)
+        }
+        _ => Value::Undefined,
+      },
+      Native::BytesLoaded => Value::Number(100.0),
+      Native::External(kind) => {
+        let arguments = arguments.iter().map(|value| self.to_string(value)).collect();
+        self.record(kind, arguments);
+        Value::Undefined
+      }
+      Native::ClipLoad(kind) => {
+        let mut arguments: Vec<String> = arguments.iter().map(|value| self.to_string(value)).collect();
+        let target = self.to_string(&this);
+        arguments.insert(arguments.len().min(1), target);
+        self.record(kind, arguments);
+        Value::Undefined
+      }
+    };
+    Ok(result)
+  }
+
+  fn record(&mut self, kind: &'static str, arguments: Vec<String>) {
+    let (code, offset) = &self.position;
+    self.calls.push(ExternalCall {
+      location: code.location.clone(),
+      offset: *offset,
+      kind,
+      arguments,
+    });
+  }
+
+  /// Returns a number in `[0, 1)`, from a xorshift generator with a fixed seed.
+  fn next_random(&mut self) -> f64 {
+    let mut x = self.random;
+    x ^= x << 13;
+    x ^= x >> 17;
+    x ^= x << 5;
+    self.random = x;
+    f64::from(x) / 4_294_967_296.0
+  }
+
+  fn array_mut(&mut self, value: &Value) -> Option<&mut Vec<Value>> {
+    match value {
+      Value::Object(object) => match &mut self.objects[*object].kind {
+        Kind::Array(elements) => Some(elements),
+        _ => None,
+      },
+      _ => None,
+    }
+  }
+
+  fn get_variable(&mut self, frame: &Frame, name: &str) -> Value {
+    if let Some(index) = name.rfind(':') {
+      return match self.resolve_target(frame, &name[..index]) {
+        Some(clip) => self.get_member(&Value::Object(clip), &name[index + 1..]),
+        None => Value::Undefined,
+      };
+    }
+    if name.contains('/') {
+      return match self.resolve_target(frame, name) {
+        Some(clip) => Value::Object(clip),
+        None => Value::Undefined,
+      };
+    }
+    let mut segments = name.split('.');
+    let mut value = self.lookup(frame, segments.next().unwrap_or_default());
+    for segment in segments {
+      value = self.get_member(&value, segment);
+    }
+    value
+  }
+
+  fn set_variable(&mut self, frame: &Frame, name: &str, value: Value) {
+    if let Some(index) = name.rfind(':') {
+      if let Some(clip) = self.resolve_target(frame, &name[..index]) {
+        self.set_member(&Value::Object(clip), &name[index + 1..], value);
+      }
+      return;
+    }
+    if let Some(index) = name.rfind('.') {
+      let object = self.get_variable(frame, &name[..index]);
+      self.set_member(&object, &name[index + 1..], value);
+      return;
+    }
+    for index in (0..frame.scopes.len()).rev() {
+      let scope = frame.scopes[index];
+      if self.find_property(scope, name).is_some() {
+        self.set_member(&Value::Object(scope), name, value);
+        return;
+      }
+    }
+    self.set_member(&Value::Object(frame.target), name, value);
+  }
+
+  /// Looks up a variable in the scope chain, then in the timeline and `_global`.
+  fn lookup(&mut self, frame: &Frame, name: &str) -> Value {
+    match name {
+      "this" => return frame.this.clone(),
+      "_global" => return Value::Object(self.global),
+      "_root" | "_level0" => return Value::Object(self.root),
+      _ => {}
+    }
+    for index in (0..frame.scopes.len()).rev() {
+      if let Some(value) = self.find_property(frame.scopes[index], name) {
+        return value;
+      }
+    }
+    if let Some(value) = self.find_property(frame.target, name) {
+      return value;
+    }
+    self.find_property(self.global, name).unwrap_or(Value::Undefined)
+  }
+
+  /// Resolves a target path (`/a/b`, `_root.a.b`, `_parent`, ...) to a clip.
+  fn resolve_target(&mut self, frame: &Frame, path: &str) -> Option<usize> {
+    let (mut clip, path) = match path.strip_prefix('/') {
+      Some(path) => (self.root, path),
+      None => (frame.target, path),
+    };
+    for segment in path.split(&['/', '.'][..]).filter(|segment| !segment.is_empty()) {
+      clip = match segment {
+        "_root" | "_level0" => self.root,
+        "this" => clip,
+        segment => match self.get_member(&Value::Object(clip), segment) {
+          Value::Object(child) => child,
+          _ => return None,
+        },
+      };
+    }
+    Some(clip)
+  }
+
+  fn target_of(&mut self, frame: &Frame, target: &Value) -> Option<usize> {
+    match target {
+      Value::Object(object) => Some(*object),
+      target => {
+        let path = self.to_string(target);
+        self.resolve_target(frame, &path)
+      }
+    }
+  }
+
+  fn get_member(&mut self, object: &Value, key: &str) -> Value {
+    let object = match object {
+      Value::Object(object) => *object,
+      Value::String(value) => {
+        if key == "length" {
+          return Value::Number(value.chars().count() as f64);
+        }
+        self.protos.string
+      }
+      Value::Number(_) | Value::Boolean(_) => self.protos.number,
+      Value::Undefined | Value::Null => return Value::Undefined,
+    };
+    self.find_property(object, key).unwrap_or(Value::Undefined)
+  }
+
+  /// Returns the property of the object or of its prototypes.
+  fn find_property(&mut self, object: usize, key: &str) -> Option<Value> {
+    if let Some(value) = self.own_property(object, key) {
+      return Some(value);
+    }
+    let mut proto = self.objects[object].proto;
+    // The depth is bounded in case of prototype cycles
+    for _ in 0..64 {
+      let current = match proto {
+        Some(current) => current,
+        None => break,
+      };
+      if let Some(value) = self.objects[current].properties.get(key) {
+        return Some(value.clone());
+      }
+      proto = self.objects[current].proto;
+    }
+    if object == self.global {
+      return self.builtins.get(key).cloned();
+    }
+    None
+  }
+
+  fn own_property(&mut self, object: usize, key: &str) -> Option<Value> {
+    if key == "__proto__" {
+      return self.objects[object].proto.map(Value::Object);
+    }
+    match &self.objects[object].kind {
+      Kind::Array(elements) => {
+        if key == "length" {
+          return Some(Value::Number(elements.len() as f64));
+        }
+        if let Ok(index) = key.parse::<usize>() {
+          return Some(elements.get(index).cloned().unwrap_or(Value::Undefined));
+        }
+      }
+      Kind::Clip { name, parent } => match key {
+        "_name" => return Some(Value::String(name.clone())),
+        "_parent" => return Some(parent.map(Value::Object).unwrap_or(Value::Undefined)),
+        "_target" => return Some(Value::String(self.clip_target(object))),
+        _ => {}
+      },
+      Kind::Function(_) if key == "prototype" && !self.objects[object].properties.contains_key(key) => {
+        // Prototypes of functions are created when they are first used
+        let proto = self.protos.object;
+        let prototype = Value::Object(self.new_object(Kind::Plain, Some(proto)));
+        self.objects[object]
+          .properties
+          .insert(String::from("prototype"), prototype.clone());
+        return Some(prototype);
+      }
+      _ => {}
+    }
+    if let Some(value) = self.objects[object].properties.get(key) {
+      return Some(value.clone());
+    }
+    match self.objects[object].kind {
+      Kind::Clip { .. } => clip_default(key),
+      _ => None,
+    }
+  }
+
+  fn set_member(&mut self, object: &Value, key: &str, value: Value) {
+    let object = match object {
+      Value::Object(object) => *object,
+      _ => return,
+    };
+    if key == "__proto__" {
+      self.objects[object].proto = match value {
+        Value::Object(proto) => Some(proto),
+        _ => None,
+      };
+      return;
+    }
+    let length = match (&self.objects[object].kind, key) {
+      (Kind::Array(_), "length") => Some(self.to_integer(&value).max(0.0).min(MAX_ARRAY_LENGTH as f64) as usize),
+      _ => None,
+    };
+    if let Kind::Array(elements) = &mut self.objects[object].kind {
+      if let Some(length) = length {
+        elements.resize(length, Value::Undefined);
+        return;
+      }
+      if let Ok(index) = key.parse::<usize>() {
+        if index < MAX_ARRAY_LENGTH {
+          if index >= elements.len() {
+            elements.resize(index + 1, Value::Undefined);
+          }
+          elements[index] = value;
+          return;
+        }
+      }
+    }
+    self.objects[object].properties.insert(key.to_string(), value);
+  }
+
+  fn delete_member(&mut self, object: &Value, key: &str) -> bool {
+    match object {
+      Value::Object(object) => self.objects[*object].properties.remove(key).is_some(),
+      _ => false,
+    }
+  }
+
+  /// Returns the enumerable keys of the object: array indexes, then properties.
+  fn keys(&self, object: usize) -> Vec<String> {
+    let object = &self.objects[object];
+    let indexes = match &object.kind {
+      Kind::Array(elements) => elements.len(),
+      _ => 0,
+    };
+    (0..indexes)
+      .map(|index| index.to_string())
+      .chain(object.properties.keys().cloned())
+      .collect()
+  }
+
+  fn instance_of(&mut self, object: &Value, constructor: &Value) -> bool {
+    let prototype = match self.get_member(constructor, "prototype") {
+      Value::Object(prototype) => prototype,
+      _ => return false,
+    };
+    let mut proto = match object {
+      Value::Object(object) => self.objects[*object].proto,
+      _ => return false,
+    };
+    for _ in 0..64 {
+      match proto {
+        Some(current) if current == prototype => return true,
+        Some(current) => proto = self.objects[current].proto,
+        None => return false,
+      }
+    }
+    false
+  }
+
+  /// Returns the path of the clip with the dot syntax (`_level0.a.b`).
+  fn clip_path(&self, clip: usize) -> String {
+    match &self.objects[clip].kind {
+      Kind::Clip {
+        name,
+        parent: Some(parent),
+      } => format!("{}.{}", self.clip_path(*parent), name),
+      _ => String::from("_level0"),
+    }
+  }
+
+  /// Returns the path of the clip with the slash syntax (`/a/b`), as in `_target`.
+  fn clip_target(&self, clip: usize) -> String {
+    match &self.objects[clip].kind {
+      Kind::Clip {
+        name,
+        parent: Some(parent),
+      } => {
+        let parent = self.clip_target(*parent);
+        format!("{}/{}", parent.trim_end_matches('/'), name)
+      }
+      _ => String::from("/"),
+    }
+  }
+
+  /// Returns a boolean as pushed by SWF 4 actions: a number before SWF 5.
+  fn legacy_bool(&self, value: bool) -> Value {
+    if self.swf_version < 5 {
+      Value::Number(if value { 1.0 } else { 0.0 })
+    } else {
+      Value::Boolean(value)
+    }
+  }
+
+  fn to_bool(&self, value: &Value) -> bool {
+    match value {
+      Value::Undefined | Value::Null => false,
+      Value::Boolean(value) => *value,
+      Value::Number(value) => *value != 0.0 && !value.is_nan(),
+      Value::String(value) => {
+        if self.swf_version >= 7 {
+          !value.is_empty()
+        } else {
+          let value = parse_number(value);
+          value != 0.0 && !value.is_nan()
+        }
+      }
+      Value::Object(_) => true,
+    }
+  }
+
+  fn to_number(&self, value: &Value) -> f64 {
+    match value {
+      Value::Undefined | Value::Null => {
+        if self.swf_version >= 7 {
+          f64::NAN
+        } else {
+          0.0
+        }
+      }
+      Value::Boolean(value) => {
+        if *value {
+          1.0
+        } else {
+          0.0
+        }
+      }
+      Value::Number(value) => *value,
+      Value::String(value) => {
+        if value.is_empty() && self.swf_version < 7 {
+          0.0
+        } else {
+          parse_number(value)
+        }
+      }
+      Value::Object(_) => parse_number(&self.to_string(value)),
+    }
+  }
+
+  /// Converts the value to a number truncated towards zero, with `NaN` converted to `0`.
+  fn to_integer(&self, value: &Value) -> f64 {
+    let value = self.to_number(value);
+    if value.is_nan() {
+      0.0
+    } else {
+      value.trunc()
+    }
+  }
+
+  /// Converts the value to a count of stack values.
+  fn to_count(&self, value: &Value) -> usize {
+    self.to_integer(value).max(0.0).min(usize::MAX as f64) as usize
+  }
+
+  fn to_string(&self, value: &Value) -> String {
+    match value {
+      Value::Undefined => {
+        if self.swf_version >= 7 {
+          String::from("undefined")
+        } else {
+          String::new()
+        }
+      }
+      Value::Null => String::from("null"),
+      Value::Boolean(value) => value.to_string(),
+      Value::Number(value) => format_number(*value),
+      Value::String(value) => value.clone(),
+      Value::Object(object) => match &self.objects[*object].kind {
+        Kind::Array(elements) => {
+          let parts: Vec<String> = elements.iter().map(|value| self.element_to_string(value)).collect();
+          parts.join(",")
+        }
+        Kind::Function(_) | Kind::Native(_) => String::from("[type Function]"),
+        Kind::Clip { .. } => self.clip_path(*object),
+        Kind::Plain => String::from("[object Object]"),
+      },
+    }
+  }
+
+  /// Converts an element of an array to a string, without following nested arrays (they may be cyclic).
+  fn element_to_string(&self, value: &Value) -> String {
+    match value {
+      Value::Undefined => String::new(),
+      Value::Object(object) if matches!(self.objects[*object].kind, Kind::Array(_)) => String::from("[array]"),
+      value => self.to_string(value),
+    }
+  }
+
+  fn loose_equals(&self, left: &Value, right: &Value) -> bool {
+    match (left, right) {
+      (Value::Undefined, Value::Undefined)
+      | (Value::Null, Value::Null)
+      | (Value::Undefined, Value::Null)
+      | (Value::Null, Value::Undefined) => true,
+      (Value::Undefined, _) | (Value::Null, _) | (_, Value::Undefined) | (_, Value::Null) => false,
+      (Value::String(left), Value::String(right)) => left == right,
+      (Value::Object(left), Value::Object(right)) => left == right,
+      (Value::Object(_), Value::String(right)) => self.to_string(left) == *right,
+      (Value::String(left), Value::Object(_)) => *left == self.to_string(right),
+      (left, right) => self.to_number(left) == self.to_number(right),
+    }
+  }
+
+  /// Formats a value as a literal for the report.
+  fn display(&self, value: &Value) -> String {
+    match value {
+      Value::Undefined => String::from("undefined"),
+      Value::String(value) => quote(value),
+      Value::Object(object) => match &self.objects[*object].kind {
+        Kind::Array(_) => String::from("[array]"),
+        Kind::Function(_) | Kind::Native(_) => String::from("[function]"),
+        Kind::Clip { .. } => format!("[movieclip {}]", self.clip_path(*object)),
+        Kind::Plain => String::from("[object]"),
+      },
+      value => self.to_string(value),
+    }
+  }
+
+  /// Lists the variables of `_root` and `_global`.
+  fn variables(&self) -> Vec<Variable> {
+    let mut variables = Vec::new();
+    let mut visited: HashMap<usize, String> = HashMap::new();
+    visited.insert(self.root, String::from("_root"));
+    visited.insert(self.global, String::from("_global"));
+    for (object, path) in [(self.root, "_root"), (self.global, "_global")].iter() {
+      for (key, value) in self.objects[*object].properties.iter() {
+        self.collect(&format!("{}.{}", path, key), value, 0, &mut visited, &mut variables);
+      }
+    }
+    variables
+  }
+
+  fn collect(
+    &self,
+    path: &str,
+    value: &Value,
+    depth: usize,
+    visited: &mut HashMap<usize, String>,
+    variables: &mut Vec<Variable>,
+  ) {
+    let object = match value {
+      Value::Object(object) => *object,
+      value => return push_variable(variables, path, self.display(value)),
+    };
+    if let Some(first) = visited.get(&object) {
+      return push_variable(variables, path, format!("[reference {}]", first));
+    }
+    visited.insert(object, path.to_string());
+    let elements: &[Value] = match &self.objects[object].kind {
+      Kind::Array(elements) => elements,
+      _ => &[],
+    };
+    let is_function = matches!(self.objects[object].kind, Kind::Function(_) | Kind::Native(_));
+    let properties = &self.objects[object].properties;
+    if is_function || depth >= MAX_VARIABLE_DEPTH || (elements.is_empty() && properties.is_empty()) {
+      let display = match &self.objects[object].kind {
+        Kind::Array(_) if elements.is_empty() => String::from("[]"),
+        Kind::Plain if properties.is_empty() => String::from("{}"),
+        _ => self.display(value),
+      };
+      push_variable(variables, path, display);
+      if !is_function || depth >= MAX_VARIABLE_DEPTH {
+        return;
+      }
+    }
+    for (index, element) in elements.iter().enumerate() {
+      self.collect(&format!("{}[{}]", path, index), element, depth + 1, visited, variables);
+    }
+    for (key, value) in properties.iter() {
+      self.collect(&format!("{}.{}", path, key), value, depth + 1, visited, variables);
+    }
+  }
+}
+
+fn push_variable(variables: &mut Vec<Variable>, name: &str, value: String) {
+  variables.push(Variable {
+    name: name.to_string(),
+    value,
+  });
+}
+
+/// Returns the position following the range after executing it, or the flow to propagate.
+fn resume(flow: Flow, end: usize) -> Result<usize, Flow> {
+  match flow {
+    Flow::Done => Ok(end),
+    Flow::Jump(target) => Ok(target),
+    flow => Err(flow),
+  }
+}
+
+/// Returns the target of a jump, `usize::MAX` (outside of any range) for negative offsets.
+fn jump_target(next: usize, offset: i16) -> usize {
+  let target = next as i64 + i64::from(offset);
+  if target < 0 {
+    usize::MAX
+  } else {
+    target as usize
+  }
+}
+
+/// Returns a string value, or an error if it is too long (such as strings doubled in a loop).
+fn new_string(value: String) -> Result<Value, Interrupt> {
+  if value.len() > MAX_STRING_LENGTH {
+    Err(Interrupt::Error(String::from("string too long")))
+  } else {
+    Ok(Value::String(value))
+  }
+}
+
+fn constant(pool: &[String], index: usize) -> Value {
+  match pool.get(index) {
+    Some(value) => Value::String(value.clone()),
+    None => Value::Undefined,
+  }
+}
+
+fn lossy(bytes: &[u8]) -> String {
+  String::from_utf8_lossy(bytes).into_owned()
+}
+
+fn has_flag(flags: u16, name: &str) -> bool {
+  match DEFINE_FUNCTION2_FLAGS.iter().position(|flag| *flag == name) {
+    Some(bit) => flags & (1 << bit) != 0,
+    None => false,
+  }
+}
+
+/// Returns the command of a `getURL` call to `FSCommand:<command>`.
+fn fscommand(url: &str) -> Option<&str> {
+  let prefix = "fscommand:";
+  if url.len() >= prefix.len() && url.is_char_boundary(prefix.len()) && url[..prefix.len()].eq_ignore_ascii_case(prefix)
+  {
+    Some(&url[prefix.len()..])
+  } else {
+    None
+  }
+}
+
+fn property_name(index: f64) -> Option<&'static str> {
+  if index >= 0.0 {
+    CLIP_PROPERTIES.get(index as usize).copied()
+  } else {
+    None
+  }
+}
+
+/// Returns the initial value of a display property of a clip.
+fn clip_default(key: &str) -> Option<Value> {
+  let value = match key {
+    "_x" | "_y" | "_width" | "_height" | "_rotation" | "_xmouse" | "_ymouse" => Value::Number(0.0),
+    "_xscale" | "_yscale" | "_alpha" => Value::Number(100.0),
+    "_currentframe" | "_totalframes" | "_framesloaded" | "_highquality" | "_focusrect" => Value::Number(1.0),
+    "_soundbuftime" => Value::Number(5.0),
+    "_visible" => Value::Boolean(true),
+    "_quality" => Value::String(String::from("HIGH")),
+    "_droptarget" | "_url" => Value::String(String::new()),
+    _ => return None,
+  };
+  Some(value)
+}
+
+/// Tests if the value is converted to a string by `Add2` and compared as a string by `Less2`.
+fn is_string_like(value: &Value) -> bool {
+  matches!(value, Value::String(_) | Value::Object(_))
+}
+
+fn parse_number(value: &str) -> f64 {
+  let value = value.trim();
+  if let Some(hex) = value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+    return match u32::from_str_radix(hex, 16) {
+      Ok(value) => f64::from(value as i32),
+      Err(_) => f64::NAN,
+    };
+  }
+  match value {
+    "Infinity" | "+Infinity" => f64::INFINITY,
+    "-Infinity" => f64::NEG_INFINITY,
+    value if !value.is_empty() && value.chars().all(|c| "0123456789+-.eE".contains(c)) => {
+      value.parse().unwrap_or(f64::NAN)
+    }
+    _ => f64::NAN,
+  }
+}
+
+fn parse_int(value: &str, radix: Option<i32>) -> f64 {
+  let value = value.trim_start();
+  let (sign, value) = match value.strip_prefix('-') {
+    Some(value) => (-1.0, value),
+    None => (1.0, value.strip_prefix('+').unwrap_or(value)),
+  };
+  let hex = value.strip_prefix("0x").or_else(|| value.strip_prefix("0X"));
+  let (radix, digits) = match (radix, hex) {
+    (None, Some(digits)) | (Some(16), Some(digits)) => (16, digits),
+    (None, None) if value.len() > 1 && value.starts_with('0') => (8, &value[1..]),
+    (None, None) => (10, value),
+    (Some(radix), _) if (2..=36).contains(&radix) => (radix as u32, value),
+    (Some(_), _) => return f64::NAN,
+  };
+  let mut result = None;
+  for c in digits.chars() {
+    match c.to_digit(radix) {
+      Some(digit) => result = Some(result.unwrap_or(0.0) * f64::from(radix) + f64::from(digit)),
+      None => break,
+    }
+  }
+  result.map(|result| sign * result).unwrap_or(f64::NAN)
+}
+
+fn parse_float(value: &str) -> f64 {
+  let value = value.trim_start();
+  let bytes = value.as_bytes();
+  let digits = |start: usize| start + bytes[start..].iter().take_while(|b| b.is_ascii_digit()).count();
+  let mut end = digits(if matches!(bytes.first(), Some(b'+') | Some(b'-')) {
+    1
+  } else {
+    0
+  });
+  if bytes.get(end) == Some(&b'.') {
+    end = digits(end + 1);
+  }
+  if end > 0 && matches!(bytes.get(end), Some(b'e') | Some(b'E')) {
+    let mut exponent = end + 1;
+    if matches!(bytes.get(exponent), Some(b'+') | Some(b'-')) {
+      exponent += 1;
+    }
+    if digits(exponent) > exponent {
+      end = digits(exponent);
+    }
+  }
+  value[..end].parse().unwrap_or(f64::NAN)
+}
+
+/// Formats a number as ActionScript does: at most 15 significant digits, exponent notation for large and small
+/// numbers.
+fn format_number(value: f64) -> String {
+  if value.is_nan() {
+    return String::from("NaN");
+  }
+  if value.is_infinite() {
+    return String::from(if value > 0.0 { "Infinity" } else { "-Infinity" });
+  }
+  if value == 0.0 {
+    return String::from("0");
+  }
+  let scientific = format!("{:.14e}", value);
+  let (mantissa, exponent) = match scientific.find('e') {
+    Some(index) => (
+      &scientific[..index],
+      scientific[index + 1..].parse::<i32>().unwrap_or(0),
+    ),
+    None => (scientific.as_str(), 0),
+  };
+  let (sign, mantissa) = match mantissa.strip_prefix('-') {
+    Some(mantissa) => ("-", mantissa),
+    None => ("", mantissa),
+  };
+  let digits: String = mantissa.chars().filter(|c| *c != '.').collect();
+  let digits = digits.trim_end_matches('0');
+  let digits = if digits.is_empty() { "0" } else { digits };
+  if !(-5..15).contains(&exponent) {
+    let (first, rest) = digits.split_at(1);
+    let fraction = if rest.is_empty() {
+      String::new()
+    } else {
+      format!(".{}", rest)
+    };
+    let exponent_sign = if exponent < 0 { "-" } else { "+" };
+    format!("{}{}{}e{}{}", sign, first, fraction, exponent_sign, exponent.abs())
+  } else if exponent < 0 {
+    format!("{}0.{}{}", sign, "0".repeat((-exponent - 1) as usize), digits)
+  } else {
+    let integer_length = exponent as usize + 1;
+    if digits.len() <= integer_length {
+      format!("{}{}{}", sign, digits, "0".repeat(integer_length - digits.len()))
+    } else {
+      format!("{}{}.{}", sign, &digits[..integer_length], &digits[integer_length..])
+    }
+  }
+}
+
+/// Formats the integer part of a number in the provided radix (`Number.prototype.toString(radix)`).
+fn format_radix(value: f64, radix: i32) -> String {
+  if !(2..=36).contains(&radix) || !value.is_finite() {
+    return format_number(value);
+  }
+  let radix = radix as u32;
+  let mut integer = value.trunc().abs() as u64;
+  let mut digits = Vec::new();
+  loop {
+    digits.push(std::char::from_digit((integer % u64::from(radix)) as u32, radix).unwrap_or('0'));
+    integer /= u64::from(radix);
+    if integer == 0 {
+      break;
+    }
+  }
+  if value < 0.0 && value.trunc() != 0.0 {
+    digits.push('-');
+  }
+  digits.iter().rev().collect()
+}
+
+fn escape(value: &str) -> String {
+  let mut out = String::with_capacity(value.len());
+  for byte in value.bytes() {
+    if byte.is_ascii_alphanumeric() {
+      out.push(char::from(byte));
+    } else {
+      out.push_str(&format!("%{:02X}", byte));
+    }
+  }
+  out
+}
+
+fn unescape(value: &str) -> String {
+  let bytes = value.as_bytes();
+  let mut out = Vec::with_capacity(bytes.len());
+  let mut i = 0;
+  while i < bytes.len() {
+    let hex = bytes.get(i + 1..i + 3).and_then(|hex| std::str::from_utf8(hex).ok());
+    match hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+      Some(byte) if bytes[i] == b'%' => {
+        out.push(byte);
+        i += 3;
+      }
+      _ => {
+        out.push(bytes[i]);
+        i += 1;
+      }
+    }
+  }
+  String::from_utf8_lossy(&out).into_owned()
+}
@@ -0,0 +1,377 @@
+//! Static checks of AVM1 bytecode.
+//!
+//! Actions are decoded by following the control flow from the start of the buffer, so code hidden behind jumps
+//! (overlapping actions) is checked too. The stack depth is tracked along every path, from the lowest depth reaching
+//! each action. It is unknown after actions popping a dynamic number of values, unless the count was pushed as a
+//! constant.
+
+use crate::avm1::raw::{self, Action, RawAction};
+use crate::avm1::simple_stack_effect;
+use crate::dump::Avm1Location;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Debug, Serialize)]
+pub(crate) struct Finding {
+  pub location: String,
+  /// Offset of the action in the buffer
+  pub offset: usize,
+  /// `jump-outside-buffer`, `jump-into-action`, `stack-underflow`, `unknown-opcode`, `length-mismatch`,
+  /// `malformed-action` or `code-after-end`
+  pub kind: &'static str,
+  pub message: String,
+}
+
+impl std::fmt::Display for Finding {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{} @{}: {}: {}", self.location, self.offset, self.kind, self.message)
+  }
+}
+
+/// Returns the findings of the buffer, by offset.
+pub(crate) fn lint(location: &Avm1Location, bytes: &[u8]) -> Vec<Finding> {
+  let mut linter = Linter {
+    bytes,
+    actions: BTreeMap::new(),
+    visited: HashMap::new(),
+    queue: vec![State {
+      offset: 0,
+      region: (0, bytes.len()),
+      stack: Some(Vec::new()),
+    }],
+    targets: Vec::new(),
+    findings: BTreeMap::new(),
+  };
+  while let Some(state) = linter.queue.pop() {
+    linter.run(state);
+  }
+  linter.check_targets();
+  linter
+    .findings
+    .into_iter()
+    .map(|((offset, kind), message)| Finding {
+      location: location.to_string(),
+      offset,
+      kind,
+      message,
+    })
+    .collect()
+}
+
+/// Stack values: numbers pushed as constants are known.
+type Stack = Vec<Option<f64>>;
+
+struct State {
+  offset: usize,
+  /// Bounds of the code containing the action (buffer, function body, `try` block, `with` body)
+  region: (usize, usize),
+  /// `None` if the depth of the stack is unknown
+  stack: Option<Stack>,
+}
+
+struct Linter<'a> {
+  bytes: &'a [u8],
+  /// Decoded actions, by offset
+  actions: BTreeMap<usize, RawAction>,
+  /// Lowest stack depth reaching each action, `None` if only reached with an unknown depth
+  visited: HashMap<usize, Option<usize>>,
+  queue: Vec<State>,
+  /// Offsets reached by jumps or by the end of nested code, with the offset of the action
+  targets: Vec<(usize, usize)>,
+  findings: BTreeMap<(usize, &'static str), String>,
+}
+
+impl<'a> Linter<'a> {
+  fn report(&mut self, offset: usize, kind: &'static str, message: String) {
+    self.findings.entry((offset, kind)).or_insert(message);
+  }
+
+  /// Follows the code from `state` until the end of its path.
+  fn run(&mut self, mut state: State) {
+    while state.offset < state.region.1 {
+      let depth = state.stack.as_ref().map(Vec::len);
+      let visit = match (self.visited.get(&state.offset), depth) {
+        (None, _) | (Some(None), Some(_)) => true,
+        (Some(Some(old)), Some(new)) => new < *old,
+        (Some(_), None) => false,
+      };
+      if !visit {
+        return;
+      }
+      self.visited.insert(state.offset, depth);
+      let bytes = self.bytes;
+      let action = self
+        .actions
+        .entry(state.offset)
+        .or_insert_with(|| raw::read_action(bytes, state.offset))
+        .clone();
+      match self.step(&action, &mut state) {
+        Some(next) => state.offset = next,
+        None => return,
+      }
+    }
+  }
+
+  /// Applies an action, returns the offset of the next action if the execution continues.
+  fn step(&mut self, action: &RawAction, state: &mut State) -> Option<usize> {
+    let (offset, end) = (action.offset, action.end());
+    match &action.action {
+      Action::Simple(0x00) => {
+        if end < self.bytes.len() {
+          let message = format!("{} bytes follow the End action", self.bytes.len() - end);
+          self.report(offset, "code-after-end", message);
+        }
+        return None;
+      }
+      Action::Simple(code) => {
+        let mnemonic = raw::mnemonic(*code).unwrap_or_default();
+        match mnemonic {
+          "Return" | "Throw" => {
+            self.pop(state, offset, 1);
+            return None;
+          }
+          // Callee (and object), then arguments
+          "CallFunction" | "NewObject" => self.pop_arguments(state, offset, 1, 1, 1),
+          "CallMethod" | "NewMethod" => self.pop_arguments(state, offset, 2, 1, 1),
+          "InitArray" | "FsCommand2" => self.pop_arguments(state, offset, 0, 1, 1),
+          "InitObject" => self.pop_arguments(state, offset, 0, 2, 1),
+          "ImplementsOp" => self.pop_arguments(state, offset, 1, 1, 0),
+          "StartDrag" => {
+            let constrain = self.pop(state, offset, 3).and_then(|values| values[0]);
+            match constrain {
+              Some(constrain) if constrain != 0.0 => {
+                self.pop(state, offset, 4);
+              }
+              Some(_) => {}
+              None => state.stack = None,
+            }
+          }
+          // Pushes the names of the properties, after an `undefined` marker
+          "Enumerate" | "Enumerate2" => {
+            self.pop(state, offset, 1);
+            state.stack = None;
+          }
+          _ => {
+            let (pops, pushes) = simple_stack_effect(*code).unwrap_or((0, 0));
+            self.pop(state, offset, pops);
+            push_unknown(state, pushes);
+          }
+        }
+      }
+      Action::StoreRegister(_) => {
+        let value = self.pop(state, offset, 1).and_then(|values| values[0]);
+        if let Some(stack) = &mut state.stack {
+          stack.push(value);
+        }
+      }
+      Action::Push(values) => {
+        if let Some(stack) = &mut state.stack {
+          stack.extend(values.iter().map(|value| match value {
+            raw::PushValue::Float32(value) => Some(f64::from(*value)),
+            raw::PushValue::Float64(value) => Some(*value),
+            raw::PushValue::Sint32(value) => Some(f64::from(*value)),
+            _ => None,
+          }));
+        }
+      }
+      Action::GotoFrame(_)
+      | Action::GetUrl { .. }
+      | Action::ConstantPool(_)
+      | Action::StrictMode(_)
+      | Action::SetTarget(_)
+      | Action::GotoLabel(_) => {}
+      Action::GotoFrame2 { .. } | Action::Call => {
+        self.pop(state, offset, 1);
+      }
+      Action::GetUrl2(_) => {
+        self.pop(state, offset, 2);
+      }
+      Action::WaitForFrame { skip_count, .. } => self.skip(state, end, *skip_count),
+      Action::WaitForFrame2 { skip_count } => {
+        self.pop(state, offset, 1);
+        self.skip(state, end, *skip_count);
+      }
+      Action::DefineFunction(_) | Action::DefineFunction2(_) => {
+        let body_end = self.nested_end(action)?;
+        self.nested((end, body_end), Some(Vec::new()));
+        let anonymous = match &action.action {
+          Action::DefineFunction(function) => function.name.is_empty(),
+          Action::DefineFunction2(function) => function.name.is_empty(),
+          _ => false,
+        };
+        push_unknown(state, if anonymous { 1 } else { 0 });
+        return Some(self.target(offset, body_end));
+      }
+      Action::Try(r#try) => {
+        let try_end = self.nested_end(action)?;
+        let catch_start = end + usize::from(r#try.try_size);
+        let finally_start = catch_start + usize::from(r#try.catch_size);
+        self.nested((end, catch_start), state.stack.clone());
+        if r#try.has_catch {
+          self.nested((catch_start, finally_start), state.stack.clone());
+        }
+        if r#try.has_finally {
+          self.nested((finally_start, try_end), state.stack.clone());
+        }
+        return Some(self.target(offset, try_end));
+      }
+      Action::With { .. } => {
+        let with_end = self.nested_end(action)?;
+        self.pop(state, offset, 1);
+        self.nested((end, with_end), state.stack.clone());
+        return Some(self.target(offset, with_end));
+      }
+      Action::Jump(jump) => {
+        let target = self.jump(offset, end, *jump)?;
+        state.region = self.region(state.region, target);
+        return Some(target);
+      }
+      Action::If(jump) => {
+        self.pop(state, offset, 1);
+        if let Some(target) = self.jump(offset, end, *jump) {
+          self.queue.push(State {
+            offset: target,
+            region: self.region(state.region, target),
+            stack: state.stack.clone(),
+          });
+        }
+      }
+      Action::Unknown { code, .. } => {
+        self.report(offset, "unknown-opcode", format!("unknown action 0x{:02x}", code));
+        state.stack = None;
+      }
+      Action::Malformed { code, error, .. } => {
+        let name = raw::mnemonic(*code).unwrap_or_default();
+        let kind = match *error {
+          "unexpected end of action" | "missing string terminator" | "action length is larger than its content" => {
+            "length-mismatch"
+          }
+          _ => "malformed-action",
+        };
+        self.report(offset, kind, format!("{}: {}", name, error));
+        state.stack = None;
+      }
+      Action::Truncated(_) => {
+        let message = String::from("the action extends past the end of the buffer");
+        self.report(offset, "length-mismatch", message);
+        return None;
+      }
+    }
+    Some(end)
+  }
+
+  /// Pops `count` values, reports an underflow if the stack is too small. Returns the popped values (top first) if
+  /// the depth of the stack is known.
+  fn pop(&mut self, state: &mut State, offset: usize, count: usize) -> Option<Stack> {
+    let stack = state.stack.as_mut()?;
+    if stack.len() < count {
+      let message = format!("needs {} values on the stack, found {}", count, stack.len());
+      self.report(offset, "stack-underflow", message);
+      stack.resize(count, None);
+    }
+    let mut values = stack.split_off(stack.len() - count);
+    values.reverse();
+    Some(values)
+  }
+
+  /// Pops `fixed` values, then a count and `count * per_item` values, then pushes `pushes` values.
+  fn pop_arguments(&mut self, state: &mut State, offset: usize, fixed: usize, per_item: usize, pushes: usize) {
+    self.pop(state, offset, fixed);
+    let count = self.pop(state, offset, 1).and_then(|values| values[0]);
+    match count {
+      Some(count) if count >= 0.0 && count == count.trunc() && count <= f64::from(u16::MAX) => {
+        self.pop(state, offset, count as usize * per_item);
+        push_unknown(state, pushes);
+      }
+      _ => state.stack = None,
+    }
+  }
+
+  /// Queues the path skipping `count` actions after a `WaitForFrame` action.
+  fn skip(&mut self, state: &State, mut offset: usize, count: u8) {
+    for _ in 0..count {
+      if offset >= self.bytes.len() {
+        break;
+      }
+      offset = raw::read_action(self.bytes, offset).end();
+    }
+    self.queue.push(State {
+      offset,
+      region: state.region,
+      stack: state.stack.clone(),
+    });
+  }
+
+  /// Returns the end of the code nested in the action, reports it if it exceeds the buffer.
+  fn nested_end(&mut self, action: &RawAction) -> Option<usize> {
+    let nested_end = action.end() + action.action.nested_size();
+    if nested_end > self.bytes.len() {
+      let message = format!(
+        "the nested code ends at {}, after the end of the buffer ({})",
+        nested_end,
+        self.bytes.len()
+      );
+      self.report(action.offset, "length-mismatch", message);
+      return None;
+    }
+    Some(nested_end)
+  }
+
+  /// Queues the code nested in an action (function body, `try` block or `with` body).
+  fn nested(&mut self, region: (usize, usize), stack: Option<Stack>) {
+    self.queue.push(State {
+      offset: region.0,
+      region,
+      stack,
+    });
+  }
+
+  /// Returns the target of a jump, or reports it if it is outside the buffer.
+  fn jump(&mut self, offset: usize, end: usize, jump: i16) -> Option<usize> {
+    let target = end as i64 + i64::from(jump);
+    if target < 0 || target > self.bytes.len() as i64 {
+      let message = format!("jump to {}, outside of the buffer (0..{})", target, self.bytes.len());
+      self.report(offset, "jump-outside-buffer", message);
+      return None;
+    }
+    Some(self.target(offset, target as usize))
+  }
+
+  /// Returns the region of a jump target: jumping out of the current region (such as out of a `with` body) continues
+  /// in the whole buffer.
+  fn region(&self, region: (usize, usize), target: usize) -> (usize, usize) {
+    if target < region.0 || target > region.1 {
+      (0, self.bytes.len())
+    } else {
+      region
+    }
+  }
+
+  /// Records a target reached from the action at `offset`.
+  fn target(&mut self, offset: usize, target: usize) -> usize {
+    self.targets.push((offset, target));
+    target
+  }
+
+  /// Reports the targets inside another decoded action.
+  fn check_targets(&mut self) {
+    let mut findings = Vec::new();
+    for (offset, target) in self.targets.iter() {
+      if let Some((_, action)) = self.actions.range(..*target).next_back() {
+        if action.end() > *target {
+          let message = format!("jump to {}, inside the action at {}", target, action.offset);
+          findings.push((*offset, message));
+        }
+      }
+    }
+    for (offset, message) in findings {
+      self.report(offset, "jump-into-action", message);
+    }
+  }
+}
+
+fn push_unknown(state: &mut State, count: usize) {
+  if let Some(stack) = &mut state.stack {
+    stack.resize(stack.len() + count, None);
+  }
+}
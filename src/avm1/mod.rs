@@ -0,0 +1,360 @@
+//! Analysis of AVM1 bytecode.
+
+pub(crate) mod asm;
+pub(crate) mod callgraph;
+pub(crate) mod classes;
+pub(crate) mod decompiler;
+pub(crate) mod deobfuscate;
+pub(crate) mod disasm;
+pub(crate) mod dot;
+pub(crate) mod emit;
+pub(crate) mod interpreter;
+pub(crate) mod lint;
+pub(crate) mod raw;
+pub(crate) mod stats;
+
+use crate::avm1::raw::Action;
+use avm1_types::cfg::{Cfg, CfgAction, CfgBlock, CfgLabel};
+use std::collections::HashMap;
+
+/// Returns the label of a CFG block.
+pub(crate) fn block_label(block: &CfgBlock) -> &CfgLabel {
+  match block {
+    CfgBlock::Error(block) => &block.label,
+    CfgBlock::If(block) => &block.label,
+    CfgBlock::Return(block) => &block.label,
+    CfgBlock::Simple(block) => &block.label,
+    CfgBlock::Throw(block) => &block.label,
+    CfgBlock::Try(block) => &block.label,
+    CfgBlock::WaitForFrame(block) => &block.label,
+    CfgBlock::WaitForFrame2(block) => &block.label,
+    CfgBlock::With(block) => &block.label,
+  }
+}
+
+/// Returns the actions of a CFG block (excluding its final control flow action).
+pub(crate) fn block_actions(block: &CfgBlock) -> &[CfgAction] {
+  match block {
+    CfgBlock::Error(block) => &block.actions,
+    CfgBlock::If(block) => &block.actions,
+    CfgBlock::Return(block) => &block.actions,
+    CfgBlock::Simple(block) => &block.actions,
+    CfgBlock::Throw(block) => &block.actions,
+    CfgBlock::Try(block) => &block.actions,
+    CfgBlock::WaitForFrame(block) => &block.actions,
+    CfgBlock::WaitForFrame2(block) => &block.actions,
+    CfgBlock::With(block) => &block.actions,
+  }
+}
+
+/// Returns the actions of a CFG block, mutably.
+pub(crate) fn block_actions_mut(block: &mut CfgBlock) -> &mut Vec<CfgAction> {
+  match block {
+    CfgBlock::Error(block) => &mut block.actions,
+    CfgBlock::If(block) => &mut block.actions,
+    CfgBlock::Return(block) => &mut block.actions,
+    CfgBlock::Simple(block) => &mut block.actions,
+    CfgBlock::Throw(block) => &mut block.actions,
+    CfgBlock::Try(block) => &mut block.actions,
+    CfgBlock::WaitForFrame(block) => &mut block.actions,
+    CfgBlock::WaitForFrame2(block) => &mut block.actions,
+    CfgBlock::With(block) => &mut block.actions,
+  }
+}
+
+/// Returns the CFGs nested directly inside a block: `try` blocks and `with` body, but not function bodies.
+pub(crate) fn block_children(block: &CfgBlock) -> Vec<&Cfg> {
+  match block {
+    CfgBlock::Try(block) => {
+      let mut children = vec![&block.r#try];
+      children.extend(block.catch.iter());
+      children.extend(block.finally.iter());
+      children
+    }
+    CfgBlock::With(block) => vec![&block.with],
+    _ => Vec::new(),
+  }
+}
+
+/// Returns all the blocks of the CFG, including nested blocks and function bodies.
+pub(crate) fn cfg_blocks(cfg: &Cfg) -> Vec<&CfgBlock> {
+  let mut blocks = Vec::new();
+  for block in cfg.blocks.iter() {
+    blocks.push(block);
+    for action in block_actions(block) {
+      match action {
+        CfgAction::DefineFunction(action) => blocks.extend(cfg_blocks(&action.body)),
+        CfgAction::DefineFunction2(action) => blocks.extend(cfg_blocks(&action.body)),
+        _ => {}
+      }
+    }
+    for child in block_children(block) {
+      blocks.extend(cfg_blocks(child));
+    }
+  }
+  blocks
+}
+
+/// Returns the labels of all the blocks of the CFG, including nested blocks and function bodies.
+pub(crate) fn cfg_labels(cfg: &Cfg) -> Vec<&CfgLabel> {
+  cfg_blocks(cfg).into_iter().map(block_label).collect()
+}
+
+/// Returns the offsets of the blocks of the CFG in the buffer, `cfg` is the result of `parse_cfg` for `bytes`.
+///
+/// The blocks are matched with the raw actions by following the control flow of the CFG from its first block, so
+/// unreachable blocks have no offset.
+pub(crate) fn block_offsets(bytes: &[u8], cfg: &Cfg) -> HashMap<CfgLabel, usize> {
+  let blocks: HashMap<&CfgLabel, &CfgBlock> = cfg_blocks(cfg)
+    .into_iter()
+    .map(|block| (block_label(block), block))
+    .collect();
+  let mut offsets: HashMap<CfgLabel, usize> = HashMap::new();
+  let mut pending: Vec<(&CfgLabel, usize)> = vec![(block_label(cfg.blocks.first()), 0)];
+  while let Some((label, mut offset)) = pending.pop() {
+    let block = match blocks.get(label) {
+      Some(block) if !offsets.contains_key(label) => *block,
+      _ => continue,
+    };
+    offsets.insert(label.clone(), offset);
+    for action in block_actions(block) {
+      if offset >= bytes.len() {
+        break;
+      }
+      let raw_action = raw::read_action(bytes, offset);
+      // Function bodies start right after the action defining them
+      match action {
+        CfgAction::DefineFunction(action) => pending.push((block_label(action.body.blocks.first()), raw_action.end())),
+        CfgAction::DefineFunction2(action) => pending.push((block_label(action.body.blocks.first()), raw_action.end())),
+        _ => {}
+      }
+      offset = raw_action.end() + raw_action.action.nested_size();
+    }
+
+    // The control flow action ending the block, if any
+    let raw_action = if offset < bytes.len() {
+      raw::read_action(bytes, offset)
+    } else {
+      continue;
+    };
+    let end = raw_action.end();
+    match (block, &raw_action.action) {
+      (CfgBlock::Simple(block), Action::Jump(jump)) => {
+        if let (Some(next), Some(target)) = (&block.next, jump_target(bytes, end, *jump)) {
+          pending.push((next, target));
+        }
+      }
+      (CfgBlock::Simple(block), _) => {
+        if let Some(next) = &block.next {
+          pending.push((next, offset));
+        }
+      }
+      (CfgBlock::If(block), Action::If(jump)) => {
+        if let (Some(if_true), Some(target)) = (&block.if_true, jump_target(bytes, end, *jump)) {
+          pending.push((if_true, target));
+        }
+        if let Some(if_false) = &block.if_false {
+          pending.push((if_false, end));
+        }
+      }
+      (CfgBlock::WaitForFrame(block), Action::WaitForFrame { skip_count, .. }) => {
+        if let Some(if_loaded) = &block.if_loaded {
+          pending.push((if_loaded, end));
+        }
+        if let Some(if_not_loaded) = &block.if_not_loaded {
+          pending.push((if_not_loaded, skip_actions(bytes, end, *skip_count)));
+        }
+      }
+      (CfgBlock::WaitForFrame2(block), Action::WaitForFrame2 { skip_count }) => {
+        if let Some(if_loaded) = &block.if_loaded {
+          pending.push((if_loaded, end));
+        }
+        if let Some(if_not_loaded) = &block.if_not_loaded {
+          pending.push((if_not_loaded, skip_actions(bytes, end, *skip_count)));
+        }
+      }
+      (CfgBlock::With(block), Action::With { .. }) => pending.push((block_label(block.with.blocks.first()), end)),
+      (CfgBlock::Try(block), Action::Try(action)) => {
+        let catch_start = end + usize::from(action.try_size);
+        pending.push((block_label(block.r#try.blocks.first()), end));
+        if let Some(catch) = &block.catch {
+          pending.push((block_label(catch.blocks.first()), catch_start));
+        }
+        if let Some(finally) = &block.finally {
+          pending.push((
+            block_label(finally.blocks.first()),
+            catch_start + usize::from(action.catch_size),
+          ));
+        }
+      }
+      _ => {}
+    }
+  }
+  offsets
+}
+
+/// Returns the absolute target of a jump ending at `end`, if it is inside the buffer (or at its end).
+fn jump_target(bytes: &[u8], end: usize, jump: i16) -> Option<usize> {
+  let target = end as i64 + i64::from(jump);
+  if target >= 0 && target as usize <= bytes.len() {
+    Some(target as usize)
+  } else {
+    None
+  }
+}
+
+/// Returns the offset following `count` actions starting at `offset`, as skipped by `WaitForFrame`.
+fn skip_actions(bytes: &[u8], mut offset: usize, count: u8) -> usize {
+  for _ in 0..count {
+    if offset >= bytes.len() {
+      break;
+    }
+    let action = raw::read_action(bytes, offset);
+    offset = action.end() + action.action.nested_size();
+  }
+  offset
+}
+
+/// Returns the code of an action without operands, or `None` if the action has operands.
+pub(crate) fn simple_code(action: &CfgAction) -> Option<u8> {
+  let code = match action {
+    CfgAction::NextFrame => 0x04,
+    CfgAction::PrevFrame => 0x05,
+    CfgAction::Play => 0x06,
+    CfgAction::Stop => 0x07,
+    CfgAction::ToggleQuality => 0x08,
+    CfgAction::StopSounds => 0x09,
+    CfgAction::Add => 0x0a,
+    CfgAction::Subtract => 0x0b,
+    CfgAction::Multiply => 0x0c,
+    CfgAction::Divide => 0x0d,
+    CfgAction::Equals => 0x0e,
+    CfgAction::Less => 0x0f,
+    CfgAction::And => 0x10,
+    CfgAction::Or => 0x11,
+    CfgAction::Not => 0x12,
+    CfgAction::StringEquals => 0x13,
+    CfgAction::StringLength => 0x14,
+    CfgAction::StringExtract => 0x15,
+    CfgAction::Pop => 0x17,
+    CfgAction::ToInteger => 0x18,
+    CfgAction::GetVariable => 0x1c,
+    CfgAction::SetVariable => 0x1d,
+    CfgAction::SetTarget2 => 0x20,
+    CfgAction::StringAdd => 0x21,
+    CfgAction::GetProperty => 0x22,
+    CfgAction::SetProperty => 0x23,
+    CfgAction::CloneSprite => 0x24,
+    CfgAction::RemoveSprite => 0x25,
+    CfgAction::Trace => 0x26,
+    CfgAction::StartDrag => 0x27,
+    CfgAction::EndDrag => 0x28,
+    CfgAction::StringLess => 0x29,
+    CfgAction::CastOp => 0x2b,
+    CfgAction::ImplementsOp => 0x2c,
+    CfgAction::FsCommand2 => 0x2d,
+    CfgAction::RandomNumber => 0x30,
+    CfgAction::MbStringLength => 0x31,
+    CfgAction::CharToAscii => 0x32,
+    CfgAction::AsciiToChar => 0x33,
+    CfgAction::GetTime => 0x34,
+    CfgAction::MbStringExtract => 0x35,
+    CfgAction::MbCharToAscii => 0x36,
+    CfgAction::MbAsciiToChar => 0x37,
+    CfgAction::Delete => 0x3a,
+    CfgAction::Delete2 => 0x3b,
+    CfgAction::DefineLocal => 0x3c,
+    CfgAction::CallFunction => 0x3d,
+    CfgAction::Modulo => 0x3f,
+    CfgAction::NewObject => 0x40,
+    CfgAction::DefineLocal2 => 0x41,
+    CfgAction::InitArray => 0x42,
+    CfgAction::InitObject => 0x43,
+    CfgAction::TypeOf => 0x44,
+    CfgAction::TargetPath => 0x45,
+    CfgAction::Enumerate => 0x46,
+    CfgAction::Add2 => 0x47,
+    CfgAction::Less2 => 0x48,
+    CfgAction::Equals2 => 0x49,
+    CfgAction::ToNumber => 0x4a,
+    CfgAction::ToString => 0x4b,
+    CfgAction::PushDuplicate => 0x4c,
+    CfgAction::StackSwap => 0x4d,
+    CfgAction::GetMember => 0x4e,
+    CfgAction::SetMember => 0x4f,
+    CfgAction::Increment => 0x50,
+    CfgAction::Decrement => 0x51,
+    CfgAction::CallMethod => 0x52,
+    CfgAction::NewMethod => 0x53,
+    CfgAction::InstanceOf => 0x54,
+    CfgAction::Enumerate2 => 0x55,
+    CfgAction::BitAnd => 0x60,
+    CfgAction::BitOr => 0x61,
+    CfgAction::BitXor => 0x62,
+    CfgAction::BitLShift => 0x63,
+    CfgAction::BitRShift => 0x64,
+    CfgAction::BitURShift => 0x65,
+    CfgAction::StrictEquals => 0x66,
+    CfgAction::Greater => 0x67,
+    CfgAction::StringGreater => 0x68,
+    CfgAction::Extends => 0x69,
+    CfgAction::Call => 0x9e,
+    CfgAction::ConstantPool(_)
+    | CfgAction::DefineFunction(_)
+    | CfgAction::DefineFunction2(_)
+    | CfgAction::GetUrl(_)
+    | CfgAction::GetUrl2(_)
+    | CfgAction::GotoFrame(_)
+    | CfgAction::GotoFrame2(_)
+    | CfgAction::GotoLabel(_)
+    | CfgAction::Push(_)
+    | CfgAction::SetTarget(_)
+    | CfgAction::StoreRegister(_)
+    | CfgAction::StrictMode(_)
+    | CfgAction::Unknown(_) => return None,
+  };
+  Some(code)
+}
+
+/// Returns the number of values popped and pushed by an action, or `None` if it depends on the values on the stack
+/// (argument counts, `Enumerate`) or is unknown.
+pub(crate) fn stack_effect(action: &CfgAction) -> Option<(usize, usize)> {
+  let effect = match action {
+    CfgAction::StoreRegister(_) => (1, 1),
+    CfgAction::GetUrl2(_) => (2, 0),
+    CfgAction::GotoFrame2(_) => (1, 0),
+    CfgAction::ConstantPool(_)
+    | CfgAction::GetUrl(_)
+    | CfgAction::GotoFrame(_)
+    | CfgAction::GotoLabel(_)
+    | CfgAction::SetTarget(_)
+    | CfgAction::StrictMode(_) => (0, 0),
+    CfgAction::Push(action) => (0, action.values.len()),
+    // Anonymous functions are pushed, named functions are stored in a variable
+    CfgAction::DefineFunction(action) => (0, if action.name.is_empty() { 1 } else { 0 }),
+    CfgAction::DefineFunction2(action) => (0, if action.name.is_empty() { 1 } else { 0 }),
+    // The stack effect of the other actions only depends on their code
+    action => return simple_code(action).and_then(simple_stack_effect),
+  };
+  Some(effect)
+}
+
+/// Returns the number of values popped and pushed by an action without operands, from its code (see `raw::mnemonic`).
+pub(crate) fn simple_stack_effect(code: u8) -> Option<(usize, usize)> {
+  let effect = match code {
+    0x0a | 0x0b | 0x0c | 0x0d | 0x0e | 0x0f | 0x10 | 0x11 | 0x13 | 0x21 | 0x22 | 0x29 | 0x2b | 0x3a | 0x3f | 0x47
+    | 0x48 | 0x49 | 0x4e | 0x54 | 0x60 | 0x61 | 0x62 | 0x63 | 0x64 | 0x65 | 0x66 | 0x67 | 0x68 => (2, 1),
+    0x12 | 0x14 | 0x18 | 0x1c | 0x30 | 0x31 | 0x32 | 0x33 | 0x36 | 0x37 | 0x3b | 0x44 | 0x45 | 0x4a | 0x4b | 0x50
+    | 0x51 => (1, 1),
+    0x15 | 0x35 => (3, 1),
+    0x23 | 0x24 | 0x4f => (3, 0),
+    0x1d | 0x3c | 0x69 => (2, 0),
+    0x17 | 0x20 | 0x25 | 0x26 | 0x2a | 0x3e | 0x41 | 0x9e => (1, 0),
+    0x00 | 0x04 | 0x05 | 0x06 | 0x07 | 0x08 | 0x09 | 0x28 => (0, 0),
+    0x34 => (0, 1),
+    0x4c => (1, 2),
+    0x4d => (2, 2),
+    _ => return None,
+  };
+  Some(effect)
+}
@@ -0,0 +1,743 @@
+//! Raw AVM1 actions, as they are stored in the bytecode.
+//!
+//! `avm1_parser::parse_cfg` abstracts away the layout of the bytecode (offsets, jumps, action lengths). This module
+//! keeps it: reading and then writing back a buffer produces the same bytes.
+
+use std::convert::TryInto;
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct RawAction {
+  /// Offset of the action header in the buffer
+  pub offset: usize,
+  /// Size of the action, header included
+  pub size: usize,
+  pub action: Action,
+}
+
+impl RawAction {
+  /// Offset of the first byte following this action.
+  pub(crate) fn end(&self) -> usize {
+    self.offset + self.size
+  }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Action {
+  /// Known action without any operand (including `End`)
+  Simple(u8),
+  GotoFrame(u16),
+  GetUrl {
+    url: Vec<u8>,
+    target: Vec<u8>,
+  },
+  StoreRegister(u8),
+  ConstantPool(Vec<Vec<u8>>),
+  StrictMode(bool),
+  WaitForFrame {
+    frame: u16,
+    skip_count: u8,
+  },
+  SetTarget(Vec<u8>),
+  GotoLabel(Vec<u8>),
+  WaitForFrame2 {
+    skip_count: u8,
+  },
+  DefineFunction2(DefineFunction2),
+  Try(Try),
+  With {
+    size: u16,
+  },
+  Push(Vec<PushValue>),
+  Jump(i16),
+  GetUrl2(GetUrl2),
+  DefineFunction(DefineFunction),
+  If(i16),
+  Call,
+  GotoFrame2 {
+    play: bool,
+    scene_bias: Option<u16>,
+  },
+  /// Action with an unknown code, `data` is `None` for short actions (`code < 0x80`)
+  Unknown {
+    code: u8,
+    data: Option<Vec<u8>>,
+  },
+  /// Action with a known code, but whose body could not be read exactly
+  Malformed {
+    code: u8,
+    data: Vec<u8>,
+    error: &'static str,
+  },
+  /// Trailing bytes too short to form an action
+  Truncated(Vec<u8>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum PushValue {
+  String(Vec<u8>),
+  Float32(f32),
+  Null,
+  Undefined,
+  Register(u8),
+  Boolean(bool),
+  Float64(f64),
+  Sint32(i32),
+  Constant8(u8),
+  Constant16(u16),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct DefineFunction {
+  pub name: Vec<u8>,
+  pub parameters: Vec<Vec<u8>>,
+  /// Size of the body, in bytes
+  pub size: u16,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct DefineFunction2 {
+  pub name: Vec<u8>,
+  pub register_count: u8,
+  /// Raw flags, see `DEFINE_FUNCTION2_FLAGS`
+  pub flags: u16,
+  /// Parameters, as `(register, name)` pairs. A register of `0` means that the parameter is not stored in a register.
+  pub parameters: Vec<(u8, Vec<u8>)>,
+  /// Size of the body, in bytes
+  pub size: u16,
+}
+
+/// Names of the `DefineFunction2` flags, indexed by bit.
+pub(crate) const DEFINE_FUNCTION2_FLAGS: [&str; 9] = [
+  "preload_this",
+  "suppress_this",
+  "preload_arguments",
+  "suppress_arguments",
+  "preload_super",
+  "suppress_super",
+  "preload_root",
+  "preload_parent",
+  "preload_global",
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Try {
+  pub catch_target: CatchTarget,
+  pub has_catch: bool,
+  pub has_finally: bool,
+  pub try_size: u16,
+  pub catch_size: u16,
+  pub finally_size: u16,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum CatchTarget {
+  Register(u8),
+  Variable(Vec<u8>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct GetUrl2 {
+  /// `0`: none, `1`: GET, `2`: POST
+  pub method: u8,
+  pub load_target: bool,
+  pub load_variables: bool,
+}
+
+/// Returns the mnemonic of the action with the provided code, if it is known.
+pub(crate) fn mnemonic(code: u8) -> Option<&'static str> {
+  let name = match code {
+    0x00 => "End",
+    0x04 => "NextFrame",
+    0x05 => "PrevFrame",
+    0x06 => "Play",
+    0x07 => "Stop",
+    0x08 => "ToggleQuality",
+    0x09 => "StopSounds",
+    0x0a => "Add",
+    0x0b => "Subtract",
+    0x0c => "Multiply",
+    0x0d => "Divide",
+    0x0e => "Equals",
+    0x0f => "Less",
+    0x10 => "And",
+    0x11 => "Or",
+    0x12 => "Not",
+    0x13 => "StringEquals",
+    0x14 => "StringLength",
+    0x15 => "StringExtract",
+    0x17 => "Pop",
+    0x18 => "ToInteger",
+    0x1c => "GetVariable",
+    0x1d => "SetVariable",
+    0x20 => "SetTarget2",
+    0x21 => "StringAdd",
+    0x22 => "GetProperty",
+    0x23 => "SetProperty",
+    0x24 => "CloneSprite",
+    0x25 => "RemoveSprite",
+    0x26 => "Trace",
+    0x27 => "StartDrag",
+    0x28 => "EndDrag",
+    0x29 => "StringLess",
+    0x2a => "Throw",
+    0x2b => "CastOp",
+    0x2c => "ImplementsOp",
+    0x2d => "FsCommand2",
+    0x30 => "RandomNumber",
+    0x31 => "MbStringLength",
+    0x32 => "CharToAscii",
+    0x33 => "AsciiToChar",
+    0x34 => "GetTime",
+    0x35 => "MbStringExtract",
+    0x36 => "MbCharToAscii",
+    0x37 => "MbAsciiToChar",
+    0x3a => "Delete",
+    0x3b => "Delete2",
+    0x3c => "DefineLocal",
+    0x3d => "CallFunction",
+    0x3e => "Return",
+    0x3f => "Modulo",
+    0x40 => "NewObject",
+    0x41 => "DefineLocal2",
+    0x42 => "InitArray",
+    0x43 => "InitObject",
+    0x44 => "TypeOf",
+    0x45 => "TargetPath",
+    0x46 => "Enumerate",
+    0x47 => "Add2",
+    0x48 => "Less2",
+    0x49 => "Equals2",
+    0x4a => "ToNumber",
+    0x4b => "ToString",
+    0x4c => "PushDuplicate",
+    0x4d => "StackSwap",
+    0x4e => "GetMember",
+    0x4f => "SetMember",
+    0x50 => "Increment",
+    0x51 => "Decrement",
+    0x52 => "CallMethod",
+    0x53 => "NewMethod",
+    0x54 => "InstanceOf",
+    0x55 => "Enumerate2",
+    0x60 => "BitAnd",
+    0x61 => "BitOr",
+    0x62 => "BitXor",
+    0x63 => "BitLShift",
+    0x64 => "BitRShift",
+    0x65 => "BitURShift",
+    0x66 => "StrictEquals",
+    0x67 => "Greater",
+    0x68 => "StringGreater",
+    0x69 => "Extends",
+    0x81 => "GotoFrame",
+    0x83 => "GetUrl",
+    0x87 => "StoreRegister",
+    0x88 => "ConstantPool",
+    0x89 => "StrictMode",
+    0x8a => "WaitForFrame",
+    0x8b => "SetTarget",
+    0x8c => "GotoLabel",
+    0x8d => "WaitForFrame2",
+    0x8e => "DefineFunction2",
+    0x8f => "Try",
+    0x94 => "With",
+    0x96 => "Push",
+    0x99 => "Jump",
+    0x9a => "GetUrl2",
+    0x9b => "DefineFunction",
+    0x9d => "If",
+    0x9e => "Call",
+    0x9f => "GotoFrame2",
+    _ => return None,
+  };
+  Some(name)
+}
+
+/// Returns the code of the action with the provided mnemonic, if it is known.
+pub(crate) fn code(mnemonic: &str) -> Option<u8> {
+  (0..=u8::MAX).find(|code| self::mnemonic(*code) == Some(mnemonic))
+}
+
+impl Action {
+  /// Code of the action, `None` for truncated actions.
+  pub(crate) fn code(&self) -> Option<u8> {
+    let code = match self {
+      Action::Simple(code) => *code,
+      Action::GotoFrame(_) => 0x81,
+      Action::GetUrl { .. } => 0x83,
+      Action::StoreRegister(_) => 0x87,
+      Action::ConstantPool(_) => 0x88,
+      Action::StrictMode(_) => 0x89,
+      Action::WaitForFrame { .. } => 0x8a,
+      Action::SetTarget(_) => 0x8b,
+      Action::GotoLabel(_) => 0x8c,
+      Action::WaitForFrame2 { .. } => 0x8d,
+      Action::DefineFunction2(_) => 0x8e,
+      Action::Try(_) => 0x8f,
+      Action::With { .. } => 0x94,
+      Action::Push(_) => 0x96,
+      Action::Jump(_) => 0x99,
+      Action::GetUrl2(_) => 0x9a,
+      Action::DefineFunction(_) => 0x9b,
+      Action::If(_) => 0x9d,
+      Action::Call => 0x9e,
+      Action::GotoFrame2 { .. } => 0x9f,
+      Action::Unknown { code, .. } => *code,
+      Action::Malformed { code, .. } => *code,
+      Action::Truncated(_) => return None,
+    };
+    Some(code)
+  }
+
+  /// Size of the code following this action that belongs to it (function body, `try` blocks, `with` body).
+  pub(crate) fn nested_size(&self) -> usize {
+    match self {
+      Action::DefineFunction(action) => usize::from(action.size),
+      Action::DefineFunction2(action) => usize::from(action.size),
+      Action::Try(action) => {
+        usize::from(action.try_size) + usize::from(action.catch_size) + usize::from(action.finally_size)
+      }
+      Action::With { size } => usize::from(*size),
+      _ => 0,
+    }
+  }
+}
+
+/// Reads all the actions of the buffer, in order, without following jumps.
+///
+/// The returned actions cover the whole buffer: unreadable bytes are represented by `Malformed`, `Unknown` or
+/// `Truncated` actions.
+pub(crate) fn read_actions(bytes: &[u8]) -> Vec<RawAction> {
+  let mut actions = Vec::new();
+  let mut offset: usize = 0;
+  while offset < bytes.len() {
+    let action = read_action(bytes, offset);
+    offset = action.end();
+    actions.push(action);
+  }
+  actions
+}
+
+/// Reads the action starting at `offset`.
+pub(crate) fn read_action(bytes: &[u8], offset: usize) -> RawAction {
+  let input = &bytes[offset..];
+  let code = input[0];
+  if code < 0x80 {
+    let action = match mnemonic(code) {
+      Some(_) => Action::Simple(code),
+      None => Action::Unknown { code, data: None },
+    };
+    return RawAction {
+      offset,
+      size: 1,
+      action,
+    };
+  }
+  let body_len = match input.get(1..3) {
+    Some(len) => usize::from(u16::from_le_bytes(len.try_into().unwrap())),
+    None => {
+      return RawAction {
+        offset,
+        size: input.len(),
+        action: Action::Truncated(input.to_vec()),
+      }
+    }
+  };
+  let body = match input.get(3..3 + body_len) {
+    Some(body) => body,
+    None => {
+      return RawAction {
+        offset,
+        size: input.len(),
+        action: Action::Truncated(input.to_vec()),
+      }
+    }
+  };
+  let action = if mnemonic(code).is_none() {
+    Action::Unknown {
+      code,
+      data: Some(body.to_vec()),
+    }
+  } else {
+    match read_body(code, body) {
+      Ok(action) => action,
+      Err(error) => Action::Malformed {
+        code,
+        data: body.to_vec(),
+        error,
+      },
+    }
+  };
+  RawAction {
+    offset,
+    size: 3 + body_len,
+    action,
+  }
+}
+
+struct Reader<'a> {
+  input: &'a [u8],
+}
+
+type ReadResult<T> = Result<T, &'static str>;
+
+impl<'a> Reader<'a> {
+  fn u8(&mut self) -> ReadResult<u8> {
+    let (first, rest) = self.input.split_first().ok_or("unexpected end of action")?;
+    self.input = rest;
+    Ok(*first)
+  }
+
+  fn take(&mut self, len: usize) -> ReadResult<&'a [u8]> {
+    if self.input.len() < len {
+      return Err("unexpected end of action");
+    }
+    let (head, rest) = self.input.split_at(len);
+    self.input = rest;
+    Ok(head)
+  }
+
+  fn u16(&mut self) -> ReadResult<u16> {
+    Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+  }
+
+  fn i16(&mut self) -> ReadResult<i16> {
+    Ok(i16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+  }
+
+  fn u32(&mut self) -> ReadResult<u32> {
+    Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+  }
+
+  fn c_string(&mut self) -> ReadResult<Vec<u8>> {
+    let end = self
+      .input
+      .iter()
+      .position(|b| *b == 0)
+      .ok_or("missing string terminator")?;
+    let value = self.input[..end].to_vec();
+    self.input = &self.input[end + 1..];
+    Ok(value)
+  }
+
+  fn bool(&mut self) -> ReadResult<bool> {
+    match self.u8()? {
+      0 => Ok(false),
+      1 => Ok(true),
+      _ => Err("invalid boolean"),
+    }
+  }
+}
+
+fn read_body(code: u8, body: &[u8]) -> ReadResult<Action> {
+  let mut reader = Reader { input: body };
+  let r = &mut reader;
+  let action = match code {
+    0x81 => Action::GotoFrame(r.u16()?),
+    0x83 => {
+      let url = r.c_string()?;
+      let target = r.c_string()?;
+      Action::GetUrl { url, target }
+    }
+    0x87 => Action::StoreRegister(r.u8()?),
+    0x88 => {
+      let count = r.u16()?;
+      let mut pool = Vec::with_capacity(usize::from(count));
+      for _ in 0..count {
+        pool.push(r.c_string()?);
+      }
+      Action::ConstantPool(pool)
+    }
+    0x89 => Action::StrictMode(r.bool()?),
+    0x8a => {
+      let frame = r.u16()?;
+      let skip_count = r.u8()?;
+      Action::WaitForFrame { frame, skip_count }
+    }
+    0x8b => Action::SetTarget(r.c_string()?),
+    0x8c => Action::GotoLabel(r.c_string()?),
+    0x8d => Action::WaitForFrame2 { skip_count: r.u8()? },
+    0x8e => {
+      let name = r.c_string()?;
+      let parameter_count = r.u16()?;
+      let register_count = r.u8()?;
+      let flags = r.u16()?;
+      if flags >> DEFINE_FUNCTION2_FLAGS.len() != 0 {
+        return Err("reserved flags are set");
+      }
+      let mut parameters = Vec::with_capacity(usize::from(parameter_count));
+      for _ in 0..parameter_count {
+        let register = r.u8()?;
+        let name = r.c_string()?;
+        parameters.push((register, name));
+      }
+      let size = r.u16()?;
+      Action::DefineFunction2(DefineFunction2 {
+        name,
+        register_count,
+        flags,
+        parameters,
+        size,
+      })
+    }
+    0x8f => {
+      let flags = r.u8()?;
+      if flags & 0xf8 != 0 {
+        return Err("reserved flags are set");
+      }
+      let try_size = r.u16()?;
+      let catch_size = r.u16()?;
+      let finally_size = r.u16()?;
+      let catch_target = if flags & 0x04 != 0 {
+        CatchTarget::Register(r.u8()?)
+      } else {
+        CatchTarget::Variable(r.c_string()?)
+      };
+      Action::Try(Try {
+        catch_target,
+        has_catch: flags & 0x01 != 0,
+        has_finally: flags & 0x02 != 0,
+        try_size,
+        catch_size,
+        finally_size,
+      })
+    }
+    0x94 => Action::With { size: r.u16()? },
+    0x96 => {
+      let mut values = Vec::new();
+      while !r.input.is_empty() {
+        let value = match r.u8()? {
+          0 => PushValue::String(r.c_string()?),
+          1 => PushValue::Float32(f32::from_bits(r.u32()?)),
+          2 => PushValue::Null,
+          3 => PushValue::Undefined,
+          4 => PushValue::Register(r.u8()?),
+          5 => PushValue::Boolean(r.bool()?),
+          6 => {
+            // The high and low words of AVM1 doubles are swapped
+            let high = r.u32()?;
+            let low = r.u32()?;
+            let bits = (u64::from(high) << 32) | u64::from(low);
+            PushValue::Float64(f64::from_bits(bits))
+          }
+          7 => PushValue::Sint32(r.u32()? as i32),
+          8 => PushValue::Constant8(r.u8()?),
+          9 => PushValue::Constant16(r.u16()?),
+          _ => return Err("unknown push value type"),
+        };
+        values.push(value);
+      }
+      Action::Push(values)
+    }
+    0x99 => Action::Jump(r.i16()?),
+    0x9a => {
+      let flags = r.u8()?;
+      if flags & 0x3c != 0 || flags >> 6 == 3 {
+        return Err("invalid flags");
+      }
+      Action::GetUrl2(GetUrl2 {
+        method: flags >> 6,
+        load_target: flags & 0x02 != 0,
+        load_variables: flags & 0x01 != 0,
+      })
+    }
+    0x9b => {
+      let name = r.c_string()?;
+      let parameter_count = r.u16()?;
+      let mut parameters = Vec::with_capacity(usize::from(parameter_count));
+      for _ in 0..parameter_count {
+        parameters.push(r.c_string()?);
+      }
+      let size = r.u16()?;
+      Action::DefineFunction(DefineFunction { name, parameters, size })
+    }
+    0x9d => Action::If(r.i16()?),
+    0x9e => Action::Call,
+    0x9f => {
+      let flags = r.u8()?;
+      if flags & 0xfc != 0 {
+        return Err("reserved flags are set");
+      }
+      let scene_bias = if flags & 0x02 != 0 { Some(r.u16()?) } else { None };
+      Action::GotoFrame2 {
+        play: flags & 0x01 != 0,
+        scene_bias,
+      }
+    }
+    _ => return Err("unexpected long action"),
+  };
+  if !reader.input.is_empty() {
+    return Err("action length is larger than its content");
+  }
+  Ok(action)
+}
+
+/// Appends the bytecode of the action to `out`.
+pub(crate) fn write_action(out: &mut Vec<u8>, action: &Action) {
+  let code = match action.code() {
+    Some(code) => code,
+    None => {
+      if let Action::Truncated(bytes) = action {
+        out.extend_from_slice(bytes);
+      }
+      return;
+    }
+  };
+  out.push(code);
+  if code < 0x80 {
+    return;
+  }
+  let header_end = out.len();
+  out.extend_from_slice(&[0, 0]);
+  write_body(out, action);
+  let body_len = out.len() - header_end - 2;
+  let body_len: u16 = body_len.try_into().expect("Action body is too large");
+  out[header_end..header_end + 2].copy_from_slice(&body_len.to_le_bytes());
+}
+
+fn write_c_string(out: &mut Vec<u8>, value: &[u8]) {
+  out.extend_from_slice(value);
+  out.push(0);
+}
+
+fn write_u16(out: &mut Vec<u8>, value: u16) {
+  out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_body(out: &mut Vec<u8>, action: &Action) {
+  match action {
+    Action::Simple(_) | Action::Call | Action::Truncated(_) => {}
+    Action::GotoFrame(frame) => write_u16(out, *frame),
+    Action::GetUrl { url, target } => {
+      write_c_string(out, url);
+      write_c_string(out, target);
+    }
+    Action::StoreRegister(register) => out.push(*register),
+    Action::ConstantPool(pool) => {
+      write_u16(out, pool.len().try_into().expect("Constant pool is too large"));
+      for value in pool {
+        write_c_string(out, value);
+      }
+    }
+    Action::StrictMode(is_strict) => out.push(u8::from(*is_strict)),
+    Action::WaitForFrame { frame, skip_count } => {
+      write_u16(out, *frame);
+      out.push(*skip_count);
+    }
+    Action::SetTarget(target) => write_c_string(out, target),
+    Action::GotoLabel(label) => write_c_string(out, label),
+    Action::WaitForFrame2 { skip_count } => out.push(*skip_count),
+    Action::DefineFunction2(action) => {
+      write_c_string(out, &action.name);
+      write_u16(out, action.parameters.len().try_into().expect("Too many parameters"));
+      out.push(action.register_count);
+      write_u16(out, action.flags);
+      for (register, name) in action.parameters.iter() {
+        out.push(*register);
+        write_c_string(out, name);
+      }
+      write_u16(out, action.size);
+    }
+    Action::Try(action) => {
+      let mut flags: u8 = 0;
+      if action.has_catch {
+        flags |= 0x01;
+      }
+      if action.has_finally {
+        flags |= 0x02;
+      }
+      if let CatchTarget::Register(_) = action.catch_target {
+        flags |= 0x04;
+      }
+      out.push(flags);
+      write_u16(out, action.try_size);
+      write_u16(out, action.catch_size);
+      write_u16(out, action.finally_size);
+      match &action.catch_target {
+        CatchTarget::Register(register) => out.push(*register),
+        CatchTarget::Variable(name) => write_c_string(out, name),
+      }
+    }
+    Action::With { size } => write_u16(out, *size),
+    Action::Push(values) => {
+      for value in values.iter() {
+        match value {
+          PushValue::String(value) => {
+            out.push(0);
+            write_c_string(out, value);
+          }
+          PushValue::Float32(value) => {
+            out.push(1);
+            out.extend_from_slice(&value.to_le_bytes());
+          }
+          PushValue::Null => out.push(2),
+          PushValue::Undefined => out.push(3),
+          PushValue::Register(register) => {
+            out.push(4);
+            out.push(*register);
+          }
+          PushValue::Boolean(value) => {
+            out.push(5);
+            out.push(u8::from(*value));
+          }
+          PushValue::Float64(value) => {
+            let bits = value.to_bits();
+            out.push(6);
+            out.extend_from_slice(&((bits >> 32) as u32).to_le_bytes());
+            out.extend_from_slice(&(bits as u32).to_le_bytes());
+          }
+          PushValue::Sint32(value) => {
+            out.push(7);
+            out.extend_from_slice(&value.to_le_bytes());
+          }
+          PushValue::Constant8(index) => {
+            out.push(8);
+            out.push(*index);
+          }
+          PushValue::Constant16(index) => {
+            out.push(9);
+            write_u16(out, *index);
+          }
+        }
+      }
+    }
+    Action::Jump(offset) | Action::If(offset) => out.extend_from_slice(&offset.to_le_bytes()),
+    Action::GetUrl2(action) => {
+      let mut flags = action.method << 6;
+      if action.load_target {
+        flags |= 0x02;
+      }
+      if action.load_variables {
+        flags |= 0x01;
+      }
+      out.push(flags);
+    }
+    Action::DefineFunction(action) => {
+      write_c_string(out, &action.name);
+      write_u16(out, action.parameters.len().try_into().expect("Too many parameters"));
+      for name in action.parameters.iter() {
+        write_c_string(out, name);
+      }
+      write_u16(out, action.size);
+    }
+    Action::GotoFrame2 { play, scene_bias } => {
+      let mut flags: u8 = 0;
+      if *play {
+        flags |= 0x01;
+      }
+      if scene_bias.is_some() {
+        flags |= 0x02;
+      }
+      out.push(flags);
+      if let Some(scene_bias) = scene_bias {
+        write_u16(out, *scene_bias);
+      }
+    }
+    Action::Unknown { data, .. } => {
+      if let Some(data) = data {
+        out.extend_from_slice(data);
+      }
+    }
+    Action::Malformed { data, .. } => out.extend_from_slice(data),
+  }
+}
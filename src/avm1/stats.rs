@@ -0,0 +1,120 @@
+//! Statistics about the AVM1 buffers of a movie.
+
+use crate::avm1::raw::{self, Action};
+use crate::avm1::{block_actions, block_children};
+use crate::dump::Avm1Location;
+use avm1_types::cfg::{Cfg, CfgAction};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::Write;
+
+/// Number of buffers listed in `Stats::largest_buffers`.
+const LARGEST_BUFFERS: usize = 10;
+
+#[derive(Debug, Default, Serialize)]
+pub(crate) struct Stats {
+  pub buffers: usize,
+  /// Total size of the buffers, in bytes
+  pub total_size: usize,
+  pub actions: usize,
+  /// Number of actions by mnemonic (`0xNN` for unknown codes)
+  pub opcodes: BTreeMap<String, usize>,
+  /// `DefineFunction` and `DefineFunction2` actions
+  pub functions: usize,
+  pub try_blocks: usize,
+  pub with_blocks: usize,
+  /// `GetUrl` and `GetUrl2` actions
+  pub get_url: usize,
+  /// Maximum nesting depth of the CFGs (function bodies, `try` blocks and `with` bodies), top-level code is `0`
+  pub max_cfg_depth: usize,
+  pub largest_buffers: Vec<BufferSize>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct BufferSize {
+  pub location: String,
+  pub size: usize,
+}
+
+/// Computes the statistics of the buffers, with their CFG.
+pub(crate) fn stats(buffers: &[(Avm1Location, &[u8], &Cfg)]) -> Stats {
+  let mut stats = Stats::default();
+  for (_, bytes, cfg) in buffers {
+    stats.buffers += 1;
+    stats.total_size += bytes.len();
+    for action in raw::read_actions(bytes) {
+      stats.actions += 1;
+      let name = match action.action.code() {
+        Some(code) => match raw::mnemonic(code) {
+          Some(mnemonic) => mnemonic.to_string(),
+          None => format!("0x{:02x}", code),
+        },
+        None => String::from("Truncated"),
+      };
+      *stats.opcodes.entry(name).or_default() += 1;
+      match action.action {
+        Action::DefineFunction(_) | Action::DefineFunction2(_) => stats.functions += 1,
+        Action::Try(_) => stats.try_blocks += 1,
+        Action::With { .. } => stats.with_blocks += 1,
+        Action::GetUrl { .. } | Action::GetUrl2(_) => stats.get_url += 1,
+        _ => {}
+      }
+    }
+    stats.max_cfg_depth = stats.max_cfg_depth.max(cfg_depth(cfg));
+  }
+
+  let mut sizes: Vec<(&Avm1Location, usize)> = buffers.iter().map(|(loc, bytes, _)| (loc, bytes.len())).collect();
+  sizes.sort_by(|left, right| right.1.cmp(&left.1).then(left.0.cmp(right.0)));
+  stats.largest_buffers = sizes
+    .into_iter()
+    .take(LARGEST_BUFFERS)
+    .map(|(loc, size)| BufferSize {
+      location: loc.to_string(),
+      size,
+    })
+    .collect();
+  stats
+}
+
+/// Returns the maximum nesting depth of the CFGs inside `cfg`.
+fn cfg_depth(cfg: &Cfg) -> usize {
+  let mut depth = 0;
+  for block in cfg.blocks.iter() {
+    for action in block_actions(block) {
+      let body = match action {
+        CfgAction::DefineFunction(action) => &action.body,
+        CfgAction::DefineFunction2(action) => &action.body,
+        _ => continue,
+      };
+      depth = depth.max(cfg_depth(body) + 1);
+    }
+    for child in block_children(block) {
+      depth = depth.max(cfg_depth(child) + 1);
+    }
+  }
+  depth
+}
+
+impl Stats {
+  /// Writes the statistics as text, with the opcodes by decreasing count.
+  pub(crate) fn print(&self, writer: &mut impl Write) -> std::io::Result<()> {
+    writeln!(writer, "AVM1 buffers: {} ({} bytes)", self.buffers, self.total_size)?;
+    writeln!(writer, "Actions: {}", self.actions)?;
+    writeln!(writer, "Functions: {}", self.functions)?;
+    writeln!(writer, "Try blocks: {}", self.try_blocks)?;
+    writeln!(writer, "With blocks: {}", self.with_blocks)?;
+    writeln!(writer, "GetUrl calls: {}", self.get_url)?;
+    writeln!(writer, "Max CFG depth: {}", self.max_cfg_depth)?;
+    writeln!(writer, "Largest buffers:")?;
+    for buffer in self.largest_buffers.iter() {
+      writeln!(writer, "  {:>8} {}", buffer.size, buffer.location)?;
+    }
+    writeln!(writer, "Opcodes:")?;
+    let mut opcodes: Vec<(&String, &usize)> = self.opcodes.iter().collect();
+    opcodes.sort_by(|left, right| right.1.cmp(left.1).then(left.0.cmp(right.0)));
+    for (name, count) in opcodes {
+      writeln!(writer, "  {:>8} {}", count, name)?;
+    }
+    Ok(())
+  }
+}
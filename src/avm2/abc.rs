@@ -0,0 +1,845 @@
+//! Parser for ABC files (`DoAbc` tags).
+//!
+//! See the "ActionScript Virtual Machine 2 (AVM2) Overview", chapter 4. References to the constant pools, methods,
+//! metadata and classes are kept as indices. The constant pools do not include their implicit first entry: the value
+//! at index `i` (`i > 0`) is at position `i - 1`, and the index `0` means "any name" or "no value".
+
+use crate::avm1::decompiler::ast::quote;
+use serde::{Serialize, Serializer};
+
+#[derive(Debug, Serialize)]
+pub(crate) struct AbcFile {
+  pub minor_version: u16,
+  pub major_version: u16,
+  pub constant_pool: ConstantPool,
+  pub methods: Vec<Method>,
+  pub metadata: Vec<Metadata>,
+  pub instances: Vec<Instance>,
+  /// Static side of the classes, with the same indices as `instances`
+  pub classes: Vec<Class>,
+  pub scripts: Vec<Script>,
+  pub method_bodies: Vec<MethodBody>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct ConstantPool {
+  pub integers: Vec<i32>,
+  pub unsigned_integers: Vec<u32>,
+  pub doubles: Vec<f64>,
+  pub strings: Vec<String>,
+  pub namespaces: Vec<Namespace>,
+  /// Sets of namespace indices
+  pub namespace_sets: Vec<Vec<u32>>,
+  pub multinames: Vec<Multiname>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct Namespace {
+  /// `namespace`, `package`, `package-internal`, `protected`, `explicit`, `static-protected` or `private`
+  pub kind: &'static str,
+  /// String index
+  pub name: u32,
+}
+
+/// Multiname, with indices of strings (`name`), namespaces (`namespace`), namespace sets (`namespace_set`) and
+/// multinames (`name` and `parameters` of generic type names).
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind")]
+#[allow(clippy::enum_variant_names)]
+pub(crate) enum Multiname {
+  #[serde(rename = "qname")]
+  QName { namespace: u32, name: u32 },
+  #[serde(rename = "qname-a")]
+  QNameA { namespace: u32, name: u32 },
+  #[serde(rename = "rtqname")]
+  RtQName { name: u32 },
+  #[serde(rename = "rtqname-a")]
+  RtQNameA { name: u32 },
+  #[serde(rename = "rtqname-l")]
+  RtQNameL,
+  #[serde(rename = "rtqname-la")]
+  RtQNameLA,
+  #[serde(rename = "multiname")]
+  Multiname { name: u32, namespace_set: u32 },
+  #[serde(rename = "multiname-a")]
+  MultinameA { name: u32, namespace_set: u32 },
+  #[serde(rename = "multiname-l")]
+  MultinameL { namespace_set: u32 },
+  #[serde(rename = "multiname-la")]
+  MultinameLA { namespace_set: u32 },
+  #[serde(rename = "typename")]
+  TypeName { name: u32, parameters: Vec<u32> },
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct Method {
+  /// Multiname indices of the parameter types
+  pub parameters: Vec<u32>,
+  /// Multiname index of the return type
+  pub return_type: u32,
+  /// String index
+  pub name: u32,
+  /// `need-arguments`, `need-activation`, `need-rest`, `has-optional`, `ignore-rest`, `native`, `set-dxns` and
+  /// `has-param-names`
+  pub flags: Vec<&'static str>,
+  /// Default values of the last parameters
+  pub options: Vec<Constant>,
+  /// String indices
+  pub parameter_names: Vec<u32>,
+}
+
+/// Reference to a constant, such as the default value of a parameter or a slot.
+#[derive(Clone, Copy, Debug, Serialize)]
+pub(crate) struct Constant {
+  /// `int`, `uint`, `double`, `utf8`, `true`, `false`, `null`, `undefined` or a namespace kind (`namespace`,
+  /// `package`, ...)
+  pub kind: &'static str,
+  /// Index in the constant pool of the kind
+  pub index: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct Metadata {
+  /// String index
+  pub name: u32,
+  pub items: Vec<MetadataItem>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct MetadataItem {
+  /// String index, `0` for keyless items
+  pub key: u32,
+  /// String index
+  pub value: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct Instance {
+  /// Multiname index
+  pub name: u32,
+  /// Multiname index, `0` for `Object` and interfaces
+  pub super_name: u32,
+  /// `sealed`, `final`, `interface` and `protected-namespace`
+  pub flags: Vec<&'static str>,
+  /// Namespace index of the protected members
+  pub protected_namespace: Option<u32>,
+  /// Multiname indices
+  pub interfaces: Vec<u32>,
+  /// Method index of the constructor
+  pub initializer: u32,
+  pub traits: Vec<Trait>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct Class {
+  /// Method index of the static initializer
+  pub initializer: u32,
+  pub traits: Vec<Trait>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct Script {
+  /// Method index of the script initializer
+  pub initializer: u32,
+  pub traits: Vec<Trait>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct Trait {
+  /// Multiname index
+  pub name: u32,
+  pub is_final: bool,
+  pub is_override: bool,
+  pub data: TraitData,
+  /// Metadata indices
+  pub metadata: Vec<u32>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub(crate) enum TraitData {
+  Slot {
+    slot_id: u32,
+    /// Multiname index
+    type_name: u32,
+    value: Option<Constant>,
+  },
+  Const {
+    slot_id: u32,
+    /// Multiname index
+    type_name: u32,
+    value: Option<Constant>,
+  },
+  Method {
+    disp_id: u32,
+    method: u32,
+  },
+  Getter {
+    disp_id: u32,
+    method: u32,
+  },
+  Setter {
+    disp_id: u32,
+    method: u32,
+  },
+  Class {
+    slot_id: u32,
+    class: u32,
+  },
+  Function {
+    slot_id: u32,
+    method: u32,
+  },
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct MethodBody {
+  /// Method index
+  pub method: u32,
+  pub max_stack: u32,
+  pub local_count: u32,
+  pub init_scope_depth: u32,
+  pub max_scope_depth: u32,
+  /// Bytecode, as an hexadecimal string
+  #[serde(serialize_with = "serialize_hex")]
+  pub code: Vec<u8>,
+  pub exceptions: Vec<Exception>,
+  pub traits: Vec<Trait>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct Exception {
+  /// Start of the protected code (offset in the bytecode)
+  pub from: u32,
+  /// End of the protected code (offset in the bytecode)
+  pub to: u32,
+  /// Offset of the handler in the bytecode
+  pub target: u32,
+  /// Multiname index of the caught type, `0` for any type
+  pub exception_type: u32,
+  /// Multiname index of the variable, `0` if there is none
+  pub variable_name: u32,
+}
+
+impl AbcFile {
+  /// Returns the string at the provided index, `None` for the index `0` or an invalid index.
+  pub(crate) fn string(&self, index: u32) -> Option<&str> {
+    let index = (index as usize).checked_sub(1)?;
+    self.constant_pool.strings.get(index).map(String::as_str)
+  }
+
+  pub(crate) fn namespace(&self, index: u32) -> Option<&Namespace> {
+    let index = (index as usize).checked_sub(1)?;
+    self.constant_pool.namespaces.get(index)
+  }
+
+  pub(crate) fn multiname(&self, index: u32) -> Option<&Multiname> {
+    let index = (index as usize).checked_sub(1)?;
+    self.constant_pool.multinames.get(index)
+  }
+
+  /// Returns a readable name for a namespace: the URI of packages and user-defined namespaces (empty for the public
+  /// namespace), or the kind of the others (`private`, `protected`, `internal`).
+  pub(crate) fn namespace_name(&self, index: u32) -> String {
+    let namespace = match (index as usize)
+      .checked_sub(1)
+      .and_then(|index| self.constant_pool.namespaces.get(index))
+    {
+      Some(namespace) => namespace,
+      None if index == 0 => return String::from("*"),
+      None => return format!("<invalid namespace {}>", index),
+    };
+    match namespace.kind {
+      "package" | "namespace" | "explicit" => self.string(namespace.name).unwrap_or_default().to_string(),
+      "package-internal" => String::from("internal"),
+      "protected" | "static-protected" => String::from("protected"),
+      kind => kind.to_string(),
+    }
+  }
+
+  /// Returns a readable name for a multiname, such as `com.example::Greeter`, `private::count`, `@id` (attribute)
+  /// or `Vector.<int>`. Names resolved at runtime are printed as `*`, and multinames with a namespace set as their
+  /// name only.
+  pub(crate) fn multiname_name(&self, index: u32) -> String {
+    let multiname = match (index as usize)
+      .checked_sub(1)
+      .and_then(|index| self.constant_pool.multinames.get(index))
+    {
+      Some(multiname) => multiname,
+      None if index == 0 => return String::from("*"),
+      None => return format!("<invalid multiname {}>", index),
+    };
+    let name = |index: u32| self.string(index).unwrap_or("*").to_string();
+    match multiname {
+      Multiname::QName { namespace, name: n } | Multiname::QNameA { namespace, name: n } => {
+        let prefix = if let Multiname::QNameA { .. } = multiname {
+          "@"
+        } else {
+          ""
+        };
+        let namespace = self.namespace_name(*namespace);
+        if namespace.is_empty() {
+          format!("{}{}", prefix, name(*n))
+        } else {
+          format!("{}{}::{}", prefix, namespace, name(*n))
+        }
+      }
+      Multiname::RtQName { name: n } => format!("*::{}", name(*n)),
+      Multiname::RtQNameA { name: n } => format!("@*::{}", name(*n)),
+      Multiname::RtQNameL => String::from("*::*"),
+      Multiname::RtQNameLA => String::from("@*::*"),
+      Multiname::Multiname { name: n, .. } => name(*n),
+      Multiname::MultinameA { name: n, .. } => format!("@{}", name(*n)),
+      Multiname::MultinameL { .. } => String::from("*"),
+      Multiname::MultinameLA { .. } => String::from("@*"),
+      Multiname::TypeName { name, parameters } => {
+        let parameters: Vec<String> = parameters.iter().map(|p| self.multiname_name(*p)).collect();
+        format!("{}.<{}>", self.multiname_name(*name), parameters.join(", "))
+      }
+    }
+  }
+
+  /// Returns a type name as written in ActionScript: `com.example.Greeter`, `int`, `Vector.<String>` or `*` (index
+  /// `0`). Names outside of a package (public or internal) namespace are printed as in `multiname_name`.
+  pub(crate) fn type_name(&self, index: u32) -> String {
+    match self.multiname(index) {
+      Some(Multiname::QName { namespace, name }) => match self.namespace(*namespace) {
+        Some(Namespace { kind, name: package }) if *kind == "package" || *kind == "package-internal" => {
+          let name = self.string(*name).unwrap_or("*");
+          match self.string(*package) {
+            Some(package) if !package.is_empty() => format!("{}.{}", package, name),
+            _ => name.to_string(),
+          }
+        }
+        _ => self.multiname_name(index),
+      },
+      Some(Multiname::TypeName { name, parameters }) => {
+        let parameters: Vec<String> = parameters.iter().map(|p| self.type_name(*p)).collect();
+        format!("{}.<{}>", self.type_name(*name), parameters.join(", "))
+      }
+      _ => self.multiname_name(index),
+    }
+  }
+
+  /// Returns the local name of a multiname, without its namespace (`*` if it is resolved at runtime).
+  pub(crate) fn local_name(&self, index: u32) -> String {
+    let name = match self.multiname(index) {
+      Some(Multiname::QName { name, .. })
+      | Some(Multiname::QNameA { name, .. })
+      | Some(Multiname::RtQName { name })
+      | Some(Multiname::RtQNameA { name })
+      | Some(Multiname::Multiname { name, .. })
+      | Some(Multiname::MultinameA { name, .. }) => self.string(*name),
+      _ => None,
+    };
+    name.unwrap_or("*").to_string()
+  }
+
+  /// Returns the access modifier of a trait name: `public`, `internal`, `protected`, `private`, or the URI of a
+  /// user-defined namespace.
+  pub(crate) fn access(&self, index: u32) -> String {
+    let namespace = match self.multiname(index) {
+      Some(Multiname::QName { namespace, .. }) | Some(Multiname::QNameA { namespace, .. }) => *namespace,
+      _ => return String::from("public"),
+    };
+    match self.namespace(namespace) {
+      Some(Namespace { kind: "package", .. }) | None => String::from("public"),
+      Some(_) => self.namespace_name(namespace),
+    }
+  }
+
+  /// Returns the ActionScript source of a metadata entry, such as `[Event(name="change", type="flash.events.Event")]`.
+  pub(crate) fn metadata_source(&self, index: u32) -> String {
+    let metadata = match self.metadata.get(index as usize) {
+      Some(metadata) => metadata,
+      None => return format!("<invalid metadata {}>", index),
+    };
+    let items: Vec<String> = metadata
+      .items
+      .iter()
+      .map(|item| {
+        let value = quote(self.string(item.value).unwrap_or_default());
+        match self.string(item.key) {
+          Some(key) => format!("{}={}", key, value),
+          None => value,
+        }
+      })
+      .collect();
+    let name = self.string(metadata.name).unwrap_or_default();
+    if items.is_empty() {
+      format!("[{}]", name)
+    } else {
+      format!("[{}({})]", name, items.join(", "))
+    }
+  }
+
+  /// Returns the ActionScript source of a constant.
+  pub(crate) fn constant_value(&self, constant: &Constant) -> String {
+    let index = (constant.index as usize).wrapping_sub(1);
+    let pool = &self.constant_pool;
+    let value = match constant.kind {
+      "int" => pool.integers.get(index).map(|value| value.to_string()),
+      "uint" => pool.unsigned_integers.get(index).map(|value| value.to_string()),
+      "double" => pool.doubles.get(index).map(|value| format_double(*value)),
+      "utf8" => self
+        .string(constant.index)
+        .map(quote)
+        .or_else(|| Some(String::from("\"\""))),
+      "true" | "false" | "null" | "undefined" => Some(constant.kind.to_string()),
+      _ => Some(format!("namespace({})", quote(&self.namespace_name(constant.index)))),
+    };
+    value.unwrap_or_else(|| format!("<invalid {} {}>", constant.kind, constant.index))
+  }
+}
+
+/// Formats a double like ActionScript.
+pub(crate) fn format_double(value: f64) -> String {
+  if value.is_nan() {
+    String::from("NaN")
+  } else if value.is_infinite() {
+    String::from(if value > 0.0 { "Infinity" } else { "-Infinity" })
+  } else {
+    format!("{}", value)
+  }
+}
+
+fn serialize_hex<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+  serializer.serialize_str(&hex::encode(bytes))
+}
+
+/// Names of the method flags, indexed by bit.
+const METHOD_FLAGS: [&str; 8] = [
+  "need-arguments",
+  "need-activation",
+  "need-rest",
+  "has-optional",
+  "ignore-rest",
+  "native",
+  "set-dxns",
+  "has-param-names",
+];
+
+/// Names of the instance flags, indexed by bit.
+const INSTANCE_FLAGS: [&str; 4] = ["sealed", "final", "interface", "protected-namespace"];
+
+/// Parses an ABC file.
+pub(crate) fn parse_abc(bytes: &[u8]) -> Result<AbcFile, String> {
+  let mut reader = Reader { bytes, offset: 0 };
+  let minor_version = reader.u16()?;
+  let major_version = reader.u16()?;
+  let constant_pool = reader.constant_pool()?;
+  let methods = reader.list(Reader::method)?;
+  let metadata = reader.list(Reader::metadata)?;
+  let class_count = reader.u30()?;
+  let mut instances = Vec::new();
+  for _ in 0..class_count {
+    instances.push(reader.instance()?);
+  }
+  let mut classes = Vec::new();
+  for _ in 0..class_count {
+    let initializer = reader.u30()?;
+    let traits = reader.list(Reader::r#trait)?;
+    classes.push(Class { initializer, traits });
+  }
+  let scripts = reader.list(|reader| {
+    let initializer = reader.u30()?;
+    let traits = reader.list(Reader::r#trait)?;
+    Ok(Script { initializer, traits })
+  })?;
+  let method_bodies = reader.list(Reader::method_body)?;
+  Ok(AbcFile {
+    minor_version,
+    major_version,
+    constant_pool,
+    methods,
+    metadata,
+    instances,
+    classes,
+    scripts,
+    method_bodies,
+  })
+}
+
+/// Returns the strings of the constant pool, with their offset in the file, or `None` if the file is malformed.
+///
+/// The first entry of the pool (the empty string, index `0`) is implicit and is not returned.
+pub(crate) fn read_strings(bytes: &[u8]) -> Option<Vec<(usize, String)>> {
+  let mut reader = Reader { bytes, offset: 0 };
+  // Minor and major version
+  reader.bytes(4).ok()?;
+  reader.pool(Reader::s32).ok()?;
+  reader.pool(Reader::u32).ok()?;
+  reader.pool(Reader::d64).ok()?;
+  let strings = reader
+    .pool(|reader| {
+      let size = reader.u30()? as usize;
+      let offset = reader.offset;
+      Ok((offset, String::from_utf8_lossy(reader.bytes(size)?).into_owned()))
+    })
+    .ok()?;
+  Some(strings)
+}
+
+/// Returns the name of a namespace kind.
+fn namespace_kind(kind: u8) -> Option<&'static str> {
+  let name = match kind {
+    0x08 => "namespace",
+    0x16 => "package",
+    0x17 => "package-internal",
+    0x18 => "protected",
+    0x19 => "explicit",
+    0x1a => "static-protected",
+    0x05 => "private",
+    _ => return None,
+  };
+  Some(name)
+}
+
+/// Returns the name of a constant kind.
+fn constant_kind(kind: u8) -> Option<&'static str> {
+  let name = match kind {
+    0x03 => "int",
+    0x04 => "uint",
+    0x06 => "double",
+    0x01 => "utf8",
+    0x0b => "true",
+    0x0a => "false",
+    0x0c => "null",
+    0x00 => "undefined",
+    kind => return namespace_kind(kind),
+  };
+  Some(name)
+}
+
+/// Returns the names of the bits set in `flags`.
+fn flag_names(flags: u8, names: &[&'static str]) -> Vec<&'static str> {
+  names
+    .iter()
+    .enumerate()
+    .filter(|(bit, _)| flags & (1 << bit) != 0)
+    .map(|(_, name)| *name)
+    .collect()
+}
+
+struct Reader<'a> {
+  bytes: &'a [u8],
+  offset: usize,
+}
+
+impl<'a> Reader<'a> {
+  fn error(&self, message: &str) -> String {
+    format!("{} at offset {}", message, self.offset)
+  }
+
+  /// Reads the next `size` bytes.
+  fn bytes(&mut self, size: usize) -> Result<&'a [u8], String> {
+    let bytes = self
+      .offset
+      .checked_add(size)
+      .and_then(|end| self.bytes.get(self.offset..end))
+      .ok_or_else(|| self.error("unexpected end of data"))?;
+    self.offset += size;
+    Ok(bytes)
+  }
+
+  fn u8(&mut self) -> Result<u8, String> {
+    Ok(self.bytes(1)?[0])
+  }
+
+  fn u16(&mut self) -> Result<u16, String> {
+    let bytes = self.bytes(2)?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+  }
+
+  /// Reads a variable-length integer (1 to 5 bytes, 7 bits per byte).
+  fn u32(&mut self) -> Result<u32, String> {
+    let mut value: u32 = 0;
+    for i in 0..5 {
+      let byte = self.u8()?;
+      value |= u32::from(byte & 0x7f).wrapping_shl(7 * i);
+      if byte & 0x80 == 0 {
+        break;
+      }
+    }
+    Ok(value)
+  }
+
+  /// Reads a variable-length integer used as a count or an index.
+  fn u30(&mut self) -> Result<u32, String> {
+    self.u32()
+  }
+
+  /// Reads a variable-length signed integer: the value is sign-extended from its last encoded bit.
+  fn s32(&mut self) -> Result<i32, String> {
+    let start = self.offset;
+    let value = self.u32()?;
+    let bits = 7 * (self.offset - start) as u32;
+    if bits >= 32 {
+      Ok(value as i32)
+    } else {
+      Ok(((value << (32 - bits)) as i32) >> (32 - bits))
+    }
+  }
+
+  fn d64(&mut self) -> Result<f64, String> {
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(self.bytes(8)?);
+    Ok(f64::from_le_bytes(bytes))
+  }
+
+  fn string(&mut self) -> Result<String, String> {
+    let size = self.u30()? as usize;
+    Ok(String::from_utf8_lossy(self.bytes(size)?).into_owned())
+  }
+
+  /// Reads a count, then the items.
+  fn list<T>(&mut self, mut item: impl FnMut(&mut Self) -> Result<T, String>) -> Result<Vec<T>, String> {
+    let count = self.u30()?;
+    let mut items = Vec::new();
+    for _ in 0..count {
+      items.push(item(self)?);
+    }
+    Ok(items)
+  }
+
+  /// Reads a constant pool: a count including the implicit first entry, then the other entries.
+  fn pool<T>(&mut self, mut item: impl FnMut(&mut Self) -> Result<T, String>) -> Result<Vec<T>, String> {
+    let count = self.u30()?;
+    let mut items = Vec::new();
+    for _ in 1..count {
+      items.push(item(self)?);
+    }
+    Ok(items)
+  }
+
+  fn constant_pool(&mut self) -> Result<ConstantPool, String> {
+    Ok(ConstantPool {
+      integers: self.pool(Reader::s32)?,
+      unsigned_integers: self.pool(Reader::u32)?,
+      doubles: self.pool(Reader::d64)?,
+      strings: self.pool(Reader::string)?,
+      namespaces: self.pool(Reader::namespace)?,
+      namespace_sets: self.pool(|reader| reader.list(Reader::u30))?,
+      multinames: self.pool(Reader::multiname)?,
+    })
+  }
+
+  fn namespace(&mut self) -> Result<Namespace, String> {
+    let kind = self.u8()?;
+    let kind = namespace_kind(kind).ok_or_else(|| self.error(&format!("unknown namespace kind 0x{:02x}", kind)))?;
+    Ok(Namespace {
+      kind,
+      name: self.u30()?,
+    })
+  }
+
+  fn multiname(&mut self) -> Result<Multiname, String> {
+    let kind = self.u8()?;
+    let multiname = match kind {
+      0x07 => Multiname::QName {
+        namespace: self.u30()?,
+        name: self.u30()?,
+      },
+      0x0d => Multiname::QNameA {
+        namespace: self.u30()?,
+        name: self.u30()?,
+      },
+      0x0f => Multiname::RtQName { name: self.u30()? },
+      0x10 => Multiname::RtQNameA { name: self.u30()? },
+      0x11 => Multiname::RtQNameL,
+      0x12 => Multiname::RtQNameLA,
+      0x09 => Multiname::Multiname {
+        name: self.u30()?,
+        namespace_set: self.u30()?,
+      },
+      0x0e => Multiname::MultinameA {
+        name: self.u30()?,
+        namespace_set: self.u30()?,
+      },
+      0x1b => Multiname::MultinameL {
+        namespace_set: self.u30()?,
+      },
+      0x1c => Multiname::MultinameLA {
+        namespace_set: self.u30()?,
+      },
+      0x1d => Multiname::TypeName {
+        name: self.u30()?,
+        parameters: self.list(Reader::u30)?,
+      },
+      kind => return Err(self.error(&format!("unknown multiname kind 0x{:02x}", kind))),
+    };
+    Ok(multiname)
+  }
+
+  /// Reads the index and kind of a constant. The kind is only present if the index is not `0`.
+  fn constant(&mut self) -> Result<Option<Constant>, String> {
+    let index = self.u30()?;
+    if index == 0 {
+      return Ok(None);
+    }
+    let kind = self.u8()?;
+    let kind = constant_kind(kind).ok_or_else(|| self.error(&format!("unknown constant kind 0x{:02x}", kind)))?;
+    Ok(Some(Constant { kind, index }))
+  }
+
+  fn method(&mut self) -> Result<Method, String> {
+    let parameter_count = self.u30()?;
+    let return_type = self.u30()?;
+    let mut parameters = Vec::new();
+    for _ in 0..parameter_count {
+      parameters.push(self.u30()?);
+    }
+    let name = self.u30()?;
+    let flags = self.u8()?;
+    let mut options = Vec::new();
+    if flags & 0x08 != 0 {
+      let option_count = self.u30()?;
+      for _ in 0..option_count {
+        let constant = self.constant()?.unwrap_or(Constant {
+          kind: "undefined",
+          index: 0,
+        });
+        options.push(constant);
+      }
+    }
+    let mut parameter_names = Vec::new();
+    if flags & 0x80 != 0 {
+      for _ in 0..parameter_count {
+        parameter_names.push(self.u30()?);
+      }
+    }
+    Ok(Method {
+      parameters,
+      return_type,
+      name,
+      flags: flag_names(flags, &METHOD_FLAGS),
+      options,
+      parameter_names,
+    })
+  }
+
+  fn metadata(&mut self) -> Result<Metadata, String> {
+    let name = self.u30()?;
+    let item_count = self.u30()?;
+    let mut keys = Vec::new();
+    for _ in 0..item_count {
+      keys.push(self.u30()?);
+    }
+    let mut items = Vec::new();
+    for key in keys {
+      items.push(MetadataItem {
+        key,
+        value: self.u30()?,
+      });
+    }
+    Ok(Metadata { name, items })
+  }
+
+  fn instance(&mut self) -> Result<Instance, String> {
+    let name = self.u30()?;
+    let super_name = self.u30()?;
+    let flags = self.u8()?;
+    let protected_namespace = if flags & 0x08 != 0 { Some(self.u30()?) } else { None };
+    let interfaces = self.list(Reader::u30)?;
+    let initializer = self.u30()?;
+    let traits = self.list(Reader::r#trait)?;
+    Ok(Instance {
+      name,
+      super_name,
+      flags: flag_names(flags, &INSTANCE_FLAGS),
+      protected_namespace,
+      interfaces,
+      initializer,
+      traits,
+    })
+  }
+
+  fn r#trait(&mut self) -> Result<Trait, String> {
+    let name = self.u30()?;
+    let kind = self.u8()?;
+    let attributes = kind >> 4;
+    let data = match kind & 0x0f {
+      0 | 6 => {
+        let slot_id = self.u30()?;
+        let type_name = self.u30()?;
+        let value = self.constant()?;
+        if kind & 0x0f == 0 {
+          TraitData::Slot {
+            slot_id,
+            type_name,
+            value,
+          }
+        } else {
+          TraitData::Const {
+            slot_id,
+            type_name,
+            value,
+          }
+        }
+      }
+      1 => TraitData::Method {
+        disp_id: self.u30()?,
+        method: self.u30()?,
+      },
+      2 => TraitData::Getter {
+        disp_id: self.u30()?,
+        method: self.u30()?,
+      },
+      3 => TraitData::Setter {
+        disp_id: self.u30()?,
+        method: self.u30()?,
+      },
+      4 => TraitData::Class {
+        slot_id: self.u30()?,
+        class: self.u30()?,
+      },
+      5 => TraitData::Function {
+        slot_id: self.u30()?,
+        method: self.u30()?,
+      },
+      kind => return Err(self.error(&format!("unknown trait kind {}", kind))),
+    };
+    let metadata = if attributes & 0x04 != 0 {
+      self.list(Reader::u30)?
+    } else {
+      Vec::new()
+    };
+    Ok(Trait {
+      name,
+      is_final: attributes & 0x01 != 0,
+      is_override: attributes & 0x02 != 0,
+      data,
+      metadata,
+    })
+  }
+
+  fn method_body(&mut self) -> Result<MethodBody, String> {
+    let method = self.u30()?;
+    let max_stack = self.u30()?;
+    let local_count = self.u30()?;
+    let init_scope_depth = self.u30()?;
+    let max_scope_depth = self.u30()?;
+    let code_size = self.u30()? as usize;
+    let code = self.bytes(code_size)?.to_vec();
+    let exceptions = self.list(|reader| {
+      Ok(Exception {
+        from: reader.u30()?,
+        to: reader.u30()?,
+        target: reader.u30()?,
+        exception_type: reader.u30()?,
+        variable_name: reader.u30()?,
+      })
+    })?;
+    let traits = self.list(Reader::r#trait)?;
+    Ok(MethodBody {
+      method,
+      max_stack,
+      local_count,
+      init_scope_depth,
+      max_scope_depth,
+      code,
+      exceptions,
+      traits,
+    })
+  }
+}
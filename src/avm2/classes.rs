@@ -0,0 +1,298 @@
+//! Inventory of the ActionScript 3 classes of ABC files.
+//!
+//! Each instance of an ABC file is paired with the class at the same index (static side), and the class trait of the
+//! script declaring it (class metadata). Classes are linked to the characters of the movie by the `SymbolClass` tags.
+
+use crate::avm2::abc::{AbcFile, Trait, TraitData};
+use crate::avm2::disasm::signature;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::Write;
+use swf_types::NamedId;
+
+#[derive(Debug, Serialize)]
+pub(crate) struct Class {
+  /// Full name, such as `com.example.Greeter`
+  pub name: String,
+  /// Name of the package, empty for the top-level package
+  pub package: String,
+  /// `class` or `interface`
+  pub kind: &'static str,
+  /// Location of the ABC file, such as `do-abc:3`
+  pub location: String,
+  /// `public` or `internal`
+  pub access: String,
+  pub is_final: bool,
+  pub is_dynamic: bool,
+  pub superclass: Option<String>,
+  pub interfaces: Vec<String>,
+  pub metadata: Vec<String>,
+  /// Ids of the characters linked to the class by `SymbolClass` tags (`0` for the main timeline)
+  pub symbols: Vec<u16>,
+  pub traits: Vec<Member>,
+  pub static_traits: Vec<Member>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct Member {
+  pub name: String,
+  /// `public`, `internal`, `protected`, `private` or the URI of a user-defined namespace
+  pub access: String,
+  /// `var`, `const`, `function`, `getter`, `setter` or `class`
+  pub kind: &'static str,
+  pub is_final: bool,
+  pub is_override: bool,
+  /// Type of a variable or constant, or signature of a method (such as `(name:String):void`)
+  #[serde(rename = "type")]
+  pub type_name: String,
+  /// Initial value of a variable or constant
+  pub value: Option<String>,
+  pub metadata: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct Symbol {
+  /// Character id, `0` for the main timeline
+  pub id: u16,
+  pub class: String,
+  /// Whether the class is defined by an ABC file of the movie
+  pub defined: bool,
+}
+
+impl Class {
+  /// Returns the ActionScript declaration of the class, such as `public class Greeter extends Sprite`.
+  pub(crate) fn declaration(&self) -> String {
+    let mut out = self.access.clone();
+    if self.is_final {
+      out.push_str(" final");
+    }
+    if self.is_dynamic {
+      out.push_str(" dynamic");
+    }
+    let short_name = self.name.rsplit('.').next().unwrap_or_default();
+    write!(out, " {} {}", self.kind, short_name).unwrap();
+    if let Some(superclass) = &self.superclass {
+      write!(out, " extends {}", superclass).unwrap();
+    }
+    if !self.interfaces.is_empty() {
+      let keyword = if self.kind == "interface" {
+        "extends"
+      } else {
+        "implements"
+      };
+      write!(out, " {} {}", keyword, self.interfaces.join(", ")).unwrap();
+    }
+    out
+  }
+}
+
+impl Member {
+  /// Returns the ActionScript declaration of the member, such as `public static const MAX:int = 10` or
+  /// `override protected function draw():void`.
+  pub(crate) fn declaration(&self, is_static: bool) -> String {
+    let mut modifiers: Vec<&str> = Vec::new();
+    if self.is_override {
+      modifiers.push("override");
+    }
+    modifiers.push(&self.access);
+    if is_static {
+      modifiers.push("static");
+    }
+    if self.is_final {
+      modifiers.push("final");
+    }
+    let modifiers = modifiers.join(" ");
+    match self.kind {
+      "var" | "const" => match &self.value {
+        Some(value) => format!(
+          "{} {} {}:{} = {}",
+          modifiers, self.kind, self.name, self.type_name, value
+        ),
+        None => format!("{} {} {}:{}", modifiers, self.kind, self.name, self.type_name),
+      },
+      "getter" => format!("{} function get {}{}", modifiers, self.name, self.type_name),
+      "setter" => format!("{} function set {}{}", modifiers, self.name, self.type_name),
+      "class" => format!("{} class {}", modifiers, self.type_name),
+      _ => format!("{} function {}{}", modifiers, self.name, self.type_name),
+    }
+  }
+}
+
+/// Returns the classes and interfaces defined by an ABC file.
+pub(crate) fn find_classes(location: &str, abc: &AbcFile) -> Vec<Class> {
+  let mut class_metadata: HashMap<u32, &[u32]> = HashMap::new();
+  for script in abc.scripts.iter() {
+    for r#trait in script.traits.iter() {
+      if let TraitData::Class { class, .. } = r#trait.data {
+        class_metadata.insert(class, &r#trait.metadata);
+      }
+    }
+  }
+
+  let mut classes: Vec<Class> = Vec::new();
+  for (i, instance) in abc.instances.iter().enumerate() {
+    let name = abc.type_name(instance.name);
+    let package = match name.rfind('.') {
+      Some(end) => name[..end].to_string(),
+      None => String::new(),
+    };
+    let is_interface = instance.flags.contains(&"interface");
+    let access = match abc.access(instance.name).as_str() {
+      "public" => String::from("public"),
+      _ => String::from("internal"),
+    };
+    let members = |traits: &[Trait]| -> Vec<Member> {
+      let mut members: Vec<Member> = traits.iter().map(|r#trait| member(abc, r#trait)).collect();
+      if is_interface {
+        for member in members.iter_mut() {
+          member.access = String::from("public");
+        }
+      }
+      members
+    };
+    let static_traits = abc
+      .classes
+      .get(i)
+      .map(|class| members(&class.traits))
+      .unwrap_or_default();
+    classes.push(Class {
+      name,
+      package,
+      kind: if is_interface { "interface" } else { "class" },
+      location: location.to_string(),
+      access,
+      is_final: instance.flags.contains(&"final"),
+      is_dynamic: !is_interface && !instance.flags.contains(&"sealed"),
+      superclass: match instance.super_name {
+        0 => None,
+        super_name => Some(abc.type_name(super_name)),
+      },
+      interfaces: instance.interfaces.iter().map(|name| abc.type_name(*name)).collect(),
+      metadata: class_metadata
+        .get(&(i as u32))
+        .map(|metadata| metadata.iter().map(|index| abc.metadata_source(*index)).collect())
+        .unwrap_or_default(),
+      symbols: Vec::new(),
+      traits: members(&instance.traits),
+      static_traits,
+    });
+  }
+  classes
+}
+
+fn member(abc: &AbcFile, r#trait: &Trait) -> Member {
+  let (kind, type_name, value) = match &r#trait.data {
+    TraitData::Slot { type_name, value, .. } => ("var", abc.type_name(*type_name), value),
+    TraitData::Const { type_name, value, .. } => ("const", abc.type_name(*type_name), value),
+    TraitData::Method { method, .. } => ("function", signature(abc, *method), &None),
+    TraitData::Getter { method, .. } => ("getter", signature(abc, *method), &None),
+    TraitData::Setter { method, .. } => ("setter", signature(abc, *method), &None),
+    TraitData::Function { method, .. } => ("function", signature(abc, *method), &None),
+    TraitData::Class { class, .. } => {
+      let name = match abc.instances.get(*class as usize) {
+        Some(instance) => abc.type_name(instance.name),
+        None => format!("<invalid class {}>", class),
+      };
+      ("class", name, &None)
+    }
+  };
+  Member {
+    name: abc.local_name(r#trait.name),
+    access: abc.access(r#trait.name),
+    kind,
+    is_final: r#trait.is_final,
+    is_override: r#trait.is_override,
+    type_name,
+    value: value.as_ref().map(|value| abc.constant_value(value)),
+    metadata: r#trait
+      .metadata
+      .iter()
+      .map(|index| abc.metadata_source(*index))
+      .collect(),
+  }
+}
+
+/// Classes of the ABC files of a movie, and symbols of its `SymbolClass` tags.
+#[derive(Debug, Serialize)]
+pub(crate) struct Inventory {
+  pub classes: Vec<Class>,
+  pub symbols: Vec<Symbol>,
+}
+
+impl Inventory {
+  /// Links the classes to the symbols of `SymbolClass` tags.
+  pub(crate) fn new(mut classes: Vec<Class>, symbols: &[&NamedId]) -> Self {
+    let symbols = symbols
+      .iter()
+      .map(|symbol| {
+        let mut defined = false;
+        for class in classes.iter_mut().filter(|class| class.name == symbol.name) {
+          class.symbols.push(symbol.id);
+          defined = true;
+        }
+        Symbol {
+          id: symbol.id,
+          class: symbol.name.clone(),
+          defined,
+        }
+      })
+      .collect();
+    Self { classes, symbols }
+  }
+
+  /// Returns the text listing of the classes, grouped by package, followed by the symbols.
+  pub(crate) fn print(&self) -> String {
+    let mut sorted: Vec<&Class> = self.classes.iter().collect();
+    sorted.sort_by(|left, right| (&left.package, &left.name).cmp(&(&right.package, &right.name)));
+    let mut out = String::new();
+    let mut package: Option<&str> = None;
+    for class in sorted {
+      if package != Some(&class.package) {
+        if package.is_some() {
+          writeln!(out).unwrap();
+        }
+        package = Some(&class.package);
+        if class.package.is_empty() {
+          writeln!(out, "package").unwrap();
+        } else {
+          writeln!(out, "package {}", class.package).unwrap();
+        }
+      }
+      for metadata in class.metadata.iter() {
+        writeln!(out, "  {}", metadata).unwrap();
+      }
+      writeln!(out, "  {}", class.declaration()).unwrap();
+      let symbols: Vec<String> = class.symbols.iter().map(|id| id.to_string()).collect();
+      if symbols.is_empty() {
+        writeln!(out, "    ; {}", class.location).unwrap();
+      } else {
+        writeln!(out, "    ; {}, symbols: {}", class.location, symbols.join(", ")).unwrap();
+      }
+      let members = class
+        .static_traits
+        .iter()
+        .map(|member| (true, member))
+        .chain(class.traits.iter().map(|member| (false, member)));
+      for (is_static, member) in members {
+        for metadata in member.metadata.iter() {
+          writeln!(out, "    {}", metadata).unwrap();
+        }
+        writeln!(out, "    {}", member.declaration(is_static)).unwrap();
+      }
+    }
+    if !self.symbols.is_empty() {
+      if !out.is_empty() {
+        writeln!(out).unwrap();
+      }
+      writeln!(out, "symbols").unwrap();
+      for symbol in self.symbols.iter() {
+        if symbol.defined {
+          writeln!(out, "  {}: {}", symbol.id, symbol.class).unwrap();
+        } else {
+          writeln!(out, "  {}: {} (not defined)", symbol.id, symbol.class).unwrap();
+        }
+      }
+    }
+    out
+  }
+}
@@ -0,0 +1,361 @@
+//! AVM2 instructions, as they are stored in the bytecode of method bodies.
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Instruction {
+  /// Offset of the instruction in the bytecode
+  pub offset: usize,
+  /// Size of the instruction, opcode included
+  pub size: usize,
+  pub code: u8,
+  pub operands: Vec<Operand>,
+}
+
+impl Instruction {
+  /// Offset of the first byte following this instruction.
+  pub(crate) fn end(&self) -> usize {
+    self.offset + self.size
+  }
+
+  pub(crate) fn name(&self) -> &'static str {
+    opcode(self.code).map(|(name, _)| name).unwrap_or_default()
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Operand {
+  /// Immediate value: register, argument count, slot index, scope index...
+  Immediate(i64),
+  /// Absolute offset of a branch target
+  Target(usize),
+  /// Index in the integer pool
+  Int(u32),
+  /// Index in the unsigned integer pool
+  UInt(u32),
+  /// Index in the double pool
+  Double(u32),
+  /// Index in the string pool
+  String(u32),
+  /// Index in the namespace pool
+  Namespace(u32),
+  /// Index in the multiname pool
+  Multiname(u32),
+  /// Method index
+  Method(u32),
+  /// Class index
+  Class(u32),
+  /// Index in the exception table of the method body
+  Exception(u32),
+}
+
+/// Encoding of the operands of an instruction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+  U8,
+  /// Signed byte (`pushbyte`)
+  S8,
+  U30,
+  /// 30-bit integer sign-extended from 16 bits (`pushshort`)
+  S16,
+  /// Relative branch offset, from the end of the instruction
+  Branch,
+  Int,
+  UInt,
+  Double,
+  String,
+  Namespace,
+  Multiname,
+  Method,
+  Class,
+  Exception,
+}
+
+/// Returns the mnemonic and the operand formats of an opcode, if it is known. `lookupswitch` has a variable number
+/// of operands and is decoded separately.
+fn opcode(code: u8) -> Option<(&'static str, &'static [Format])> {
+  use Format::*;
+  let opcode: (&'static str, &'static [Format]) = match code {
+    0x01 => ("bkpt", &[]),
+    0x02 => ("nop", &[]),
+    0x03 => ("throw", &[]),
+    0x04 => ("getsuper", &[Multiname]),
+    0x05 => ("setsuper", &[Multiname]),
+    0x06 => ("dxns", &[String]),
+    0x07 => ("dxnslate", &[]),
+    0x08 => ("kill", &[U30]),
+    0x09 => ("label", &[]),
+    0x0c => ("ifnlt", &[Branch]),
+    0x0d => ("ifnle", &[Branch]),
+    0x0e => ("ifngt", &[Branch]),
+    0x0f => ("ifnge", &[Branch]),
+    0x10 => ("jump", &[Branch]),
+    0x11 => ("iftrue", &[Branch]),
+    0x12 => ("iffalse", &[Branch]),
+    0x13 => ("ifeq", &[Branch]),
+    0x14 => ("ifne", &[Branch]),
+    0x15 => ("iflt", &[Branch]),
+    0x16 => ("ifle", &[Branch]),
+    0x17 => ("ifgt", &[Branch]),
+    0x18 => ("ifge", &[Branch]),
+    0x19 => ("ifstricteq", &[Branch]),
+    0x1a => ("ifstrictne", &[Branch]),
+    0x1b => ("lookupswitch", &[]),
+    0x1c => ("pushwith", &[]),
+    0x1d => ("popscope", &[]),
+    0x1e => ("nextname", &[]),
+    0x1f => ("hasnext", &[]),
+    0x20 => ("pushnull", &[]),
+    0x21 => ("pushundefined", &[]),
+    0x23 => ("nextvalue", &[]),
+    0x24 => ("pushbyte", &[S8]),
+    0x25 => ("pushshort", &[S16]),
+    0x26 => ("pushtrue", &[]),
+    0x27 => ("pushfalse", &[]),
+    0x28 => ("pushnan", &[]),
+    0x29 => ("pop", &[]),
+    0x2a => ("dup", &[]),
+    0x2b => ("swap", &[]),
+    0x2c => ("pushstring", &[String]),
+    0x2d => ("pushint", &[Int]),
+    0x2e => ("pushuint", &[UInt]),
+    0x2f => ("pushdouble", &[Double]),
+    0x30 => ("pushscope", &[]),
+    0x31 => ("pushnamespace", &[Namespace]),
+    0x32 => ("hasnext2", &[U30, U30]),
+    0x35 => ("li8", &[]),
+    0x36 => ("li16", &[]),
+    0x37 => ("li32", &[]),
+    0x38 => ("lf32", &[]),
+    0x39 => ("lf64", &[]),
+    0x3a => ("si8", &[]),
+    0x3b => ("si16", &[]),
+    0x3c => ("si32", &[]),
+    0x3d => ("sf32", &[]),
+    0x3e => ("sf64", &[]),
+    0x40 => ("newfunction", &[Method]),
+    0x41 => ("call", &[U30]),
+    0x42 => ("construct", &[U30]),
+    0x43 => ("callmethod", &[U30, U30]),
+    0x44 => ("callstatic", &[Method, U30]),
+    0x45 => ("callsuper", &[Multiname, U30]),
+    0x46 => ("callproperty", &[Multiname, U30]),
+    0x47 => ("returnvoid", &[]),
+    0x48 => ("returnvalue", &[]),
+    0x49 => ("constructsuper", &[U30]),
+    0x4a => ("constructprop", &[Multiname, U30]),
+    0x4c => ("callproplex", &[Multiname, U30]),
+    0x4e => ("callsupervoid", &[Multiname, U30]),
+    0x4f => ("callpropvoid", &[Multiname, U30]),
+    0x50 => ("sxi1", &[]),
+    0x51 => ("sxi8", &[]),
+    0x52 => ("sxi16", &[]),
+    0x53 => ("applytype", &[U30]),
+    0x55 => ("newobject", &[U30]),
+    0x56 => ("newarray", &[U30]),
+    0x57 => ("newactivation", &[]),
+    0x58 => ("newclass", &[Class]),
+    0x59 => ("getdescendants", &[Multiname]),
+    0x5a => ("newcatch", &[Exception]),
+    0x5d => ("findpropstrict", &[Multiname]),
+    0x5e => ("findproperty", &[Multiname]),
+    0x5f => ("finddef", &[Multiname]),
+    0x60 => ("getlex", &[Multiname]),
+    0x61 => ("setproperty", &[Multiname]),
+    0x62 => ("getlocal", &[U30]),
+    0x63 => ("setlocal", &[U30]),
+    0x64 => ("getglobalscope", &[]),
+    0x65 => ("getscopeobject", &[U8]),
+    0x66 => ("getproperty", &[Multiname]),
+    0x67 => ("getouterscope", &[U30]),
+    0x68 => ("initproperty", &[Multiname]),
+    0x6a => ("deleteproperty", &[Multiname]),
+    0x6c => ("getslot", &[U30]),
+    0x6d => ("setslot", &[U30]),
+    0x6e => ("getglobalslot", &[U30]),
+    0x6f => ("setglobalslot", &[U30]),
+    0x70 => ("convert_s", &[]),
+    0x71 => ("esc_xelem", &[]),
+    0x72 => ("esc_xattr", &[]),
+    0x73 => ("convert_i", &[]),
+    0x74 => ("convert_u", &[]),
+    0x75 => ("convert_d", &[]),
+    0x76 => ("convert_b", &[]),
+    0x77 => ("convert_o", &[]),
+    0x78 => ("checkfilter", &[]),
+    0x80 => ("coerce", &[Multiname]),
+    0x81 => ("coerce_b", &[]),
+    0x82 => ("coerce_a", &[]),
+    0x83 => ("coerce_i", &[]),
+    0x84 => ("coerce_d", &[]),
+    0x85 => ("coerce_s", &[]),
+    0x86 => ("astype", &[Multiname]),
+    0x87 => ("astypelate", &[]),
+    0x88 => ("coerce_u", &[]),
+    0x89 => ("coerce_o", &[]),
+    0x90 => ("negate", &[]),
+    0x91 => ("increment", &[]),
+    0x92 => ("inclocal", &[U30]),
+    0x93 => ("decrement", &[]),
+    0x94 => ("declocal", &[U30]),
+    0x95 => ("typeof", &[]),
+    0x96 => ("not", &[]),
+    0x97 => ("bitnot", &[]),
+    0xa0 => ("add", &[]),
+    0xa1 => ("subtract", &[]),
+    0xa2 => ("multiply", &[]),
+    0xa3 => ("divide", &[]),
+    0xa4 => ("modulo", &[]),
+    0xa5 => ("lshift", &[]),
+    0xa6 => ("rshift", &[]),
+    0xa7 => ("urshift", &[]),
+    0xa8 => ("bitand", &[]),
+    0xa9 => ("bitor", &[]),
+    0xaa => ("bitxor", &[]),
+    0xab => ("equals", &[]),
+    0xac => ("strictequals", &[]),
+    0xad => ("lessthan", &[]),
+    0xae => ("lessequals", &[]),
+    0xaf => ("greaterthan", &[]),
+    0xb0 => ("greaterequals", &[]),
+    0xb1 => ("instanceof", &[]),
+    0xb2 => ("istype", &[Multiname]),
+    0xb3 => ("istypelate", &[]),
+    0xb4 => ("in", &[]),
+    0xc0 => ("increment_i", &[]),
+    0xc1 => ("decrement_i", &[]),
+    0xc2 => ("inclocal_i", &[U30]),
+    0xc3 => ("declocal_i", &[U30]),
+    0xc4 => ("negate_i", &[]),
+    0xc5 => ("add_i", &[]),
+    0xc6 => ("subtract_i", &[]),
+    0xc7 => ("multiply_i", &[]),
+    0xd0 => ("getlocal0", &[]),
+    0xd1 => ("getlocal1", &[]),
+    0xd2 => ("getlocal2", &[]),
+    0xd3 => ("getlocal3", &[]),
+    0xd4 => ("setlocal0", &[]),
+    0xd5 => ("setlocal1", &[]),
+    0xd6 => ("setlocal2", &[]),
+    0xd7 => ("setlocal3", &[]),
+    0xef => ("debug", &[U8, String, U8, U30]),
+    0xf0 => ("debugline", &[U30]),
+    0xf1 => ("debugfile", &[String]),
+    0xf2 => ("bkptline", &[U30]),
+    0xf3 => ("timestamp", &[]),
+    _ => return None,
+  };
+  Some(opcode)
+}
+
+/// Reads all the instructions of the bytecode, in order, without following branches.
+///
+/// Returns the instructions and, if the bytecode could not be read until its end, an error describing the first
+/// unreadable instruction.
+pub(crate) fn read_code(code: &[u8]) -> (Vec<Instruction>, Option<String>) {
+  let mut instructions = Vec::new();
+  let mut offset: usize = 0;
+  while offset < code.len() {
+    match read_instruction(code, offset) {
+      Ok(instruction) => {
+        offset = instruction.end();
+        instructions.push(instruction);
+      }
+      Err(error) => return (instructions, Some(format!("{} at offset {}", error, offset))),
+    }
+  }
+  (instructions, None)
+}
+
+/// Reads the instruction starting at `offset`.
+pub(crate) fn read_instruction(code: &[u8], offset: usize) -> Result<Instruction, String> {
+  let opcode_byte = code[offset];
+  let mut reader = Reader {
+    code,
+    offset: offset + 1,
+  };
+  let mut operands = Vec::new();
+  if opcode_byte == 0x1b {
+    // Offsets of `lookupswitch` are relative to the start of the instruction
+    let default = reader.s24()?;
+    operands.push(Operand::Target(target(offset, default)?));
+    let case_count = reader.u30()?;
+    for _ in 0..=case_count {
+      let case = reader.s24()?;
+      operands.push(Operand::Target(target(offset, case)?));
+    }
+  } else {
+    let formats = match opcode(opcode_byte) {
+      Some((_, formats)) => formats,
+      None => return Err(format!("unknown opcode 0x{:02x}", opcode_byte)),
+    };
+    for format in formats {
+      let operand = match format {
+        Format::U8 => Operand::Immediate(i64::from(reader.u8()?)),
+        Format::S8 => Operand::Immediate(i64::from(reader.u8()? as i8)),
+        Format::U30 => Operand::Immediate(i64::from(reader.u30()?)),
+        Format::S16 => Operand::Immediate(i64::from(reader.u30()? as u16 as i16)),
+        Format::Branch => {
+          let branch = reader.s24()?;
+          Operand::Target(target(reader.offset, branch)?)
+        }
+        Format::Int => Operand::Int(reader.u30()?),
+        Format::UInt => Operand::UInt(reader.u30()?),
+        Format::Double => Operand::Double(reader.u30()?),
+        Format::String => Operand::String(reader.u30()?),
+        Format::Namespace => Operand::Namespace(reader.u30()?),
+        Format::Multiname => Operand::Multiname(reader.u30()?),
+        Format::Method => Operand::Method(reader.u30()?),
+        Format::Class => Operand::Class(reader.u30()?),
+        Format::Exception => Operand::Exception(reader.u30()?),
+      };
+      operands.push(operand);
+    }
+  }
+  Ok(Instruction {
+    offset,
+    size: reader.offset - offset,
+    code: opcode_byte,
+    operands,
+  })
+}
+
+/// Returns the absolute offset of a branch, relative to `base`.
+fn target(base: usize, branch: i32) -> Result<usize, String> {
+  let target = base as i64 + i64::from(branch);
+  if target < 0 {
+    return Err(String::from("branch before the start of the code"));
+  }
+  Ok(target as usize)
+}
+
+struct Reader<'a> {
+  code: &'a [u8],
+  offset: usize,
+}
+
+impl<'a> Reader<'a> {
+  fn u8(&mut self) -> Result<u8, String> {
+    let byte = *self
+      .code
+      .get(self.offset)
+      .ok_or_else(|| String::from("truncated instruction"))?;
+    self.offset += 1;
+    Ok(byte)
+  }
+
+  fn u30(&mut self) -> Result<u32, String> {
+    let mut value: u32 = 0;
+    for i in 0..5 {
+      let byte = self.u8()?;
+      value |= u32::from(byte & 0x7f).wrapping_shl(7 * i);
+      if byte & 0x80 == 0 {
+        break;
+      }
+    }
+    Ok(value)
+  }
+
+  fn s24(&mut self) -> Result<i32, String> {
+    let bytes = [self.u8()?, self.u8()?, self.u8()?];
+    Ok(i32::from(bytes[0]) | i32::from(bytes[1]) << 8 | i32::from(bytes[2] as i8) << 16)
+  }
+}
@@ -0,0 +1,1645 @@
+//! ActionScript 3 decompiler.
+//!
+//! Method bodies are split in basic blocks, the operand stack of each block is lifted to the syntax tree of the
+//! ActionScript 2 decompiler, and the control flow is recovered with `crate::structure`. The protected ranges of the
+//! exception table become `try` statements with a `catch` clause per handler. Classes are rebuilt from their traits
+//! and initializers: the `newclass` sequences of the script initializers only define the classes and are dropped.
+
+use crate::avm1::decompiler::ast::{self, Expr, Function, Stmt};
+use crate::avm1::decompiler::simplify::simplify_body;
+use crate::avm1::decompiler::{entry_stack, temporary};
+use crate::avm2::abc::{AbcFile, Exception, MethodBody, Multiname, Namespace, Trait, TraitData};
+use crate::avm2::code::{read_code, Instruction, Operand};
+use crate::source_paths::{sanitize, source_path};
+use crate::structure::{self, successors, Structured, Target, Terminator};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt::Write;
+use std::path::PathBuf;
+
+/// Scope objects, kept as markers on the stack: their properties are printed as variables.
+const GLOBAL: &str = "$global";
+const SCOPE: &str = "$scope";
+const ACTIVATION: &str = "$activation";
+const CATCH: &str = "$catch";
+/// Result of `newclass`, the assignments of this marker are removed.
+const NEW_CLASS: &str = "$newclass";
+
+/// Decompiled source file of a class or of a package-level definition.
+pub(crate) struct SourceFile {
+  /// Relative path, such as `com/example/Greeter.as`
+  pub path: PathBuf,
+  pub source: String,
+}
+
+/// Decompiles the classes, package-level definitions and script initializers of an ABC file.
+pub(crate) fn decompile(location: &str, abc: &AbcFile) -> Vec<SourceFile> {
+  let mut class_metadata: HashMap<u32, &[u32]> = HashMap::new();
+  for script in abc.scripts.iter() {
+    for r#trait in script.traits.iter() {
+      if let TraitData::Class { class, .. } = r#trait.data {
+        class_metadata.insert(class, &r#trait.metadata);
+      }
+    }
+  }
+
+  let mut files = Vec::new();
+  for (i, instance) in abc.instances.iter().enumerate() {
+    let name = abc.type_name(instance.name);
+    let mut writer = Writer::new(abc, &name);
+    let metadata = class_metadata.get(&(i as u32)).copied().unwrap_or_default();
+    let definition = writer.class(i, metadata);
+    files.push(writer.file(location, &name, &definition));
+  }
+  for (i, script) in abc.scripts.iter().enumerate() {
+    let slots = trait_slots(abc, &script.traits);
+    for r#trait in script.traits.iter() {
+      if let TraitData::Class { .. } = r#trait.data {
+        continue;
+      }
+      let name = abc.type_name(r#trait.name);
+      let mut writer = Writer::new(abc, &name);
+      let modifiers = format!("{} ", abc.access(r#trait.name));
+      let definition = writer.member(r#trait, &modifiers, 1, &HashMap::new());
+      files.push(writer.file(location, &name, &definition));
+    }
+    let mut writer = Writer::new(abc, "");
+    if let Some(statements) = writer.method(script.initializer, true, &slots) {
+      if !statements.is_empty() {
+        let mut source = format!("// {}\n", location);
+        for import in writer.imports.iter() {
+          writeln!(source, "import {};", import).unwrap();
+        }
+        if !writer.imports.is_empty() {
+          source.push('\n');
+        }
+        source.push_str(&ast::print(&statements, 0));
+        files.push(SourceFile {
+          path: PathBuf::from(format!("{}-script{}.as", sanitize(location), i)),
+          source,
+        });
+      }
+    }
+  }
+  files
+}
+
+/// Returns the names of the slots defined by traits, by slot id.
+fn trait_slots(abc: &AbcFile, traits: &[Trait]) -> HashMap<u32, String> {
+  let mut slots = HashMap::new();
+  for r#trait in traits {
+    match r#trait.data {
+      TraitData::Slot { slot_id, .. }
+      | TraitData::Const { slot_id, .. }
+      | TraitData::Class { slot_id, .. }
+      | TraitData::Function { slot_id, .. } => {
+        slots.insert(slot_id, abc.local_name(r#trait.name));
+      }
+      _ => {}
+    }
+  }
+  slots
+}
+
+/// Returns the names of the parameters of a method (`argN` if it is not known).
+fn parameter_names(abc: &AbcFile, method: u32) -> Vec<String> {
+  let method = match abc.methods.get(method as usize) {
+    Some(method) => method,
+    None => return Vec::new(),
+  };
+  (0..method.parameters.len())
+    .map(
+      |i| match method.parameter_names.get(i).and_then(|name| abc.string(*name)) {
+        Some(name) => name.to_string(),
+        None => format!("arg{}", i + 1),
+      },
+    )
+    .collect()
+}
+
+fn is_marker(expr: &Expr) -> bool {
+  match expr {
+    Expr::Builtin(name) => name.starts_with('$'),
+    _ => false,
+  }
+}
+
+/// Tests if the statement only stores a class created by `newclass`.
+fn is_class_definition(statement: &Stmt) -> bool {
+  let value = match statement {
+    Stmt::Assign(_, value) | Stmt::Expr(value) => value,
+    _ => return false,
+  };
+  match value {
+    Expr::Call(callee, _) => **callee == Expr::Builtin(NEW_CLASS),
+    _ => false,
+  }
+}
+
+/// Writer of a source file: collects the imports of the names it references.
+struct Writer<'a> {
+  abc: &'a AbcFile,
+  /// Package of the definition
+  package: String,
+  /// Full names of the referenced definitions of other packages
+  imports: BTreeSet<String>,
+  /// Methods being decompiled, to stop recursive `newfunction` instructions
+  active: HashSet<u32>,
+}
+
+impl<'a> Writer<'a> {
+  fn new(abc: &'a AbcFile, name: &str) -> Self {
+    let package = match name.rfind('.') {
+      Some(end) => name[..end].to_string(),
+      None => String::new(),
+    };
+    Self {
+      abc,
+      package,
+      imports: BTreeSet::new(),
+      active: HashSet::new(),
+    }
+  }
+
+  /// Wraps a definition in its package.
+  fn file(&self, location: &str, name: &str, definition: &str) -> SourceFile {
+    let mut source = format!("// {}\n", location);
+    if self.package.is_empty() {
+      source.push_str("package {\n");
+    } else {
+      writeln!(source, "package {} {{", self.package).unwrap();
+    }
+    for import in self.imports.iter() {
+      writeln!(source, "  import {};", import).unwrap();
+    }
+    if !self.imports.is_empty() {
+      source.push('\n');
+    }
+    source.push_str(definition);
+    source.push_str("}\n");
+    SourceFile {
+      path: source_path(name),
+      source,
+    }
+  }
+
+  /// Returns the name of a definition as written in the source, and imports it if needed.
+  fn reference(&mut self, index: u32) -> String {
+    let abc = self.abc;
+    match abc.multiname(index) {
+      Some(Multiname::QName { namespace, name }) => {
+        let name = abc.string(*name).unwrap_or("*").to_string();
+        if let Some(Namespace {
+          kind: "package",
+          name: package,
+        }) = abc.namespace(*namespace)
+        {
+          let package = abc.string(*package).unwrap_or_default();
+          if !package.is_empty() && package != self.package && !package.starts_with("__AS3__") {
+            self.imports.insert(format!("{}.{}", package, name));
+          }
+        }
+        name
+      }
+      Some(Multiname::TypeName { name, parameters }) => {
+        let parameters: Vec<String> = parameters.iter().map(|p| self.reference(*p)).collect();
+        format!("{}.<{}>", self.reference(*name), parameters.join(", "))
+      }
+      _ if index == 0 => String::from("*"),
+      _ => abc.local_name(index),
+    }
+  }
+
+  /// Returns the parameters of a method (`name:Type = value`) and its return type.
+  fn signature(&mut self, method: u32) -> (Vec<String>, String) {
+    let abc = self.abc;
+    let names = parameter_names(abc, method);
+    let method = match abc.methods.get(method as usize) {
+      Some(method) => method,
+      None => return (Vec::new(), String::from("*")),
+    };
+    let first_option = method.parameters.len().saturating_sub(method.options.len());
+    let mut parameters = Vec::new();
+    for (i, (name, parameter)) in names.iter().zip(method.parameters.iter()).enumerate() {
+      let mut parameter = format!("{}:{}", name, self.reference(*parameter));
+      if i >= first_option {
+        write!(
+          parameter,
+          " = {}",
+          abc.constant_value(&method.options[i - first_option])
+        )
+        .unwrap();
+      }
+      parameters.push(parameter);
+    }
+    if method.flags.contains(&"need-rest") {
+      parameters.push(String::from("...rest"));
+    }
+    (parameters, self.reference(method.return_type))
+  }
+
+  /// Returns the source of a class or interface, indented for its package.
+  fn class(&mut self, index: usize, metadata: &[u32]) -> String {
+    let abc = self.abc;
+    let instance = &abc.instances[index];
+    let static_traits: &[Trait] = match abc.classes.get(index) {
+      Some(class) => &class.traits,
+      None => &[],
+    };
+    let is_interface = instance.flags.contains(&"interface");
+    let name = abc.local_name(instance.name);
+
+    let mut out = String::new();
+    for metadata in metadata {
+      writeln!(out, "  {}", abc.metadata_source(*metadata)).unwrap();
+    }
+    let mut declaration = match abc.access(instance.name).as_str() {
+      "public" => String::from("public"),
+      _ => String::from("internal"),
+    };
+    if instance.flags.contains(&"final") {
+      declaration.push_str(" final");
+    }
+    if !is_interface && !instance.flags.contains(&"sealed") {
+      declaration.push_str(" dynamic");
+    }
+    let kind = if is_interface { "interface" } else { "class" };
+    write!(declaration, " {} {}", kind, name).unwrap();
+    if instance.super_name != 0 && abc.type_name(instance.super_name) != "Object" {
+      write!(declaration, " extends {}", self.reference(instance.super_name)).unwrap();
+    }
+    if !instance.interfaces.is_empty() {
+      let interfaces: Vec<String> = instance.interfaces.iter().map(|name| self.reference(*name)).collect();
+      let keyword = if is_interface { "extends" } else { "implements" };
+      write!(declaration, " {} {}", keyword, interfaces.join(", ")).unwrap();
+    }
+    writeln!(out, "  {} {{", declaration).unwrap();
+
+    let static_slots = trait_slots(abc, static_traits);
+    let instance_slots = trait_slots(abc, &instance.traits);
+    let traits: Vec<(bool, &Trait)> = static_traits
+      .iter()
+      .map(|r#trait| (true, r#trait))
+      .chain(instance.traits.iter().map(|r#trait| (false, r#trait)))
+      .collect();
+    let modifiers = |r#trait: &Trait, is_static: bool| -> String {
+      if is_interface {
+        return String::new();
+      }
+      let mut modifiers = String::new();
+      if r#trait.is_override {
+        modifiers.push_str("override ");
+      }
+      modifiers.push_str(&abc.access(r#trait.name));
+      modifiers.push(' ');
+      if is_static {
+        modifiers.push_str("static ");
+      }
+      if r#trait.is_final {
+        modifiers.push_str("final ");
+      }
+      modifiers
+    };
+
+    let mut sections: Vec<String> = Vec::new();
+    let mut variables = String::new();
+    for (is_static, r#trait) in traits.iter() {
+      if let TraitData::Slot { .. } | TraitData::Const { .. } = r#trait.data {
+        variables.push_str(&self.member(r#trait, &modifiers(r#trait, *is_static), 2, &HashMap::new()));
+      }
+    }
+    if !variables.is_empty() {
+      sections.push(variables);
+    }
+    if !is_interface {
+      if let Some(class) = abc.classes.get(index) {
+        match self.method(class.initializer, false, &static_slots) {
+          Some(statements) if !statements.is_empty() => {
+            sections.push(format!("    // Static initializer\n{}", ast::print(&statements, 2)));
+          }
+          _ => {}
+        }
+      }
+      let header = format!("public function {}", name);
+      sections.push(self.function_source(&header, instance.initializer, 2, true, &instance_slots));
+    }
+    for (is_static, r#trait) in traits.iter() {
+      let slots = if *is_static { &static_slots } else { &instance_slots };
+      match r#trait.data {
+        TraitData::Slot { .. } | TraitData::Const { .. } | TraitData::Class { .. } => {}
+        _ => sections.push(self.member(r#trait, &modifiers(r#trait, *is_static), 2, slots)),
+      }
+    }
+    out.push_str(&sections.join("\n"));
+    out.push_str("  }\n");
+    out
+  }
+
+  /// Returns the source of a trait, with its metadata; `slots` are the slots of the object of the methods.
+  fn member(&mut self, r#trait: &Trait, modifiers: &str, indent: usize, slots: &HashMap<u32, String>) -> String {
+    let abc = self.abc;
+    let pad = "  ".repeat(indent);
+    let mut out = String::new();
+    for metadata in r#trait.metadata.iter() {
+      writeln!(out, "{}{}", pad, abc.metadata_source(*metadata)).unwrap();
+    }
+    let name = abc.local_name(r#trait.name);
+    let header = match &r#trait.data {
+      TraitData::Slot { type_name, value, .. } | TraitData::Const { type_name, value, .. } => {
+        let keyword = match r#trait.data {
+          TraitData::Const { .. } => "const",
+          _ => "var",
+        };
+        write!(
+          out,
+          "{}{}{} {}:{}",
+          pad,
+          modifiers,
+          keyword,
+          name,
+          self.reference(*type_name)
+        )
+        .unwrap();
+        if let Some(value) = value {
+          write!(out, " = {}", abc.constant_value(value)).unwrap();
+        }
+        out.push_str(";\n");
+        return out;
+      }
+      TraitData::Method { method, .. } | TraitData::Function { method, .. } => {
+        (format!("{}function {}", modifiers, name), *method)
+      }
+      TraitData::Getter { method, .. } => (format!("{}function get {}", modifiers, name), *method),
+      TraitData::Setter { method, .. } => (format!("{}function set {}", modifiers, name), *method),
+      TraitData::Class { class, .. } => {
+        writeln!(out, "{}// {}class {}", pad, modifiers, class).unwrap();
+        return out;
+      }
+    };
+    out.push_str(&self.function_source(&header.0, header.1, indent, false, slots));
+    out
+  }
+
+  /// Returns the source of a method: its header, followed by its body or `;` for methods without body (interfaces,
+  /// native methods).
+  fn function_source(
+    &mut self,
+    header: &str,
+    method: u32,
+    indent: usize,
+    is_constructor: bool,
+    slots: &HashMap<u32, String>,
+  ) -> String {
+    let pad = "  ".repeat(indent);
+    let (parameters, return_type) = self.signature(method);
+    let mut out = format!("{}{}({})", pad, header, parameters.join(", "));
+    if !is_constructor {
+      write!(out, ":{}", return_type).unwrap();
+    }
+    match self.method(method, false, slots) {
+      Some(statements) => {
+        writeln!(out, " {{\n{}{}}}", ast::print(&statements, indent + 1), pad).unwrap();
+      }
+      None => out.push_str(";\n"),
+    }
+    out
+  }
+
+  /// Decompiles a method body, `None` if the method has no body; `slots` are the slots of `this`.
+  fn method(&mut self, method: u32, is_script: bool, slots: &HashMap<u32, String>) -> Option<Vec<Stmt>> {
+    let abc = self.abc;
+    let body = abc.method_bodies.iter().find(|body| body.method == method)?;
+    let info = abc.methods.get(method as usize)?;
+    if !self.active.insert(method) {
+      return Some(vec![Stmt::Comment(String::from("recursive method"))]);
+    }
+    let (instructions, error) = read_code(&body.code);
+    let graph = Graph::new(instructions, &body.exceptions);
+    let mut locals = vec![String::from("this")];
+    locals.extend(parameter_names(abc, method));
+    if info.flags.contains(&"need-rest") {
+      locals.push(String::from("rest"));
+    } else if info.flags.contains(&"need-arguments") {
+      locals.push(String::from("arguments"));
+    }
+    let mut markers = HashMap::new();
+    if is_script {
+      markers.insert(0, Expr::Builtin(GLOBAL));
+    }
+    let mut decompiler = Decompiler {
+      writer: self,
+      graph: &graph,
+      body,
+      slots,
+      first_local: locals.len(),
+      locals,
+      declared: HashSet::new(),
+      declared_slots: HashSet::new(),
+      markers,
+      scopes: Vec::new(),
+      coercion: None,
+      catch_name: None,
+      cases: HashMap::new(),
+    };
+    let blocks: Vec<usize> = (0..graph.blocks.len()).collect();
+    let exceptions: Vec<usize> = (0..body.exceptions.len()).collect();
+    let mut statements = decompiler.region(&blocks, &exceptions, &[None], Vec::new());
+    if let Some(error) = error {
+      statements.push(Stmt::Comment(format!(
+        "{}, the rest of the bytecode is not decoded",
+        error
+      )));
+    }
+    simplify_body(&mut statements);
+    statements.retain(|statement| !is_class_definition(statement));
+    self.active.remove(&method);
+    Some(statements)
+  }
+
+  /// Decompiles the method of a `newfunction` instruction.
+  fn function(&mut self, method: u32) -> Function {
+    let (parameters, _) = self.signature(method);
+    let body = self.method(method, false, &HashMap::new()).unwrap_or_default();
+    Function {
+      name: String::new(),
+      parameters,
+      body,
+    }
+  }
+}
+
+/// Basic block: range of instructions, only the last one can branch.
+struct Block {
+  offset: usize,
+  start: usize,
+  end: usize,
+}
+
+/// Control flow graph of a method body.
+struct Graph {
+  instructions: Vec<Instruction>,
+  blocks: Vec<Block>,
+  /// Index of the block starting at each offset
+  block_at: HashMap<usize, usize>,
+  /// Protected ranges and handlers of the exception table, as offsets
+  exceptions: Vec<(usize, usize, usize)>,
+  /// Immediate dominator of each block (including the edges to the exception handlers), `None` if unreachable
+  dominators: Vec<Option<usize>>,
+}
+
+fn is_branch(name: &str) -> bool {
+  name == "jump" || name == "lookupswitch" || name.starts_with("if")
+}
+
+impl Graph {
+  fn new(instructions: Vec<Instruction>, exceptions: &[Exception]) -> Self {
+    let exceptions: Vec<(usize, usize, usize)> = exceptions
+      .iter()
+      .map(|e| (e.from as usize, e.to as usize, e.target as usize))
+      .collect();
+    let mut leaders: BTreeSet<usize> = BTreeSet::new();
+    for instruction in instructions.iter() {
+      for operand in instruction.operands.iter() {
+        if let Operand::Target(target) = operand {
+          leaders.insert(*target);
+        }
+      }
+      let name = instruction.name();
+      if is_branch(name) || name == "returnvoid" || name == "returnvalue" || name == "throw" {
+        leaders.insert(instruction.end());
+      }
+    }
+    for (from, to, target) in exceptions.iter() {
+      leaders.extend(&[*from, *to, *target]);
+    }
+    let mut blocks: Vec<Block> = Vec::new();
+    for (i, instruction) in instructions.iter().enumerate() {
+      match blocks.last_mut() {
+        Some(block) if !leaders.contains(&instruction.offset) => block.end = i + 1,
+        _ => blocks.push(Block {
+          offset: instruction.offset,
+          start: i,
+          end: i + 1,
+        }),
+      }
+    }
+    let block_at = blocks.iter().enumerate().map(|(i, block)| (block.offset, i)).collect();
+    let mut graph = Self {
+      instructions,
+      blocks,
+      block_at,
+      exceptions,
+      dominators: Vec::new(),
+    };
+    graph.dominators = graph.find_dominators();
+    graph
+  }
+
+  fn last(&self, block: usize) -> &Instruction {
+    &self.instructions[self.blocks[block].end - 1]
+  }
+
+  /// Returns the blocks following a block in the normal flow, `None` for the end of the code or an invalid target.
+  /// The targets of `lookupswitch` are its default target, then its cases.
+  fn targets(&self, block: usize) -> Vec<Option<usize>> {
+    let next = if block + 1 < self.blocks.len() {
+      Some(block + 1)
+    } else {
+      None
+    };
+    let last = self.last(block);
+    let targets = || {
+      last.operands.iter().map(|operand| match operand {
+        Operand::Target(target) => self.block_at.get(target).copied(),
+        _ => None,
+      })
+    };
+    match last.name() {
+      "jump" | "lookupswitch" => targets().collect(),
+      "returnvoid" | "returnvalue" | "throw" => Vec::new(),
+      name if name.starts_with("if") => targets().chain(std::iter::once(next)).collect(),
+      _ => vec![next],
+    }
+  }
+
+  /// Returns the handlers of the protected ranges containing a block.
+  fn handlers(&self, block: usize) -> impl Iterator<Item = usize> + '_ {
+    let offset = self.blocks[block].offset;
+    self
+      .exceptions
+      .iter()
+      .filter(move |(from, to, _)| *from <= offset && offset < *to)
+      .filter_map(move |(_, _, target)| self.block_at.get(target).copied())
+  }
+
+  /// Computes the immediate dominators (Cooper, Harvey and Kennedy, "A Simple, Fast Dominance Algorithm").
+  fn find_dominators(&self) -> Vec<Option<usize>> {
+    let count = self.blocks.len();
+    let mut dominators = vec![None; count];
+    if count == 0 {
+      return dominators;
+    }
+    let successors: Vec<Vec<usize>> = (0..count)
+      .map(|block| {
+        let mut successors: Vec<usize> = self.targets(block).into_iter().flatten().collect();
+        successors.extend(self.handlers(block));
+        successors
+      })
+      .collect();
+    let mut postorder = Vec::with_capacity(count);
+    let mut visited = vec![false; count];
+    let mut stack = vec![(0, 0)];
+    visited[0] = true;
+    while let Some(top) = stack.last_mut() {
+      let (block, next) = *top;
+      match successors[block].get(next) {
+        Some(&successor) => {
+          top.1 += 1;
+          if !visited[successor] {
+            visited[successor] = true;
+            stack.push((successor, 0));
+          }
+        }
+        None => {
+          stack.pop();
+          postorder.push(block);
+        }
+      }
+    }
+    let mut rank = vec![0; count];
+    for (i, block) in postorder.iter().enumerate() {
+      rank[*block] = i;
+    }
+    let mut predecessors: Vec<Vec<usize>> = vec![Vec::new(); count];
+    for block in postorder.iter() {
+      for successor in successors[*block].iter() {
+        predecessors[*successor].push(*block);
+      }
+    }
+    dominators[0] = Some(0);
+    let mut changed = true;
+    while changed {
+      changed = false;
+      for block in postorder.iter().rev().skip(1) {
+        let mut dominator: Option<usize> = None;
+        for predecessor in predecessors[*block].iter() {
+          if dominators[*predecessor].is_none() {
+            continue;
+          }
+          dominator = Some(match dominator {
+            None => *predecessor,
+            Some(mut other) => {
+              let mut predecessor = *predecessor;
+              while predecessor != other {
+                while rank[predecessor] < rank[other] {
+                  predecessor = dominators[predecessor].unwrap();
+                }
+                while rank[other] < rank[predecessor] {
+                  other = dominators[other].unwrap();
+                }
+              }
+              other
+            }
+          });
+        }
+        if dominators[*block] != dominator {
+          dominators[*block] = dominator;
+          changed = true;
+        }
+      }
+    }
+    dominators
+  }
+
+  /// Tests if all the paths to `block` go through `dominator`.
+  fn dominates(&self, dominator: usize, mut block: usize) -> bool {
+    loop {
+      if block == dominator {
+        return true;
+      }
+      match self.dominators[block] {
+        Some(parent) if parent != block => block = parent,
+        _ => return false,
+      }
+    }
+  }
+}
+
+/// Node of a region: a block, a `try` statement, or a case of a `lookupswitch`.
+enum Unit {
+  Block(usize),
+  Try {
+    /// All the blocks of the `try` statement, the first one is its entry
+    blocks: Vec<usize>,
+    body: Vec<usize>,
+    /// Exceptions nested in the body
+    exceptions: Vec<usize>,
+    /// Exception, blocks and nested exceptions of each handler
+    catches: Vec<(usize, Vec<usize>, Vec<usize>)>,
+    /// Block following the statement, `Some(None)` for the end of the code and `None` if it never completes
+    follow: Option<Option<usize>>,
+  },
+  /// Test of the case `k` (`k > 0`) of the `lookupswitch` ending a block
+  Case(usize, usize),
+}
+
+impl Unit {
+  fn first(&self) -> usize {
+    match self {
+      Unit::Block(block) | Unit::Case(block, _) => *block,
+      Unit::Try { blocks, .. } => blocks[0],
+    }
+  }
+}
+
+/// Statements of a block, before structuring.
+struct Lifted {
+  statements: Vec<Stmt>,
+  /// Condition of the conditional jump ending the block
+  condition: Option<Expr>,
+}
+
+struct Lifter {
+  stack: Vec<Expr>,
+  statements: Vec<Stmt>,
+  underflow: bool,
+  condition: Option<Expr>,
+}
+
+impl Lifter {
+  fn push(&mut self, value: Expr) {
+    self.stack.push(value);
+  }
+
+  fn pop(&mut self) -> Expr {
+    match self.stack.pop() {
+      Some(value) => value,
+      None => {
+        self.underflow = true;
+        Expr::Undefined
+      }
+    }
+  }
+
+  /// Pops `count` values, in the order they were pushed.
+  fn pop_n(&mut self, count: i64) -> Vec<Expr> {
+    let count = (count.max(0) as usize).min(self.stack.len() + 1);
+    let mut values: Vec<Expr> = (0..count).map(|_| self.pop()).collect();
+    values.reverse();
+    values
+  }
+
+  fn unary(&mut self, op: &'static str) {
+    let value = self.pop();
+    self.push(Expr::unary(op, value));
+  }
+
+  fn binary(&mut self, op: &'static str) {
+    let right = self.pop();
+    let left = self.pop();
+    self.push(Expr::binary(op, left, right));
+  }
+}
+
+/// Property name of an instruction, resolved at runtime or not.
+enum Key {
+  /// Multiname index
+  Name(u32),
+  Runtime(Expr),
+}
+
+struct Decompiler<'w, 'a> {
+  writer: &'w mut Writer<'a>,
+  graph: &'w Graph,
+  body: &'a MethodBody,
+  /// Names of the slots of `this` (or of the global object in script initializers)
+  slots: &'w HashMap<u32, String>,
+  /// Names of the registers: `this`, the parameters, then `rest` or `arguments`
+  locals: Vec<String>,
+  /// First register of the local variables
+  first_local: usize,
+  /// Local variables already declared with `var`
+  declared: HashSet<usize>,
+  /// Slots of the activation already declared with `var`
+  declared_slots: HashSet<u32>,
+  /// Registers holding a scope object
+  markers: HashMap<usize, Expr>,
+  scopes: Vec<Expr>,
+  /// Type of the last coercion, for the declarations of local variables
+  coercion: Option<String>,
+  /// Variable of the last `newcatch` instruction
+  catch_name: Option<String>,
+  /// Conditions of the cases of the `lookupswitch` ending a block
+  cases: HashMap<usize, Vec<Expr>>,
+}
+
+/// Decompiled region, with its nodes indexed by position.
+struct Region {
+  labels: Vec<String>,
+  lifted: Vec<Lifted>,
+  /// Outer blocks targeted from the region, `None` is the end of the code
+  exits: Vec<Option<usize>>,
+  /// Exits reached when the region completes normally
+  follows: Vec<Option<usize>>,
+  /// Labels of the blocks of the method
+  block_labels: Vec<String>,
+}
+
+impl<'w, 'a> Decompiler<'w, 'a> {
+  /// Decompiles a set of blocks (sorted, the first one is the entry) with the exceptions of the protected ranges
+  /// starting in these blocks; `follows` are the outer blocks where the execution continues when it reaches the end
+  /// of the region (`None` for the end of the code).
+  fn region(
+    &mut self,
+    blocks: &[usize],
+    exceptions: &[usize],
+    follows: &[Option<usize>],
+    entry: Vec<Expr>,
+  ) -> Vec<Stmt> {
+    let graph = self.graph;
+    let entry_block = match blocks.first() {
+      Some(block) => *block,
+      None => return Vec::new(),
+    };
+    let members: HashSet<usize> = blocks.iter().copied().collect();
+    let exceptions: Vec<usize> = exceptions
+      .iter()
+      .copied()
+      .filter(|e| {
+        let (from, to, _) = graph.exceptions[*e];
+        from < to && matches!(graph.block_at.get(&from), Some(block) if members.contains(block))
+      })
+      .collect();
+    let range = |e: &usize| {
+      let (from, to, _) = graph.exceptions[*e];
+      (from, to)
+    };
+    // Outermost protected ranges, each becomes a `try` statement
+    let mut ranges: Vec<(usize, usize)> = exceptions.iter().map(range).collect();
+    let all_ranges = ranges.clone();
+    ranges.retain(|(from, to)| {
+      !all_ranges
+        .iter()
+        .any(|(f, t)| (f, t) != (from, to) && f <= from && to <= t)
+    });
+    ranges.sort_unstable();
+    ranges.dedup();
+
+    let mut covered: HashSet<usize> = HashSet::new();
+    let mut units: Vec<Unit> = Vec::new();
+    for (from, to) in ranges {
+      let body: Vec<usize> = blocks
+        .iter()
+        .copied()
+        .filter(|block| {
+          let offset = graph.blocks[*block].offset;
+          from <= offset && offset < to && !covered.contains(block)
+        })
+        .collect();
+      if body.is_empty() {
+        continue;
+      }
+      covered.extend(body.iter().copied());
+      let mut all_blocks = body.clone();
+      let mut catches = Vec::new();
+      for e in exceptions.iter().copied().filter(|e| range(e) == (from, to)) {
+        let mut handler = Vec::new();
+        if let Some(&target) = graph.block_at.get(&graph.exceptions[e].2) {
+          if members.contains(&target) && !covered.contains(&target) {
+            handler.push(target);
+            handler.extend(
+              blocks
+                .iter()
+                .copied()
+                .filter(|block| *block != target && !covered.contains(block) && graph.dominates(target, *block)),
+            );
+          }
+        }
+        covered.extend(handler.iter().copied());
+        all_blocks.extend(handler.iter().copied());
+        catches.push((e, handler, Vec::new()));
+      }
+      let starts_in = |e: &usize, blocks: &[usize]| {
+        let block = graph.block_at[&graph.exceptions[*e].0];
+        blocks.contains(&block)
+      };
+      let nested: Vec<usize> = exceptions
+        .iter()
+        .copied()
+        .filter(|e| range(e) != (from, to) && starts_in(e, &body))
+        .collect();
+      for (_, handler, nested) in catches.iter_mut() {
+        *nested = exceptions.iter().copied().filter(|e| starts_in(e, handler)).collect();
+      }
+      // The code following the statement is usually the closest block after it
+      let inside: HashSet<usize> = all_blocks.iter().copied().collect();
+      let exits: Vec<Option<usize>> = all_blocks
+        .iter()
+        .flat_map(|block| graph.targets(*block))
+        .filter(|target| match target {
+          Some(target) => !inside.contains(target),
+          None => true,
+        })
+        .collect();
+      let after = exits
+        .iter()
+        .filter_map(|exit| *exit)
+        .min_by_key(|block| graph.blocks[*block].offset);
+      let follow = match after {
+        Some(block) => Some(Some(block)),
+        None => exits.first().copied(),
+      };
+      units.push(Unit::Try {
+        blocks: all_blocks,
+        body,
+        exceptions: nested,
+        catches,
+        follow,
+      });
+    }
+    for block in blocks.iter().copied().filter(|block| !covered.contains(block)) {
+      units.push(Unit::Block(block));
+      let last = graph.last(block);
+      if last.name() == "lookupswitch" {
+        for case in 1..last.operands.len().saturating_sub(1) {
+          units.push(Unit::Case(block, case));
+        }
+      }
+    }
+    // The entry comes first, the cases follow their `lookupswitch`
+    units.sort_by_key(|unit| {
+      let case = match unit {
+        Unit::Case(_, case) => *case,
+        _ => 0,
+      };
+      let first = unit.first();
+      let is_entry = match unit {
+        Unit::Try { blocks, .. } => blocks.contains(&entry_block),
+        _ => first == entry_block,
+      };
+      (!is_entry, graph.blocks[first].offset, case)
+    });
+
+    let mut node_of: HashMap<usize, usize> = HashMap::new();
+    for (i, unit) in units.iter().enumerate() {
+      match unit {
+        Unit::Block(block) => {
+          node_of.insert(*block, i);
+        }
+        Unit::Try { blocks, .. } => node_of.extend(blocks.iter().map(|block| (*block, i))),
+        Unit::Case(..) => {}
+      }
+    }
+    let mut exits: Vec<Option<usize>> = Vec::new();
+    let mut resolve = |target: Option<usize>| -> Target {
+      if let Some(node) = target.and_then(|target| node_of.get(&target)) {
+        return Target::Node(*node);
+      }
+      match exits.iter().position(|exit| *exit == target) {
+        Some(position) => Target::Exit(position),
+        None => {
+          exits.push(target);
+          Target::Exit(exits.len() - 1)
+        }
+      }
+    };
+    let mut terminators = Vec::with_capacity(units.len());
+    for (i, unit) in units.iter().enumerate() {
+      let terminator = match unit {
+        Unit::Block(block) | Unit::Case(block, _) => {
+          let targets = graph.targets(*block);
+          let case = match unit {
+            Unit::Case(_, case) => *case,
+            _ => 0,
+          };
+          match graph.last(*block).name() {
+            "lookupswitch" if targets.len() > 1 => {
+              let cases = targets.len() - 1;
+              Terminator::Branch {
+                if_true: resolve(targets[case + 1]),
+                if_false: if case + 1 < cases {
+                  Target::Node(i + 1)
+                } else {
+                  resolve(targets[0])
+                },
+              }
+            }
+            name if name.starts_with("if") => Terminator::Branch {
+              if_true: resolve(targets[0]),
+              if_false: resolve(targets[1]),
+            },
+            _ => match targets.first() {
+              Some(target) => Terminator::Jump(resolve(*target)),
+              None => Terminator::End,
+            },
+          }
+        }
+        Unit::Try { follow, .. } => match follow {
+          Some(follow) => Terminator::Jump(resolve(*follow)),
+          None => Terminator::End,
+        },
+      };
+      terminators.push(terminator);
+    }
+
+    let mut predecessors: Vec<Vec<usize>> = vec![Vec::new(); units.len()];
+    for (i, terminator) in terminators.iter().enumerate() {
+      for target in successors(terminator) {
+        if let Target::Node(successor) = target {
+          predecessors[successor].push(i);
+        }
+      }
+    }
+    let mut entry = Some(entry);
+    let mut entry_stacks: Vec<Vec<Expr>> = Vec::with_capacity(units.len());
+    let mut exit_stacks: Vec<Vec<Expr>> = Vec::with_capacity(units.len());
+    let mut lifted = Vec::with_capacity(units.len());
+    for (i, unit) in units.iter().enumerate() {
+      let stack = match entry.take() {
+        Some(stack) => stack,
+        None => entry_stack(&predecessors[i], &exit_stacks),
+      };
+      let (unit_lifted, exit_stack) = match unit {
+        Unit::Block(block) => self.lift(*block, stack.clone()),
+        Unit::Case(block, case) => {
+          let condition = self.cases.get(block).and_then(|cases| cases.get(*case)).cloned();
+          let lifted = Lifted {
+            statements: Vec::new(),
+            condition,
+          };
+          (lifted, stack.clone())
+        }
+        Unit::Try {
+          body,
+          exceptions,
+          catches,
+          follow,
+          ..
+        } => {
+          let follows: Vec<Option<usize>> = follow.iter().copied().collect();
+          let body = self.region(body, exceptions, &follows, Vec::new());
+          let mut clauses = Vec::new();
+          for (e, handler, nested) in catches.iter() {
+            let exception = &self.body.exceptions[*e];
+            let abc = self.writer.abc;
+            let name = match exception.variable_name {
+              0 => String::from("e"),
+              name => abc.local_name(name),
+            };
+            let type_name = self.writer.reference(exception.exception_type);
+            // The handlers start with an empty scope stack and the exception on the operand stack
+            self.scopes.clear();
+            let statements = self.region(handler, nested, &follows, vec![Expr::Var(name.clone())]);
+            clauses.push((format!("{}:{}", name, type_name), statements));
+          }
+          let statement = Stmt::Try {
+            body,
+            catches: clauses,
+            finally: None,
+          };
+          let lifted = Lifted {
+            statements: vec![statement],
+            condition: None,
+          };
+          (lifted, Vec::new())
+        }
+      };
+      entry_stacks.push(stack);
+      exit_stacks.push(exit_stack);
+      lifted.push(unit_lifted);
+    }
+    // Store the values expected in temporaries by the successors
+    for (i, exit_stack) in exit_stacks.iter().enumerate() {
+      let mut slots = BTreeSet::new();
+      for target in successors(&terminators[i]) {
+        if let Target::Node(successor) = target {
+          for (slot, value) in entry_stacks[successor].iter().enumerate() {
+            if *value == temporary(slot) && slot < exit_stack.len() && exit_stack[slot] != *value {
+              slots.insert(slot);
+            }
+          }
+        }
+      }
+      for slot in slots {
+        let value = exit_stack[slot].clone();
+        lifted[i].statements.push(Stmt::Assign(temporary(slot), value));
+      }
+    }
+
+    let structured = structure::structure(&terminators);
+    let mut region = Region {
+      labels: units
+        .iter()
+        .map(|unit| match unit {
+          Unit::Case(block, case) => format!("L{}_{}", graph.blocks[*block].offset, case),
+          unit => format!("L{}", graph.blocks[unit.first()].offset),
+        })
+        .collect(),
+      lifted,
+      exits,
+      follows: follows.to_vec(),
+      block_labels: graph.blocks.iter().map(|block| format!("L{}", block.offset)).collect(),
+    };
+    let mut statements = Vec::new();
+    region.convert(structured, true, &mut statements);
+    statements
+  }
+
+  /// Converts the instructions of a block to statements, returns the values left on the stack.
+  fn lift(&mut self, block: usize, stack: Vec<Expr>) -> (Lifted, Vec<Expr>) {
+    let graph = self.graph;
+    let mut lifter = Lifter {
+      stack,
+      statements: Vec::new(),
+      underflow: false,
+      condition: None,
+    };
+    let Block { start, end, .. } = graph.blocks[block];
+    for instruction in graph.instructions[start..end].iter() {
+      self.instruction(&mut lifter, block, instruction);
+    }
+    let mut statements = lifter.statements;
+    if lifter.underflow {
+      statements.insert(0, Stmt::Comment("stack underflow".to_string()));
+    }
+    let lifted = Lifted {
+      statements,
+      condition: lifter.condition,
+    };
+    (lifted, lifter.stack)
+  }
+
+  fn local_name(&self, register: usize) -> String {
+    match self.locals.get(register) {
+      Some(name) => name.clone(),
+      None => format!("_local{}", register),
+    }
+  }
+
+  fn get_local(&self, register: usize) -> Expr {
+    match self.markers.get(&register) {
+      Some(marker) => marker.clone(),
+      None => Expr::Var(self.local_name(register)),
+    }
+  }
+
+  /// Stores a value in a register: scope objects are only tracked, the first assignment of a local variable
+  /// declares it with the type of the last coercion.
+  fn set_local(&mut self, lifter: &mut Lifter, register: usize, value: Expr, coercion: Option<String>) {
+    if is_marker(&value) {
+      self.markers.insert(register, value);
+      return;
+    }
+    self.markers.remove(&register);
+    let name = self.local_name(register);
+    if register >= self.first_local && self.declared.insert(register) {
+      let name = format!("{}:{}", name, coercion.unwrap_or_else(|| String::from("*")));
+      lifter.statements.push(Stmt::Var(Expr::String(name), Some(value)));
+    } else {
+      lifter.statements.push(Stmt::Assign(Expr::Var(name), value));
+    }
+  }
+
+  /// Pops the parts of a multiname resolved at runtime.
+  fn key(&mut self, lifter: &mut Lifter, index: u32) -> Key {
+    match self.writer.abc.multiname(index) {
+      Some(Multiname::RtQName { .. }) | Some(Multiname::RtQNameA { .. }) => {
+        lifter.pop();
+        Key::Name(index)
+      }
+      Some(Multiname::RtQNameL) | Some(Multiname::RtQNameLA) => {
+        let name = lifter.pop();
+        lifter.pop();
+        Key::Runtime(name)
+      }
+      Some(Multiname::MultinameL { .. }) | Some(Multiname::MultinameLA { .. }) => Key::Runtime(lifter.pop()),
+      _ => Key::Name(index),
+    }
+  }
+
+  /// Returns a property of an object, the properties of scope objects are variables.
+  fn member(&mut self, object: Expr, key: Key) -> Expr {
+    match key {
+      Key::Name(index) if is_marker(&object) => Expr::Var(self.writer.reference(index)),
+      Key::Name(index) => Expr::Member(
+        Box::new(object),
+        Box::new(Expr::String(self.writer.abc.local_name(index))),
+      ),
+      Key::Runtime(key) if is_marker(&object) => Expr::Member(Box::new(Expr::Var(String::from("this"))), Box::new(key)),
+      Key::Runtime(key) => Expr::Member(Box::new(object), Box::new(key)),
+    }
+  }
+
+  /// Returns the declaration (`name:Type`) of the first assignment of a slot of the activation.
+  fn declare_slot(&mut self, slot: i64) -> Option<String> {
+    let slot = slot as u32;
+    let (name, type_name) = self.body.traits.iter().find_map(|r#trait| match r#trait.data {
+      TraitData::Slot { slot_id, type_name, .. } | TraitData::Const { slot_id, type_name, .. } if slot_id == slot => {
+        Some((r#trait.name, type_name))
+      }
+      _ => None,
+    })?;
+    if !self.declared_slots.insert(slot) {
+      return None;
+    }
+    Some(format!(
+      "{}:{}",
+      self.writer.abc.local_name(name),
+      self.writer.reference(type_name)
+    ))
+  }
+
+  fn slot(&self, object: Expr, slot: i64) -> Expr {
+    let slot = slot as u32;
+    let trait_name = |traits: &[Trait]| {
+      traits.iter().find_map(|r#trait| match r#trait.data {
+        TraitData::Slot { slot_id, .. } | TraitData::Const { slot_id, .. } if slot_id == slot => {
+          Some(self.writer.abc.local_name(r#trait.name))
+        }
+        _ => None,
+      })
+    };
+    let name = match object {
+      Expr::Builtin(ACTIVATION) => trait_name(&self.body.traits),
+      Expr::Builtin(CATCH) => self.catch_name.clone(),
+      Expr::Builtin(_) => self.slots.get(&slot).cloned(),
+      _ => {
+        let name = match self.slots.get(&slot) {
+          Some(name) => name.clone(),
+          None => format!("slot{}", slot),
+        };
+        return Expr::Member(Box::new(object), Box::new(Expr::String(name)));
+      }
+    };
+    Expr::Var(name.unwrap_or_else(|| format!("_slot{}", slot)))
+  }
+
+  fn instruction(&mut self, lifter: &mut Lifter, block: usize, instruction: &Instruction) {
+    let abc = self.writer.abc;
+    let pool = &abc.constant_pool;
+    let coercion = self.coercion.take();
+    let immediate = |i: usize| match instruction.operands.get(i) {
+      Some(Operand::Immediate(value)) => *value,
+      _ => 0,
+    };
+    let index = match instruction.operands.first() {
+      Some(Operand::Int(index))
+      | Some(Operand::UInt(index))
+      | Some(Operand::Double(index))
+      | Some(Operand::String(index))
+      | Some(Operand::Namespace(index))
+      | Some(Operand::Multiname(index))
+      | Some(Operand::Method(index))
+      | Some(Operand::Class(index))
+      | Some(Operand::Exception(index)) => *index,
+      _ => 0,
+    };
+    let pool_index = (index as usize).wrapping_sub(1);
+    let name = instruction.name();
+    match name {
+      "nop" | "label" | "bkpt" | "bkptline" | "debug" | "debugline" | "debugfile" | "timestamp" | "jump" | "kill"
+      | "sxi1" | "sxi8" | "sxi16" | "esc_xelem" | "esc_xattr" | "checkfilter" => {}
+      "throw" => {
+        let value = lifter.pop();
+        lifter.statements.push(Stmt::Throw(value));
+      }
+      "returnvoid" => lifter.statements.push(Stmt::Return(None)),
+      "returnvalue" => {
+        let value = lifter.pop();
+        lifter.statements.push(Stmt::Return(Some(value)));
+      }
+      "iftrue" => lifter.condition = Some(lifter.pop()),
+      "iffalse" => lifter.condition = Some(lifter.pop().not()),
+      "ifeq" | "ifne" | "iflt" | "ifle" | "ifgt" | "ifge" | "ifstricteq" | "ifstrictne" | "ifnlt" | "ifnle"
+      | "ifngt" | "ifnge" => {
+        let op = match name {
+          "ifeq" => "==",
+          "ifne" => "!=",
+          "iflt" | "ifnlt" => "<",
+          "ifle" | "ifnle" => "<=",
+          "ifgt" | "ifngt" => ">",
+          "ifge" | "ifnge" => ">=",
+          "ifstricteq" => "===",
+          _ => "!==",
+        };
+        let right = lifter.pop();
+        let left = lifter.pop();
+        let condition = Expr::binary(op, left, right);
+        lifter.condition = Some(if name.starts_with("ifn") && name != "ifne" {
+          condition.not()
+        } else {
+          condition
+        });
+      }
+      "lookupswitch" => {
+        let mut value = lifter.pop();
+        if value.has_side_effects() {
+          let switch = Expr::Var(String::from("_switch"));
+          lifter.statements.push(Stmt::Assign(switch.clone(), value));
+          value = switch;
+        }
+        let cases = (0..instruction.operands.len().saturating_sub(1))
+          .map(|case| Expr::binary("==", value.clone(), Expr::Number(case as f64)))
+          .collect::<Vec<Expr>>();
+        lifter.condition = cases.first().cloned();
+        self.cases.insert(block, cases);
+      }
+      "pushwith" | "pushscope" => {
+        let value = lifter.pop();
+        self.scopes.push(value);
+      }
+      "popscope" => {
+        self.scopes.pop();
+      }
+      "nextname" | "nextvalue" | "hasnext" => {
+        let index = lifter.pop();
+        let object = lifter.pop();
+        lifter.push(Expr::call(name, vec![object, index]));
+      }
+      "hasnext2" => {
+        let args = vec![
+          self.get_local(immediate(0) as usize),
+          self.get_local(immediate(1) as usize),
+        ];
+        lifter.push(Expr::call("hasnext2", args));
+      }
+      "pushnull" => lifter.push(Expr::Null),
+      "pushundefined" => lifter.push(Expr::Undefined),
+      "pushtrue" => lifter.push(Expr::Boolean(true)),
+      "pushfalse" => lifter.push(Expr::Boolean(false)),
+      "pushnan" => lifter.push(Expr::Number(f64::NAN)),
+      "pushbyte" | "pushshort" => lifter.push(Expr::Number(immediate(0) as f64)),
+      "pushint" => lifter.push(match pool.integers.get(pool_index) {
+        Some(value) => Expr::Number(f64::from(*value)),
+        None => Expr::Undefined,
+      }),
+      "pushuint" => lifter.push(match pool.unsigned_integers.get(pool_index) {
+        Some(value) => Expr::Number(f64::from(*value)),
+        None => Expr::Undefined,
+      }),
+      "pushdouble" => lifter.push(match pool.doubles.get(pool_index) {
+        Some(value) => Expr::Number(*value),
+        None => Expr::Undefined,
+      }),
+      "pushstring" => lifter.push(Expr::String(abc.string(index).unwrap_or_default().to_string())),
+      "pushnamespace" => lifter.push(Expr::Var(format!(
+        "Namespace({})",
+        ast::quote(&abc.namespace_name(index))
+      ))),
+      "pop" => {
+        let value = lifter.pop();
+        if value.has_side_effects() {
+          lifter.statements.push(Stmt::Expr(value));
+        }
+      }
+      "dup" => {
+        let value = lifter.pop();
+        lifter.push(value.clone());
+        lifter.push(value);
+      }
+      "swap" => {
+        let top = lifter.pop();
+        let second = lifter.pop();
+        lifter.push(top);
+        lifter.push(second);
+      }
+      "li8" | "li16" | "li32" | "lf32" | "lf64" => {
+        let address = lifter.pop();
+        lifter.push(Expr::call(name, vec![address]));
+      }
+      "si8" | "si16" | "si32" | "sf32" | "sf64" => {
+        let address = lifter.pop();
+        let value = lifter.pop();
+        lifter
+          .statements
+          .push(Stmt::Expr(Expr::call(name, vec![value, address])));
+      }
+      "newfunction" => {
+        let function = self.writer.function(index);
+        lifter.push(Expr::Function(Box::new(function)));
+      }
+      "call" => {
+        let args = lifter.pop_n(immediate(0));
+        lifter.pop();
+        let function = lifter.pop();
+        lifter.push(Expr::Call(Box::new(function), args));
+      }
+      "construct" => {
+        let args = lifter.pop_n(immediate(0));
+        let constructor = lifter.pop();
+        lifter.push(Expr::New(Box::new(constructor), args));
+      }
+      "callmethod" | "callstatic" => {
+        let args = lifter.pop_n(immediate(1));
+        let object = lifter.pop();
+        let method = match name {
+          "callmethod" => format!("method{}", immediate(0)),
+          _ => format!("function{}", index),
+        };
+        let callee = Expr::Member(Box::new(object), Box::new(Expr::String(method)));
+        lifter.push(Expr::Call(Box::new(callee), args));
+      }
+      "callsuper" | "callsupervoid" | "callproperty" | "callproplex" | "callpropvoid" | "constructprop" => {
+        let args = lifter.pop_n(immediate(1));
+        let key = self.key(lifter, index);
+        let object = lifter.pop();
+        let value = match name {
+          "callsuper" | "callsupervoid" => {
+            let callee = self.member(Expr::Builtin("super"), key);
+            Expr::Call(Box::new(callee), args)
+          }
+          "constructprop" => Expr::New(Box::new(self.member(object, key)), args),
+          _ => Expr::Call(Box::new(self.member(object, key)), args),
+        };
+        if name.ends_with("void") {
+          lifter.statements.push(Stmt::Expr(value));
+        } else {
+          lifter.push(value);
+        }
+      }
+      "constructsuper" => {
+        let args = lifter.pop_n(immediate(0));
+        lifter.pop();
+        lifter
+          .statements
+          .push(Stmt::Expr(Expr::Call(Box::new(Expr::Builtin("super")), args)));
+      }
+      "applytype" => {
+        let parameters: Vec<String> = lifter.pop_n(immediate(0)).iter().map(ast::print_expr).collect();
+        let base = lifter.pop();
+        lifter.push(Expr::Var(format!(
+          "{}.<{}>",
+          ast::print_expr(&base),
+          parameters.join(", ")
+        )));
+      }
+      "newobject" => {
+        let values = lifter.pop_n(immediate(0).saturating_mul(2));
+        let properties = values
+          .chunks(2)
+          .map(|pair| (pair[0].clone(), pair.get(1).cloned().unwrap_or(Expr::Undefined)))
+          .collect();
+        lifter.push(Expr::Object(properties));
+      }
+      "newarray" => {
+        let items = lifter.pop_n(immediate(0));
+        lifter.push(Expr::Array(items));
+      }
+      "newactivation" => lifter.push(Expr::Builtin(ACTIVATION)),
+      "newclass" => {
+        lifter.pop();
+        lifter.push(Expr::call(NEW_CLASS, vec![Expr::Number(f64::from(index))]));
+      }
+      "getdescendants" => {
+        let key = self.key(lifter, index);
+        let object = lifter.pop();
+        let name = match key {
+          Key::Name(index) => abc.local_name(index),
+          Key::Runtime(key) => ast::print_expr(&key),
+        };
+        lifter.push(Expr::Var(format!("{}..{}", ast::print_expr(&object), name)));
+      }
+      "newcatch" => {
+        self.catch_name = self
+          .body
+          .exceptions
+          .get(index as usize)
+          .map(|exception| abc.local_name(exception.variable_name));
+        lifter.push(Expr::Builtin(CATCH));
+      }
+      "findpropstrict" | "findproperty" => {
+        self.key(lifter, index);
+        lifter.push(Expr::Builtin(SCOPE));
+      }
+      "finddef" | "getouterscope" => lifter.push(Expr::Builtin(SCOPE)),
+      "getlex" => {
+        let name = self.writer.reference(index);
+        lifter.push(Expr::Var(name));
+      }
+      "setproperty" | "initproperty" | "setsuper" => {
+        let value = lifter.pop();
+        let key = self.key(lifter, index);
+        let mut object = lifter.pop();
+        if name == "setsuper" {
+          object = Expr::Builtin("super");
+        }
+        let target = self.member(object, key);
+        lifter.statements.push(Stmt::Assign(target, value));
+      }
+      "getproperty" | "getsuper" => {
+        let key = self.key(lifter, index);
+        let mut object = lifter.pop();
+        if name == "getsuper" {
+          object = Expr::Builtin("super");
+        }
+        let value = self.member(object, key);
+        lifter.push(value);
+      }
+      "deleteproperty" => {
+        let key = self.key(lifter, index);
+        let object = lifter.pop();
+        let property = self.member(object, key);
+        lifter.push(Expr::unary("delete", property));
+      }
+      "getlocal" | "getlocal0" | "getlocal1" | "getlocal2" | "getlocal3" => {
+        let register = match name.strip_prefix("getlocal") {
+          Some(register) if !register.is_empty() => register.parse().unwrap_or_default(),
+          _ => immediate(0) as usize,
+        };
+        lifter.push(self.get_local(register));
+      }
+      "setlocal" | "setlocal0" | "setlocal1" | "setlocal2" | "setlocal3" => {
+        let register = match name.strip_prefix("setlocal") {
+          Some(register) if !register.is_empty() => register.parse().unwrap_or_default(),
+          _ => immediate(0) as usize,
+        };
+        let value = lifter.pop();
+        self.set_local(lifter, register, value, coercion);
+      }
+      "getglobalscope" => lifter.push(Expr::Builtin(GLOBAL)),
+      "getscopeobject" => {
+        let scope = self.scopes.get(immediate(0) as usize).cloned();
+        lifter.push(scope.unwrap_or(Expr::Builtin(SCOPE)));
+      }
+      "getslot" => {
+        let object = lifter.pop();
+        lifter.push(self.slot(object, immediate(0)));
+      }
+      "setslot" => {
+        let value = lifter.pop();
+        let object = lifter.pop();
+        // Stores the exception in the scope of a `catch` clause
+        if object == Expr::Builtin(CATCH) {
+          return;
+        }
+        if object == Expr::Builtin(ACTIVATION) {
+          if let Some(name) = self.declare_slot(immediate(0)) {
+            lifter.statements.push(Stmt::Var(Expr::String(name), Some(value)));
+            return;
+          }
+        }
+        let target = self.slot(object, immediate(0));
+        lifter.statements.push(Stmt::Assign(target, value));
+      }
+      "getglobalslot" => lifter.push(self.slot(Expr::Builtin(GLOBAL), immediate(0))),
+      "setglobalslot" => {
+        let value = lifter.pop();
+        let target = self.slot(Expr::Builtin(GLOBAL), immediate(0));
+        lifter.statements.push(Stmt::Assign(target, value));
+      }
+      "coerce" => self.coercion = Some(self.writer.reference(index)),
+      "coerce_a" => self.coercion = Some(String::from("*")),
+      "convert_s" | "coerce_s" => self.coercion = Some(String::from("String")),
+      "convert_i" | "coerce_i" => self.coercion = Some(String::from("int")),
+      "convert_u" | "coerce_u" => self.coercion = Some(String::from("uint")),
+      "convert_d" | "coerce_d" => self.coercion = Some(String::from("Number")),
+      "convert_b" | "coerce_b" => self.coercion = Some(String::from("Boolean")),
+      "convert_o" | "coerce_o" => self.coercion = Some(String::from("Object")),
+      "astype" | "istype" => {
+        let value = lifter.pop();
+        let type_name = Expr::Var(self.writer.reference(index));
+        let op = if name == "astype" { "as" } else { "is" };
+        lifter.push(Expr::binary(op, value, type_name));
+      }
+      "astypelate" => lifter.binary("as"),
+      "istypelate" => lifter.binary("is"),
+      "instanceof" => lifter.binary("instanceof"),
+      "in" => lifter.binary("in"),
+      "negate" | "negate_i" => lifter.unary("-"),
+      "increment" | "increment_i" | "decrement" | "decrement_i" => {
+        let value = lifter.pop();
+        let op = if name.starts_with("increment") { "+" } else { "-" };
+        lifter.push(Expr::binary(op, value, Expr::Number(1.0)));
+      }
+      "inclocal" | "inclocal_i" | "declocal" | "declocal_i" => {
+        let register = immediate(0) as usize;
+        let op = if name.starts_with("inclocal") { "+" } else { "-" };
+        let value = Expr::binary(op, self.get_local(register), Expr::Number(1.0));
+        lifter
+          .statements
+          .push(Stmt::Assign(Expr::Var(self.local_name(register)), value));
+      }
+      "typeof" => lifter.unary("typeof"),
+      "not" => {
+        let value = lifter.pop();
+        lifter.push(value.not());
+      }
+      "bitnot" => lifter.unary("~"),
+      "add" | "add_i" => lifter.binary("+"),
+      "subtract" | "subtract_i" => lifter.binary("-"),
+      "multiply" | "multiply_i" => lifter.binary("*"),
+      "divide" => lifter.binary("/"),
+      "modulo" => lifter.binary("%"),
+      "lshift" => lifter.binary("<<"),
+      "rshift" => lifter.binary(">>"),
+      "urshift" => lifter.binary(">>>"),
+      "bitand" => lifter.binary("&"),
+      "bitor" => lifter.binary("|"),
+      "bitxor" => lifter.binary("^"),
+      "equals" => lifter.binary("=="),
+      "strictequals" => lifter.binary("==="),
+      "lessthan" => lifter.binary("<"),
+      "lessequals" => lifter.binary("<="),
+      "greaterthan" => lifter.binary(">"),
+      "greaterequals" => lifter.binary(">="),
+      "" => lifter
+        .statements
+        .push(Stmt::Comment(format!("unknown opcode 0x{:02x}", instruction.code))),
+      name => lifter
+        .statements
+        .push(Stmt::Comment(format!("unsupported instruction {}", name))),
+    }
+  }
+}
+
+impl Region {
+  /// Converts structured code to statements; `tail` indicates that the code completes the region.
+  fn convert(&mut self, items: Vec<Structured>, tail: bool, out: &mut Vec<Stmt>) {
+    let len = items.len();
+    for (i, item) in items.into_iter().enumerate() {
+      let tail = tail && i + 1 == len;
+      match item {
+        Structured::Node(node) => {
+          out.push(Stmt::Label(self.labels[node].clone()));
+          out.append(&mut self.lifted[node].statements);
+        }
+        Structured::If {
+          node,
+          negate,
+          then,
+          r#else,
+        } => {
+          let condition = self.lifted[node].condition.take().unwrap_or(Expr::Undefined);
+          let mut condition = if negate { condition.not() } else { condition };
+          let (mut then_statements, mut else_statements) = (Vec::new(), Vec::new());
+          self.convert(then, tail, &mut then_statements);
+          self.convert(r#else, tail, &mut else_statements);
+          if let Expr::Unary("!", _) = condition {
+            if !else_statements.is_empty() {
+              condition = condition.not();
+              std::mem::swap(&mut then_statements, &mut else_statements);
+            }
+          }
+          out.push(Stmt::If(condition, then_statements, else_statements));
+        }
+        Structured::Loop(body) => {
+          let mut statements = Vec::new();
+          self.convert(body, false, &mut statements);
+          out.push(Stmt::While(Expr::Boolean(true), statements));
+        }
+        Structured::Break => out.push(Stmt::Break),
+        Structured::Continue => out.push(Stmt::Continue),
+        Structured::Goto(node) => out.push(Stmt::Goto(self.labels[node].clone())),
+        Structured::Exit(exit) => {
+          let exit = self.exits[exit];
+          if tail && self.follows.contains(&exit) {
+            continue;
+          }
+          match exit {
+            Some(block) => out.push(Stmt::Goto(self.block_labels[block].clone())),
+            None => out.push(Stmt::Return(None)),
+          }
+        }
+      }
+    }
+  }
+}
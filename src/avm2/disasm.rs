@@ -0,0 +1,199 @@
+//! Textual disassembly of the method bodies of an ABC file.
+//!
+//! Each method body is listed with its signature, its limits (stack, locals, scopes) and its exception table, then
+//! its instructions with their offset. Branch targets and exception bounds are labeled `L<offset>`, and the pool
+//! operands are resolved to readable names.
+
+use crate::avm1::decompiler::ast::quote;
+use crate::avm2::abc::{format_double, AbcFile, Trait, TraitData};
+use crate::avm2::code::{read_code, Operand};
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+/// Returns the disassembly of all the method bodies of the ABC file, ordered by method index.
+pub(crate) fn disassemble(abc: &AbcFile) -> String {
+  let mut out = String::new();
+  writeln!(
+    out,
+    "; ABC {}.{}: {} methods, {} method bodies, {} classes, {} scripts",
+    abc.major_version,
+    abc.minor_version,
+    abc.methods.len(),
+    abc.method_bodies.len(),
+    abc.classes.len(),
+    abc.scripts.len()
+  )
+  .unwrap();
+  let names = method_names(abc);
+  let mut bodies: Vec<_> = abc.method_bodies.iter().collect();
+  bodies.sort_by_key(|body| body.method);
+  for body in bodies {
+    let name = names.get(body.method as usize).cloned().flatten().unwrap_or_default();
+    writeln!(out).unwrap();
+    writeln!(out, "method {}: {}{}", body.method, name, signature(abc, body.method)).unwrap();
+    writeln!(
+      out,
+      "; max_stack: {}, local_count: {}, init_scope_depth: {}, max_scope_depth: {}",
+      body.max_stack, body.local_count, body.init_scope_depth, body.max_scope_depth
+    )
+    .unwrap();
+    let mut labels: BTreeSet<usize> = BTreeSet::new();
+    for (i, exception) in body.exceptions.iter().enumerate() {
+      labels.extend(&[
+        exception.from as usize,
+        exception.to as usize,
+        exception.target as usize,
+      ]);
+      writeln!(
+        out,
+        "; exception {}: from L{} to L{}, target L{}, type {}, variable {}",
+        i,
+        exception.from,
+        exception.to,
+        exception.target,
+        abc.multiname_name(exception.exception_type),
+        abc.multiname_name(exception.variable_name)
+      )
+      .unwrap();
+    }
+    let (instructions, error) = read_code(&body.code);
+    for instruction in instructions.iter() {
+      for operand in instruction.operands.iter() {
+        if let Operand::Target(target) = operand {
+          labels.insert(*target);
+        }
+      }
+    }
+    for instruction in instructions.iter() {
+      if labels.contains(&instruction.offset) {
+        writeln!(out, "L{}:", instruction.offset).unwrap();
+      }
+      write!(out, "{:>6}: {}", instruction.offset, instruction.name()).unwrap();
+      let operands: Vec<String> = instruction
+        .operands
+        .iter()
+        .map(|operand| format_operand(abc, operand))
+        .collect();
+      if !operands.is_empty() {
+        write!(out, " {}", operands.join(", ")).unwrap();
+      }
+      writeln!(out).unwrap();
+    }
+    // Labels past the last instruction (end of a `try` block, invalid branches)
+    let end = instructions.last().map(|instruction| instruction.end()).unwrap_or(0);
+    for label in labels.range(end..) {
+      writeln!(out, "L{}:", label).unwrap();
+    }
+    if let Some(error) = error {
+      writeln!(out, "; {}, the rest of the bytecode is not decoded", error).unwrap();
+    }
+  }
+  out
+}
+
+fn format_operand(abc: &AbcFile, operand: &Operand) -> String {
+  let pool = &abc.constant_pool;
+  let invalid = |kind: &str, index: &u32| format!("<invalid {} {}>", kind, index);
+  match operand {
+    Operand::Immediate(value) => value.to_string(),
+    Operand::Target(target) => format!("L{}", target),
+    Operand::Int(index) => pool
+      .integers
+      .get((*index as usize).wrapping_sub(1))
+      .map(|value| value.to_string())
+      .unwrap_or_else(|| invalid("int", index)),
+    Operand::UInt(index) => pool
+      .unsigned_integers
+      .get((*index as usize).wrapping_sub(1))
+      .map(|value| value.to_string())
+      .unwrap_or_else(|| invalid("uint", index)),
+    Operand::Double(index) => pool
+      .doubles
+      .get((*index as usize).wrapping_sub(1))
+      .map(|value| format_double(*value))
+      .unwrap_or_else(|| invalid("double", index)),
+    Operand::String(index) => abc
+      .string(*index)
+      .map(quote)
+      .unwrap_or_else(|| invalid("string", index)),
+    Operand::Namespace(index) => format!("namespace({})", quote(&abc.namespace_name(*index))),
+    Operand::Multiname(index) => abc.multiname_name(*index),
+    Operand::Method(index) => format!("method {}", index),
+    Operand::Class(index) => match abc.instances.get(*index as usize) {
+      Some(instance) => format!("class {} ({})", index, abc.multiname_name(instance.name)),
+      None => invalid("class", index),
+    },
+    Operand::Exception(index) => format!("exception {}", index),
+  }
+}
+
+/// Returns the signature of a method, such as `(name:String, count:int = 0):void`.
+pub(crate) fn signature(abc: &AbcFile, method: u32) -> String {
+  let method = match abc.methods.get(method as usize) {
+    Some(method) => method,
+    None => return String::from("(<invalid method>)"),
+  };
+  let first_option = method.parameters.len().saturating_sub(method.options.len());
+  let mut parameters: Vec<String> = Vec::new();
+  for (i, parameter) in method.parameters.iter().enumerate() {
+    let name = match method.parameter_names.get(i).and_then(|name| abc.string(*name)) {
+      Some(name) => name.to_string(),
+      None => format!("arg{}", i + 1),
+    };
+    let mut parameter = format!("{}:{}", name, abc.type_name(*parameter));
+    if i >= first_option {
+      let value = abc.constant_value(&method.options[i - first_option]);
+      write!(parameter, " = {}", value).unwrap();
+    }
+    parameters.push(parameter);
+  }
+  if method.flags.contains(&"need-rest") {
+    parameters.push(String::from("...rest"));
+  }
+  format!("({}):{}", parameters.join(", "), abc.type_name(method.return_type))
+}
+
+/// Returns the name of each method, from the traits and initializers referencing it: `script0/init`,
+/// `com.example::Greeter/iinit` (constructor), `com.example::Greeter/cinit` (static initializer),
+/// `com.example::Greeter/greet`, `com.example::Greeter/static get instance`, ...
+pub(crate) fn method_names(abc: &AbcFile) -> Vec<Option<String>> {
+  let mut names: Vec<Option<String>> = vec![None; abc.methods.len()];
+  let mut set = |method: u32, name: String| {
+    if let Some(slot) = names.get_mut(method as usize) {
+      slot.get_or_insert(name);
+    }
+  };
+  for (i, instance) in abc.instances.iter().enumerate() {
+    let class = abc.multiname_name(instance.name);
+    set(instance.initializer, format!("{}/iinit", class));
+    if let Some(static_side) = abc.classes.get(i) {
+      set(static_side.initializer, format!("{}/cinit", class));
+      trait_method_names(abc, &static_side.traits, &format!("{}/static ", class), &mut set);
+    }
+    trait_method_names(abc, &instance.traits, &format!("{}/", class), &mut set);
+  }
+  for (i, script) in abc.scripts.iter().enumerate() {
+    set(script.initializer, format!("script{}/init", i));
+    trait_method_names(abc, &script.traits, "", &mut set);
+  }
+  for (i, method) in abc.methods.iter().enumerate() {
+    if names[i].is_none() {
+      names[i] = Some(abc.string(method.name).unwrap_or("function").to_string());
+    }
+  }
+  names
+}
+
+fn trait_method_names(abc: &AbcFile, traits: &[Trait], prefix: &str, set: &mut impl FnMut(u32, String)) {
+  for r#trait in traits {
+    let name = abc.multiname_name(r#trait.name);
+    match r#trait.data {
+      TraitData::Method { method, .. } | TraitData::Function { method, .. } => {
+        set(method, format!("{}{}", prefix, name))
+      }
+      TraitData::Getter { method, .. } => set(method, format!("{}get {}", prefix, name)),
+      TraitData::Setter { method, .. } => set(method, format!("{}set {}", prefix, name)),
+      _ => {}
+    }
+  }
+}
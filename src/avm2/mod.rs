@@ -0,0 +1,7 @@
+//! Analysis of AVM2 bytecode (ABC files of `DoAbc` tags).
+
+pub(crate) mod abc;
+pub(crate) mod classes;
+pub(crate) mod code;
+pub(crate) mod decompiler;
+pub(crate) mod disasm;
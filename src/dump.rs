@@ -3,8 +3,8 @@ use serde::Serialize;
 use std::collections::HashMap;
 use std::io::Write;
 use std::path::PathBuf;
-use swf_types::tags::{DefineSprite, DoAction, DoInitAction};
-use swf_types::Movie;
+use swf_types::tags::{DefineSprite, DoAbc, DoAction, DoInitAction, PlaceObject};
+use swf_types::{ClipAction, ClipEventFlags, Movie, NamedId};
 use swf_types::{Header, Tag};
 
 pub(crate) fn dump_movie(dir: &PathBuf, movie: &Movie) {
@@ -77,11 +77,148 @@ fn dump_sprite_tag(dir: &PathBuf, tag: &Tag) {
 
 #[derive(Copy, Clone, Hash, Ord, PartialOrd, PartialEq, Eq, Debug)]
 #[allow(clippy::enum_variant_names)]
+#[rustfmt::skip]
 pub(crate) enum Avm1Location {
   RootDoAction { tag_index: usize },
   RootDoInitAction { tag_index: usize },
   SpriteDoAction { tag_index: usize, sprite_tag_index: usize },
   SpriteDoInitAction { tag_index: usize, sprite_tag_index: usize },
+  /// Clip action handler of a `PlaceObject` tag in the root timeline (`onClipEvent`)
+  RootClipAction { tag_index: usize, clip_action_index: usize, events: ClipEvents },
+  /// Clip action handler of a `PlaceObject` tag in a sprite timeline (`onClipEvent`)
+  SpriteClipAction { tag_index: usize, sprite_tag_index: usize, clip_action_index: usize, events: ClipEvents },
+  /// Condition action of a `DefineButton` tag (the only action of a `DefineButton1` tag uses the index `0`)
+  ButtonAction { tag_index: usize, button_id: u16, cond_index: usize },
+}
+
+/// Formats the location as `<kind>:<tag_index>[/<sprite_tag_index>][#<index>]`, for example `do-action:3/5`.
+///
+/// Clip actions end with their events, for example `clip-action:3#0(load-enterFrame)`, and button actions include the
+/// button id, for example `button-action:3/12#0`.
+impl std::fmt::Display for Avm1Location {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Avm1Location::RootDoAction { tag_index } => write!(f, "do-action:{}", tag_index),
+      Avm1Location::RootDoInitAction { tag_index } => write!(f, "do-init-action:{}", tag_index),
+      Avm1Location::SpriteDoAction {
+        tag_index,
+        sprite_tag_index,
+      } => write!(f, "do-action:{}/{}", tag_index, sprite_tag_index),
+      Avm1Location::SpriteDoInitAction {
+        tag_index,
+        sprite_tag_index,
+      } => write!(f, "do-init-action:{}/{}", tag_index, sprite_tag_index),
+      Avm1Location::RootClipAction {
+        tag_index,
+        clip_action_index,
+        events,
+      } => write!(f, "clip-action:{}#{}({})", tag_index, clip_action_index, events),
+      Avm1Location::SpriteClipAction {
+        tag_index,
+        sprite_tag_index,
+        clip_action_index,
+        events,
+      } => write!(
+        f,
+        "clip-action:{}/{}#{}({})",
+        tag_index, sprite_tag_index, clip_action_index, events
+      ),
+      Avm1Location::ButtonAction {
+        tag_index,
+        button_id,
+        cond_index,
+      } => write!(f, "button-action:{}/{}#{}", tag_index, button_id, cond_index),
+    }
+  }
+}
+
+/// Set of clip events triggering a clip action handler.
+///
+/// This is a compact copy of `ClipEventFlags` so it can be part of `Avm1Location`.
+#[derive(Copy, Clone, Hash, Ord, PartialOrd, PartialEq, Eq, Debug)]
+pub(crate) struct ClipEvents(u32);
+
+/// Names of the clip events, as used by `onClipEvent(...)` in ActionScript.
+const CLIP_EVENT_NAMES: [&str; 19] = [
+  "load",
+  "enterFrame",
+  "unload",
+  "mouseMove",
+  "mouseDown",
+  "mouseUp",
+  "keyDown",
+  "keyUp",
+  "data",
+  "initialize",
+  "press",
+  "release",
+  "releaseOutside",
+  "rollOver",
+  "rollOut",
+  "dragOver",
+  "dragOut",
+  "keyPress",
+  "construct",
+];
+
+impl ClipEvents {
+  pub(crate) fn new(flags: &ClipEventFlags) -> Self {
+    let flags: [bool; 19] = [
+      flags.load,
+      flags.enter_frame,
+      flags.unload,
+      flags.mouse_move,
+      flags.mouse_down,
+      flags.mouse_up,
+      flags.key_down,
+      flags.key_up,
+      flags.data,
+      flags.initialize,
+      flags.press,
+      flags.release,
+      flags.release_outside,
+      flags.roll_over,
+      flags.roll_out,
+      flags.drag_over,
+      flags.drag_out,
+      flags.key_press,
+      flags.construct,
+    ];
+    let mut bits: u32 = 0;
+    for (i, flag) in flags.iter().enumerate() {
+      if *flag {
+        bits |= 1 << i;
+      }
+    }
+    Self(bits)
+  }
+
+  /// Returns the names of the events in this set, in the canonical order.
+  pub(crate) fn names(self) -> Vec<&'static str> {
+    CLIP_EVENT_NAMES
+      .iter()
+      .enumerate()
+      .filter(|(i, _)| self.0 & (1 << i) != 0)
+      .map(|(_, name)| *name)
+      .collect()
+  }
+}
+
+/// Formats the names of the events separated by `-`, for example `load-enterFrame`.
+impl std::fmt::Display for ClipEvents {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.write_str(&self.names().join("-"))
+  }
+}
+
+/// Returns the name of the dump directory of a clip action handler: its index, followed by its events if any (for
+/// example `0-load-enterFrame`).
+pub(crate) fn clip_action_dir_name(clip_action_index: usize, events: ClipEvents) -> String {
+  if events.names().is_empty() {
+    format!("{}", clip_action_index)
+  } else {
+    format!("{}-{}", clip_action_index, events)
+  }
 }
 
 pub(crate) fn find_avm1(movie: &Movie) -> HashMap<Avm1Location, &Vec<u8>> {
@@ -109,6 +246,19 @@ pub(crate) fn find_avm1(movie: &Movie) -> HashMap<Avm1Location, &Vec<u8>> {
                 &tag.actions,
               );
             }
+            Tag::PlaceObject(tag) => {
+              for (clip_action_index, clip_action) in clip_actions(tag).iter().enumerate() {
+                avm1_buffers.insert(
+                  Avm1Location::SpriteClipAction {
+                    tag_index,
+                    sprite_tag_index,
+                    clip_action_index,
+                    events: ClipEvents::new(&clip_action.events),
+                  },
+                  &clip_action.actions,
+                );
+              }
+            }
             _ => {}
           }
         }
@@ -119,12 +269,114 @@ pub(crate) fn find_avm1(movie: &Movie) -> HashMap<Avm1Location, &Vec<u8>> {
       Tag::DoInitAction(tag) => {
         avm1_buffers.insert(Avm1Location::RootDoInitAction { tag_index }, &tag.actions);
       }
+      Tag::DefineButton(tag) => {
+        for (cond_index, cond_action) in tag.actions.iter().enumerate() {
+          avm1_buffers.insert(
+            Avm1Location::ButtonAction {
+              tag_index,
+              button_id: tag.id,
+              cond_index,
+            },
+            &cond_action.actions,
+          );
+        }
+      }
+      Tag::PlaceObject(tag) => {
+        for (clip_action_index, clip_action) in clip_actions(tag).iter().enumerate() {
+          avm1_buffers.insert(
+            Avm1Location::RootClipAction {
+              tag_index,
+              clip_action_index,
+              events: ClipEvents::new(&clip_action.events),
+            },
+            &clip_action.actions,
+          );
+        }
+      }
       _ => {}
     }
   }
   avm1_buffers
 }
 
+/// Returns the AVM1 buffer at the provided location, mutably.
+pub(crate) fn find_avm1_mut<'a>(movie: &'a mut Movie, location: &Avm1Location) -> Option<&'a mut Vec<u8>> {
+  let (tag_index, sprite_tag_index) = match *location {
+    Avm1Location::RootDoAction { tag_index }
+    | Avm1Location::RootDoInitAction { tag_index }
+    | Avm1Location::RootClipAction { tag_index, .. }
+    | Avm1Location::ButtonAction { tag_index, .. } => (tag_index, None),
+    Avm1Location::SpriteDoAction {
+      tag_index,
+      sprite_tag_index,
+    }
+    | Avm1Location::SpriteDoInitAction {
+      tag_index,
+      sprite_tag_index,
+    }
+    | Avm1Location::SpriteClipAction {
+      tag_index,
+      sprite_tag_index,
+      ..
+    } => (tag_index, Some(sprite_tag_index)),
+  };
+  let mut tag = movie.tags.get_mut(tag_index)?;
+  if let Some(sprite_tag_index) = sprite_tag_index {
+    tag = match tag {
+      Tag::DefineSprite(sprite) => sprite.tags.get_mut(sprite_tag_index)?,
+      _ => return None,
+    };
+  }
+  match (location, tag) {
+    (Avm1Location::RootDoAction { .. }, Tag::DoAction(tag))
+    | (Avm1Location::SpriteDoAction { .. }, Tag::DoAction(tag)) => Some(&mut tag.actions),
+    (Avm1Location::RootDoInitAction { .. }, Tag::DoInitAction(tag))
+    | (Avm1Location::SpriteDoInitAction { .. }, Tag::DoInitAction(tag)) => Some(&mut tag.actions),
+    (Avm1Location::RootClipAction { clip_action_index, .. }, Tag::PlaceObject(tag))
+    | (Avm1Location::SpriteClipAction { clip_action_index, .. }, Tag::PlaceObject(tag)) => {
+      let clip_action = tag.clip_actions.as_mut()?.get_mut(*clip_action_index)?;
+      Some(&mut clip_action.actions)
+    }
+    (Avm1Location::ButtonAction { cond_index, .. }, Tag::DefineButton(tag)) => {
+      Some(&mut tag.actions.get_mut(*cond_index)?.actions)
+    }
+    _ => None,
+  }
+}
+
+/// Returns the `DoAbc` tags of the movie, with their tag index.
+pub(crate) fn find_abc(movie: &Movie) -> Vec<(usize, &DoAbc)> {
+  movie
+    .tags
+    .iter()
+    .enumerate()
+    .filter_map(|(tag_index, tag)| match tag {
+      Tag::DoAbc(tag) => Some((tag_index, tag)),
+      _ => None,
+    })
+    .collect()
+}
+
+/// Returns the entries of the `SymbolClass` tags of the movie, in tag order.
+pub(crate) fn find_symbols(movie: &Movie) -> Vec<&NamedId> {
+  movie
+    .tags
+    .iter()
+    .filter_map(|tag| match tag {
+      Tag::SymbolClass(tag) => Some(&tag.symbols),
+      _ => None,
+    })
+    .flatten()
+    .collect()
+}
+
+fn clip_actions(tag: &PlaceObject) -> &[ClipAction] {
+  match &tag.clip_actions {
+    Some(clip_actions) => clip_actions,
+    None => &[],
+  }
+}
+
 fn dump_do_action(dir: &PathBuf, tag: &DoAction) {
   {
     let path = dir.join("main.avm1");
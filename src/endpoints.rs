@@ -0,0 +1,481 @@
+//! Extraction of the network endpoints and external calls of the scripts of a movie.
+//!
+//! AVM1 buffers are read linearly (without following jumps) while tracking the values pushed on the stack: the
+//! arguments of `getURL`, `loadMovie`, `loadVariables`, `fscommand`, `ExternalInterface.call` and `LocalConnection`
+//! are reported as literals, concatenations (`"http://" + host`) or dynamic values. The remaining URLs of the
+//! constant pools and push literals are reported too, as well as the URLs of the string pools of ABC files.
+
+use crate::avm1::decompiler::ast::{print_expr, Expr};
+use crate::avm1::raw::{self, Action, PushValue};
+use crate::avm1::simple_stack_effect;
+use crate::dump::Avm1Location;
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap};
+
+#[derive(Debug, Serialize)]
+pub(crate) struct Endpoint {
+  pub location: String,
+  /// Offset of the action in the AVM1 buffer, or of the string in the ABC file
+  pub offset: usize,
+  /// `get-url`, `load-movie`, `load-variables`, `fscommand`, `external-interface`, `local-connection` or `url`
+  pub kind: &'static str,
+  /// `literal`, `concatenation` or `dynamic`
+  pub source: &'static str,
+  /// String for literals, ActionScript expression otherwise
+  pub value: String,
+}
+
+impl std::fmt::Display for Endpoint {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "{} @{}: {} ({}): {}",
+      self.location, self.offset, self.kind, self.source, self.value
+    )
+  }
+}
+
+/// Schemes of the strings reported as URLs.
+const URL_PREFIXES: [&str; 14] = [
+  "http://",
+  "https://",
+  "ftp://",
+  "file://",
+  "rtmp://",
+  "rtmpe://",
+  "rtmps://",
+  "rtmpt://",
+  "ws://",
+  "wss://",
+  "mailto:",
+  "javascript:",
+  "asfunction:",
+  "www.",
+];
+
+/// Tests if a string constant looks like a URL.
+fn is_url(value: &str) -> bool {
+  let value = value.trim_start().to_ascii_lowercase();
+  URL_PREFIXES.iter().any(|prefix| value.starts_with(prefix))
+}
+
+/// Returns the endpoints of an AVM1 buffer, by offset.
+pub(crate) fn find_avm1_endpoints(location: &Avm1Location, bytes: &[u8]) -> Vec<Endpoint> {
+  let mut scanner = Scanner {
+    location: location.to_string(),
+    constant_pool: Vec::new(),
+    stack: Vec::new(),
+    registers: HashMap::new(),
+    classes: HashMap::new(),
+    endpoints: Vec::new(),
+    strings: Vec::new(),
+  };
+  // Stacks of the code containing the function bodies, with the end of the body
+  let mut functions: Vec<(usize, Vec<Expr>, bool)> = Vec::new();
+  for action in raw::read_actions(bytes) {
+    while let Some((end, _, _)) = functions.last() {
+      if action.offset < *end {
+        break;
+      }
+      let (_, stack, anonymous) = functions.pop().unwrap();
+      scanner.stack = stack;
+      if anonymous {
+        scanner.push(unknown());
+      }
+    }
+    match &action.action {
+      Action::DefineFunction(_) | Action::DefineFunction2(_) => {
+        let anonymous = match &action.action {
+          Action::DefineFunction(function) => function.name.is_empty(),
+          Action::DefineFunction2(function) => function.name.is_empty(),
+          _ => false,
+        };
+        let stack = std::mem::take(&mut scanner.stack);
+        functions.push((action.end() + action.action.nested_size(), stack, anonymous));
+      }
+      _ => scanner.step(action.offset, &action.action),
+    }
+  }
+
+  let reported: BTreeSet<&str> = scanner
+    .endpoints
+    .iter()
+    .filter(|call| call.endpoint.source != "dynamic")
+    .flat_map(|call| call.parts.iter().map(String::as_str))
+    .collect();
+  let mut urls: Vec<Endpoint> = Vec::new();
+  let mut seen: BTreeSet<&str> = BTreeSet::new();
+  for (offset, value) in scanner.strings.iter() {
+    if is_url(value) && !reported.contains(value.as_str()) && seen.insert(value) {
+      urls.push(Endpoint {
+        location: scanner.location.clone(),
+        offset: *offset,
+        kind: "url",
+        source: "literal",
+        value: value.clone(),
+      });
+    }
+  }
+  let mut endpoints: Vec<Endpoint> = scanner.endpoints.into_iter().map(|call| call.endpoint).collect();
+  endpoints.extend(urls);
+  endpoints.sort_by_key(|endpoint| endpoint.offset);
+  endpoints
+}
+
+/// Returns the URLs of the string pool of an ABC file, or `None` if the string pool is malformed.
+pub(crate) fn find_abc_endpoints(location: &str, bytes: &[u8]) -> Option<Vec<Endpoint>> {
+  let strings = crate::avm2::abc::read_strings(bytes)?;
+  let endpoints = strings
+    .into_iter()
+    .filter(|(_, value)| is_url(value))
+    .map(|(offset, value)| Endpoint {
+      location: location.to_string(),
+      offset,
+      kind: "url",
+      source: "literal",
+      value,
+    })
+    .collect();
+  Some(endpoints)
+}
+
+/// Endpoint found at a call site, with the string constants it is built from.
+struct CallEndpoint {
+  endpoint: Endpoint,
+  parts: Vec<String>,
+}
+
+struct Scanner {
+  location: String,
+  constant_pool: Vec<String>,
+  /// Values on the stack, missing values are unknown
+  stack: Vec<Expr>,
+  /// Values stored in registers
+  registers: HashMap<u8, Expr>,
+  /// Classes of the objects stored in variables and registers (such as `lc = new LocalConnection()`)
+  classes: HashMap<String, String>,
+  endpoints: Vec<CallEndpoint>,
+  /// String constants, with the offset of the action defining them
+  strings: Vec<(usize, String)>,
+}
+
+impl Scanner {
+  fn push(&mut self, value: Expr) {
+    self.stack.push(value);
+  }
+
+  fn pop(&mut self) -> Expr {
+    self.stack.pop().unwrap_or_else(unknown)
+  }
+
+  /// Pops an argument count, then the arguments (first argument first).
+  fn pop_args(&mut self) -> Vec<Expr> {
+    let count = match self.pop() {
+      Expr::Number(count) if count >= 0.0 && count <= self.stack.len() as f64 => count as usize,
+      _ => {
+        // The stack content is unknown past a dynamic argument count
+        return std::mem::take(&mut self.stack).into_iter().rev().collect();
+      }
+    };
+    (0..count).map(|_| self.pop()).collect()
+  }
+
+  fn step(&mut self, offset: usize, action: &Action) {
+    match action {
+      Action::ConstantPool(pool) => {
+        self.constant_pool = pool.iter().map(|value| lossy(value)).collect();
+        for value in self.constant_pool.iter() {
+          self.strings.push((offset, value.clone()));
+        }
+      }
+      Action::Push(values) => {
+        for value in values {
+          let value = match value {
+            PushValue::String(value) => {
+              let value = lossy(value);
+              self.strings.push((offset, value.clone()));
+              Expr::String(value)
+            }
+            PushValue::Constant8(index) => self.constant(usize::from(*index)),
+            PushValue::Constant16(index) => self.constant(usize::from(*index)),
+            PushValue::Float32(value) => Expr::Number(f64::from(*value)),
+            PushValue::Float64(value) => Expr::Number(*value),
+            PushValue::Sint32(value) => Expr::Number(f64::from(*value)),
+            PushValue::Boolean(value) => Expr::Boolean(*value),
+            PushValue::Null => Expr::Null,
+            PushValue::Undefined => Expr::Undefined,
+            PushValue::Register(register) => self
+              .registers
+              .get(register)
+              .cloned()
+              .unwrap_or_else(|| Expr::Var(format!("_r{}", register))),
+          };
+          self.push(value);
+        }
+      }
+      Action::StoreRegister(register) => {
+        let value = self.pop();
+        self.store(format!("_r{}", register), &value);
+        self.registers.insert(*register, value.clone());
+        self.push(value);
+      }
+      Action::GetUrl { url, .. } => {
+        let url = lossy(url);
+        match url.strip_prefix("FSCommand:") {
+          Some(command) => self.report(offset, "fscommand", &Expr::String(command.to_string())),
+          None => self.report(offset, "get-url", &Expr::String(url)),
+        }
+      }
+      Action::GetUrl2(action) => {
+        let target = self.pop();
+        let url = self.pop();
+        // `loadMovieNum` is compiled to a `getURL` to a `_levelN` target
+        let is_level = matches!(&target, Expr::String(target) if target.starts_with("_level"));
+        let kind = if action.load_variables {
+          "load-variables"
+        } else if action.load_target || is_level {
+          "load-movie"
+        } else {
+          "get-url"
+        };
+        match &url {
+          Expr::String(value) if kind == "get-url" && value.starts_with("FSCommand:") => {
+            let command = Expr::String(value["FSCommand:".len()..].to_string());
+            self.report(offset, "fscommand", &command)
+          }
+          _ => self.report(offset, kind, &url),
+        }
+      }
+      Action::Simple(code) => self.simple(offset, *code),
+      Action::GotoFrame2 { .. } | Action::Call | Action::WaitForFrame2 { .. } | Action::If(_) | Action::With { .. } => {
+        self.pop();
+      }
+      Action::Jump(_) | Action::Unknown { .. } | Action::Malformed { .. } | Action::Truncated(_) => self.stack.clear(),
+      _ => {}
+    }
+  }
+
+  fn simple(&mut self, offset: usize, code: u8) {
+    let mnemonic = raw::mnemonic(code).unwrap_or_default();
+    match mnemonic {
+      "Add" | "Add2" | "StringAdd" => {
+        let right = self.pop();
+        let left = self.pop();
+        let op = if mnemonic == "StringAdd" { "add" } else { "+" };
+        self.push(Expr::binary(op, left, right));
+      }
+      "GetVariable" => {
+        let name = self.pop();
+        self.push(Expr::Eval(Box::new(name)));
+      }
+      "GetMember" => {
+        let key = self.pop();
+        let object = self.pop();
+        self.push(Expr::Member(Box::new(object), Box::new(key)));
+      }
+      "SetVariable" | "DefineLocal" => {
+        let value = self.pop();
+        if let Expr::String(name) = self.pop() {
+          self.store(name, &value);
+        }
+      }
+      "CallFunction" => {
+        let name = self.pop();
+        let args = self.pop_args();
+        if let Expr::String(name) = &name {
+          let kind = match name.as_str() {
+            "getURL" => Some("get-url"),
+            "loadMovie" | "loadMovieNum" => Some("load-movie"),
+            "loadVariables" | "loadVariablesNum" => Some("load-variables"),
+            "fscommand" => Some("fscommand"),
+            _ => None,
+          };
+          if let (Some(kind), Some(url)) = (kind, args.first()) {
+            self.report(offset, kind, url);
+          }
+        }
+        self.push(Expr::Call(Box::new(Expr::Eval(Box::new(name))), args));
+      }
+      "CallMethod" => {
+        let name = self.pop();
+        let object = self.pop();
+        let args = self.pop_args();
+        if let Expr::String(name) = &name {
+          if let (Some(kind), Some(arg)) = (self.method_kind(&object, name), args.first()) {
+            self.report(offset, kind, arg);
+          }
+        }
+        self.push(Expr::Call(
+          Box::new(Expr::Member(Box::new(object), Box::new(name))),
+          args,
+        ));
+      }
+      "NewObject" => {
+        let name = self.pop();
+        let args = self.pop_args();
+        self.push(Expr::New(Box::new(Expr::Eval(Box::new(name))), args));
+      }
+      "NewMethod" => {
+        let name = self.pop();
+        let object = self.pop();
+        let args = self.pop_args();
+        self.push(Expr::New(
+          Box::new(Expr::Member(Box::new(object), Box::new(name))),
+          args,
+        ));
+      }
+      "FsCommand2" => {
+        let args = self.pop_args();
+        if let Some(command) = args.first() {
+          self.report(offset, "fscommand", command);
+        }
+        self.push(unknown());
+      }
+      "InitArray" => {
+        let items = self.pop_args();
+        self.push(Expr::Array(items));
+      }
+      "ImplementsOp" => {
+        self.pop();
+        self.pop_args();
+      }
+      "InitObject" => {
+        let count = match self.pop() {
+          Expr::Number(count) if count >= 0.0 => count as usize,
+          _ => 0,
+        };
+        let depth = self.stack.len().saturating_sub(count.saturating_mul(2));
+        self.stack.truncate(depth);
+        self.push(Expr::Object(Vec::new()));
+      }
+      "PushDuplicate" => {
+        let value = self.pop();
+        self.push(value.clone());
+        self.push(value);
+      }
+      "StackSwap" => {
+        let top = self.pop();
+        let below = self.pop();
+        self.push(top);
+        self.push(below);
+      }
+      "Return" | "Throw" | "End" => self.stack.clear(),
+      _ => match simple_stack_effect(code) {
+        Some((pops, pushes)) => {
+          for _ in 0..pops {
+            self.pop();
+          }
+          for _ in 0..pushes {
+            self.push(unknown());
+          }
+        }
+        None => self.stack.clear(),
+      },
+    }
+  }
+
+  fn constant(&self, index: usize) -> Expr {
+    match self.constant_pool.get(index) {
+      Some(value) => Expr::String(value.clone()),
+      None => Expr::Undefined,
+    }
+  }
+
+  /// Records the class of the objects stored in variables and registers.
+  fn store(&mut self, name: String, value: &Expr) {
+    match class_name(value) {
+      Some(class) => {
+        self.classes.insert(name, class);
+      }
+      None => {
+        self.classes.remove(&name);
+      }
+    }
+  }
+
+  /// Returns the kind of endpoint passed as the first argument of a method.
+  fn method_kind(&self, object: &Expr, name: &str) -> Option<&'static str> {
+    // Static methods are called on the class itself (`ExternalInterface` or `flash.external.ExternalInterface`)
+    let class: Option<String> = match object {
+      Expr::Eval(name) => match name.as_ref() {
+        Expr::String(name) => Some(self.classes.get(name).unwrap_or(name).clone()),
+        _ => None,
+      },
+      Expr::Member(_, key) => match key.as_ref() {
+        Expr::String(key) => Some(key.clone()),
+        _ => None,
+      },
+      Expr::Var(name) => self.classes.get(name).cloned(),
+      object => class_name(object),
+    };
+    let kind = match (class.as_deref(), name) {
+      (Some("ExternalInterface"), "call") => "external-interface",
+      (Some("LocalConnection"), "connect") | (Some("LocalConnection"), "send") => "local-connection",
+      (Some("LoadVars"), "load") | (Some("LoadVars"), "send") | (Some("XML"), "load") | (Some("XML"), "send") => {
+        "load-variables"
+      }
+      (Some("MovieClipLoader"), "loadClip") => "load-movie",
+      (_, "sendAndLoad") | (_, "loadVariables") => "load-variables",
+      (_, "loadMovie") => "load-movie",
+      _ => return None,
+    };
+    Some(kind)
+  }
+
+  fn report(&mut self, offset: usize, kind: &'static str, value: &Expr) {
+    let mut parts = Vec::new();
+    string_parts(value, &mut parts);
+    let (source, value) = match value {
+      Expr::String(value) => ("literal", value.clone()),
+      _ if !parts.is_empty() => ("concatenation", print_expr(value)),
+      _ => ("dynamic", print_expr(value)),
+    };
+    self.endpoints.push(CallEndpoint {
+      endpoint: Endpoint {
+        location: self.location.clone(),
+        offset,
+        kind,
+        source,
+        value,
+      },
+      parts,
+    });
+  }
+}
+
+/// Value computed by an action whose result is not tracked.
+fn unknown() -> Expr {
+  Expr::Var(String::from("?"))
+}
+
+/// Returns the class of an object created by `new`.
+fn class_name(value: &Expr) -> Option<String> {
+  let name = match value {
+    Expr::New(constructor, _) => match constructor.as_ref() {
+      Expr::Eval(name) => name.as_ref(),
+      Expr::Member(_, name) => name.as_ref(),
+      _ => return None,
+    },
+    _ => return None,
+  };
+  match name {
+    Expr::String(name) => Some(name.rsplit('.').next().unwrap_or_default().to_string()),
+    _ => None,
+  }
+}
+
+/// Collects the string constants of a concatenation.
+fn string_parts(value: &Expr, parts: &mut Vec<String>) {
+  match value {
+    Expr::String(value) => parts.push(value.clone()),
+    Expr::Binary("+", left, right) | Expr::Binary("add", left, right) => {
+      string_parts(left, parts);
+      string_parts(right, parts);
+    }
+    _ => {}
+  }
+}
+
+fn lossy(bytes: &[u8]) -> String {
+  String::from_utf8_lossy(bytes).into_owned()
+}
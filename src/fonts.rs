@@ -0,0 +1,773 @@
+//! Conversion of the `DefineFont2`, `DefineFont3` and `DefineFont4` tags to font files.
+
+use crate::shapes;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use swf_types::tags::{DefineCffFont, DefineFont, DefineFontName};
+use swf_types::{EmSquareSize, Glyph, Movie, ShapeStyles, Tag};
+
+/// Entry of the font manifest (`fonts.json`)
+#[derive(Debug, Serialize)]
+pub(crate) struct FontInfo {
+  /// Character id
+  pub id: u16,
+  pub tag_index: usize,
+  /// Path of the font file, relative to the output directory
+  pub file: String,
+  /// `truetype` or `opentype`
+  pub format: &'static str,
+  pub name: String,
+  pub bold: bool,
+  pub italic: bool,
+  /// Number of glyphs, without the `.notdef` glyph added to TrueType fonts
+  pub glyphs: usize,
+}
+
+pub(crate) struct Font {
+  pub info: FontInfo,
+  pub data: Vec<u8>,
+}
+
+/// Font tag with embedded outlines
+pub(crate) enum FontTag<'a> {
+  /// `DefineFont2` or `DefineFont3`
+  Font(&'a DefineFont),
+  /// `DefineFont4`
+  Cff(&'a DefineCffFont),
+}
+
+/// Returns the `DefineFont2`, `DefineFont3` and `DefineFont4` tags of the movie, with the `DefineFontName` tags by
+/// font id.
+pub(crate) fn find_fonts(movie: &Movie) -> (Vec<(usize, FontTag<'_>)>, HashMap<u16, &DefineFontName>) {
+  let mut fonts = Vec::new();
+  let mut names = HashMap::new();
+  for (tag_index, tag) in movie.tags.iter().enumerate() {
+    match tag {
+      Tag::DefineFont(tag) => fonts.push((tag_index, FontTag::Font(tag))),
+      Tag::DefineCffFont(tag) => fonts.push((tag_index, FontTag::Cff(tag))),
+      Tag::DefineFontName(tag) => {
+        names.insert(tag.font_id, tag);
+      }
+      _ => {}
+    }
+  }
+  (fonts, names)
+}
+
+/// Converts a font tag to a font file.
+///
+/// The glyphs of `DefineFont2` and `DefineFont3` tags are converted to a TrueType font, and the CFF font of
+/// `DefineFont4` tags is written as-is. The name of the `DefineFontName` tag is used when available.
+pub(crate) fn export_font(tag_index: usize, tag: &FontTag, name: Option<&DefineFontName>) -> Result<Font, String> {
+  match tag {
+    FontTag::Font(tag) => {
+      let glyphs = match &tag.glyphs {
+        Some(glyphs) if !glyphs.is_empty() => glyphs,
+        _ => return Err(String::from("No glyph outlines (device font)")),
+      };
+      let family = font_family(name.map_or(&tag.font_name, |name| &name.name), tag.id);
+      let copyright = name.map_or("", |name| &name.copyright);
+      Ok(Font {
+        info: FontInfo {
+          id: tag.id,
+          tag_index,
+          file: format!("fonts/{}.ttf", tag.id),
+          format: "truetype",
+          name: family.clone(),
+          bold: tag.is_bold,
+          italic: tag.is_italic,
+          glyphs: glyphs.len(),
+        },
+        data: TrueTypeFont::new(tag, glyphs).write(&family, copyright),
+      })
+    }
+    FontTag::Cff(tag) => {
+      let data = match &tag.data {
+        Some(data) => data,
+        None => return Err(String::from("No font data (device font)")),
+      };
+      Ok(Font {
+        info: FontInfo {
+          id: tag.id,
+          tag_index,
+          file: format!("fonts/{}.otf", tag.id),
+          format: "opentype",
+          name: font_family(name.map_or(&tag.font_name, |name| &name.name), tag.id),
+          bold: tag.is_bold,
+          italic: tag.is_italic,
+          glyphs: 0,
+        },
+        data: data.clone(),
+      })
+    }
+  }
+}
+
+fn font_family(name: &str, id: u16) -> String {
+  let name = name.trim_end_matches('\0').trim();
+  if name.is_empty() {
+    format!("Font {}", id)
+  } else {
+    String::from(name)
+  }
+}
+
+/// Units per em of the TrueType fonts: the EM square of `DefineFont2` glyphs (`DefineFont3` glyphs use 20 times
+/// more precise units)
+const UNITS_PER_EM: u16 = 1024;
+
+/// Glyph converted to TrueType units (y axis pointing up)
+struct TrueTypeGlyph {
+  /// Points of the contours, with the on-curve flag
+  contours: Vec<Vec<(i16, i16, bool)>>,
+  /// `(x_min, y_min, x_max, y_max)`, computed from the points (`(0, 0, 0, 0)` for empty glyphs)
+  bounds: (i16, i16, i16, i16),
+  advance: u16,
+}
+
+/// TrueType font built from a `DefineFont2` or `DefineFont3` tag.
+///
+/// SWF glyph indices are shifted by one for the `.notdef` glyph.
+struct TrueTypeFont {
+  glyphs: Vec<TrueTypeGlyph>,
+  /// Character codes with their glyph index, sorted by code
+  codes: Vec<(u16, u16)>,
+  /// Kerning pairs of glyph indices, sorted
+  kerning: Vec<(u16, u16, i16)>,
+  ascent: i16,
+  descent: i16,
+  leading: i16,
+  bold: bool,
+  italic: bool,
+}
+
+impl TrueTypeFont {
+  fn new(tag: &DefineFont, glyphs: &[Glyph]) -> Self {
+    let scale = match tag.em_square_size {
+      EmSquareSize::EmSquareSize1024 => 1.0,
+      EmSquareSize::EmSquareSize20480 => 20.0,
+    };
+    let units = |value: f64| (value / scale).round() as i16;
+    let layout = tag.layout.as_ref();
+
+    let mut ttf_glyphs = vec![TrueTypeGlyph {
+      contours: Vec::new(),
+      bounds: (0, 0, 0, 0),
+      advance: UNITS_PER_EM / 2,
+    }];
+    for (index, glyph) in glyphs.iter().enumerate() {
+      let contours = glyph_contours(glyph, scale);
+      let bounds = contours_bounds(&contours);
+      // Without layout, the right side bearing of the glyphs is their left side bearing
+      let advance = match layout.and_then(|layout| layout.advances.get(index)) {
+        Some(advance) => units(*advance as f64).max(0) as u16,
+        None => (bounds.2 as i32 + bounds.0.max(0) as i32).max(0) as u16,
+      };
+      ttf_glyphs.push(TrueTypeGlyph {
+        contours,
+        bounds,
+        advance,
+      });
+    }
+
+    let mut codes: Vec<(u16, u16)> = Vec::new();
+    let mut glyph_by_code: HashMap<u16, u16> = HashMap::new();
+    if let Some(code_units) = &tag.code_units {
+      for (index, code) in code_units.iter().enumerate().take(glyphs.len()) {
+        if *code != 0xffff && !glyph_by_code.contains_key(code) {
+          glyph_by_code.insert(*code, index as u16 + 1);
+          codes.push((*code, index as u16 + 1));
+        }
+      }
+    }
+    codes.sort_unstable();
+
+    // Kerning records use character codes
+    let mut kerning: Vec<(u16, u16, i16)> = Vec::new();
+    let mut kerning_pairs: HashSet<(u16, u16)> = HashSet::new();
+    if let Some(layout) = layout {
+      for record in layout.kerning.iter() {
+        if let (Some(left), Some(right)) = (glyph_by_code.get(&record.left), glyph_by_code.get(&record.right)) {
+          let adjustment = units(record.adjustment as f64);
+          if adjustment != 0 && kerning_pairs.insert((*left, *right)) {
+            kerning.push((*left, *right, adjustment));
+          }
+        }
+      }
+    }
+    kerning.sort_unstable();
+
+    let (ascent, descent, leading) = match layout {
+      Some(layout) => (
+        units(layout.ascent as f64),
+        units(layout.descent as f64),
+        units(layout.leading as f64),
+      ),
+      None => (
+        ttf_glyphs.iter().map(|glyph| glyph.bounds.3).max().unwrap_or(0),
+        -ttf_glyphs.iter().map(|glyph| glyph.bounds.1).min().unwrap_or(0),
+        0,
+      ),
+    };
+
+    TrueTypeFont {
+      glyphs: ttf_glyphs,
+      codes,
+      kerning,
+      ascent,
+      descent,
+      leading,
+      bold: tag.is_bold,
+      italic: tag.is_italic,
+    }
+  }
+
+  /// Writes the font file, with the `OS/2`, `cmap`, `glyf`, `head`, `hhea`, `hmtx`, `kern` (when there are kerning
+  /// pairs), `loca`, `maxp`, `name` and `post` tables.
+  fn write(&self, family: &str, copyright: &str) -> Vec<u8> {
+    let (glyf, loca) = self.glyf_loca();
+    let mut tables: Vec<([u8; 4], Vec<u8>)> = vec![
+      (*b"OS/2", self.os2()),
+      (*b"cmap", self.cmap()),
+      (*b"glyf", glyf),
+      (*b"head", self.head()),
+      (*b"hhea", self.hhea()),
+      (*b"hmtx", self.hmtx()),
+    ];
+    if !self.kerning.is_empty() {
+      tables.push((*b"kern", self.kern()));
+    }
+    tables.push((*b"loca", loca));
+    tables.push((*b"maxp", self.maxp()));
+    tables.push((*b"name", self.name(family, copyright)));
+    tables.push((*b"post", self.post()));
+
+    let mut out = Vec::new();
+    let (search_range, entry_selector, range_shift) = binary_search_header(tables.len() as u16, 16);
+    put_u32(&mut out, 0x0001_0000);
+    put_u16(&mut out, tables.len() as u16);
+    put_u16(&mut out, search_range);
+    put_u16(&mut out, entry_selector);
+    put_u16(&mut out, range_shift);
+    let mut offset = 12 + 16 * tables.len();
+    for (tag, data) in tables.iter() {
+      out.extend_from_slice(tag);
+      put_u32(&mut out, checksum(data));
+      put_u32(&mut out, offset as u32);
+      put_u32(&mut out, data.len() as u32);
+      offset += padded_len(data.len());
+    }
+    let mut head_offset = 0;
+    for (tag, data) in tables.iter() {
+      if tag == b"head" {
+        head_offset = out.len();
+      }
+      out.extend_from_slice(data);
+      out.resize(padded_len(out.len()), 0);
+    }
+    // `checkSumAdjustment` of the `head` table
+    let adjustment = 0xb1b0_afbau32.wrapping_sub(checksum(&out));
+    out[head_offset + 8..head_offset + 12].copy_from_slice(&adjustment.to_be_bytes());
+    out
+  }
+
+  fn bounds(&self) -> (i16, i16, i16, i16) {
+    let glyphs = self.glyphs.iter().filter(|glyph| !glyph.contours.is_empty());
+    glyphs
+      .fold(None, |acc: Option<(i16, i16, i16, i16)>, glyph| {
+        let b = glyph.bounds;
+        Some(match acc {
+          None => b,
+          Some(a) => (a.0.min(b.0), a.1.min(b.1), a.2.max(b.2), a.3.max(b.3)),
+        })
+      })
+      .unwrap_or((0, 0, 0, 0))
+  }
+
+  fn os2(&self) -> Vec<u8> {
+    let advances: Vec<u32> = self.glyphs[1..]
+      .iter()
+      .map(|glyph| glyph.advance as u32)
+      .filter(|advance| *advance > 0)
+      .collect();
+    let average_width = if advances.is_empty() {
+      0
+    } else {
+      advances.iter().sum::<u32>() / advances.len() as u32
+    };
+    let bounds = self.bounds();
+    let em = UNITS_PER_EM as i16;
+    let mut out = Vec::new();
+    put_u16(&mut out, 4);
+    put_i16(&mut out, average_width as i16);
+    put_u16(&mut out, if self.bold { 700 } else { 400 });
+    // Medium width, installable embedding
+    put_u16(&mut out, 5);
+    put_u16(&mut out, 0);
+    // Subscript, superscript and strikeout metrics
+    for value in &[
+      em * 2 / 3,
+      em * 2 / 3,
+      0,
+      em / 7,
+      em * 2 / 3,
+      em * 2 / 3,
+      0,
+      em * 3 / 7,
+      em / 20,
+      em / 4,
+    ] {
+      put_i16(&mut out, *value);
+    }
+    // Family class and PANOSE classification
+    out.extend_from_slice(&[0; 12]);
+    // Unicode ranges
+    out.extend_from_slice(&[0; 16]);
+    out.extend_from_slice(b"    ");
+    let mut selection = 0;
+    if self.italic {
+      selection |= 0x0001;
+    }
+    if self.bold {
+      selection |= 0x0020;
+    }
+    if !self.italic && !self.bold {
+      selection |= 0x0040;
+    }
+    put_u16(&mut out, selection);
+    put_u16(&mut out, self.codes.first().map_or(0, |(code, _)| *code));
+    put_u16(&mut out, self.codes.last().map_or(0, |(code, _)| *code));
+    put_i16(&mut out, self.ascent);
+    put_i16(&mut out, -self.descent);
+    put_i16(&mut out, self.leading.max(0));
+    put_u16(&mut out, self.ascent.max(bounds.3).max(0) as u16);
+    put_u16(&mut out, self.descent.max(-bounds.1).max(0) as u16);
+    // Latin 1 code page
+    put_u32(&mut out, 1);
+    put_u32(&mut out, 0);
+    // x-height and cap height (unknown), default and break characters, maximum context
+    put_i16(&mut out, 0);
+    put_i16(&mut out, 0);
+    put_u16(&mut out, 0);
+    put_u16(&mut out, 0x20);
+    put_u16(&mut out, if self.kerning.is_empty() { 1 } else { 2 });
+    out
+  }
+
+  /// Unicode character map, using a format 4 subtable for the Basic Multilingual Plane, or a format 12 subtable when
+  /// there are too many segments.
+  fn cmap(&self) -> Vec<u8> {
+    // Runs of consecutive codes mapped to consecutive glyphs: `(start code, end code, start glyph)`
+    let mut runs: Vec<(u16, u16, u16)> = Vec::new();
+    for (code, glyph) in self.codes.iter().copied() {
+      match runs.last_mut() {
+        Some(run) if run.1 as u32 + 1 == code as u32 && run.2 as u32 + (code - run.0) as u32 == glyph as u32 => {
+          run.1 = code
+        }
+        _ => runs.push((code, code, glyph)),
+      }
+    }
+
+    let mut subtable = Vec::new();
+    let encodings: [(u16, u16); 2];
+    let seg_count = runs.len() + 1;
+    if 16 + 8 * seg_count <= 0xffff {
+      encodings = [(0, 3), (3, 1)];
+      let (search_range, entry_selector, range_shift) = binary_search_header(seg_count as u16, 2);
+      put_u16(&mut subtable, 4);
+      put_u16(&mut subtable, (16 + 8 * seg_count) as u16);
+      put_u16(&mut subtable, 0);
+      put_u16(&mut subtable, seg_count as u16 * 2);
+      put_u16(&mut subtable, search_range);
+      put_u16(&mut subtable, entry_selector);
+      put_u16(&mut subtable, range_shift);
+      for (_, end, _) in runs.iter() {
+        put_u16(&mut subtable, *end);
+      }
+      put_u16(&mut subtable, 0xffff);
+      put_u16(&mut subtable, 0);
+      for (start, _, _) in runs.iter() {
+        put_u16(&mut subtable, *start);
+      }
+      put_u16(&mut subtable, 0xffff);
+      for (start, _, glyph) in runs.iter() {
+        put_u16(&mut subtable, glyph.wrapping_sub(*start));
+      }
+      put_u16(&mut subtable, 1);
+      for _ in 0..seg_count {
+        put_u16(&mut subtable, 0);
+      }
+    } else {
+      encodings = [(0, 4), (3, 10)];
+      put_u16(&mut subtable, 12);
+      put_u16(&mut subtable, 0);
+      put_u32(&mut subtable, 16 + 12 * runs.len() as u32);
+      put_u32(&mut subtable, 0);
+      put_u32(&mut subtable, runs.len() as u32);
+      for (start, end, glyph) in runs.iter() {
+        put_u32(&mut subtable, *start as u32);
+        put_u32(&mut subtable, *end as u32);
+        put_u32(&mut subtable, *glyph as u32);
+      }
+    }
+
+    let mut out = Vec::new();
+    put_u16(&mut out, 0);
+    put_u16(&mut out, encodings.len() as u16);
+    for (platform, encoding) in encodings.iter() {
+      put_u16(&mut out, *platform);
+      put_u16(&mut out, *encoding);
+      put_u32(&mut out, 4 + 8 * encodings.len() as u32);
+    }
+    out.extend_from_slice(&subtable);
+    out
+  }
+
+  /// Returns the `glyf` table and the long `loca` table.
+  fn glyf_loca(&self) -> (Vec<u8>, Vec<u8>) {
+    let mut glyf = Vec::new();
+    let mut loca = Vec::new();
+    for glyph in self.glyphs.iter() {
+      put_u32(&mut loca, glyf.len() as u32);
+      if glyph.contours.is_empty() {
+        continue;
+      }
+      put_i16(&mut glyf, glyph.contours.len() as i16);
+      put_i16(&mut glyf, glyph.bounds.0);
+      put_i16(&mut glyf, glyph.bounds.1);
+      put_i16(&mut glyf, glyph.bounds.2);
+      put_i16(&mut glyf, glyph.bounds.3);
+      let mut end = 0;
+      for contour in glyph.contours.iter() {
+        end += contour.len();
+        put_u16(&mut glyf, (end - 1) as u16);
+      }
+      // No instructions
+      put_u16(&mut glyf, 0);
+      let points: Vec<&(i16, i16, bool)> = glyph.contours.iter().flatten().collect();
+      // Coordinates are written as 16-bit deltas
+      for (_, _, on_curve) in points.iter() {
+        glyf.push(if *on_curve { 0x01 } else { 0x00 });
+      }
+      let mut previous = 0i16;
+      for (x, _, _) in points.iter() {
+        put_i16(&mut glyf, x.wrapping_sub(previous));
+        previous = *x;
+      }
+      let mut previous = 0i16;
+      for (_, y, _) in points.iter() {
+        put_i16(&mut glyf, y.wrapping_sub(previous));
+        previous = *y;
+      }
+      glyf.resize(padded_len(glyf.len()), 0);
+    }
+    put_u32(&mut loca, glyf.len() as u32);
+    (glyf, loca)
+  }
+
+  fn head(&self) -> Vec<u8> {
+    let bounds = self.bounds();
+    let mut out = Vec::new();
+    put_u32(&mut out, 0x0001_0000);
+    // Font revision 1.0, checksum adjustment (set once the file is written) and magic number
+    put_u32(&mut out, 0x0001_0000);
+    put_u32(&mut out, 0);
+    put_u32(&mut out, 0x5f0f_3cf5);
+    // Baseline at `y = 0`, integer scaling
+    put_u16(&mut out, 0x0009);
+    put_u16(&mut out, UNITS_PER_EM);
+    // Creation and modification dates, left unset for reproducible files
+    out.extend_from_slice(&[0; 16]);
+    put_i16(&mut out, bounds.0);
+    put_i16(&mut out, bounds.1);
+    put_i16(&mut out, bounds.2);
+    put_i16(&mut out, bounds.3);
+    let mut mac_style = 0;
+    if self.bold {
+      mac_style |= 0x0001;
+    }
+    if self.italic {
+      mac_style |= 0x0002;
+    }
+    put_u16(&mut out, mac_style);
+    // Smallest readable size, mixed directional glyphs, long `loca` offsets, current glyph data format
+    put_u16(&mut out, 8);
+    put_i16(&mut out, 2);
+    put_i16(&mut out, 1);
+    put_i16(&mut out, 0);
+    out
+  }
+
+  fn hhea(&self) -> Vec<u8> {
+    let mut out = Vec::new();
+    put_u32(&mut out, 0x0001_0000);
+    put_i16(&mut out, self.ascent);
+    put_i16(&mut out, -self.descent);
+    put_i16(&mut out, self.leading.max(0));
+    put_u16(
+      &mut out,
+      self.glyphs.iter().map(|glyph| glyph.advance).max().unwrap_or(0),
+    );
+    let outlines: Vec<&TrueTypeGlyph> = self.glyphs.iter().filter(|glyph| !glyph.contours.is_empty()).collect();
+    put_i16(&mut out, outlines.iter().map(|glyph| glyph.bounds.0).min().unwrap_or(0));
+    put_i16(
+      &mut out,
+      outlines
+        .iter()
+        .map(|glyph| (glyph.advance as i32 - glyph.bounds.2 as i32) as i16)
+        .min()
+        .unwrap_or(0),
+    );
+    put_i16(&mut out, outlines.iter().map(|glyph| glyph.bounds.2).max().unwrap_or(0));
+    // Vertical caret
+    put_i16(&mut out, 1);
+    put_i16(&mut out, 0);
+    put_i16(&mut out, 0);
+    out.extend_from_slice(&[0; 10]);
+    put_u16(&mut out, self.glyphs.len() as u16);
+    out
+  }
+
+  fn hmtx(&self) -> Vec<u8> {
+    let mut out = Vec::new();
+    for glyph in self.glyphs.iter() {
+      put_u16(&mut out, glyph.advance);
+      put_i16(&mut out, glyph.bounds.0);
+    }
+    out
+  }
+
+  /// Horizontal kerning table (version 0, with a single format 0 subtable)
+  fn kern(&self) -> Vec<u8> {
+    // The length of the subtable is a 16-bit value
+    let pairs = &self.kerning[..self.kerning.len().min((0xffff - 14) / 6)];
+    let (search_range, entry_selector, range_shift) = binary_search_header(pairs.len() as u16, 6);
+    let mut out = Vec::new();
+    put_u16(&mut out, 0);
+    put_u16(&mut out, 1);
+    put_u16(&mut out, 0);
+    put_u16(&mut out, (14 + 6 * pairs.len()) as u16);
+    put_u16(&mut out, 0x0001);
+    put_u16(&mut out, pairs.len() as u16);
+    put_u16(&mut out, search_range);
+    put_u16(&mut out, entry_selector);
+    put_u16(&mut out, range_shift);
+    for (left, right, value) in pairs.iter() {
+      put_u16(&mut out, *left);
+      put_u16(&mut out, *right);
+      put_i16(&mut out, *value);
+    }
+    out
+  }
+
+  fn maxp(&self) -> Vec<u8> {
+    let mut out = Vec::new();
+    put_u32(&mut out, 0x0001_0000);
+    put_u16(&mut out, self.glyphs.len() as u16);
+    let points = self
+      .glyphs
+      .iter()
+      .map(|glyph| glyph.contours.iter().map(Vec::len).sum::<usize>());
+    put_u16(&mut out, points.max().unwrap_or(0) as u16);
+    put_u16(
+      &mut out,
+      self.glyphs.iter().map(|glyph| glyph.contours.len()).max().unwrap_or(0) as u16,
+    );
+    // No composite glyphs, no instructions
+    put_u16(&mut out, 0);
+    put_u16(&mut out, 0);
+    put_u16(&mut out, 2);
+    out.extend_from_slice(&[0; 16]);
+    out
+  }
+
+  /// Naming table, with Windows Unicode names
+  fn name(&self, family: &str, copyright: &str) -> Vec<u8> {
+    let style = match (self.bold, self.italic) {
+      (false, false) => "Regular",
+      (true, false) => "Bold",
+      (false, true) => "Italic",
+      (true, true) => "Bold Italic",
+    };
+    let full_name = if self.bold || self.italic {
+      format!("{} {}", family, style)
+    } else {
+      String::from(family)
+    };
+    let postscript_name: String = format!("{}-{}", family, style)
+      .chars()
+      .filter(|c| c.is_ascii_graphic() && !"[](){}<>/%".contains(*c))
+      .take(63)
+      .collect();
+    let mut names: Vec<(u16, &str)> = Vec::new();
+    if !copyright.is_empty() {
+      names.push((0, copyright));
+    }
+    names.extend_from_slice(&[
+      (1, family),
+      (2, style),
+      (3, full_name.as_str()),
+      (4, full_name.as_str()),
+      (6, postscript_name.as_str()),
+    ]);
+
+    let mut out = Vec::new();
+    let mut strings = Vec::new();
+    put_u16(&mut out, 0);
+    put_u16(&mut out, names.len() as u16);
+    put_u16(&mut out, 6 + 12 * names.len() as u16);
+    for (name_id, value) in names {
+      let encoded: Vec<u8> = value
+        .encode_utf16()
+        .flat_map(|unit| unit.to_be_bytes().to_vec())
+        .collect();
+      // Windows platform, Unicode BMP encoding, English (United States)
+      put_u16(&mut out, 3);
+      put_u16(&mut out, 1);
+      put_u16(&mut out, 0x0409);
+      put_u16(&mut out, name_id);
+      put_u16(&mut out, encoded.len() as u16);
+      put_u16(&mut out, strings.len() as u16);
+      strings.extend_from_slice(&encoded);
+    }
+    out.extend_from_slice(&strings);
+    out
+  }
+
+  /// PostScript table (version 3, without glyph names)
+  fn post(&self) -> Vec<u8> {
+    let mut out = Vec::new();
+    put_u32(&mut out, 0x0003_0000);
+    put_u32(&mut out, 0);
+    // Underline position and thickness
+    put_i16(&mut out, -(UNITS_PER_EM as i16) / 10);
+    put_i16(&mut out, UNITS_PER_EM as i16 / 20);
+    // Proportional font, no memory usage hints
+    out.extend_from_slice(&[0; 20]);
+    out
+  }
+}
+
+/// Converts the shape records of a glyph to TrueType contours.
+///
+/// The glyphs are filled with the even-odd rule, while TrueType uses the nonzero rule: the contours are oriented
+/// clockwise when they are inside an even number of other contours, and counterclockwise otherwise.
+fn glyph_contours(glyph: &Glyph, scale: f64) -> Vec<Vec<(i16, i16, bool)>> {
+  let styles = ShapeStyles {
+    fill: Vec::new(),
+    line: Vec::new(),
+  };
+  let edges: Vec<shapes::Edge> = shapes::layers(&styles, &glyph.records)
+    .into_iter()
+    .flat_map(|layer| layer.fills.into_iter().flatten())
+    .collect();
+  let point = |(x, y): shapes::Point| ((x as f64 / scale).round() as i16, (-y as f64 / scale).round() as i16);
+  let mut contours: Vec<Vec<(i16, i16, bool)>> = Vec::new();
+  for path in shapes::contours(&edges) {
+    let mut contour = Vec::new();
+    let (x, y) = point(edges[path.edges[0]].from);
+    contour.push((x, y, true));
+    for (i, edge) in path.edges.iter().map(|i| &edges[*i]).enumerate() {
+      if let Some(control) = edge.control {
+        let (x, y) = point(control);
+        contour.push((x, y, false));
+      }
+      // The contours are implicitly closed
+      if !(path.closed && i == path.edges.len() - 1) {
+        let (x, y) = point(edge.to);
+        contour.push((x, y, true));
+      }
+    }
+    if contour.len() >= 3 {
+      contours.push(contour);
+    }
+  }
+
+  let depths: Vec<usize> = contours
+    .iter()
+    .enumerate()
+    .map(|(i, contour)| {
+      let (x, y, _) = contour[0];
+      contours
+        .iter()
+        .enumerate()
+        .filter(|(j, other)| *j != i && contains(other, (x as f64, y as f64)))
+        .count()
+    })
+    .collect();
+  for (contour, depth) in contours.iter_mut().zip(depths) {
+    let clockwise = signed_area(contour) < 0.0;
+    if clockwise != (depth % 2 == 0) {
+      contour.reverse();
+    }
+  }
+  contours
+}
+
+fn contours_bounds(contours: &[Vec<(i16, i16, bool)>]) -> (i16, i16, i16, i16) {
+  let mut points = contours.iter().flatten();
+  let (x, y, _) = match points.next() {
+    Some(point) => *point,
+    None => return (0, 0, 0, 0),
+  };
+  points.fold((x, y, x, y), |(x_min, y_min, x_max, y_max), (x, y, _)| {
+    (x_min.min(*x), y_min.min(*y), x_max.max(*x), y_max.max(*y))
+  })
+}
+
+/// Area of the polygon of the points of a contour, positive for counterclockwise contours
+fn signed_area(contour: &[(i16, i16, bool)]) -> f64 {
+  let mut area = 0.0;
+  for (i, (x0, y0, _)) in contour.iter().enumerate() {
+    let (x1, y1, _) = contour[(i + 1) % contour.len()];
+    area += *x0 as f64 * y1 as f64 - x1 as f64 * *y0 as f64;
+  }
+  area / 2.0
+}
+
+/// Tests if a point is inside the polygon of the points of a contour (even-odd rule).
+fn contains(contour: &[(i16, i16, bool)], (x, y): (f64, f64)) -> bool {
+  let mut inside = false;
+  for (i, (x0, y0, _)) in contour.iter().enumerate() {
+    let (x1, y1, _) = contour[(i + 1) % contour.len()];
+    let (x0, y0, x1, y1) = (*x0 as f64, *y0 as f64, x1 as f64, y1 as f64);
+    if (y0 > y) != (y1 > y) && x < x0 + (y - y0) * (x1 - x0) / (y1 - y0) {
+      inside = !inside;
+    }
+  }
+  inside
+}
+
+/// Returns the `searchRange`, `entrySelector` and `rangeShift` fields for `count` items of `size` bytes.
+fn binary_search_header(count: u16, size: u16) -> (u16, u16, u16) {
+  let mut entry_selector = 0;
+  while count >> (entry_selector + 1) > 0 {
+    entry_selector += 1;
+  }
+  let search_range = if count == 0 { 0 } else { (1 << entry_selector) * size };
+  (search_range, entry_selector, count * size - search_range)
+}
+
+fn checksum(data: &[u8]) -> u32 {
+  data.chunks(4).fold(0u32, |sum, chunk| {
+    let mut word = [0; 4];
+    word[..chunk.len()].copy_from_slice(chunk);
+    sum.wrapping_add(u32::from_be_bytes(word))
+  })
+}
+
+fn padded_len(len: usize) -> usize {
+  (len + 3) & !3
+}
+
+fn put_u16(out: &mut Vec<u8>, value: u16) {
+  out.extend_from_slice(&value.to_be_bytes());
+}
+
+fn put_i16(out: &mut Vec<u8>, value: i16) {
+  out.extend_from_slice(&value.to_be_bytes());
+}
+
+fn put_u32(out: &mut Vec<u8>, value: u32) {
+  out.extend_from_slice(&value.to_be_bytes());
+}
@@ -0,0 +1,354 @@
+//! Export of the bitmaps defined by the `DefineBits` and `DefineBitsLossless` families of tags.
+
+use serde::Serialize;
+use swf_types::tags::DefineBitmap;
+use swf_types::{ImageType, Movie, Tag};
+
+/// Entry of the image manifest (`images.json`)
+#[derive(Debug, Serialize)]
+pub(crate) struct ImageInfo {
+  /// Character id
+  pub id: u16,
+  pub tag_index: usize,
+  /// Path of the image file, relative to the output directory
+  pub file: String,
+  /// `jpeg`, `png` or `gif`
+  pub format: &'static str,
+  pub width: u16,
+  pub height: u16,
+  /// The image has an alpha channel (`DefineBitsLossless2` tags, and `DefineBitsJpeg3` and `DefineBitsJpeg4` tags with
+  /// alpha data)
+  pub alpha: bool,
+}
+
+pub(crate) struct Image {
+  pub info: ImageInfo,
+  pub data: Vec<u8>,
+}
+
+const PNG_START: [u8; 4] = [0x89, b'P', b'N', b'G'];
+const GIF_START: [u8; 4] = *b"GIF8";
+
+/// Returns the bitmap tags of the movie, with the data of the `DefineJpegTables` tag.
+pub(crate) fn find_bitmaps(movie: &Movie) -> (Vec<(usize, &DefineBitmap)>, Option<&[u8]>) {
+  let mut bitmaps = Vec::new();
+  let mut jpeg_tables = None;
+  for (tag_index, tag) in movie.tags.iter().enumerate() {
+    match tag {
+      Tag::DefineBitmap(tag) => bitmaps.push((tag_index, tag)),
+      // There is at most one `DefineJpegTables` tag, shared by all the `DefineBits` tags
+      Tag::DefineJpegTables(tag) if jpeg_tables.is_none() => jpeg_tables = Some(tag.data.as_slice()),
+      _ => {}
+    }
+  }
+  (bitmaps, jpeg_tables)
+}
+
+/// Converts a bitmap tag to an image file.
+///
+/// `DefineBits` images are merged with the JPEG tables, the alpha channel of `DefineBitsJpeg3` and
+/// `DefineBitsJpeg4` images is combined with the JPEG image into a PNG image, and lossless bitmaps are decoded to PNG
+/// images.
+pub(crate) fn export_image(tag_index: usize, tag: &DefineBitmap, jpeg_tables: Option<&[u8]>) -> Result<Image, String> {
+  let (format, data, alpha): (&'static str, Vec<u8>, &[u8]) = match tag.media_type {
+    ImageType::SwfPartialJpeg => match jpeg_tables {
+      Some(tables) => ("jpeg", normalize_jpeg(&[tables, &tag.data].concat()), &[]),
+      None => ("jpeg", normalize_jpeg(&tag.data), &[]),
+    },
+    ImageType::Jpeg => ("jpeg", normalize_jpeg(&tag.data), &[]),
+    ImageType::SwfJpeg3 => {
+      let (jpeg, alpha) = split_alpha(&tag.data, 4)?;
+      ("jpeg", normalize_jpeg(jpeg), alpha)
+    }
+    ImageType::SwfJpeg4 => {
+      let (jpeg, alpha) = split_alpha(&tag.data, 6)?;
+      ("jpeg", normalize_jpeg(jpeg), alpha)
+    }
+    ImageType::Png => ("png", embedded_image(&tag.data, &PNG_START)?.to_vec(), &[]),
+    ImageType::Gif => ("gif", embedded_image(&tag.data, &GIF_START)?.to_vec(), &[]),
+    ImageType::SwfBmp | ImageType::SwfAbmp => {
+      let alpha = tag.media_type == ImageType::SwfAbmp;
+      let (width, height, rgba) = decode_lossless(&tag.data, alpha)?;
+      return Ok(Image {
+        info: ImageInfo {
+          id: tag.id,
+          tag_index,
+          file: format!("images/{}.png", tag.id),
+          format: "png",
+          width,
+          height,
+          alpha,
+        },
+        data: encode_png(width, height, &rgba, alpha)?,
+      });
+    }
+  };
+
+  let (width, height) = match format {
+    "jpeg" => jpeg_size(&data).unwrap_or((tag.width, tag.height)),
+    _ => (tag.width, tag.height),
+  };
+  let mut info = ImageInfo {
+    id: tag.id,
+    tag_index,
+    file: format!("images/{}.{}", tag.id, if format == "jpeg" { "jpg" } else { format }),
+    format,
+    width,
+    height,
+    alpha: false,
+  };
+  if alpha.is_empty() {
+    return Ok(Image { info, data });
+  }
+
+  let (width, height, rgba) = combine_alpha(&data, alpha)?;
+  info.file = format!("images/{}.png", tag.id);
+  info.format = "png";
+  info.width = width;
+  info.height = height;
+  info.alpha = true;
+  Ok(Image {
+    info,
+    data: encode_png(width, height, &rgba, true)?,
+  })
+}
+
+/// Splits the data of `DefineBitsJpeg3` and `DefineBitsJpeg4` tags into the image and the zlib-compressed alpha
+/// channel. The data starts with the size of the image, followed by the deblocking filter for `DefineBitsJpeg4`.
+fn split_alpha(data: &[u8], header_len: usize) -> Result<(&[u8], &[u8]), String> {
+  if data.len() < header_len {
+    return Err(String::from("Truncated image header"));
+  }
+  let size = u32::from_le_bytes([data[0], data[1], data[2], data[3]]) as usize;
+  let data = &data[header_len..];
+  if size > data.len() {
+    return Err(format!("Image size out of bounds: {}", size));
+  }
+  Ok(data.split_at(size))
+}
+
+/// Returns the PNG or GIF image of a bitmap tag, skipping the header of `DefineBitsJpeg3` and `DefineBitsJpeg4` tags.
+fn embedded_image<'a>(data: &'a [u8], start: &[u8]) -> Result<&'a [u8], String> {
+  if data.starts_with(start) {
+    return Ok(data);
+  }
+  for header_len in [4, 6].iter() {
+    if let Ok((image, _)) = split_alpha(data, *header_len) {
+      if image.starts_with(start) {
+        return Ok(image);
+      }
+    }
+  }
+  Err(String::from("Invalid image signature"))
+}
+
+/// Rebuilds a well-formed JPEG stream: a single SOI marker, the segments (with the JPEG tables) and the scans.
+///
+/// Before SWF 8, images could start with an erroneous EOI/SOI pair (`FF D9 FF D8`), and `DefineBits` images are
+/// stored without their tables, ending the tables stream with an EOI marker and starting the image with an other SOI
+/// marker. All the SOI and EOI markers before the first scan are removed.
+fn normalize_jpeg(data: &[u8]) -> Vec<u8> {
+  let mut out = vec![0xff, 0xd8];
+  let mut i = 0;
+  while i < data.len() {
+    if data[i] != 0xff || i + 1 >= data.len() {
+      // Malformed stream: keep the rest as-is
+      out.extend_from_slice(&data[i..]);
+      break;
+    }
+    let marker = data[i + 1];
+    match marker {
+      // Fill byte
+      0xff => i += 1,
+      0xd8 | 0xd9 => i += 2,
+      0x01 | 0xd0..=0xd7 => {
+        out.extend_from_slice(&data[i..i + 2]);
+        i += 2;
+      }
+      // Start of scan: the entropy-coded data and the following segments are copied as-is
+      0xda => {
+        out.extend_from_slice(&data[i..]);
+        break;
+      }
+      _ => {
+        let end = match data.get(i + 2..i + 4) {
+          Some(len) => (i + 2 + u16::from_be_bytes([len[0], len[1]]) as usize).min(data.len()),
+          None => data.len(),
+        };
+        out.extend_from_slice(&data[i..end]);
+        i = end;
+      }
+    }
+  }
+  if !out.ends_with(&[0xff, 0xd9]) {
+    out.extend_from_slice(&[0xff, 0xd9]);
+  }
+  out
+}
+
+/// Reads the dimensions of a JPEG image from its start of frame segment.
+fn jpeg_size(data: &[u8]) -> Option<(u16, u16)> {
+  let mut i = 2;
+  while i + 4 <= data.len() && data[i] == 0xff {
+    let marker = data[i + 1];
+    let len = u16::from_be_bytes([data[i + 2], data[i + 3]]) as usize;
+    match marker {
+      0xc0..=0xcf if marker != 0xc4 && marker != 0xc8 && marker != 0xcc => {
+        let frame = data.get(i + 5..i + 9)?;
+        let height = u16::from_be_bytes([frame[0], frame[1]]);
+        let width = u16::from_be_bytes([frame[2], frame[3]]);
+        return Some((width, height));
+      }
+      0xda => return None,
+      _ => i += 2 + len,
+    }
+  }
+  None
+}
+
+/// Decodes a JPEG image and combines it with its zlib-compressed alpha channel, returning RGBA pixels.
+///
+/// The colors of the JPEG image are premultiplied by the alpha channel: they are clamped to the alpha value (as Flash
+/// Player does for images with invalid colors) and converted to straight alpha.
+fn combine_alpha(jpeg: &[u8], alpha: &[u8]) -> Result<(u16, u16, Vec<u8>), String> {
+  let mut decoder = jpeg_decoder::Decoder::new(jpeg);
+  let pixels = decoder
+    .decode()
+    .map_err(|e| format!("Failed to decode JPEG image: {}", e))?;
+  let info = decoder.info().ok_or_else(|| String::from("Missing JPEG image info"))?;
+  let rgb: Vec<u8> = match info.pixel_format {
+    jpeg_decoder::PixelFormat::L8 => pixels.iter().flat_map(|l| vec![*l; 3]).collect(),
+    jpeg_decoder::PixelFormat::RGB24 => pixels,
+    jpeg_decoder::PixelFormat::CMYK32 => return Err(String::from("Unsupported CMYK JPEG image")),
+  };
+  let alpha = inflate::inflate_bytes_zlib(alpha).map_err(|e| format!("Failed to inflate alpha channel: {}", e))?;
+  let pixel_count = info.width as usize * info.height as usize;
+  if alpha.len() < pixel_count {
+    return Err(format!(
+      "Alpha channel too short: expected {} bytes, got {}",
+      pixel_count,
+      alpha.len()
+    ));
+  }
+
+  let mut rgba = Vec::with_capacity(pixel_count * 4);
+  for (color, a) in rgb.chunks(3).zip(alpha.iter()) {
+    for c in color {
+      rgba.push(unmultiply((*c).min(*a), *a));
+    }
+    rgba.push(*a);
+  }
+  Ok((info.width, info.height, rgba))
+}
+
+/// Decodes the data of `DefineBitsLossless` and `DefineBitsLossless2` tags, returning RGBA pixels.
+///
+/// The data starts with the format, the dimensions and, for colormapped images, the size of the color table minus 1.
+/// The rest is zlib-compressed: the color table (RGB, or premultiplied RGBA with alpha) followed by the pixels.
+/// The rows of colormapped and 15-bit images are padded to a multiple of 4 bytes.
+fn decode_lossless(data: &[u8], alpha: bool) -> Result<(u16, u16, Vec<u8>), String> {
+  if data.len() < 5 {
+    return Err(String::from("Truncated bitmap header"));
+  }
+  let format = data[0];
+  let width = u16::from_le_bytes([data[1], data[2]]);
+  let height = u16::from_le_bytes([data[3], data[4]]);
+  if width == 0 || height == 0 {
+    return Err(format!("Empty bitmap: {}x{}", width, height));
+  }
+  let (color_count, compressed) = match format {
+    3 => match data.get(5) {
+      Some(size) => (*size as usize + 1, &data[6..]),
+      None => return Err(String::from("Truncated bitmap header")),
+    },
+    _ => (0, &data[5..]),
+  };
+  let data = inflate::inflate_bytes_zlib(compressed).map_err(|e| format!("Failed to inflate bitmap data: {}", e))?;
+  let (width_, height_) = (width as usize, height as usize);
+  let mut rgba = Vec::with_capacity(width_ * height_ * 4);
+  match format {
+    3 => {
+      let color_size = if alpha { 4 } else { 3 };
+      let table_size = color_count * color_size;
+      let row_size = (width_ + 3) & !3;
+      if data.len() < table_size + row_size * height_ {
+        return Err(String::from("Truncated colormapped bitmap data"));
+      }
+      let (table, pixels) = data.split_at(table_size);
+      for row in pixels.chunks(row_size).take(height_) {
+        for index in &row[..width_] {
+          let index = *index as usize;
+          match table.get(index * color_size..(index + 1) * color_size) {
+            Some(&[r, g, b]) => rgba.extend_from_slice(&[r, g, b, 255]),
+            Some(&[r, g, b, a]) => rgba.extend_from_slice(&[unmultiply(r, a), unmultiply(g, a), unmultiply(b, a), a]),
+            // Indices out of the color table are transparent black
+            _ => rgba.extend_from_slice(&[0, 0, 0, 0]),
+          }
+        }
+      }
+    }
+    4 if !alpha => {
+      let row_size = (width_ * 2 + 3) & !3;
+      if data.len() < row_size * height_ {
+        return Err(String::from("Truncated 15-bit bitmap data"));
+      }
+      for row in data.chunks(row_size).take(height_) {
+        for pixel in row[..width_ * 2].chunks(2) {
+          let pixel = u16::from_be_bytes([pixel[0], pixel[1]]);
+          let component = |shift: u16| (((pixel >> shift) & 0x1f) * 255 + 15) / 31;
+          rgba.extend_from_slice(&[component(10) as u8, component(5) as u8, component(0) as u8, 255]);
+        }
+      }
+    }
+    5 => {
+      if data.len() < width_ * height_ * 4 {
+        return Err(String::from("Truncated 32-bit bitmap data"));
+      }
+      for pixel in data.chunks(4).take(width_ * height_) {
+        // The first byte is the alpha of `DefineBitsLossless2` tags, and is ignored by `DefineBitsLossless` tags
+        let a = if alpha { pixel[0] } else { 255 };
+        rgba.extend_from_slice(&[
+          unmultiply(pixel[1], a),
+          unmultiply(pixel[2], a),
+          unmultiply(pixel[3], a),
+          a,
+        ]);
+      }
+    }
+    format => return Err(format!("Unsupported bitmap format: {}", format)),
+  }
+  Ok((width, height, rgba))
+}
+
+/// Converts a color component premultiplied by `alpha` to straight alpha.
+fn unmultiply(color: u8, alpha: u8) -> u8 {
+  match alpha {
+    0 => 0,
+    255 => color,
+    alpha => ((color as u32 * 255 + alpha as u32 / 2) / alpha as u32).min(255) as u8,
+  }
+}
+
+/// Encodes RGBA pixels as a PNG image, dropping the alpha channel of opaque images.
+fn encode_png(width: u16, height: u16, rgba: &[u8], alpha: bool) -> Result<Vec<u8>, String> {
+  let rgb: Vec<u8>;
+  let (color_type, pixels) = if alpha {
+    (png::ColorType::RGBA, rgba)
+  } else {
+    rgb = rgba.chunks(4).flat_map(|pixel| pixel[..3].to_vec()).collect();
+    (png::ColorType::RGB, rgb.as_slice())
+  };
+  let mut out = Vec::new();
+  {
+    let mut encoder = png::Encoder::new(&mut out, width as u32, height as u32);
+    encoder.set_color(color_type);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder
+      .write_header()
+      .map_err(|e| format!("Failed to encode PNG image: {}", e))?;
+    writer
+      .write_image_data(pixels)
+      .map_err(|e| format!("Failed to encode PNG image: {}", e))?;
+  }
+  Ok(out)
+}
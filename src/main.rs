@@ -1,18 +1,31 @@
-use crate::dump::{find_avm1, Avm1Location};
+use crate::dump::{clip_action_dir_name, find_abc, find_avm1, find_avm1_mut, find_symbols, Avm1Location};
 use avm1_parser::parse_cfg;
 use avm1_types::cfg::Cfg;
 use clap::Clap;
 use serde::ser::Serialize;
 use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
 use std::ffi::OsString;
 use std::fs;
 use std::io::Write;
 use std::path::{Path, PathBuf};
 use swf_parser::parse_swf;
 use swf_parser::streaming::movie::parse_swf_signature;
+use swf_types::Movie;
 
+mod avm1;
+mod avm2;
 mod dump;
+mod endpoints;
+mod fonts;
+mod images;
+mod morph_shapes;
 mod report;
+mod shapes;
+mod sounds;
+mod source_paths;
+mod structure;
+mod texts;
 
 #[derive(Debug, Clap)]
 #[clap(author = "Charles \"Demurgos\" Samborski")]
@@ -26,15 +39,156 @@ struct CliArgs {
 
 #[derive(Debug, Clap)]
 enum CliCommand {
+  /// Process AVM1 bytecode.
+  #[clap(name = "avm1")]
+  Avm1(Avm1Args),
+
+  /// Process AVM2 bytecode (ABC files).
+  #[clap(name = "avm2")]
+  Avm2(Avm2Args),
+
+  /// List the ActionScript 3 classes of a SWF file and the characters linked to them.
+  #[clap(name = "classes")]
+  Classes(ClassesArgs),
+
   /// Extract all data from a SWF file.
   #[clap(name = "dump")]
   Dump(DumpArgs),
 
+  /// List the URLs and external calls (`fscommand`, `ExternalInterface`, `LocalConnection`) of the scripts.
+  #[clap(name = "endpoints")]
+  Endpoints(EndpointsArgs),
+
+  /// Decompile the AVM1 bytecode of a SWF file to ActionScript 2, or its ABC files to ActionScript 3.
+  #[clap(name = "decompile")]
+  Decompile(DecompileArgs),
+
   /// Parse a SWF file.
   #[clap(name = "parse")]
   Parse(ParseArgs),
 }
 
+/// Arguments to the `avm1` subcommand.
+#[derive(Debug, Clap)]
+struct Avm1Args {
+  #[clap(subcommand)]
+  command: Avm1Command,
+}
+
+#[derive(Debug, Clap)]
+enum Avm1Command {
+  /// Assemble a textual disassembly (such as `main.avm1.txt`) into an AVM1 buffer.
+  #[clap(name = "assemble")]
+  Assemble(AssembleArgs),
+
+  /// Print the control flow graph of an AVM1 buffer.
+  #[clap(name = "cfg")]
+  Cfg(CfgArgs),
+
+  /// Simplify an obfuscated AVM1 buffer: fold constants, remove dead branches and collapse jump chains.
+  #[clap(name = "deobfuscate")]
+  Deobfuscate(DeobfuscateArgs),
+
+  /// Check the AVM1 buffers of a SWF file for malformed or suspicious bytecode.
+  #[clap(name = "lint")]
+  Lint(LintArgs),
+
+  /// Replace an AVM1 buffer of a SWF file and write the patched SWF file.
+  #[clap(name = "patch")]
+  Patch(PatchArgs),
+
+  /// Run AVM1 buffers of a SWF file in a sandboxed interpreter and report traces, external calls and variables.
+  #[clap(name = "run")]
+  Run(RunArgs),
+}
+
+/// Arguments to the `avm2` subcommand.
+#[derive(Debug, Clap)]
+struct Avm2Args {
+  #[clap(subcommand)]
+  command: Avm2Command,
+}
+
+#[derive(Debug, Clap)]
+enum Avm2Command {
+  /// Disassemble the method bodies of an ABC file, or of the `DoAbc` tags of a SWF file.
+  #[clap(name = "disasm")]
+  Disasm(DisasmArgs),
+}
+
+/// Arguments to the `avm1 assemble` subcommand.
+#[derive(Debug, Clap)]
+struct AssembleArgs {
+  /// Output AVM1 file, the buffer is written to the standard output if missing.
+  #[clap(short = 'o', long = "output")]
+  output: Option<PathBuf>,
+  /// Input disassembly.
+  input: PathBuf,
+}
+
+/// Arguments to the `avm1 cfg` subcommand.
+#[derive(Debug, Clap)]
+struct CfgArgs {
+  /// Output format: `json` (same as `main.cfg.json`) or `dot` (Graphviz).
+  #[clap(long = "format", default_value = "json", possible_values = &["json", "dot"])]
+  format: String,
+  /// Input AVM1 file.
+  input: PathBuf,
+}
+
+/// Arguments to the `avm1 deobfuscate` subcommand.
+#[derive(Debug, Clap)]
+struct DeobfuscateArgs {
+  /// Input AVM1 file.
+  input: PathBuf,
+  /// Output AVM1 file. The control flow graph of the output is written next to it, with the `.cfg.json` extension.
+  output: PathBuf,
+}
+
+/// Arguments to the `avm1 lint` subcommand.
+#[derive(Debug, Clap)]
+struct LintArgs {
+  /// Input SWF file.
+  swf: PathBuf,
+}
+
+/// Arguments to the `avm1 patch` subcommand.
+#[derive(Debug, Clap)]
+struct PatchArgs {
+  /// Location of the buffer to replace, such as `do-action:1` (see `avm1 lint`).
+  #[clap(long = "location")]
+  location: String,
+  /// AVM1 file with the new bytecode.
+  #[clap(long = "with")]
+  with: PathBuf,
+  /// Output SWF file, compressed with the same method as the input.
+  #[clap(short = 'o', long = "output")]
+  output: PathBuf,
+  /// Input SWF file.
+  swf: PathBuf,
+}
+
+/// Arguments to the `avm1 run` subcommand.
+#[derive(Debug, Clap)]
+struct RunArgs {
+  /// Location of the buffer to run, such as `do-action:1` (see `avm1 lint`). By default, the `DoInitAction` and
+  /// `DoAction` buffers of the root timeline are run in tag order.
+  #[clap(long = "location")]
+  location: Option<String>,
+  /// Maximum number of actions to execute.
+  #[clap(long = "max-steps", default_value = "1000000")]
+  max_steps: usize,
+  /// Input SWF file.
+  swf: PathBuf,
+}
+
+/// Arguments to the `avm2 disasm` subcommand.
+#[derive(Debug, Clap)]
+struct DisasmArgs {
+  /// Input ABC or SWF file.
+  input: PathBuf,
+}
+
 /// Arguments to the `dump` subcommand.
 #[derive(Debug, Clap)]
 struct DumpArgs {
@@ -48,12 +202,46 @@ struct DumpArgs {
   /// ```
   #[clap(long = "force")]
   force: bool,
+  /// Also write the control flow graphs of the AVM1 buffers as Graphviz DOT files (`main.cfg.dot`).
+  #[clap(long = "dot")]
+  dot: bool,
+  /// Also write the morph shapes interpolated at this ratio, from `0` (start shape) to `1` (end shape).
+  #[clap(long = "morph-ratio")]
+  morph_ratio: Option<f64>,
+  /// Also write the morph shapes as SVG animations (SMIL) from the start shape to the end shape.
+  #[clap(long = "morph-animation")]
+  morph_animation: bool,
   /// Input SWF file.
   swf: PathBuf,
   /// Output directory.
   output: Option<PathBuf>,
 }
 
+/// Arguments to the `classes` subcommand.
+#[derive(Debug, Clap)]
+struct ClassesArgs {
+  /// Input SWF file.
+  swf: PathBuf,
+}
+
+/// Arguments to the `decompile` subcommand.
+#[derive(Debug, Clap)]
+struct DecompileArgs {
+  /// Input SWF file.
+  swf: PathBuf,
+
+  /// Decompile the ABC files (`DoAbc` tags) to ActionScript 3 classes.
+  #[clap(long = "avm2")]
+  avm2: bool,
+}
+
+/// Arguments to the `endpoints` subcommand.
+#[derive(Debug, Clap)]
+struct EndpointsArgs {
+  /// Input SWF file.
+  swf: PathBuf,
+}
+
 /// Arguments to the `parse` subcommand.
 #[derive(Debug, Clap)]
 struct ParseArgs {
@@ -66,7 +254,21 @@ async fn main() {
   let args: CliArgs = CliArgs::parse();
 
   let code = match &args.command {
+    CliCommand::Avm1(ref avm1_args) => match &avm1_args.command {
+      Avm1Command::Assemble(ref assemble_args) => assemble_cmd(assemble_args).await,
+      Avm1Command::Cfg(ref cfg_args) => cfg_cmd(cfg_args).await,
+      Avm1Command::Deobfuscate(ref deobfuscate_args) => deobfuscate_cmd(deobfuscate_args).await,
+      Avm1Command::Lint(ref lint_args) => lint_cmd(lint_args, args.json).await,
+      Avm1Command::Patch(ref patch_args) => patch_cmd(patch_args).await,
+      Avm1Command::Run(ref run_args) => run_cmd(run_args, args.json).await,
+    },
+    CliCommand::Avm2(ref avm2_args) => match &avm2_args.command {
+      Avm2Command::Disasm(ref disasm_args) => disasm_cmd(disasm_args).await,
+    },
+    CliCommand::Classes(ref classes_args) => classes_cmd(classes_args, args.json).await,
+    CliCommand::Decompile(ref decompile_args) => decompile_cmd(decompile_args).await,
     CliCommand::Dump(ref dump_args) => dump_cmd(dump_args).await,
+    CliCommand::Endpoints(ref endpoints_args) => endpoints_cmd(endpoints_args, args.json).await,
     CliCommand::Parse(ref parse_args) => parse_cmd(parse_args).await,
   };
 
@@ -102,8 +304,537 @@ async fn parse_cmd(args: &ParseArgs) -> i32 {
   exitcode::OK
 }
 
+async fn assemble_cmd(args: &AssembleArgs) -> i32 {
+  let text = match tokio::fs::read_to_string(&args.input).await {
+    Ok(text) => text,
+    Err(e) => {
+      eprintln!("Failed to read input disassembly");
+      eprintln!("{:?}", &e);
+      return exitcode::NOINPUT;
+    }
+  };
+
+  let avm1_buffer = match avm1::asm::assemble(&text) {
+    Ok(bytes) => bytes,
+    Err(e) => {
+      eprintln!("Failed to assemble AVM1 buffer");
+      eprintln!("{}: {}", args.input.display(), e);
+      return exitcode::DATAERR;
+    }
+  };
+
+  match &args.output {
+    Some(output) => {
+      if let Err(e) = fs::write(output, &avm1_buffer) {
+        eprintln!("Failed to write AVM1 file: {}", output.display());
+        eprintln!("{:?}", &e);
+        return exitcode::IOERR;
+      }
+    }
+    None => {
+      let stdout = std::io::stdout();
+      let mut stdout_lock = stdout.lock();
+      stdout_lock.write_all(&avm1_buffer).expect("Failed to write AVM1");
+    }
+  }
+  exitcode::OK
+}
+
+async fn cfg_cmd(args: &CfgArgs) -> i32 {
+  let avm1_buffer = match tokio::fs::read(&args.input).await {
+    Ok(bytes) => bytes,
+    Err(e) => {
+      eprintln!("Failed to read input AVM1 file");
+      eprintln!("{:?}", &e);
+      return exitcode::NOINPUT;
+    }
+  };
+
+  let cfg: Cfg = parse_cfg(&avm1_buffer);
+  let stdout = std::io::stdout();
+  let mut stdout_lock = stdout.lock();
+  if args.format == "dot" {
+    let dot = avm1::dot::cfg_to_dot(&cfg);
+    stdout_lock.write_all(dot.as_bytes()).expect("Failed to write CFG");
+  } else {
+    let mut ser = serde_json_v8::Serializer::pretty(stdout_lock);
+    cfg.serialize(&mut ser).expect("Failed to serialize CFG");
+    ser.into_inner().write_all(b"\n").expect("Failed to write CFG");
+  }
+  exitcode::OK
+}
+
+async fn deobfuscate_cmd(args: &DeobfuscateArgs) -> i32 {
+  let avm1_buffer = match tokio::fs::read(&args.input).await {
+    Ok(bytes) => bytes,
+    Err(e) => {
+      eprintln!("Failed to read input AVM1 file");
+      eprintln!("{:?}", &e);
+      return exitcode::NOINPUT;
+    }
+  };
+
+  let cfg: Cfg = parse_cfg(&avm1_buffer);
+  let (cfg, stats) = avm1::deobfuscate::deobfuscate(&cfg);
+  eprintln!("Folded actions: {}", stats.folded);
+  eprintln!("Removed conditional branches: {}", stats.branches);
+  eprintln!("Collapsed jumps: {}", stats.jumps);
+  eprintln!("Removed unreachable blocks: {}", stats.removed);
+  eprintln!("Merged blocks: {}", stats.merged);
+
+  let output_buffer = match avm1::emit::emit(&cfg) {
+    Ok(bytes) => bytes,
+    Err(e) => {
+      eprintln!("Failed to emit the deobfuscated AVM1 buffer: {}", e);
+      return exitcode::DATAERR;
+    }
+  };
+  eprintln!("Size (bytes): {} -> {}", avm1_buffer.len(), output_buffer.len());
+  if let Err(e) = fs::write(&args.output, &output_buffer) {
+    eprintln!("Failed to write AVM1 file: {}", args.output.display());
+    eprintln!("{:?}", &e);
+    return exitcode::IOERR;
+  }
+
+  // The CFG is parsed again so its labels match the offsets of the output
+  let output_cfg: Cfg = parse_cfg(&output_buffer);
+  let cfg_path = args.output.with_extension("cfg.json");
+  {
+    let file = match std::fs::File::create(&cfg_path) {
+      Ok(file) => file,
+      Err(e) => {
+        eprintln!("Failed to create CFG file: {}", cfg_path.display());
+        eprintln!("{:?}", &e);
+        return exitcode::IOERR;
+      }
+    };
+    let writer = std::io::BufWriter::new(file);
+    let mut ser = serde_json_v8::Serializer::pretty(writer);
+    output_cfg.serialize(&mut ser).expect("Failed to serialize CFG");
+    ser.into_inner().write_all(b"\n").expect("Failed to write CFG");
+  }
+  exitcode::OK
+}
+
+async fn lint_cmd(args: &LintArgs, json: bool) -> i32 {
+  let swf_bytes = match tokio::fs::read(&args.swf).await {
+    Ok(bytes) => bytes,
+    Err(e) => {
+      eprintln!("Failed to read input SWF");
+      eprintln!("{:?}", &e);
+      return exitcode::NOINPUT;
+    }
+  };
+
+  let movie = match parse_swf(&swf_bytes) {
+    Ok(movie) => movie,
+    Err(e) => {
+      eprintln!("Failed to parse SWF file. Please report this error at https://github.com/open-flash/swf-parser/");
+      eprintln!("{:?}", &e);
+      return exitcode::DATAERR;
+    }
+  };
+
+  let avm1_buffers = find_avm1(&movie);
+  let mut locations: Vec<&Avm1Location> = avm1_buffers.keys().collect();
+  locations.sort();
+  let findings: Vec<avm1::lint::Finding> = locations
+    .into_iter()
+    .flat_map(|loc| avm1::lint::lint(loc, avm1_buffers[loc]))
+    .collect();
+
+  let stdout = std::io::stdout();
+  let mut stdout_lock = stdout.lock();
+  if json {
+    let mut ser = serde_json_v8::Serializer::pretty(stdout_lock);
+    findings.serialize(&mut ser).expect("Failed to serialize findings");
+    ser.into_inner().write_all(b"\n").expect("Failed to write findings");
+  } else {
+    for finding in findings.iter() {
+      writeln!(stdout_lock, "{}", finding).expect("Failed to write findings");
+    }
+  }
+  eprintln!("AVM1 buffers: {}, findings: {}", avm1_buffers.len(), findings.len());
+  if findings.is_empty() {
+    exitcode::OK
+  } else {
+    exitcode::DATAERR
+  }
+}
+
+async fn patch_cmd(args: &PatchArgs) -> i32 {
+  let swf_bytes = match tokio::fs::read(&args.swf).await {
+    Ok(bytes) => bytes,
+    Err(e) => {
+      eprintln!("Failed to read input SWF");
+      eprintln!("{:?}", &e);
+      return exitcode::NOINPUT;
+    }
+  };
+  let avm1_buffer = match tokio::fs::read(&args.with).await {
+    Ok(bytes) => bytes,
+    Err(e) => {
+      eprintln!("Failed to read input AVM1");
+      eprintln!("{:?}", &e);
+      return exitcode::NOINPUT;
+    }
+  };
+
+  let swf_signature = match parse_swf_signature(&swf_bytes) {
+    Ok((_, signature)) => signature,
+    Err(e) => {
+      eprintln!("Invalid SWF signature. The file is corrupted or not an SWF file.");
+      eprintln!("{:?}", &e);
+      return exitcode::DATAERR;
+    }
+  };
+  let mut movie = match parse_swf(&swf_bytes) {
+    Ok(movie) => movie,
+    Err(e) => {
+      eprintln!("Failed to parse SWF file. Please report this error at https://github.com/open-flash/swf-parser/");
+      eprintln!("{:?}", &e);
+      return exitcode::DATAERR;
+    }
+  };
+
+  let location: Avm1Location = match find_avm1(&movie).keys().find(|loc| loc.to_string() == args.location) {
+    Some(location) => *location,
+    None => {
+      eprintln!("AVM1 buffer not found: {}", args.location);
+      return exitcode::USAGE;
+    }
+  };
+  let findings = avm1::lint::lint(&location, &avm1_buffer);
+  if !findings.is_empty() {
+    eprintln!("Warning: the new AVM1 buffer has {} lint findings:", findings.len());
+    for finding in findings.iter() {
+      eprintln!("{}", finding);
+    }
+  }
+  let buffer = find_avm1_mut(&mut movie, &location).expect("Failed to find the AVM1 buffer");
+  eprintln!(
+    "Replaced {} ({} bytes) with {} bytes",
+    location,
+    buffer.len(),
+    avm1_buffer.len()
+  );
+  *buffer = avm1_buffer;
+
+  let mut output_bytes: Vec<u8> = Vec::new();
+  if let Err(e) = swf_emitter::emit_swf(&mut output_bytes, &movie, swf_signature.compression_method) {
+    eprintln!("Failed to emit the patched SWF file");
+    eprintln!("{:?}", &e);
+    return exitcode::SOFTWARE;
+  }
+  if let Err(e) = fs::write(&args.output, &output_bytes) {
+    eprintln!("Failed to write SWF file: {}", args.output.display());
+    eprintln!("{:?}", &e);
+    return exitcode::IOERR;
+  }
+  exitcode::OK
+}
+
+async fn run_cmd(args: &RunArgs, json: bool) -> i32 {
+  let swf_bytes = match tokio::fs::read(&args.swf).await {
+    Ok(bytes) => bytes,
+    Err(e) => {
+      eprintln!("Failed to read input SWF");
+      eprintln!("{:?}", &e);
+      return exitcode::NOINPUT;
+    }
+  };
+
+  let movie = match parse_swf(&swf_bytes) {
+    Ok(movie) => movie,
+    Err(e) => {
+      eprintln!("Failed to parse SWF file. Please report this error at https://github.com/open-flash/swf-parser/");
+      eprintln!("{:?}", &e);
+      return exitcode::DATAERR;
+    }
+  };
+
+  let avm1_buffers = find_avm1(&movie);
+  let mut locations: Vec<&Avm1Location> = match &args.location {
+    Some(location) => avm1_buffers.keys().filter(|loc| loc.to_string() == *location).collect(),
+    None => avm1_buffers
+      .keys()
+      .filter(|loc| {
+        matches!(
+          loc,
+          Avm1Location::RootDoAction { .. } | Avm1Location::RootDoInitAction { .. }
+        )
+      })
+      .collect(),
+  };
+  if let (Some(location), true) = (&args.location, locations.is_empty()) {
+    eprintln!("AVM1 buffer not found: {}", location);
+    return exitcode::USAGE;
+  }
+  locations.sort_by_key(|loc| match loc {
+    Avm1Location::RootDoAction { tag_index } | Avm1Location::RootDoInitAction { tag_index } => *tag_index,
+    _ => 0,
+  });
+  let buffers: Vec<(Avm1Location, &[u8])> = locations
+    .into_iter()
+    .map(|loc| (*loc, avm1_buffers[loc].as_slice()))
+    .collect();
+
+  let options = avm1::interpreter::Options {
+    max_steps: args.max_steps,
+    swf_version: movie.header.swf_version,
+  };
+  let report = avm1::interpreter::run(&buffers, &options);
+
+  let stdout = std::io::stdout();
+  let mut stdout_lock = stdout.lock();
+  if json {
+    let mut ser = serde_json_v8::Serializer::pretty(stdout_lock);
+    report.serialize(&mut ser).expect("Failed to serialize report");
+    ser.into_inner().write_all(b"\n").expect("Failed to write report");
+  } else {
+    report.print(&mut stdout_lock).expect("Failed to write report");
+  }
+  eprintln!("AVM1 buffers: {}, steps: {}", buffers.len(), report.steps);
+  if report.status == "step-limit" {
+    eprintln!("Stopped: the step limit ({}) was reached", args.max_steps);
+  }
+  exitcode::OK
+}
+
+async fn decompile_cmd(args: &DecompileArgs) -> i32 {
+  let swf_bytes = match tokio::fs::read(&args.swf).await {
+    Ok(bytes) => bytes,
+    Err(e) => {
+      eprintln!("Failed to read input SWF");
+      eprintln!("{:?}", &e);
+      return exitcode::NOINPUT;
+    }
+  };
+
+  let movie = match parse_swf(&swf_bytes) {
+    Ok(movie) => movie,
+    Err(e) => {
+      eprintln!("Failed to parse SWF file. Please report this error at https://github.com/open-flash/swf-parser/");
+      eprintln!("{:?}", &e);
+      return exitcode::DATAERR;
+    }
+  };
+
+  if args.avm2 {
+    return decompile_avm2(&movie);
+  }
+
+  let avm1_buffers = find_avm1(&movie);
+  if avm1_buffers.is_empty() {
+    eprintln!("No AVM1 buffers found");
+  }
+  let mut locations: Vec<&Avm1Location> = avm1_buffers.keys().collect();
+  locations.sort();
+  let stdout = std::io::stdout();
+  let mut stdout_lock = stdout.lock();
+  for (i, loc) in locations.into_iter().enumerate() {
+    let cfg: Cfg = parse_cfg(avm1_buffers[loc]);
+    let source = avm1::decompiler::decompile(avm1_buffers[loc], &cfg);
+    if i > 0 {
+      writeln!(stdout_lock).expect("Failed to write source");
+    }
+    writeln!(stdout_lock, "// {}", loc).expect("Failed to write source");
+    stdout_lock
+      .write_all(source.as_bytes())
+      .expect("Failed to write source");
+  }
+  exitcode::OK
+}
+
+fn decompile_avm2(movie: &Movie) -> i32 {
+  let abc_tags = find_abc(movie);
+  let stdout = std::io::stdout();
+  let mut stdout_lock = stdout.lock();
+  let mut file_count: usize = 0;
+  for (tag_index, tag) in abc_tags.iter() {
+    let abc = match avm2::abc::parse_abc(&tag.data) {
+      Ok(abc) => abc,
+      Err(e) => {
+        eprintln!("Failed to parse the ABC file of tag {}: {}", tag_index, e);
+        continue;
+      }
+    };
+    for file in avm2::decompiler::decompile(&format!("do-abc:{}", tag_index), &abc) {
+      if file_count > 0 {
+        writeln!(stdout_lock).expect("Failed to write source");
+      }
+      file_count += 1;
+      stdout_lock
+        .write_all(file.source.as_bytes())
+        .expect("Failed to write source");
+    }
+  }
+  eprintln!("ABC files: {}, source files: {}", abc_tags.len(), file_count);
+  exitcode::OK
+}
+
+async fn classes_cmd(args: &ClassesArgs, json: bool) -> i32 {
+  let swf_bytes = match tokio::fs::read(&args.swf).await {
+    Ok(bytes) => bytes,
+    Err(e) => {
+      eprintln!("Failed to read input SWF");
+      eprintln!("{:?}", &e);
+      return exitcode::NOINPUT;
+    }
+  };
+
+  let movie = match parse_swf(&swf_bytes) {
+    Ok(movie) => movie,
+    Err(e) => {
+      eprintln!("Failed to parse SWF file. Please report this error at https://github.com/open-flash/swf-parser/");
+      eprintln!("{:?}", &e);
+      return exitcode::DATAERR;
+    }
+  };
+
+  let abc_tags = find_abc(&movie);
+  let mut classes: Vec<avm2::classes::Class> = Vec::new();
+  for (tag_index, tag) in abc_tags.iter() {
+    match avm2::abc::parse_abc(&tag.data) {
+      Ok(abc) => classes.extend(avm2::classes::find_classes(&format!("do-abc:{}", tag_index), &abc)),
+      Err(e) => eprintln!("Failed to parse the ABC file of tag {}: {}", tag_index, e),
+    }
+  }
+  let inventory = avm2::classes::Inventory::new(classes, &find_symbols(&movie));
+
+  let stdout = std::io::stdout();
+  let mut stdout_lock = stdout.lock();
+  if json {
+    let mut ser = serde_json_v8::Serializer::pretty(stdout_lock);
+    inventory.serialize(&mut ser).expect("Failed to serialize classes");
+    ser.into_inner().write_all(b"\n").expect("Failed to write classes");
+  } else {
+    stdout_lock
+      .write_all(inventory.print().as_bytes())
+      .expect("Failed to write classes");
+  }
+  eprintln!(
+    "ABC files: {}, classes: {}, symbols: {}",
+    abc_tags.len(),
+    inventory.classes.len(),
+    inventory.symbols.len()
+  );
+  exitcode::OK
+}
+
+async fn disasm_cmd(args: &DisasmArgs) -> i32 {
+  let input_bytes = match tokio::fs::read(&args.input).await {
+    Ok(bytes) => bytes,
+    Err(e) => {
+      eprintln!("Failed to read input file");
+      eprintln!("{:?}", &e);
+      return exitcode::NOINPUT;
+    }
+  };
+
+  let is_swf = matches!(input_bytes.get(0..3), Some(b"FWS") | Some(b"CWS") | Some(b"ZWS"));
+  let abc_files: Vec<(Option<usize>, Vec<u8>)> = if is_swf {
+    let movie = match parse_swf(&input_bytes) {
+      Ok(movie) => movie,
+      Err(e) => {
+        eprintln!("Failed to parse SWF file. Please report this error at https://github.com/open-flash/swf-parser/");
+        eprintln!("{:?}", &e);
+        return exitcode::DATAERR;
+      }
+    };
+    find_abc(&movie)
+      .into_iter()
+      .map(|(tag_index, tag)| (Some(tag_index), tag.data.clone()))
+      .collect()
+  } else {
+    vec![(None, input_bytes)]
+  };
+
+  let stdout = std::io::stdout();
+  let mut stdout_lock = stdout.lock();
+  let mut code = exitcode::OK;
+  for (tag_index, data) in abc_files.iter() {
+    if let Some(tag_index) = tag_index {
+      writeln!(stdout_lock, "; do-abc:{}", tag_index).expect("Failed to write disassembly");
+    }
+    match avm2::abc::parse_abc(data) {
+      Ok(abc) => stdout_lock
+        .write_all(avm2::disasm::disassemble(&abc).as_bytes())
+        .expect("Failed to write disassembly"),
+      Err(e) => {
+        eprintln!("Failed to parse ABC file: {}", e);
+        code = exitcode::DATAERR;
+      }
+    }
+  }
+  if is_swf {
+    eprintln!("ABC files: {}", abc_files.len());
+  }
+  code
+}
+
+async fn endpoints_cmd(args: &EndpointsArgs, json: bool) -> i32 {
+  let swf_bytes = match tokio::fs::read(&args.swf).await {
+    Ok(bytes) => bytes,
+    Err(e) => {
+      eprintln!("Failed to read input SWF");
+      eprintln!("{:?}", &e);
+      return exitcode::NOINPUT;
+    }
+  };
+
+  let movie = match parse_swf(&swf_bytes) {
+    Ok(movie) => movie,
+    Err(e) => {
+      eprintln!("Failed to parse SWF file. Please report this error at https://github.com/open-flash/swf-parser/");
+      eprintln!("{:?}", &e);
+      return exitcode::DATAERR;
+    }
+  };
+
+  let avm1_buffers = find_avm1(&movie);
+  let mut locations: Vec<&Avm1Location> = avm1_buffers.keys().collect();
+  locations.sort();
+  let mut endpoints: Vec<endpoints::Endpoint> = locations
+    .into_iter()
+    .flat_map(|loc| endpoints::find_avm1_endpoints(loc, avm1_buffers[loc]))
+    .collect();
+  let abc_tags = find_abc(&movie);
+  for (tag_index, tag) in abc_tags.iter() {
+    match endpoints::find_abc_endpoints(&format!("do-abc:{}", tag_index), &tag.data) {
+      Some(abc_endpoints) => endpoints.extend(abc_endpoints),
+      None => eprintln!("Failed to read the string pool of the ABC file of tag {}", tag_index),
+    }
+  }
+
+  let stdout = std::io::stdout();
+  let mut stdout_lock = stdout.lock();
+  if json {
+    let mut ser = serde_json_v8::Serializer::pretty(stdout_lock);
+    endpoints.serialize(&mut ser).expect("Failed to serialize endpoints");
+    ser.into_inner().write_all(b"\n").expect("Failed to write endpoints");
+  } else {
+    for endpoint in endpoints.iter() {
+      writeln!(stdout_lock, "{}", endpoint).expect("Failed to write endpoints");
+    }
+  }
+  eprintln!(
+    "AVM1 buffers: {}, ABC files: {}, endpoints: {}",
+    avm1_buffers.len(),
+    abc_tags.len(),
+    endpoints.len()
+  );
+  exitcode::OK
+}
+
 async fn dump_cmd(args: &DumpArgs) -> i32 {
   eprintln!("Step 0: Initialization");
+  if let Some(ratio) = args.morph_ratio {
+    if !(0.0..=1.0).contains(&ratio) {
+      eprintln!("Invalid morph ratio: {}, it must be between 0 and 1", ratio);
+      return exitcode::USAGE;
+    }
+  }
   let cwd = match std::env::current_dir() {
     Ok(cwd) => cwd,
     Err(e) => {
@@ -248,59 +979,415 @@ async fn dump_cmd(args: &DumpArgs) -> i32 {
   eprintln!("Unimplemented: Display stats about the number of tags and their type (definition, action, etc.)");
 
   eprintln!("Step 3: Analyze AVM1 bytecode");
-  let avm1_buffers = find_avm1(&movie);
+  if let Err(code) = dump_avm1(output_dir_path, &movie, args.dot) {
+    return code;
+  }
+
+  eprintln!("Step 4: Analyze AVM2 bytecode");
+  if let Err(code) = dump_avm2(output_dir_path, &movie) {
+    return code;
+  }
+
+  eprintln!("Step 5: Export images");
+  let manifest = match dump_images(output_dir_path, &movie) {
+    Ok(manifest) => manifest,
+    Err(code) => return code,
+  };
+
+  eprintln!("Step 6: Export shapes");
+  let images: HashMap<u16, &images::ImageInfo> = manifest.iter().map(|image| (image.id, image)).collect();
+  if let Err(code) = dump_shapes(output_dir_path, &movie, &images) {
+    return code;
+  }
+  if let Err(code) = dump_morph_shapes(output_dir_path, &movie, &images, args) {
+    return code;
+  }
+
+  eprintln!("Step 7: Export fonts");
+  if let Err(code) = dump_fonts(output_dir_path, &movie) {
+    return code;
+  }
+
+  eprintln!("Step 8: Extract texts");
+  dump_texts(output_dir_path, &movie);
+
+  eprintln!("Step 9: Export sounds");
+  if let Err(code) = dump_sounds(output_dir_path, &movie) {
+    return code;
+  }
+
+  eprintln!("Success: dump complete");
+
+  exitcode::OK
+}
+
+/// Writes the AVM1 buffers with their CFG, disassembly and source, then the call graph, the statistics and the AS2
+/// classes.
+fn dump_avm1(output_dir_path: &Path, movie: &Movie, dot: bool) -> Result<(), i32> {
+  let avm1_buffers = find_avm1(movie);
   if avm1_buffers.is_empty() {
     eprintln!("No AVM1 buffers found");
   } else {
     eprintln!("AVM1 buffers found: {}", avm1_buffers.len());
   }
-  for (loc, avm1_buffer) in avm1_buffers.iter() {
-    let (avm1_path, cfg_path) = match loc {
-      Avm1Location::RootDoAction { tag_index } => {
-        let dir = output_dir_path.join(format!("{}", tag_index));
-        (dir.join("main.avm1"), dir.join("main.cfg.json"))
-      }
-      Avm1Location::RootDoInitAction { tag_index } => {
-        let dir = output_dir_path.join(format!("{}", tag_index));
-        (dir.join("main.avm1"), dir.join("main.cfg.json"))
-      }
+  let mut locations: Vec<&Avm1Location> = avm1_buffers.keys().collect();
+  locations.sort();
+  let mut cfgs: Vec<(Avm1Location, Cfg)> = Vec::new();
+  for loc in locations {
+    let avm1_buffer = avm1_buffers[loc];
+    let dir = match loc {
+      Avm1Location::RootDoAction { tag_index } => output_dir_path.join(format!("{}", tag_index)),
+      Avm1Location::RootDoInitAction { tag_index } => output_dir_path.join(format!("{}", tag_index)),
       Avm1Location::SpriteDoAction {
         tag_index,
         sprite_tag_index,
-      } => {
-        let dir = output_dir_path
-          .join(format!("{}", tag_index))
-          .join(format!("{}", sprite_tag_index));
-        (dir.join("main.avm1"), dir.join("main.cfg.json"))
-      }
+      } => output_dir_path
+        .join(format!("{}", tag_index))
+        .join(format!("{}", sprite_tag_index)),
       Avm1Location::SpriteDoInitAction {
         tag_index,
         sprite_tag_index,
-      } => {
-        let dir = output_dir_path
-          .join(format!("{}", tag_index))
-          .join(format!("{}", sprite_tag_index));
-        (dir.join("main.avm1"), dir.join("main.cfg.json"))
-      }
+      } => output_dir_path
+        .join(format!("{}", tag_index))
+        .join(format!("{}", sprite_tag_index)),
+      Avm1Location::RootClipAction {
+        tag_index,
+        clip_action_index,
+        events,
+      } => output_dir_path
+        .join(format!("{}", tag_index))
+        .join("clip-actions")
+        .join(clip_action_dir_name(*clip_action_index, *events)),
+      Avm1Location::SpriteClipAction {
+        tag_index,
+        sprite_tag_index,
+        clip_action_index,
+        events,
+      } => output_dir_path
+        .join(format!("{}", tag_index))
+        .join(format!("{}", sprite_tag_index))
+        .join("clip-actions")
+        .join(clip_action_dir_name(*clip_action_index, *events)),
+      Avm1Location::ButtonAction {
+        tag_index, cond_index, ..
+      } => output_dir_path
+        .join(format!("{}", tag_index))
+        .join("actions")
+        .join(format!("{}", cond_index)),
     };
+    if let Err(e) = fs::create_dir_all(&dir) {
+      eprintln!("Failed to create AVM1 directory: {}", dir.display());
+      eprintln!("{:?}", &e);
+      return Err(exitcode::IOERR);
+    }
+    let (avm1_path, cfg_path, disasm_path, source_path) = (
+      dir.join("main.avm1"),
+      dir.join("main.cfg.json"),
+      dir.join("main.avm1.txt"),
+      dir.join("main.as"),
+    );
     {
       let file = std::fs::File::create(avm1_path).expect("Failed to create AVM1 file");
       let mut writer = std::io::BufWriter::new(file);
       writer.write_all(avm1_buffer).expect("Failed to write AVM1");
     }
     let cfg: Cfg = parse_cfg(avm1_buffer);
+    write_json(&cfg_path, &cfg);
     {
-      let file = std::fs::File::create(cfg_path).expect("Failed to create CFG file");
-      let writer = std::io::BufWriter::new(file);
-      let mut ser = serde_json_v8::Serializer::pretty(writer);
-      cfg.serialize(&mut ser).expect("Failed to serialize CFG");
-      ser.into_inner().write_all(b"\n").expect("Failed to write CFG");
+      let disasm = avm1::disasm::disassemble(avm1_buffer, &cfg);
+      fs::write(disasm_path, disasm).expect("Failed to write AVM1 disassembly");
     }
+    {
+      let source = avm1::decompiler::decompile(avm1_buffer, &cfg);
+      fs::write(source_path, source).expect("Failed to write ActionScript source");
+    }
+    if dot {
+      let dot = avm1::dot::cfg_to_dot(&cfg);
+      fs::write(dir.join("main.cfg.dot"), dot).expect("Failed to write CFG graph");
+    }
+    cfgs.push((*loc, cfg));
+  }
+  if cfgs.is_empty() {
+    return Ok(());
   }
 
-  eprintln!("Success: dump complete");
+  let buffers: Vec<(Avm1Location, &[u8], &Cfg)> = cfgs
+    .iter()
+    .map(|(loc, cfg)| (*loc, avm1_buffers[loc].as_slice(), cfg))
+    .collect();
+  let call_graph = avm1::callgraph::call_graph(&buffers);
+  eprintln!(
+    "AVM1 functions: {}, call sites: {}",
+    call_graph.functions.len(),
+    call_graph.calls.len()
+  );
+  write_json(&output_dir_path.join("avm1-callgraph.json"), &call_graph);
+  let dot = avm1::callgraph::call_graph_to_dot(&call_graph);
+  fs::write(output_dir_path.join("avm1-callgraph.dot"), dot).expect("Failed to write call graph");
 
-  exitcode::OK
+  let stats = avm1::stats::stats(&buffers);
+  stats
+    .print(&mut std::io::stderr())
+    .expect("Failed to print AVM1 statistics");
+  write_json(&output_dir_path.join("avm1-stats.json"), &stats);
+
+  // AS2 classes are defined in `#initclip` blocks
+  let init_buffers: Vec<(Avm1Location, &[u8], &Cfg)> = buffers
+    .iter()
+    .filter(|(loc, _, _)| {
+      matches!(
+        loc,
+        Avm1Location::RootDoInitAction { .. } | Avm1Location::SpriteDoInitAction { .. }
+      )
+    })
+    .copied()
+    .collect();
+  let classes = avm1::classes::find_classes(&init_buffers);
+  if classes.is_empty() {
+    return Ok(());
+  }
+  eprintln!("AS2 classes: {}", classes.len());
+  write_json(
+    &output_dir_path.join("avm1-classes.json"),
+    &avm1::classes::packages(&classes),
+  );
+  let classes_dir = output_dir_path.join("classes");
+  let mut stub_paths: HashSet<PathBuf> = HashSet::new();
+  for class in classes.iter() {
+    let path = classes_dir.join(class.stub_path());
+    if !stub_paths.insert(path.clone()) {
+      eprintln!(
+        "Skipping the stub of class {}: {} is already used by another class",
+        class.name,
+        path.display()
+      );
+      continue;
+    }
+    if let Err(e) = fs::create_dir_all(path.parent().unwrap()) {
+      eprintln!("Failed to create class directory: {}", path.display());
+      eprintln!("{:?}", &e);
+      return Err(exitcode::IOERR);
+    }
+    fs::write(path, class.stub()).expect("Failed to write class stub");
+  }
+  Ok(())
+}
+
+/// Writes the ABC files with their parsed form and disassembly, then the ActionScript 3 sources.
+fn dump_avm2(output_dir_path: &Path, movie: &Movie) -> Result<(), i32> {
+  let abc_tags = find_abc(movie);
+  if abc_tags.is_empty() {
+    eprintln!("No ABC files found");
+  } else {
+    eprintln!("ABC files found: {}", abc_tags.len());
+  }
+  let mut source_paths: HashSet<PathBuf> = HashSet::new();
+  for (tag_index, tag) in abc_tags {
+    let dir = output_dir_path.join(format!("{}", tag_index));
+    fs::write(dir.join("main.abc"), &tag.data).expect("Failed to write ABC file");
+    let abc = match avm2::abc::parse_abc(&tag.data) {
+      Ok(abc) => abc,
+      Err(e) => {
+        eprintln!("Failed to parse the ABC file of tag {}: {}", tag_index, e);
+        continue;
+      }
+    };
+    write_json(&dir.join("main.abc.json"), &abc);
+    fs::write(dir.join("main.abc.txt"), avm2::disasm::disassemble(&abc)).expect("Failed to write ABC disassembly");
+    for file in avm2::decompiler::decompile(&format!("do-abc:{}", tag_index), &abc) {
+      let path = output_dir_path.join("as3").join(&file.path);
+      if !source_paths.insert(path.clone()) {
+        eprintln!(
+          "Skipping a source file of tag {}: {} is already used by another definition",
+          tag_index,
+          path.display()
+        );
+        continue;
+      }
+      if let Err(e) = fs::create_dir_all(path.parent().unwrap()) {
+        eprintln!("Failed to create source directory: {}", path.display());
+        eprintln!("{:?}", &e);
+        return Err(exitcode::IOERR);
+      }
+      fs::write(path, file.source).expect("Failed to write ActionScript 3 source");
+    }
+  }
+  Ok(())
+}
+
+/// Writes the bitmaps and their manifest, and returns the manifest.
+fn dump_images(output_dir_path: &Path, movie: &Movie) -> Result<Vec<images::ImageInfo>, i32> {
+  let (bitmaps, jpeg_tables) = images::find_bitmaps(movie);
+  let mut manifest: Vec<images::ImageInfo> = Vec::new();
+  if bitmaps.is_empty() {
+    eprintln!("No images found");
+    return Ok(manifest);
+  }
+  eprintln!("Images found: {}", bitmaps.len());
+  let images_dir = output_dir_path.join("images");
+  if let Err(e) = fs::create_dir_all(&images_dir) {
+    eprintln!("Failed to create images directory: {}", images_dir.display());
+    eprintln!("{:?}", &e);
+    return Err(exitcode::IOERR);
+  }
+  for (tag_index, tag) in bitmaps {
+    let image = match images::export_image(tag_index, tag, jpeg_tables) {
+      Ok(image) => image,
+      Err(e) => {
+        eprintln!("Failed to export the image of tag {}: {}", tag_index, e);
+        continue;
+      }
+    };
+    fs::write(output_dir_path.join(&image.info.file), &image.data).expect("Failed to write image");
+    manifest.push(image.info);
+  }
+  write_json(&output_dir_path.join("images.json"), &manifest);
+  Ok(manifest)
+}
+
+/// Writes the shapes as SVG files.
+fn dump_shapes(output_dir_path: &Path, movie: &Movie, images: &HashMap<u16, &images::ImageInfo>) -> Result<(), i32> {
+  let shapes = shapes::find_shapes(movie);
+  if shapes.is_empty() {
+    eprintln!("No shapes found");
+    return Ok(());
+  }
+  eprintln!("Shapes found: {}", shapes.len());
+  let shapes_dir = output_dir_path.join("shapes");
+  if let Err(e) = fs::create_dir_all(&shapes_dir) {
+    eprintln!("Failed to create shapes directory: {}", shapes_dir.display());
+    eprintln!("{:?}", &e);
+    return Err(exitcode::IOERR);
+  }
+  for (_, tag) in shapes {
+    let svg = shapes::shape_to_svg(tag, images);
+    fs::write(shapes_dir.join(format!("{}.svg", tag.id)), svg).expect("Failed to write shape");
+  }
+  Ok(())
+}
+
+/// Writes the start and end shapes of the morph shapes as SVG files, and the ratio and animation requested by `args`.
+fn dump_morph_shapes(
+  output_dir_path: &Path,
+  movie: &Movie,
+  images: &HashMap<u16, &images::ImageInfo>,
+  args: &DumpArgs,
+) -> Result<(), i32> {
+  let morph_shapes = morph_shapes::find_morph_shapes(movie);
+  if morph_shapes.is_empty() {
+    eprintln!("No morph shapes found");
+    return Ok(());
+  }
+  eprintln!("Morph shapes found: {}", morph_shapes.len());
+  let morph_shapes_dir = output_dir_path.join("morph-shapes");
+  if let Err(e) = fs::create_dir_all(&morph_shapes_dir) {
+    eprintln!(
+      "Failed to create morph shapes directory: {}",
+      morph_shapes_dir.display()
+    );
+    eprintln!("{:?}", &e);
+    return Err(exitcode::IOERR);
+  }
+  for (_, tag) in morph_shapes {
+    let mut svgs = vec![
+      (
+        String::from("start"),
+        morph_shapes::morph_shape_to_svg(tag, 0.0, images),
+      ),
+      (String::from("end"), morph_shapes::morph_shape_to_svg(tag, 1.0, images)),
+    ];
+    if let Some(ratio) = args.morph_ratio {
+      svgs.push((ratio.to_string(), morph_shapes::morph_shape_to_svg(tag, ratio, images)));
+    }
+    if args.morph_animation {
+      svgs.push((
+        String::from("animated"),
+        morph_shapes::morph_shape_to_animated_svg(tag, images),
+      ));
+    }
+    for (suffix, svg) in svgs {
+      fs::write(morph_shapes_dir.join(format!("{}-{}.svg", tag.id, suffix)), svg).expect("Failed to write morph shape");
+    }
+  }
+  Ok(())
+}
+
+/// Writes the fonts as TrueType or OpenType files and their manifest.
+fn dump_fonts(output_dir_path: &Path, movie: &Movie) -> Result<(), i32> {
+  let (fonts, font_names) = fonts::find_fonts(movie);
+  if fonts.is_empty() {
+    eprintln!("No fonts found");
+    return Ok(());
+  }
+  eprintln!("Fonts found: {}", fonts.len());
+  let fonts_dir = output_dir_path.join("fonts");
+  if let Err(e) = fs::create_dir_all(&fonts_dir) {
+    eprintln!("Failed to create fonts directory: {}", fonts_dir.display());
+    eprintln!("{:?}", &e);
+    return Err(exitcode::IOERR);
+  }
+  let mut manifest: Vec<fonts::FontInfo> = Vec::new();
+  for (tag_index, tag) in fonts {
+    let id = match tag {
+      fonts::FontTag::Font(tag) => tag.id,
+      fonts::FontTag::Cff(tag) => tag.id,
+    };
+    let font = match fonts::export_font(tag_index, &tag, font_names.get(&id).copied()) {
+      Ok(font) => font,
+      Err(e) => {
+        eprintln!("Failed to export the font of tag {}: {}", tag_index, e);
+        continue;
+      }
+    };
+    fs::write(output_dir_path.join(&font.info.file), &font.data).expect("Failed to write font");
+    manifest.push(font.info);
+  }
+  write_json(&output_dir_path.join("fonts.json"), &manifest);
+  Ok(())
+}
+
+/// Writes the static and edit texts to `texts.json`.
+fn dump_texts(output_dir_path: &Path, movie: &Movie) {
+  let texts = texts::extract_texts(movie);
+  if texts.is_empty() {
+    eprintln!("No texts found");
+    return;
+  }
+  eprintln!("Texts found: {}", texts.len());
+  write_json(&output_dir_path.join("texts.json"), &texts);
+}
+
+/// Writes the sounds and their manifest.
+fn dump_sounds(output_dir_path: &Path, movie: &Movie) -> Result<(), i32> {
+  let sounds = sounds::find_sounds(movie);
+  if sounds.is_empty() {
+    eprintln!("No sounds found");
+    return Ok(());
+  }
+  eprintln!("Sounds found: {}", sounds.len());
+  let sounds_dir = output_dir_path.join("sounds");
+  if let Err(e) = fs::create_dir_all(&sounds_dir) {
+    eprintln!("Failed to create sounds directory: {}", sounds_dir.display());
+    eprintln!("{:?}", &e);
+    return Err(exitcode::IOERR);
+  }
+  let mut manifest: Vec<sounds::SoundInfo> = Vec::new();
+  for (tag_index, tag) in sounds {
+    let sound = sounds::export_sound(tag_index, tag);
+    fs::write(output_dir_path.join(&sound.info.file), &sound.data).expect("Failed to write sound");
+    manifest.push(sound.info);
+  }
+  write_json(&output_dir_path.join("sounds.json"), &manifest);
+  Ok(())
+}
+
+/// Writes `value` to `path` as pretty JSON, followed by a newline.
+fn write_json(path: &Path, value: &impl Serialize) {
+  let file = std::fs::File::create(path).expect("Failed to create JSON file");
+  let writer = std::io::BufWriter::new(file);
+  let mut ser = serde_json_v8::Serializer::pretty(writer);
+  value.serialize(&mut ser).expect("Failed to serialize JSON");
+  ser.into_inner().write_all(b"\n").expect("Failed to write JSON");
 }
 
 enum OutputDirCheck {
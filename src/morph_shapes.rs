@@ -0,0 +1,237 @@
+//! Conversion of the `DefineMorphShape` family of tags to SVG.
+
+use crate::images::ImageInfo;
+use crate::shapes::Svg;
+use std::collections::HashMap;
+use swf_types::shape_records::{Edge, StyleChange};
+use swf_types::tags::DefineMorphShape;
+use swf_types::{
+  fill_styles, ColorStop, FillStyle, Gradient, LineStyle, Matrix, MorphFillStyle, MorphGradient, MorphLineStyle,
+  MorphShape, MorphShapeRecord, MorphShapeStyles, Movie, Rect, Sfixed16P16, Sfixed8P8, Shape, ShapeRecord, ShapeStyles,
+  StraightSRgba8, Tag, Vector2D,
+};
+
+/// Returns the `DefineMorphShape` and `DefineMorphShape2` tags of the movie.
+pub(crate) fn find_morph_shapes(movie: &Movie) -> Vec<(usize, &DefineMorphShape)> {
+  movie
+    .tags
+    .iter()
+    .enumerate()
+    .filter_map(|(tag_index, tag)| match tag {
+      Tag::DefineMorphShape(tag) => Some((tag_index, tag)),
+      _ => None,
+    })
+    .collect()
+}
+
+/// Converts a morph shape interpolated at `ratio` (from `0` for the start shape to `1` for the end shape) to an SVG
+/// document.
+pub(crate) fn morph_shape_to_svg(tag: &DefineMorphShape, ratio: f64, images: &HashMap<u16, &ImageInfo>) -> String {
+  let mut svg = Svg::new(images);
+  svg.shape(&interpolate(&tag.shape, ratio), false);
+  svg.finish(&Rect {
+    x_min: lerp_i32(tag.bounds.x_min, tag.morph_bounds.x_min, ratio),
+    x_max: lerp_i32(tag.bounds.x_max, tag.morph_bounds.x_max, ratio),
+    y_min: lerp_i32(tag.bounds.y_min, tag.morph_bounds.y_min, ratio),
+    y_max: lerp_i32(tag.bounds.y_max, tag.morph_bounds.y_max, ratio),
+  })
+}
+
+/// Converts a morph shape to an animated SVG document (SMIL), going from the start shape to the end shape.
+pub(crate) fn morph_shape_to_animated_svg(tag: &DefineMorphShape, images: &HashMap<u16, &ImageInfo>) -> String {
+  let mut svg = Svg::new(images);
+  svg.morph(&interpolate(&tag.shape, 0.0), &interpolate(&tag.shape, 1.0));
+  svg.finish(&Rect {
+    x_min: tag.bounds.x_min.min(tag.morph_bounds.x_min),
+    x_max: tag.bounds.x_max.max(tag.morph_bounds.x_max),
+    y_min: tag.bounds.y_min.min(tag.morph_bounds.y_min),
+    y_max: tag.bounds.y_max.max(tag.morph_bounds.y_max),
+  })
+}
+
+/// Interpolates a morph shape at `ratio`, as a regular shape.
+///
+/// Edges are paired as in Flash Player: a straight edge paired with a curved edge is converted to a curved edge with
+/// its control point in the middle of the edge, so the shapes at any ratio have the same records. The end position
+/// is only moved by the style change records with an end position.
+pub(crate) fn interpolate(shape: &MorphShape, ratio: f64) -> Shape {
+  let mut records = Vec::with_capacity(shape.records.len());
+  // Positions in the start shape, in the end shape and in the interpolated shape
+  let mut start: (f64, f64) = (0.0, 0.0);
+  let mut end: (f64, f64) = (0.0, 0.0);
+  let mut position: (i32, i32) = (0, 0);
+  for record in shape.records.iter() {
+    match record {
+      MorphShapeRecord::StyleChange(change) => {
+        if let Some(move_to) = &change.move_to {
+          start = (move_to.x as f64, move_to.y as f64);
+        }
+        if let Some(move_to) = &change.morph_move_to {
+          end = (move_to.x as f64, move_to.y as f64);
+        }
+        let move_to = if change.move_to.is_some() || change.morph_move_to.is_some() {
+          position = lerp_point(start, end, ratio);
+          Some(Vector2D {
+            x: position.0,
+            y: position.1,
+          })
+        } else {
+          None
+        };
+        records.push(ShapeRecord::StyleChange(StyleChange {
+          move_to,
+          left_fill: change.left_fill,
+          right_fill: change.right_fill,
+          line_style: change.line_style,
+          new_styles: change
+            .new_styles
+            .as_ref()
+            .map(|styles| interpolate_styles(styles, ratio)),
+        }));
+      }
+      MorphShapeRecord::Edge(edge) => {
+        let start_to = (start.0 + edge.delta.x as f64, start.1 + edge.delta.y as f64);
+        let end_to = (end.0 + edge.morph_delta.x as f64, end.1 + edge.morph_delta.y as f64);
+        let to = lerp_point(start_to, end_to, ratio);
+        let control_delta = if edge.control_delta.is_some() || edge.morph_control_delta.is_some() {
+          let start_control = control_point(start, start_to, edge.control_delta.as_ref());
+          let end_control = control_point(end, end_to, edge.morph_control_delta.as_ref());
+          let control = lerp_point(start_control, end_control, ratio);
+          Some(Vector2D {
+            x: control.0 - position.0,
+            y: control.1 - position.1,
+          })
+        } else {
+          None
+        };
+        records.push(ShapeRecord::Edge(Edge {
+          delta: Vector2D {
+            x: to.0 - position.0,
+            y: to.1 - position.1,
+          },
+          control_delta,
+        }));
+        start = start_to;
+        end = end_to;
+        position = to;
+      }
+    }
+  }
+  Shape {
+    initial_styles: interpolate_styles(&shape.initial_styles, ratio),
+    records,
+  }
+}
+
+/// Returns the control point of an edge, or the middle of a straight edge.
+fn control_point(from: (f64, f64), to: (f64, f64), control_delta: Option<&Vector2D>) -> (f64, f64) {
+  match control_delta {
+    Some(delta) => (from.0 + delta.x as f64, from.1 + delta.y as f64),
+    None => ((from.0 + to.0) / 2.0, (from.1 + to.1) / 2.0),
+  }
+}
+
+fn interpolate_styles(styles: &MorphShapeStyles, ratio: f64) -> ShapeStyles {
+  ShapeStyles {
+    fill: styles.fill.iter().map(|style| interpolate_fill(style, ratio)).collect(),
+    line: styles.line.iter().map(|style| interpolate_line(style, ratio)).collect(),
+  }
+}
+
+fn interpolate_fill(style: &MorphFillStyle, ratio: f64) -> FillStyle {
+  match style {
+    MorphFillStyle::Bitmap(style) => FillStyle::Bitmap(fill_styles::Bitmap {
+      bitmap_id: style.bitmap_id,
+      matrix: lerp_matrix(&style.matrix, &style.morph_matrix, ratio),
+      repeating: style.repeating,
+      smoothed: style.smoothed,
+    }),
+    MorphFillStyle::FocalGradient(style) => FillStyle::FocalGradient(fill_styles::FocalGradient {
+      matrix: lerp_matrix(&style.matrix, &style.morph_matrix, ratio),
+      gradient: interpolate_gradient(&style.gradient, ratio),
+      focal_point: Sfixed8P8 {
+        epsilons: lerp(
+          style.focal_point.epsilons as f64,
+          style.morph_focal_point.epsilons as f64,
+          ratio,
+        ) as i16,
+      },
+    }),
+    MorphFillStyle::LinearGradient(style) => FillStyle::LinearGradient(fill_styles::LinearGradient {
+      matrix: lerp_matrix(&style.matrix, &style.morph_matrix, ratio),
+      gradient: interpolate_gradient(&style.gradient, ratio),
+    }),
+    MorphFillStyle::RadialGradient(style) => FillStyle::RadialGradient(fill_styles::RadialGradient {
+      matrix: lerp_matrix(&style.matrix, &style.morph_matrix, ratio),
+      gradient: interpolate_gradient(&style.gradient, ratio),
+    }),
+    MorphFillStyle::Solid(style) => FillStyle::Solid(fill_styles::Solid {
+      color: lerp_color(&style.color, &style.morph_color, ratio),
+    }),
+  }
+}
+
+fn interpolate_gradient(gradient: &MorphGradient, ratio: f64) -> Gradient {
+  Gradient {
+    spread: gradient.spread,
+    color_space: gradient.color_space,
+    colors: gradient
+      .colors
+      .iter()
+      .map(|stop| ColorStop {
+        ratio: lerp(stop.ratio as f64, stop.morph_ratio as f64, ratio) as u8,
+        color: lerp_color(&stop.color, &stop.morph_color, ratio),
+      })
+      .collect(),
+  }
+}
+
+fn interpolate_line(style: &MorphLineStyle, ratio: f64) -> LineStyle {
+  LineStyle {
+    width: lerp(style.width as f64, style.morph_width as f64, ratio) as u16,
+    start_cap: style.start_cap,
+    end_cap: style.end_cap,
+    join: style.join,
+    no_h_scale: style.no_h_scale,
+    no_v_scale: style.no_v_scale,
+    no_close: style.no_close,
+    pixel_hinting: style.pixel_hinting,
+    fill: interpolate_fill(&style.fill, ratio),
+  }
+}
+
+fn lerp(start: f64, end: f64, ratio: f64) -> f64 {
+  (start + (end - start) * ratio).round()
+}
+
+fn lerp_i32(start: i32, end: i32, ratio: f64) -> i32 {
+  lerp(start as f64, end as f64, ratio) as i32
+}
+
+fn lerp_point(start: (f64, f64), end: (f64, f64), ratio: f64) -> (i32, i32) {
+  (lerp(start.0, end.0, ratio) as i32, lerp(start.1, end.1, ratio) as i32)
+}
+
+fn lerp_color(start: &StraightSRgba8, end: &StraightSRgba8, ratio: f64) -> StraightSRgba8 {
+  let channel = |start: u8, end: u8| lerp(start as f64, end as f64, ratio) as u8;
+  StraightSRgba8 {
+    r: channel(start.r, end.r),
+    g: channel(start.g, end.g),
+    b: channel(start.b, end.b),
+    a: channel(start.a, end.a),
+  }
+}
+
+fn lerp_matrix(start: &Matrix, end: &Matrix, ratio: f64) -> Matrix {
+  let fixed = |start: Sfixed16P16, end: Sfixed16P16| Sfixed16P16 {
+    epsilons: lerp_i32(start.epsilons, end.epsilons, ratio),
+  };
+  Matrix {
+    scale_x: fixed(start.scale_x, end.scale_x),
+    scale_y: fixed(start.scale_y, end.scale_y),
+    rotate_skew0: fixed(start.rotate_skew0, end.rotate_skew0),
+    rotate_skew1: fixed(start.rotate_skew1, end.rotate_skew1),
+    translate_x: lerp_i32(start.translate_x, end.translate_x, ratio),
+    translate_y: lerp_i32(start.translate_y, end.translate_y, ratio),
+  }
+}
@@ -0,0 +1,576 @@
+//! Conversion of the `DefineShape` family of tags to SVG.
+
+use crate::images::ImageInfo;
+use std::collections::HashMap;
+use std::fmt::Write;
+use swf_types::tags::DefineShape;
+use swf_types::{
+  CapStyle, ColorSpace, FillStyle, Gradient, GradientSpread, JoinStyle, LineStyle, Matrix, Movie, Rect, Shape,
+  ShapeRecord, ShapeStyles, StraightSRgba8, Tag,
+};
+
+/// Position in twips
+pub(crate) type Point = (i32, i32);
+
+/// Straight or quadratic curved edge
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Edge {
+  pub from: Point,
+  pub control: Option<Point>,
+  pub to: Point,
+}
+
+impl Edge {
+  fn reverse(self) -> Self {
+    Edge {
+      from: self.to,
+      control: self.control,
+      to: self.from,
+    }
+  }
+}
+
+/// Edges drawn with the same style arrays: a style change record with new styles starts a new layer, drawn over the
+/// previous ones.
+pub(crate) struct Layer<'a> {
+  pub styles: &'a ShapeStyles,
+  /// Edges of each fill style (by 0-based index), oriented with the fill on their right side
+  pub fills: Vec<Vec<Edge>>,
+  /// Edges of each line style (by 0-based index), in drawing order
+  pub lines: Vec<Vec<Edge>>,
+}
+
+/// Returns the `DefineShape`, `DefineShape2`, `DefineShape3` and `DefineShape4` tags of the movie.
+pub(crate) fn find_shapes(movie: &Movie) -> Vec<(usize, &DefineShape)> {
+  movie
+    .tags
+    .iter()
+    .enumerate()
+    .filter_map(|(tag_index, tag)| match tag {
+      Tag::DefineShape(tag) => Some((tag_index, tag)),
+      _ => None,
+    })
+    .collect()
+}
+
+/// Resolves the styles of the edges of a shape.
+///
+/// Style indices are 1-based (`0` clears the style). Edges between two regions using the same fill style are ignored.
+pub(crate) fn layers<'a>(initial_styles: &'a ShapeStyles, records: &'a [ShapeRecord]) -> Vec<Layer<'a>> {
+  let mut layers = vec![Layer::new(initial_styles)];
+  let mut position: Point = (0, 0);
+  let (mut left_fill, mut right_fill, mut line) = (0, 0, 0);
+  for record in records {
+    match record {
+      ShapeRecord::StyleChange(change) => {
+        if let Some(styles) = &change.new_styles {
+          layers.push(Layer::new(styles));
+          left_fill = 0;
+          right_fill = 0;
+          line = 0;
+        }
+        if let Some(move_to) = &change.move_to {
+          position = (move_to.x, move_to.y);
+        }
+        left_fill = change.left_fill.unwrap_or(left_fill);
+        right_fill = change.right_fill.unwrap_or(right_fill);
+        line = change.line_style.unwrap_or(line);
+      }
+      ShapeRecord::Edge(record) => {
+        let to = (position.0 + record.delta.x, position.1 + record.delta.y);
+        let edge = Edge {
+          from: position,
+          control: record
+            .control_delta
+            .as_ref()
+            .map(|control| (position.0 + control.x, position.1 + control.y)),
+          to,
+        };
+        position = to;
+        let layer = layers.last_mut().unwrap();
+        if left_fill != right_fill {
+          if right_fill != 0 {
+            style_edges(&mut layer.fills, right_fill).push(edge);
+          }
+          if left_fill != 0 {
+            style_edges(&mut layer.fills, left_fill).push(edge.reverse());
+          }
+        }
+        if line != 0 {
+          style_edges(&mut layer.lines, line).push(edge);
+        }
+      }
+    }
+  }
+  layers
+}
+
+impl<'a> Layer<'a> {
+  fn new(styles: &'a ShapeStyles) -> Self {
+    Layer {
+      styles,
+      fills: vec![Vec::new(); styles.fill.len()],
+      lines: vec![Vec::new(); styles.line.len()],
+    }
+  }
+}
+
+fn style_edges(styles: &mut Vec<Vec<Edge>>, index: usize) -> &mut Vec<Edge> {
+  if styles.len() < index {
+    styles.resize(index, Vec::new());
+  }
+  &mut styles[index - 1]
+}
+
+/// Path following edges (by index in their style), closed when it ends at its start
+pub(crate) struct Path {
+  pub edges: Vec<usize>,
+  pub closed: bool,
+}
+
+/// Joins the edges of a fill into contours, following the edges starting where the previous one ends.
+pub(crate) fn contours(edges: &[Edge]) -> Vec<Path> {
+  let mut starts: HashMap<Point, Vec<usize>> = HashMap::new();
+  for (i, edge) in edges.iter().enumerate() {
+    starts.entry(edge.from).or_default().push(i);
+  }
+  let mut used = vec![false; edges.len()];
+  let mut contours = Vec::new();
+  for first in 0..edges.len() {
+    if used[first] {
+      continue;
+    }
+    used[first] = true;
+    let mut contour = vec![first];
+    let start = edges[first].from;
+    let mut end = edges[first].to;
+    while end != start {
+      let next = starts
+        .get(&end)
+        .and_then(|next| next.iter().copied().find(|i| !used[*i]));
+      match next {
+        Some(next) => {
+          used[next] = true;
+          contour.push(next);
+          end = edges[next].to;
+        }
+        None => break,
+      }
+    }
+    contours.push(Path {
+      edges: contour,
+      closed: end == start,
+    });
+  }
+  contours
+}
+
+/// Splits the edges of a line into continuous strokes, closing the strokes ending at their start if `close` is set.
+pub(crate) fn strokes(edges: &[Edge], close: bool) -> Vec<Path> {
+  let mut strokes: Vec<Path> = Vec::new();
+  for (i, edge) in edges.iter().enumerate() {
+    match strokes.last_mut() {
+      Some(stroke) if edges[*stroke.edges.last().unwrap()].to == edge.from => stroke.edges.push(i),
+      _ => strokes.push(Path {
+        edges: vec![i],
+        closed: false,
+      }),
+    }
+  }
+  if close {
+    for stroke in strokes.iter_mut() {
+      stroke.closed = edges[*stroke.edges.last().unwrap()].to == edges[stroke.edges[0]].from;
+    }
+  }
+  strokes
+}
+
+/// Returns the SVG path data of the contours or strokes.
+pub(crate) fn path_data(edges: &[Edge], paths: &[Path]) -> String {
+  let mut data = String::new();
+  for path in paths {
+    let (x, y) = edges[path.edges[0]].from;
+    write!(data, "M{} {}", x, y).unwrap();
+    for edge in path.edges.iter().map(|i| &edges[*i]) {
+      match edge.control {
+        Some((cx, cy)) => write!(data, "Q{} {} {} {}", cx, cy, edge.to.0, edge.to.1).unwrap(),
+        None => write!(data, "L{} {}", edge.to.0, edge.to.1).unwrap(),
+      }
+    }
+    if path.closed {
+      data.push('Z');
+    }
+  }
+  data
+}
+
+/// Converts a `DefineShape` tag to an SVG document, using twips as user units.
+///
+/// Bitmap fills reference the exported images of the `images` directory.
+pub(crate) fn shape_to_svg(tag: &DefineShape, images: &HashMap<u16, &ImageInfo>) -> String {
+  let mut svg = Svg::new(images);
+  svg.shape(&tag.shape, tag.has_fill_winding);
+  svg.finish(&tag.bounds)
+}
+
+/// Duration of the animations of morph shapes
+const MORPH_DURATION: &str = "1s";
+
+/// SVG document builder
+pub(crate) struct Svg<'a> {
+  images: &'a HashMap<u16, &'a ImageInfo>,
+  defs: String,
+  body: String,
+  /// Number of paint servers (gradients and patterns) in `defs`
+  paints: usize,
+}
+
+impl<'a> Svg<'a> {
+  pub(crate) fn new(images: &'a HashMap<u16, &'a ImageInfo>) -> Self {
+    Svg {
+      images,
+      defs: String::new(),
+      body: String::new(),
+      paints: 0,
+    }
+  }
+
+  /// Draws the layers of a shape: in each layer, the fills are drawn in style order, then the lines.
+  pub(crate) fn shape(&mut self, shape: &Shape, fill_winding: bool) {
+    self.draw(shape, None, fill_winding);
+  }
+
+  /// Draws a shape animated (SMIL) from `start` to `end`, two shapes with the same records and styles, but different
+  /// positions and style values (such as the start and end shapes of a morph shape).
+  ///
+  /// The edges are joined into paths using the positions of the start shape. The path data, colors, gradient stops
+  /// and line widths are animated: the gradient and bitmap transforms are the ones of the start shape.
+  pub(crate) fn morph(&mut self, start: &Shape, end: &Shape) {
+    self.draw(start, Some(end), false);
+  }
+
+  fn draw(&mut self, shape: &Shape, end: Option<&Shape>, fill_winding: bool) {
+    let fill_rule = if fill_winding { "" } else { " fill-rule=\"evenodd\"" };
+    let end_layers = end.map(|end| layers(&end.initial_styles, &end.records));
+    for (i, layer) in layers(&shape.initial_styles, &shape.records).iter().enumerate() {
+      let end_layer = end_layers.as_ref().map(|layers| &layers[i]);
+      for (j, (style, edges)) in layer.styles.fill.iter().zip(layer.fills.iter()).enumerate() {
+        if edges.is_empty() {
+          continue;
+        }
+        let paths = contours(edges);
+        let data = path_data(edges, &paths);
+        let (paint, paint_animations) = self.paint(style, end_layer.map(|layer| &layer.styles.fill[j]), "fill");
+        let mut animations = String::new();
+        if let Some(end_layer) = end_layer {
+          animations.push_str(&animate("    ", "d", &data, &path_data(&end_layer.fills[j], &paths)));
+        }
+        animations.push_str(&paint_animations);
+        let attributes = format!("d=\"{}\"{}{}", data, paint, fill_rule);
+        element(&mut self.body, "  ", "path", &attributes, &animations);
+      }
+      for (j, (style, edges)) in layer.styles.line.iter().zip(layer.lines.iter()).enumerate() {
+        if edges.is_empty() {
+          continue;
+        }
+        let paths = strokes(edges, !style.no_close);
+        let data = path_data(edges, &paths);
+        let (stroke, stroke_animations) = self.stroke(style, end_layer.map(|layer| &layer.styles.line[j]));
+        let mut animations = String::new();
+        if let Some(end_layer) = end_layer {
+          animations.push_str(&animate("    ", "d", &data, &path_data(&end_layer.lines[j], &paths)));
+        }
+        animations.push_str(&stroke_animations);
+        let attributes = format!("d=\"{}\" fill=\"none\"{}", data, stroke);
+        element(&mut self.body, "  ", "path", &attributes, &animations);
+      }
+    }
+  }
+
+  /// Returns the document, with the bounds of the shape as its view box.
+  pub(crate) fn finish(self, bounds: &Rect) -> String {
+    let (width, height) = (bounds.x_max - bounds.x_min, bounds.y_max - bounds.y_min);
+    let mut svg = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    writeln!(
+      svg,
+      "<svg xmlns=\"http://www.w3.org/2000/svg\" xmlns:xlink=\"http://www.w3.org/1999/xlink\" width=\"{}\" height=\"{}\" viewBox=\"{} {} {} {}\">",
+      number(width as f64 / 20.0),
+      number(height as f64 / 20.0),
+      bounds.x_min,
+      bounds.y_min,
+      width,
+      height
+    )
+    .unwrap();
+    if !self.defs.is_empty() {
+      svg.push_str("  <defs>\n");
+      svg.push_str(&self.defs);
+      svg.push_str("  </defs>\n");
+    }
+    svg.push_str(&self.body);
+    svg.push_str("</svg>\n");
+    svg
+  }
+
+  /// Returns the paint attributes (`fill` or `stroke`) of a fill style, defining its gradient or pattern, and the
+  /// animations to the `end` style.
+  fn paint(&mut self, style: &FillStyle, end: Option<&FillStyle>, attribute: &str) -> (String, String) {
+    match style {
+      FillStyle::Solid(style) => {
+        let opacity = format!("{}-opacity", attribute);
+        let animations = match end {
+          Some(FillStyle::Solid(end)) => color_animations("    ", attribute, &opacity, &style.color, &end.color),
+          _ => String::new(),
+        };
+        (color_attributes(attribute, &opacity, &style.color), animations)
+      }
+      FillStyle::LinearGradient(style) => {
+        let id = self.paint_id();
+        let end = match end {
+          Some(FillStyle::LinearGradient(end)) => Some(&end.gradient),
+          _ => None,
+        };
+        let attributes = "x1=\"-16384\" y1=\"0\" x2=\"16384\" y2=\"0\"";
+        self.gradient("linearGradient", &id, attributes, &style.matrix, &style.gradient, end);
+        (format!(" {}=\"url(#{})\"", attribute, id), String::new())
+      }
+      FillStyle::RadialGradient(style) => {
+        let id = self.paint_id();
+        let end = match end {
+          Some(FillStyle::RadialGradient(end)) => Some(&end.gradient),
+          _ => None,
+        };
+        let attributes = "cx=\"0\" cy=\"0\" r=\"16384\"";
+        self.gradient("radialGradient", &id, attributes, &style.matrix, &style.gradient, end);
+        (format!(" {}=\"url(#{})\"", attribute, id), String::new())
+      }
+      FillStyle::FocalGradient(style) => {
+        let id = self.paint_id();
+        let end = match end {
+          Some(FillStyle::FocalGradient(end)) => Some(&end.gradient),
+          _ => None,
+        };
+        let focal_point = style.focal_point.epsilons as f64 / 256.0;
+        let attributes = format!(
+          "cx=\"0\" cy=\"0\" r=\"16384\" fx=\"{}\" fy=\"0\"",
+          number(focal_point * 16384.0)
+        );
+        self.gradient("radialGradient", &id, &attributes, &style.matrix, &style.gradient, end);
+        (format!(" {}=\"url(#{})\"", attribute, id), String::new())
+      }
+      FillStyle::Bitmap(style) => {
+        // Missing bitmaps (such as the id `65535`) are not drawn
+        let image = match self.images.get(&style.bitmap_id) {
+          Some(image) => *image,
+          None => return (format!(" {}=\"none\"", attribute), String::new()),
+        };
+        let id = self.paint_id();
+        // Clipped bitmaps are not repeated: the pattern tile is larger than any shape
+        let (tile_width, tile_height) = if style.repeating {
+          (image.width as u32, image.height as u32)
+        } else {
+          (0xffff, 0xffff)
+        };
+        writeln!(
+          self.defs,
+          "    <pattern id=\"{}\" patternUnits=\"userSpaceOnUse\" width=\"{}\" height=\"{}\" patternTransform=\"{}\">",
+          id,
+          tile_width,
+          tile_height,
+          matrix(&style.matrix)
+        )
+        .unwrap();
+        writeln!(
+          self.defs,
+          "      <image xlink:href=\"../{}\" width=\"{}\" height=\"{}\"{}/>",
+          image.file,
+          image.width,
+          image.height,
+          if style.smoothed {
+            ""
+          } else {
+            " image-rendering=\"optimizeSpeed\""
+          }
+        )
+        .unwrap();
+        self.defs.push_str("    </pattern>\n");
+        (format!(" {}=\"url(#{})\"", attribute, id), String::new())
+      }
+    }
+  }
+
+  fn paint_id(&mut self) -> String {
+    self.paints += 1;
+    format!("paint{}", self.paints)
+  }
+
+  /// Defines a gradient: the gradient square spans from `-16384` to `16384` twips before the transformation.
+  fn gradient(
+    &mut self,
+    element_name: &str,
+    id: &str,
+    attributes: &str,
+    transform: &Matrix,
+    gradient: &Gradient,
+    end: Option<&Gradient>,
+  ) {
+    let spread = match gradient.spread {
+      GradientSpread::Pad => "pad",
+      GradientSpread::Reflect => "reflect",
+      GradientSpread::Repeat => "repeat",
+    };
+    let color_space = match gradient.color_space {
+      ColorSpace::SRgb => "",
+      ColorSpace::LinearRgb => " color-interpolation=\"linearRGB\"",
+    };
+    writeln!(
+      self.defs,
+      "    <{} id=\"{}\" gradientUnits=\"userSpaceOnUse\" {} gradientTransform=\"{}\" spreadMethod=\"{}\"{}>",
+      element_name,
+      id,
+      attributes,
+      matrix(transform),
+      spread,
+      color_space
+    )
+    .unwrap();
+    for (i, stop) in gradient.colors.iter().enumerate() {
+      let offset = number(stop.ratio as f64 / 255.0);
+      let attributes = format!(
+        "offset=\"{}\"{}",
+        offset,
+        color_attributes("stop-color", "stop-opacity", &stop.color)
+      );
+      let animations = match end.and_then(|end| end.colors.get(i)) {
+        Some(end) => {
+          let mut animations = animate("        ", "offset", &offset, &number(end.ratio as f64 / 255.0));
+          animations.push_str(&color_animations(
+            "        ",
+            "stop-color",
+            "stop-opacity",
+            &stop.color,
+            &end.color,
+          ));
+          animations
+        }
+        None => String::new(),
+      };
+      element(&mut self.defs, "      ", "stop", &attributes, &animations);
+    }
+    writeln!(self.defs, "    </{}>", element_name).unwrap();
+  }
+
+  /// Returns the stroke attributes of a line style, and the animations to the `end` style.
+  ///
+  /// SVG only supports a single cap style: the start cap style is used for both ends. Lines with a width of `0` are
+  /// hairlines, drawn with a width of 1 pixel at any scale.
+  fn stroke(&mut self, style: &LineStyle, end: Option<&LineStyle>) -> (String, String) {
+    let (mut attributes, mut animations) = self.paint(&style.fill, end.map(|end| &end.fill), "stroke");
+    if style.width == 0 {
+      attributes.push_str(" stroke-width=\"1\" vector-effect=\"non-scaling-stroke\"");
+    } else {
+      write!(attributes, " stroke-width=\"{}\"", style.width).unwrap();
+      if let Some(end) = end {
+        animations.push_str(&animate(
+          "    ",
+          "stroke-width",
+          &style.width.to_string(),
+          &end.width.to_string(),
+        ));
+      }
+    }
+    let cap = match style.start_cap {
+      CapStyle::None => "butt",
+      CapStyle::Round => "round",
+      CapStyle::Square => "square",
+    };
+    write!(attributes, " stroke-linecap=\"{}\"", cap).unwrap();
+    match style.join {
+      JoinStyle::Bevel => attributes.push_str(" stroke-linejoin=\"bevel\""),
+      JoinStyle::Round => attributes.push_str(" stroke-linejoin=\"round\""),
+      JoinStyle::Miter(miter) => write!(
+        attributes,
+        " stroke-linejoin=\"miter\" stroke-miterlimit=\"{}\"",
+        number((miter.limit.epsilons as f64 / 256.0).max(1.0))
+      )
+      .unwrap(),
+    }
+    (attributes, animations)
+  }
+}
+
+/// Writes an element, self-closing if it has no children.
+fn element(out: &mut String, indent: &str, name: &str, attributes: &str, children: &str) {
+  if children.is_empty() {
+    writeln!(out, "{}<{} {}/>", indent, name, attributes).unwrap();
+  } else {
+    writeln!(out, "{}<{} {}>", indent, name, attributes).unwrap();
+    out.push_str(children);
+    writeln!(out, "{}</{}>", indent, name).unwrap();
+  }
+}
+
+/// Returns the animation of an attribute from the start value to the end value, if they are different.
+fn animate(indent: &str, attribute: &str, from: &str, to: &str) -> String {
+  if from == to {
+    return String::new();
+  }
+  format!(
+    "{}<animate attributeName=\"{}\" values=\"{};{}\" dur=\"{}\" repeatCount=\"indefinite\"/>\n",
+    indent, attribute, from, to, MORPH_DURATION
+  )
+}
+
+/// Returns the animations of a color attribute and its opacity attribute.
+fn color_animations(
+  indent: &str,
+  attribute: &str,
+  opacity: &str,
+  start: &StraightSRgba8,
+  end: &StraightSRgba8,
+) -> String {
+  let mut animations = animate(indent, attribute, &hex_color(start), &hex_color(end));
+  animations.push_str(&animate(
+    indent,
+    opacity,
+    &number(start.a as f64 / 255.0),
+    &number(end.a as f64 / 255.0),
+  ));
+  animations
+}
+
+pub(crate) fn hex_color(color: &StraightSRgba8) -> String {
+  format!("#{:02x}{:02x}{:02x}", color.r, color.g, color.b)
+}
+
+/// Returns the color attribute (such as `fill`), with the opacity attribute for translucent colors.
+fn color_attributes(attribute: &str, opacity: &str, color: &StraightSRgba8) -> String {
+  let mut attributes = format!(" {}=\"{}\"", attribute, hex_color(color));
+  if color.a != 255 {
+    write!(attributes, " {}=\"{}\"", opacity, number(color.a as f64 / 255.0)).unwrap();
+  }
+  attributes
+}
+
+/// Formats an SWF matrix as an SVG transform, with the exact value of the fixed-point numbers.
+pub(crate) fn matrix(matrix: &Matrix) -> String {
+  format!(
+    "matrix({} {} {} {} {} {})",
+    matrix.scale_x.epsilons as f64 / 65536.0,
+    matrix.rotate_skew0.epsilons as f64 / 65536.0,
+    matrix.rotate_skew1.epsilons as f64 / 65536.0,
+    matrix.scale_y.epsilons as f64 / 65536.0,
+    matrix.translate_x,
+    matrix.translate_y
+  )
+}
+
+/// Formats a number with at most 4 decimals, without trailing zeros.
+pub(crate) fn number(value: f64) -> String {
+  let formatted = format!("{:.4}", value);
+  let formatted = formatted.trim_end_matches('0').trim_end_matches('.');
+  match formatted {
+    "-0" => String::from("0"),
+    formatted => formatted.to_string(),
+  }
+}
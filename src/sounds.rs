@@ -0,0 +1,127 @@
+//! Export of the sounds defined by the `DefineSound` tags.
+
+use serde::Serialize;
+use swf_types::tags::DefineSound;
+use swf_types::{AudioCodingFormat, Movie, SoundRate, SoundSize, SoundType, Tag};
+
+/// Entry of the sound manifest (`sounds.json`)
+#[derive(Debug, Serialize)]
+pub(crate) struct SoundInfo {
+  /// Character id
+  pub id: u16,
+  pub tag_index: usize,
+  /// Path of the sound file, relative to the output directory
+  pub file: String,
+  /// Format of the file: `mp3`, `wav`, or the raw data of the `adpcm`, `nellymoser` and `speex` codecs
+  pub format: &'static str,
+  /// Audio coding format of the tag
+  pub codec: &'static str,
+  /// Sampling rate, in Hz
+  pub rate: u32,
+  pub channels: u16,
+  /// Bits per sample declared by the tag (only meaningful for uncompressed data)
+  pub bits: u16,
+  pub sample_count: u32,
+}
+
+pub(crate) struct Sound {
+  pub info: SoundInfo,
+  pub data: Vec<u8>,
+}
+
+/// Returns the `DefineSound` tags of the movie.
+pub(crate) fn find_sounds(movie: &Movie) -> Vec<(usize, &DefineSound)> {
+  movie
+    .tags
+    .iter()
+    .enumerate()
+    .filter_map(|(tag_index, tag)| match tag {
+      Tag::DefineSound(tag) => Some((tag_index, tag)),
+      _ => None,
+    })
+    .collect()
+}
+
+/// Converts a `DefineSound` tag to a sound file.
+///
+/// MP3 data is written without its seek samples prefix, and uncompressed data is written as a WAV file. The data of
+/// the other codecs is written as-is: the manifest entry describes its format.
+pub(crate) fn export_sound(tag_index: usize, tag: &DefineSound) -> Sound {
+  let channels = match tag.sound_type {
+    SoundType::Mono => 1,
+    SoundType::Stereo => 2,
+  };
+  let bits = match tag.sound_size {
+    SoundSize::SoundSize8 => 8,
+    SoundSize::SoundSize16 => 16,
+  };
+  let rate = match tag.sound_rate {
+    SoundRate::SoundRate5500 => 5512,
+    SoundRate::SoundRate11000 => 11025,
+    SoundRate::SoundRate22000 => 22050,
+    SoundRate::SoundRate44000 => 44100,
+  };
+  // Nellymoser and Speex use fixed rates, regardless of the rate of the tag
+  let (format, codec, rate, data) = match tag.format {
+    AudioCodingFormat::Mp3 => ("mp3", "mp3", rate, tag.data.get(2..).unwrap_or(&[]).to_vec()),
+    // The native endianness was little-endian on all the platforms of Flash Player
+    AudioCodingFormat::UncompressedNativeEndian => (
+      "wav",
+      "uncompressed-native-endian",
+      rate,
+      wav(&tag.data, rate, channels, bits),
+    ),
+    AudioCodingFormat::UncompressedLittleEndian => (
+      "wav",
+      "uncompressed-little-endian",
+      rate,
+      wav(&tag.data, rate, channels, bits),
+    ),
+    AudioCodingFormat::Adpcm => ("adpcm", "adpcm", rate, tag.data.clone()),
+    AudioCodingFormat::Nellymoser16 => ("nellymoser", "nellymoser-16", 16000, tag.data.clone()),
+    AudioCodingFormat::Nellymoser8 => ("nellymoser", "nellymoser-8", 8000, tag.data.clone()),
+    AudioCodingFormat::Nellymoser => ("nellymoser", "nellymoser", rate, tag.data.clone()),
+    AudioCodingFormat::Speex => ("speex", "speex", 16000, tag.data.clone()),
+  };
+  Sound {
+    info: SoundInfo {
+      id: tag.id,
+      tag_index,
+      file: format!("sounds/{}.{}", tag.id, format),
+      format,
+      codec,
+      rate,
+      channels,
+      bits,
+      sample_count: tag.sample_count,
+    },
+    data,
+  }
+}
+
+/// Wraps little-endian PCM samples (unsigned for 8-bit samples, signed for 16-bit samples) in a WAV file.
+fn wav(samples: &[u8], rate: u32, channels: u16, bits: u16) -> Vec<u8> {
+  let block_align = channels * bits / 8;
+  let samples = &samples[..samples.len() - samples.len() % block_align as usize];
+  let mut out = Vec::with_capacity(44 + samples.len() + 1);
+  out.extend_from_slice(b"RIFF");
+  out.extend_from_slice(&(36 + samples.len() as u32 + samples.len() as u32 % 2).to_le_bytes());
+  out.extend_from_slice(b"WAVE");
+  out.extend_from_slice(b"fmt ");
+  out.extend_from_slice(&16u32.to_le_bytes());
+  // PCM
+  out.extend_from_slice(&1u16.to_le_bytes());
+  out.extend_from_slice(&channels.to_le_bytes());
+  out.extend_from_slice(&rate.to_le_bytes());
+  out.extend_from_slice(&(rate * block_align as u32).to_le_bytes());
+  out.extend_from_slice(&block_align.to_le_bytes());
+  out.extend_from_slice(&bits.to_le_bytes());
+  out.extend_from_slice(b"data");
+  out.extend_from_slice(&(samples.len() as u32).to_le_bytes());
+  out.extend_from_slice(samples);
+  // Chunks are padded to an even size
+  if samples.len() % 2 == 1 {
+    out.push(0);
+  }
+  out
+}
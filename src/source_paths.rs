@@ -0,0 +1,27 @@
+//! Paths of the source files written by the decompilers.
+
+use std::path::PathBuf;
+
+/// Replaces the characters that are not allowed in file names.
+pub(crate) fn sanitize(segment: &str) -> String {
+  let segment: String = segment
+    .chars()
+    .map(|c| match c {
+      '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+      c if c.is_control() => '_',
+      c => c,
+    })
+    .collect();
+  match segment.as_str() {
+    "" | "." | ".." => segment.replace('.', "_") + "_",
+    _ => segment,
+  }
+}
+
+/// Returns the path of the source file of a definition, under the directory of its package (for example
+/// `com/example/Main.as` for `com.example.Main`).
+pub(crate) fn source_path(name: &str) -> PathBuf {
+  let mut path: PathBuf = name.split('.').map(sanitize).collect();
+  path.set_extension("as");
+  path
+}
@@ -0,0 +1,422 @@
+//! Control flow structuring.
+//!
+//! Turns a control flow graph into a tree of structured statements (`if`, loops, `break`, `continue`). Edges that
+//! can't be expressed with structured statements are kept as `Goto`: the result is always complete, even for
+//! irreducible graphs.
+
+use std::collections::BTreeSet;
+
+/// Destination of an edge.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub(crate) enum Target {
+  /// Node of the graph
+  Node(usize),
+  /// Destination outside of the graph, the meaning of the index is defined by the caller
+  Exit(usize),
+}
+
+/// How control leaves a node.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) enum Terminator {
+  /// Unconditional jump
+  Jump(Target),
+  /// Conditional jump
+  Branch { if_true: Target, if_false: Target },
+  /// End of the code (end of the script, return, throw)
+  End,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) enum Structured {
+  /// Straight-line code of a node
+  Node(usize),
+  /// Condition evaluated at the end of a node ending with a `Branch`
+  If {
+    node: usize,
+    negate: bool,
+    then: Vec<Structured>,
+    r#else: Vec<Structured>,
+  },
+  /// Infinite loop, left with `Break` (or `Goto`, `Exit`, end of the code)
+  Loop(Vec<Structured>),
+  Break,
+  Continue,
+  /// Jump to a node that can't be reached with structured statements
+  Goto(usize),
+  /// Jump outside of the graph
+  Exit(usize),
+}
+
+/// Structures the graph starting at the node `0`.
+pub(crate) fn structure(terminators: &[Terminator]) -> Vec<Structured> {
+  if terminators.is_empty() {
+    return Vec::new();
+  }
+  let graph = Graph::new(terminators);
+  let mut structurer = Structurer {
+    loops: Vec::new(),
+    emitted: vec![false; terminators.len()],
+    graph,
+  };
+  let mut result = structurer.seq(Target::Node(0), None);
+  simplify(&mut result, false);
+  result
+}
+
+struct Graph<'a> {
+  terminators: &'a [Terminator],
+  /// Natural loop body of each loop header
+  loop_bodies: Vec<Option<BTreeSet<usize>>>,
+  /// Target following each loop
+  loop_follows: Vec<Option<Target>>,
+  /// Immediate post-dominator of each node, `None` if it is the virtual exit
+  post_dominators: Vec<Option<usize>>,
+}
+
+/// Returns the targets of the edges leaving a node.
+pub(crate) fn successors(terminator: &Terminator) -> Vec<Target> {
+  match terminator {
+    Terminator::Jump(target) => vec![*target],
+    Terminator::Branch { if_true, if_false } => vec![*if_true, *if_false],
+    Terminator::End => Vec::new(),
+  }
+}
+
+fn node_successors(terminator: &Terminator) -> Vec<usize> {
+  successors(terminator)
+    .into_iter()
+    .filter_map(|target| match target {
+      Target::Node(node) => Some(node),
+      Target::Exit(_) => None,
+    })
+    .collect()
+}
+
+impl<'a> Graph<'a> {
+  fn new(terminators: &'a [Terminator]) -> Self {
+    let len = terminators.len();
+    let rpo = reverse_post_order(len, 0, |node| node_successors(&terminators[node]));
+    let mut rpo_index = vec![None; len];
+    for (i, node) in rpo.iter().enumerate() {
+      rpo_index[*node] = Some(i);
+    }
+
+    let predecessors = {
+      let mut predecessors: Vec<Vec<usize>> = vec![Vec::new(); len];
+      for node in rpo.iter() {
+        for successor in node_successors(&terminators[*node]) {
+          predecessors[successor].push(*node);
+        }
+      }
+      predecessors
+    };
+    let dominators = dominators(&rpo, &rpo_index, &predecessors);
+
+    let mut loop_bodies: Vec<Option<BTreeSet<usize>>> = vec![None; len];
+    // Sources of the back edges of each loop header
+    let mut latches: Vec<Vec<usize>> = vec![Vec::new(); len];
+    for node in rpo.iter() {
+      for successor in node_successors(&terminators[*node]) {
+        if dominates(&dominators, successor, *node) {
+          // Back edge `node -> successor`
+          latches[successor].push(*node);
+          let body = loop_bodies[successor].get_or_insert_with(BTreeSet::new);
+          body.insert(successor);
+          let mut stack = vec![*node];
+          while let Some(member) = stack.pop() {
+            if body.insert(member) {
+              stack.extend(predecessors[member].iter().copied());
+            }
+          }
+        }
+      }
+    }
+
+    let loop_follows = (0..len)
+      .map(|header| {
+        let body = loop_bodies[header].as_ref()?;
+        // Prefer the exit of the header (`while` loop), then the exit of a latch (`do..while` loop)
+        let candidates = std::iter::once(header)
+          .chain(latches[header].iter().copied())
+          .chain(body.iter().copied());
+        candidates
+          .flat_map(|member| successors(&terminators[member]))
+          .find(|target| match target {
+            Target::Node(node) => !body.contains(node),
+            Target::Exit(_) => true,
+          })
+      })
+      .collect();
+
+    let post_dominators = post_dominators(terminators, &rpo);
+
+    Self {
+      terminators,
+      loop_bodies,
+      loop_follows,
+      post_dominators,
+    }
+  }
+}
+
+/// Returns the reachable nodes in reverse post-order.
+fn reverse_post_order(len: usize, entry: usize, successors: impl Fn(usize) -> Vec<usize>) -> Vec<usize> {
+  let mut visited = vec![false; len];
+  let mut post_order = Vec::with_capacity(len);
+  // Iterative DFS to support deep graphs: `(node, next successor index)`
+  let mut stack: Vec<(usize, Vec<usize>, usize)> = Vec::new();
+  visited[entry] = true;
+  stack.push((entry, successors(entry), 0));
+  while let Some((node, node_successors, index)) = stack.last_mut() {
+    if let Some(successor) = node_successors.get(*index).copied() {
+      *index += 1;
+      if !visited[successor] {
+        visited[successor] = true;
+        let next = successors(successor);
+        stack.push((successor, next, 0));
+      }
+    } else {
+      post_order.push(*node);
+      stack.pop();
+    }
+  }
+  post_order.reverse();
+  post_order
+}
+
+/// Computes the immediate dominators ("A Simple, Fast Dominance Algorithm", Cooper, Harvey and Kennedy).
+///
+/// `rpo` must start with the entry node. Unreachable nodes have no dominator.
+fn dominators(rpo: &[usize], rpo_index: &[Option<usize>], predecessors: &[Vec<usize>]) -> Vec<Option<usize>> {
+  let mut idom: Vec<Option<usize>> = vec![None; rpo_index.len()];
+  let entry = match rpo.first() {
+    Some(entry) => *entry,
+    None => return idom,
+  };
+  idom[entry] = Some(entry);
+  let mut changed = true;
+  while changed {
+    changed = false;
+    for node in rpo.iter().skip(1) {
+      let mut new_idom: Option<usize> = None;
+      for predecessor in predecessors[*node].iter() {
+        if idom[*predecessor].is_none() {
+          continue;
+        }
+        new_idom = Some(match new_idom {
+          None => *predecessor,
+          Some(current) => intersect(&idom, rpo_index, *predecessor, current),
+        });
+      }
+      if new_idom.is_some() && idom[*node] != new_idom {
+        idom[*node] = new_idom;
+        changed = true;
+      }
+    }
+  }
+  idom
+}
+
+fn intersect(idom: &[Option<usize>], rpo_index: &[Option<usize>], mut a: usize, mut b: usize) -> usize {
+  while a != b {
+    while rpo_index[a] > rpo_index[b] {
+      a = idom[a].unwrap();
+    }
+    while rpo_index[b] > rpo_index[a] {
+      b = idom[b].unwrap();
+    }
+  }
+  a
+}
+
+/// Tests if `a` dominates `b`.
+fn dominates(idom: &[Option<usize>], a: usize, mut b: usize) -> bool {
+  loop {
+    if a == b {
+      return true;
+    }
+    match idom[b] {
+      Some(parent) if parent != b => b = parent,
+      _ => return false,
+    }
+  }
+}
+
+/// Computes the immediate post-dominators, using a virtual exit node reached by all the ends and exits.
+fn post_dominators(terminators: &[Terminator], rpo: &[usize]) -> Vec<Option<usize>> {
+  let len = terminators.len();
+  let exit = len;
+  // Successors in the reversed graph
+  let mut reversed: Vec<Vec<usize>> = vec![Vec::new(); len + 1];
+  for node in rpo.iter() {
+    let targets = successors(&terminators[*node]);
+    if targets.is_empty() {
+      reversed[exit].push(*node);
+    }
+    for target in targets {
+      match target {
+        Target::Node(successor) => reversed[successor].push(*node),
+        Target::Exit(_) => reversed[exit].push(*node),
+      }
+    }
+  }
+  let reversed_rpo = reverse_post_order(len + 1, exit, |node| reversed[node].clone());
+  let mut rpo_index = vec![None; len + 1];
+  for (i, node) in reversed_rpo.iter().enumerate() {
+    rpo_index[*node] = Some(i);
+  }
+  let mut predecessors: Vec<Vec<usize>> = vec![Vec::new(); len + 1];
+  for node in reversed_rpo.iter() {
+    for successor in reversed[*node].iter() {
+      predecessors[*successor].push(*node);
+    }
+  }
+  let idom = dominators(&reversed_rpo, &rpo_index, &predecessors);
+  idom
+    .into_iter()
+    .take(len)
+    .map(|dominator| dominator.filter(|dominator| *dominator != exit))
+    .collect()
+}
+
+struct LoopContext {
+  header: usize,
+  follow: Option<Target>,
+}
+
+struct Structurer<'a> {
+  graph: Graph<'a>,
+  loops: Vec<LoopContext>,
+  emitted: Vec<bool>,
+}
+
+impl<'a> Structurer<'a> {
+  /// Structures the code starting at `current`, until `stop` is reached.
+  fn seq(&mut self, mut current: Target, stop: Option<Target>) -> Vec<Structured> {
+    let mut out = Vec::new();
+    loop {
+      if Some(current) == stop {
+        break;
+      }
+      if let Some(jump) = self.loop_jump(current) {
+        out.push(jump);
+        break;
+      }
+      let node = match current {
+        Target::Node(node) => node,
+        Target::Exit(exit) => {
+          out.push(Structured::Exit(exit));
+          break;
+        }
+      };
+      if self.emitted[node] {
+        out.push(Structured::Goto(node));
+        break;
+      }
+      if self.graph.loop_bodies[node].is_some() {
+        let follow = self.graph.loop_follows[node];
+        self.loops.push(LoopContext { header: node, follow });
+        let mut body = Vec::new();
+        if let Some(next) = self.node(node, None, &mut body) {
+          body.extend(self.seq(next, None));
+        }
+        self.loops.pop();
+        out.push(Structured::Loop(body));
+        match follow {
+          Some(follow) => current = follow,
+          None => break,
+        }
+        continue;
+      }
+      match self.node(node, stop, &mut out) {
+        Some(next) => current = next,
+        None => break,
+      }
+    }
+    out
+  }
+
+  /// Returns the `break` or `continue` statement corresponding to a jump to `target`, if any.
+  fn loop_jump(&self, target: Target) -> Option<Structured> {
+    for (depth, context) in self.loops.iter().rev().enumerate() {
+      let innermost = depth == 0;
+      if target == Target::Node(context.header) {
+        return Some(if innermost {
+          Structured::Continue
+        } else {
+          Structured::Goto(context.header)
+        });
+      }
+      if Some(target) == context.follow {
+        return Some(match (innermost, target) {
+          (true, _) => Structured::Break,
+          (false, Target::Node(node)) => Structured::Goto(node),
+          (false, Target::Exit(exit)) => Structured::Exit(exit),
+        });
+      }
+    }
+    None
+  }
+
+  /// Emits a node and its conditional, returns the next target in the sequence.
+  fn node(&mut self, node: usize, stop: Option<Target>, out: &mut Vec<Structured>) -> Option<Target> {
+    self.emitted[node] = true;
+    out.push(Structured::Node(node));
+    match self.graph.terminators[node] {
+      Terminator::End => None,
+      Terminator::Jump(target) => Some(target),
+      Terminator::Branch { if_true, if_false } if if_true == if_false => Some(if_true),
+      Terminator::Branch { if_true, if_false } => {
+        let follow = self.if_follow(node);
+        let arm_stop = follow.or(stop);
+        let then = self.seq(if_true, arm_stop);
+        let r#else = self.seq(if_false, arm_stop);
+        out.push(Structured::If {
+          node,
+          negate: false,
+          then,
+          r#else,
+        });
+        follow
+      }
+    }
+  }
+
+  /// Returns the target where the two arms of the condition ending `node` join.
+  fn if_follow(&self, node: usize) -> Option<Target> {
+    let follow = self.graph.post_dominators[node]?;
+    if let Some(context) = self.loops.last() {
+      // Leaving the loop is expressed with `break` or `continue` in each arm
+      let body = self.graph.loop_bodies[context.header].as_ref().unwrap();
+      if follow == context.header || !body.contains(&follow) {
+        return None;
+      }
+    }
+    Some(Target::Node(follow))
+  }
+}
+
+/// Removes redundant statements: trailing `continue` in loops and empty `then` branches.
+fn simplify(statements: &mut Vec<Structured>, in_loop_tail: bool) {
+  let len = statements.len();
+  for (i, statement) in statements.iter_mut().enumerate() {
+    let is_tail = in_loop_tail && i + 1 == len;
+    match statement {
+      Structured::Loop(body) => simplify(body, true),
+      Structured::If {
+        negate, then, r#else, ..
+      } => {
+        simplify(then, is_tail);
+        simplify(r#else, is_tail);
+        if then.is_empty() && !r#else.is_empty() {
+          std::mem::swap(then, r#else);
+          *negate = !*negate;
+        }
+      }
+      _ => {}
+    }
+  }
+  if in_loop_tail && statements.last() == Some(&Structured::Continue) {
+    statements.pop();
+  }
+}
@@ -0,0 +1,438 @@
+//! Extraction of the text of the `DefineText` and `DefineEditText` families of tags.
+
+use crate::shapes::hex_color;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use swf_types::tags::{DefineDynamicText, DefineText};
+use swf_types::{Movie, Rect, Tag, TextAlignment};
+
+/// Entry of the text file (`texts.json`)
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub(crate) enum TextInfo {
+  /// `DefineText` or `DefineText2`
+  Static {
+    tag_index: usize,
+    bounds: Rect,
+    /// Transform from the text space (used by the positions of the runs) to the character space
+    matrix: String,
+    /// Text of the runs, with a line break between runs on different lines
+    text: String,
+    runs: Vec<StaticRun>,
+  },
+  /// `DefineEditText`
+  Edit {
+    tag_index: usize,
+    bounds: Rect,
+    variable_name: Option<String>,
+    font_id: Option<u16>,
+    font_name: Option<String>,
+    /// Font size, in twips
+    size: Option<u16>,
+    color: Option<String>,
+    alpha: Option<u8>,
+    align: &'static str,
+    html: bool,
+    /// Initial text, rendered to plain text for HTML text fields
+    text: Option<String>,
+    /// Initial HTML source, for HTML text fields
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<String>,
+    /// Formatted runs of the initial text, for HTML text fields
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    runs: Vec<HtmlRun>,
+  },
+}
+
+/// Glyphs of a text record, with the resolved style
+#[derive(Debug, Serialize)]
+pub(crate) struct StaticRun {
+  /// Position of the first glyph on the baseline, in twips
+  pub x: i32,
+  pub y: i32,
+  pub font_id: Option<u16>,
+  pub font_name: Option<String>,
+  /// Font size, in twips
+  pub size: Option<u16>,
+  pub color: Option<String>,
+  pub alpha: Option<u8>,
+  pub text: String,
+}
+
+/// Text with the same format in an HTML text field
+#[derive(Debug, Serialize)]
+pub(crate) struct HtmlRun {
+  pub text: String,
+  #[serde(flatten)]
+  pub format: HtmlFormat,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub(crate) struct HtmlFormat {
+  pub font_name: Option<String>,
+  /// Font size, in twips
+  pub size: Option<u16>,
+  pub color: Option<String>,
+  pub bold: bool,
+  pub italic: bool,
+  pub underline: bool,
+  pub url: Option<String>,
+}
+
+/// Font used to resolve the glyph indices of the static texts
+struct FontCodes<'a> {
+  name: Option<&'a str>,
+  code_units: Option<&'a [u16]>,
+}
+
+/// Extracts the text of the `DefineText`, `DefineText2` and `DefineEditText` tags, by character id.
+///
+/// The glyph indices of static texts are mapped to characters through the code table of their font (the code table
+/// of the `DefineFontInfo` tag for `DefineFont` tags). Unknown glyphs are replaced by `U+FFFD`.
+pub(crate) fn extract_texts(movie: &Movie) -> BTreeMap<u16, TextInfo> {
+  let mut fonts: HashMap<u16, FontCodes> = HashMap::new();
+  let mut font_names: HashMap<u16, &str> = HashMap::new();
+  for tag in movie.tags.iter() {
+    match tag {
+      Tag::DefineFont(tag) => {
+        fonts.insert(
+          tag.id,
+          FontCodes {
+            name: Some(&tag.font_name),
+            code_units: tag.code_units.as_deref(),
+          },
+        );
+      }
+      Tag::DefineGlyphFont(tag) => {
+        fonts.entry(tag.id).or_insert(FontCodes {
+          name: None,
+          code_units: None,
+        });
+      }
+      Tag::DefineCffFont(tag) => {
+        fonts.insert(
+          tag.id,
+          FontCodes {
+            name: Some(&tag.font_name),
+            code_units: None,
+          },
+        );
+      }
+      Tag::DefineFontInfo(tag) => {
+        fonts.insert(
+          tag.font_id,
+          FontCodes {
+            name: Some(&tag.font_name),
+            code_units: Some(&tag.code_units),
+          },
+        );
+      }
+      Tag::DefineFontName(tag) => {
+        font_names.insert(tag.font_id, &tag.name);
+      }
+      _ => {}
+    }
+  }
+  for (id, name) in font_names {
+    if let Some(font) = fonts.get_mut(&id) {
+      font.name = Some(name);
+    }
+  }
+
+  let mut texts = BTreeMap::new();
+  for (tag_index, tag) in movie.tags.iter().enumerate() {
+    match tag {
+      Tag::DefineText(tag) => {
+        texts.insert(tag.id, static_text(tag_index, tag, &fonts));
+      }
+      Tag::DefineDynamicText(tag) => {
+        texts.insert(tag.id, edit_text(tag_index, tag, &fonts));
+      }
+      _ => {}
+    }
+  }
+  texts
+}
+
+/// Resolves the runs of a static text.
+///
+/// The style and position of the text records persist until they are changed. The offsets of the records default to
+/// `0` when they are absent, so a record without offsets continues the previous one.
+fn static_text(tag_index: usize, tag: &DefineText, fonts: &HashMap<u16, FontCodes>) -> TextInfo {
+  let mut runs: Vec<StaticRun> = Vec::new();
+  let mut text = String::new();
+  let (mut x, mut y) = (0i32, 0i32);
+  let (mut font_id, mut size, mut color) = (None, None, None);
+  for (i, record) in tag.records.iter().enumerate() {
+    font_id = record.font_id.or(font_id);
+    size = record.font_size.or(size);
+    color = record.color.or(color);
+    if i == 0 || record.offset_x != 0 || record.offset_y != 0 {
+      // Records only moving horizontally stay on the same line
+      let line_y = if i == 0 || record.offset_y != 0 {
+        record.offset_y as i32
+      } else {
+        y
+      };
+      if i > 0 && line_y != y {
+        text.push('\n');
+      }
+      x = record.offset_x as i32;
+      y = line_y;
+    }
+    let font = font_id.and_then(|id| fonts.get(&id));
+    let run_text: String = record
+      .entries
+      .iter()
+      .map(|entry| {
+        font
+          .and_then(|font| font.code_units)
+          .and_then(|code_units| code_units.get(entry.index))
+          .and_then(|code| std::char::from_u32(*code as u32))
+          .unwrap_or('\u{fffd}')
+      })
+      .collect();
+    text.push_str(&run_text);
+    runs.push(StaticRun {
+      x,
+      y,
+      font_id,
+      font_name: font.and_then(|font| font.name).map(String::from),
+      size,
+      color: color.as_ref().map(hex_color),
+      alpha: color.map(|color| color.a),
+      text: run_text,
+    });
+    x += record.entries.iter().map(|entry| entry.advance).sum::<i32>();
+  }
+  TextInfo::Static {
+    tag_index,
+    bounds: tag.bounds,
+    matrix: crate::shapes::matrix(&tag.matrix),
+    text,
+    runs,
+  }
+}
+
+fn edit_text(tag_index: usize, tag: &DefineDynamicText, fonts: &HashMap<u16, FontCodes>) -> TextInfo {
+  let font_name = match (&tag.font_id, &tag.font_class) {
+    (Some(id), _) => fonts.get(id).and_then(|font| font.name).map(String::from),
+    (None, Some(class)) => Some(class.clone()),
+    (None, None) => None,
+  };
+  let (text, source, runs) = match &tag.text {
+    Some(source) if tag.html => {
+      let default_format = HtmlFormat {
+        font_name: font_name.clone(),
+        size: tag.font_size,
+        color: tag.color.as_ref().map(hex_color),
+        bold: false,
+        italic: false,
+        underline: false,
+        url: None,
+      };
+      let runs = render_html(source, default_format);
+      let text = runs.iter().map(|run| run.text.as_str()).collect();
+      (Some(text), Some(source.clone()), runs)
+    }
+    Some(text) => (Some(text.replace("\r\n", "\n").replace('\r', "\n")), None, Vec::new()),
+    None => (None, None, Vec::new()),
+  };
+  TextInfo::Edit {
+    tag_index,
+    bounds: tag.bounds,
+    variable_name: tag.variable_name.clone(),
+    font_id: tag.font_id,
+    font_name,
+    size: tag.font_size,
+    color: tag.color.as_ref().map(hex_color),
+    alpha: tag.color.map(|color| color.a),
+    align: match tag.align {
+      TextAlignment::Left => "left",
+      TextAlignment::Right => "right",
+      TextAlignment::Center => "center",
+      TextAlignment::Justify => "justify",
+    },
+    html: tag.html,
+    text,
+    source,
+    runs,
+  }
+}
+
+/// Renders the HTML subset of text fields to formatted runs.
+///
+/// Supports the `p`, `br`, `li`, `b`, `i`, `u`, `a` and `font` (`face`, `size` in pixels and `color` attributes)
+/// tags, and the character references. Other tags (such as `img`, `span` and `textformat`) are ignored.
+pub(crate) fn render_html(source: &str, default_format: HtmlFormat) -> Vec<HtmlRun> {
+  let mut runs: Vec<HtmlRun> = Vec::new();
+  // Open tags, with the format inside them
+  let mut stack: Vec<(String, HtmlFormat)> = Vec::new();
+  // Paragraphs start on a new line
+  let mut line_break = false;
+  let mut rest = source;
+  while !rest.is_empty() {
+    let format = stack.last().map_or(&default_format, |(_, format)| format);
+    if !rest.starts_with('<') {
+      let end = rest.find('<').unwrap_or(rest.len());
+      push_text(&mut runs, format, &decode_entities(&rest[..end]), &mut line_break);
+      rest = &rest[end..];
+      continue;
+    }
+    let end = match rest.find('>') {
+      Some(end) => end,
+      None => {
+        push_text(&mut runs, format, &decode_entities(rest), &mut line_break);
+        break;
+      }
+    };
+    let tag = &rest[1..end];
+    rest = &rest[end + 1..];
+    if let Some(name) = tag.strip_prefix('/') {
+      let name = name.trim().to_ascii_lowercase();
+      if name == "p" || name == "li" {
+        line_break = true;
+      }
+      if let Some(position) = stack.iter().rposition(|(open, _)| *open == name) {
+        stack.truncate(position);
+      }
+      continue;
+    }
+    let self_closing = tag.ends_with('/');
+    let tag = tag.trim_end_matches('/');
+    let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+    let name = tag[..name_end].to_ascii_lowercase();
+    let attributes = html_attributes(&tag[name_end..]);
+    let mut inner = format.clone();
+    match name.as_str() {
+      "br" => {
+        push_text(&mut runs, format, "\n", &mut line_break);
+        continue;
+      }
+      "p" => line_break = true,
+      "li" => {
+        line_break = true;
+        push_text(&mut runs, format, "\u{2022} ", &mut line_break);
+      }
+      "b" => inner.bold = true,
+      "i" => inner.italic = true,
+      "u" => inner.underline = true,
+      "a" => inner.url = attributes.get("href").cloned(),
+      "font" => {
+        if let Some(face) = attributes.get("face") {
+          inner.font_name = Some(face.clone());
+        }
+        if let Some(size) = attributes.get("size") {
+          inner.size = font_size(size, format.size).or(format.size);
+        }
+        if let Some(color) = attributes.get("color") {
+          inner.color = Some(color.to_ascii_lowercase());
+        }
+      }
+      _ => {}
+    }
+    if !self_closing && name != "img" {
+      stack.push((name, inner));
+    }
+  }
+  runs
+}
+
+/// Appends text to the runs, merging it with the last run when it has the same format.
+///
+/// A pending line break ends the last run, unless the text starts the field.
+fn push_text(runs: &mut Vec<HtmlRun>, format: &HtmlFormat, text: &str, line_break: &mut bool) {
+  if text.is_empty() {
+    return;
+  }
+  if *line_break {
+    *line_break = false;
+    if let Some(last) = runs.last_mut() {
+      last.text.push('\n');
+    }
+  }
+  match runs.last_mut() {
+    Some(last) if last.format == *format => last.text.push_str(text),
+    _ => runs.push(HtmlRun {
+      text: String::from(text),
+      format: format.clone(),
+    }),
+  }
+}
+
+/// Parses the size attribute of a `font` tag (in pixels, or relative with a sign) to twips.
+fn font_size(size: &str, current: Option<u16>) -> Option<u16> {
+  let size = size.trim();
+  let pixels: i32 = size.trim_start_matches('+').parse().ok()?;
+  let twips = if size.starts_with('+') || size.starts_with('-') {
+    i32::from(current.unwrap_or(0)).saturating_add(pixels.saturating_mul(20))
+  } else {
+    pixels.saturating_mul(20)
+  };
+  Some(twips.max(0).min(u16::MAX as i32) as u16)
+}
+
+/// Parses the attributes of an HTML tag, with lowercase names.
+fn html_attributes(source: &str) -> HashMap<String, String> {
+  let mut attributes = HashMap::new();
+  let mut rest = source.trim_start();
+  while let Some(equal) = rest.find('=') {
+    let name = rest[..equal].trim().to_ascii_lowercase();
+    let value = rest[equal + 1..].trim_start();
+    let (value, next) = match value.chars().next() {
+      Some(quote) if quote == '"' || quote == '\'' => match value[1..].find(quote) {
+        Some(end) => (&value[1..end + 1], &value[end + 2..]),
+        None => (&value[1..], ""),
+      },
+      _ => {
+        let end = value.find(char::is_whitespace).unwrap_or(value.len());
+        (&value[..end], &value[end..])
+      }
+    };
+    attributes.insert(name, decode_entities(value));
+    rest = next.trim_start();
+  }
+  attributes
+}
+
+/// Decodes the character references of HTML text.
+fn decode_entities(text: &str) -> String {
+  let mut out = String::with_capacity(text.len());
+  let mut rest = text;
+  while let Some(start) = rest.find('&') {
+    out.push_str(&rest[..start]);
+    rest = &rest[start..];
+    let decoded = rest.find(';').and_then(|end| {
+      let entity = &rest[1..end];
+      let c = match entity {
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "amp" => Some('&'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some('\u{a0}'),
+        _ => match entity.strip_prefix('#') {
+          Some(code) => match code.strip_prefix('x').or_else(|| code.strip_prefix('X')) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok(),
+            None => code.parse().ok(),
+          }
+          .and_then(std::char::from_u32),
+          None => None,
+        },
+      };
+      c.map(|c| (c, end))
+    });
+    match decoded {
+      Some((c, end)) => {
+        out.push(c);
+        rest = &rest[end + 1..];
+      }
+      None => {
+        out.push('&');
+        rest = &rest[1..];
+      }
+    }
+  }
+  out.push_str(rest);
+  out
+}
@@ -0,0 +1,318 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use tempfile::TempDir;
+
+/// Returns the paths of the AVM1 disassemblies (`main.avm1.txt`) inside a directory, recursively.
+fn find_disassemblies(dir: &Path) -> Vec<PathBuf> {
+  let mut paths = Vec::new();
+  for entry in std::fs::read_dir(dir).unwrap() {
+    let path = entry.unwrap().path();
+    if path.is_dir() {
+      paths.extend(find_disassemblies(&path));
+    } else if path.file_name().unwrap() == "main.avm1.txt" {
+      paths.push(path);
+    }
+  }
+  paths
+}
+
+#[test]
+fn assemble_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+  let root_dir = TempDir::new()?;
+  let output_dir = root_dir.path().join("actions");
+
+  let mut cmd = Command::cargo_bin("ofl")?;
+  cmd.arg("dump").arg("./tests/data/actions/actions.swf").arg(&output_dir);
+  cmd.assert().success();
+
+  let disassemblies = find_disassemblies(&output_dir);
+  assert!(!disassemblies.is_empty());
+  for disassembly in disassemblies {
+    let expected = std::fs::read(disassembly.with_file_name("main.avm1"))?;
+    let mut cmd = Command::cargo_bin("ofl")?;
+    cmd.arg("avm1").arg("assemble").arg(&disassembly);
+    cmd.assert().success().stdout(expected);
+  }
+
+  Ok(())
+}
+
+#[test]
+fn disasm() -> Result<(), Box<dyn std::error::Error>> {
+  let root_dir = TempDir::new()?;
+  let output_dir = root_dir.path().join("disasm");
+
+  let mut cmd = Command::cargo_bin("ofl")?;
+  cmd.arg("dump").arg("./tests/data/disasm/disasm.swf").arg(&output_dir);
+  cmd.assert().success();
+
+  // Offsets, block labels, constant pool values and escaped strings
+  let actual = std::fs::read_to_string(output_dir.join("0").join("main.avm1.txt"))?;
+  let expected = std::fs::read_to_string("./tests/data/disasm/main.avm1.txt")?;
+  assert_eq!(actual, expected);
+
+  Ok(())
+}
+
+#[test]
+fn assemble_undefined_label() -> Result<(), Box<dyn std::error::Error>> {
+  let root_dir = TempDir::new()?;
+  let input = root_dir.path().join("main.avm1.txt");
+  std::fs::write(&input, "Push 1\nIf missing\nEnd\n")?;
+
+  let mut cmd = Command::cargo_bin("ofl")?;
+  cmd.arg("avm1").arg("assemble").arg(&input);
+  cmd
+    .assert()
+    .failure()
+    .stderr(predicate::str::contains("line 2: undefined label: missing"));
+
+  Ok(())
+}
+
+#[test]
+fn cfg_dot() -> Result<(), Box<dyn std::error::Error>> {
+  let mut cmd = Command::cargo_bin("ofl")?;
+  cmd
+    .arg("avm1")
+    .arg("cfg")
+    .arg("--format")
+    .arg("dot")
+    .arg("./tests/data/actions/main.avm1");
+  let expected = std::fs::read_to_string("./tests/data/actions/main.cfg.dot")?;
+  cmd.assert().success().stdout(expected);
+
+  Ok(())
+}
+
+#[test]
+fn deobfuscate() -> Result<(), Box<dyn std::error::Error>> {
+  let root_dir = TempDir::new()?;
+  let input = root_dir.path().join("obfuscated.avm1");
+  let output = root_dir.path().join("deobfuscated.avm1");
+
+  let mut cmd = Command::cargo_bin("ofl")?;
+  cmd
+    .arg("avm1")
+    .arg("assemble")
+    .arg("-o")
+    .arg(&input)
+    .arg("./tests/data/obfuscated/obfuscated.avm1.txt");
+  cmd.assert().success();
+
+  let mut cmd = Command::cargo_bin("ofl")?;
+  cmd.arg("avm1").arg("deobfuscate").arg(&input).arg(&output);
+  cmd.assert().success();
+
+  let mut cmd = Command::cargo_bin("ofl")?;
+  cmd
+    .arg("avm1")
+    .arg("assemble")
+    .arg("./tests/data/obfuscated/deobfuscated.avm1.txt");
+  cmd.assert().success().stdout(std::fs::read(&output)?);
+  assert!(output.with_extension("cfg.json").is_file());
+
+  Ok(())
+}
+
+#[test]
+fn call_graph() -> Result<(), Box<dyn std::error::Error>> {
+  let root_dir = TempDir::new()?;
+  let output_dir = root_dir.path().join("actions");
+
+  let mut cmd = Command::cargo_bin("ofl")?;
+  cmd.arg("dump").arg("./tests/data/actions/actions.swf").arg(&output_dir);
+  cmd.assert().success();
+
+  for name in &["avm1-callgraph.json", "avm1-callgraph.dot"] {
+    let actual = std::fs::read_to_string(output_dir.join(name))?;
+    let expected = std::fs::read_to_string(Path::new("./tests/data/actions").join(name))?;
+    assert_eq!(actual, expected);
+  }
+
+  Ok(())
+}
+
+#[test]
+fn classes() -> Result<(), Box<dyn std::error::Error>> {
+  let root_dir = TempDir::new()?;
+  let output_dir = root_dir.path().join("classes");
+
+  let mut cmd = Command::cargo_bin("ofl")?;
+  cmd.arg("dump").arg("./tests/data/classes/classes.swf").arg(&output_dir);
+  cmd
+    .assert()
+    .success()
+    .stderr(predicate::str::contains("AS2 classes: 3"));
+
+  for name in &[
+    "avm1-classes.json",
+    "classes/com/example/Circle.as",
+    "classes/com/example/IDrawable.as",
+    "classes/com/example/Shape.as",
+  ] {
+    let actual = std::fs::read_to_string(output_dir.join(name))?;
+    let expected = std::fs::read_to_string(Path::new("./tests/data/classes").join(name))?;
+    assert_eq!(actual, expected);
+  }
+
+  Ok(())
+}
+
+#[test]
+fn stats() -> Result<(), Box<dyn std::error::Error>> {
+  let root_dir = TempDir::new()?;
+  let output_dir = root_dir.path().join("actions");
+
+  let mut cmd = Command::cargo_bin("ofl")?;
+  cmd.arg("dump").arg("./tests/data/actions/actions.swf").arg(&output_dir);
+  cmd
+    .assert()
+    .success()
+    .stderr(predicate::str::contains("Max CFG depth: 1"));
+
+  let actual = std::fs::read_to_string(output_dir.join("avm1-stats.json"))?;
+  let expected = std::fs::read_to_string("./tests/data/actions/avm1-stats.json")?;
+  assert_eq!(actual, expected);
+
+  Ok(())
+}
+
+#[test]
+fn lint() -> Result<(), Box<dyn std::error::Error>> {
+  let mut cmd = Command::cargo_bin("ofl")?;
+  cmd.arg("avm1").arg("lint").arg("./tests/data/lint/lint.swf");
+  let expected = std::fs::read_to_string("./tests/data/lint/lint.txt")?;
+  cmd.assert().failure().stdout(expected);
+
+  let mut cmd = Command::cargo_bin("ofl")?;
+  cmd
+    .arg("--json")
+    .arg("avm1")
+    .arg("lint")
+    .arg("./tests/data/lint/lint.swf");
+  cmd
+    .assert()
+    .failure()
+    .stdout(predicate::str::contains("\"kind\": \"jump-into-action\""));
+
+  // Button action locations include the button id
+  let mut cmd = Command::cargo_bin("ofl")?;
+  cmd
+    .arg("avm1")
+    .arg("lint")
+    .arg("./tests/data/button-actions/button-actions.swf");
+  cmd
+    .assert()
+    .failure()
+    .stdout(predicate::str::contains("button-action:1/11#1 @0: stack-underflow"));
+
+  // Clip action locations include their events
+  let mut cmd = Command::cargo_bin("ofl")?;
+  cmd
+    .arg("avm1")
+    .arg("lint")
+    .arg("./tests/data/clip-actions/clip-actions.swf");
+  cmd.assert().failure().stdout(predicate::str::contains(
+    "clip-action:1#1(keyPress) @0: stack-underflow",
+  ));
+
+  let mut cmd = Command::cargo_bin("ofl")?;
+  cmd.arg("avm1").arg("lint").arg("./tests/data/actions/actions.swf");
+  cmd.assert().success().stdout("");
+
+  Ok(())
+}
+
+#[test]
+fn run() -> Result<(), Box<dyn std::error::Error>> {
+  let expected = std::fs::read_to_string("./tests/data/run/run.txt")?;
+
+  let mut cmd = Command::cargo_bin("ofl")?;
+  cmd
+    .arg("avm1")
+    .arg("run")
+    .arg("--max-steps")
+    .arg("1000")
+    .arg("./tests/data/run/run.swf");
+  cmd
+    .assert()
+    .success()
+    .stdout(expected)
+    .stderr(predicate::str::contains("the step limit (1000) was reached"));
+
+  let mut cmd = Command::cargo_bin("ofl")?;
+  cmd
+    .arg("--json")
+    .arg("avm1")
+    .arg("run")
+    .arg("--location")
+    .arg("do-action:0")
+    .arg("./tests/data/run/run.swf");
+  cmd
+    .assert()
+    .success()
+    .stdout(predicate::str::contains(r#""status": "completed""#))
+    .stdout(predicate::str::contains(r#""kind": "fscommand""#));
+
+  let mut cmd = Command::cargo_bin("ofl")?;
+  cmd
+    .arg("avm1")
+    .arg("run")
+    .arg("--location")
+    .arg("do-action:9")
+    .arg("./tests/data/run/run.swf");
+  cmd.assert().failure();
+
+  Ok(())
+}
+
+#[test]
+fn patch() -> Result<(), Box<dyn std::error::Error>> {
+  let root_dir = TempDir::new()?;
+  let output = root_dir.path().join("patched.swf");
+
+  let mut cmd = Command::cargo_bin("ofl")?;
+  cmd
+    .arg("avm1")
+    .arg("patch")
+    .arg("--location")
+    .arg("do-action:0/0")
+    .arg("--with")
+    .arg("./tests/data/patch/patch.avm1")
+    .arg("-o")
+    .arg(&output)
+    .arg("./tests/data/patch/patch.swf");
+  cmd.assert().success().stderr(predicate::str::contains(
+    "Replaced do-action:0/0 (12 bytes) with 13 bytes",
+  ));
+
+  // The compression method of the input is kept
+  let bytes = std::fs::read(&output)?;
+  assert_eq!(&bytes[0..3], b"CWS");
+  let mut cmd = Command::cargo_bin("ofl")?;
+  cmd.arg("decompile").arg(&output);
+  cmd
+    .assert()
+    .success()
+    .stdout(predicate::str::contains("trace(\"original\");"))
+    .stdout(predicate::str::contains("trace(\"patched\");"))
+    .stdout(predicate::str::contains("trace(\"sprite\");").not());
+
+  let mut cmd = Command::cargo_bin("ofl")?;
+  cmd
+    .arg("avm1")
+    .arg("patch")
+    .arg("--location")
+    .arg("do-action:9")
+    .arg("--with")
+    .arg("./tests/data/patch/patch.avm1")
+    .arg("-o")
+    .arg(&output)
+    .arg("./tests/data/patch/patch.swf");
+  cmd.assert().failure();
+
+  Ok(())
+}
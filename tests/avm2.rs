@@ -0,0 +1,112 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::process::Command;
+use tempfile::TempDir;
+
+#[test]
+fn disasm() -> Result<(), Box<dyn std::error::Error>> {
+  let expected = std::fs::read_to_string("./tests/data/avm2/counter.txt")?;
+
+  let mut cmd = Command::cargo_bin("ofl")?;
+  cmd.arg("avm2").arg("disasm").arg("./tests/data/avm2/counter.swf");
+  cmd
+    .assert()
+    .success()
+    .stdout(expected.clone())
+    .stderr(predicate::str::contains("ABC files: 1"));
+
+  // The disassembly written by `dump` is the same, and a raw ABC file is accepted as input
+  let root_dir = TempDir::new()?;
+  let output_dir = root_dir.path().join("counter");
+  let mut cmd = Command::cargo_bin("ofl")?;
+  cmd.arg("dump").arg("./tests/data/avm2/counter.swf").arg(&output_dir);
+  cmd.assert().success();
+  let expected = expected.trim_start_matches("; do-abc:0\n");
+  let actual = std::fs::read_to_string(output_dir.join("0").join("main.abc.txt"))?;
+  assert_eq!(actual, expected);
+
+  let mut cmd = Command::cargo_bin("ofl")?;
+  cmd.arg("avm2").arg("disasm").arg(output_dir.join("0").join("main.abc"));
+  cmd.assert().success().stdout(expected.to_string());
+
+  Ok(())
+}
+
+#[test]
+fn classes() -> Result<(), Box<dyn std::error::Error>> {
+  let expected = std::fs::read_to_string("./tests/data/avm2/classes.txt")?;
+
+  let mut cmd = Command::cargo_bin("ofl")?;
+  cmd.arg("classes").arg("./tests/data/avm2/classes.swf");
+  cmd
+    .assert()
+    .success()
+    .stdout(expected)
+    .stderr(predicate::str::contains("ABC files: 2, classes: 4, symbols: 3"));
+
+  let mut cmd = Command::cargo_bin("ofl")?;
+  cmd.arg("--json").arg("classes").arg("./tests/data/avm2/classes.swf");
+  cmd
+    .assert()
+    .success()
+    .stdout(predicate::str::contains(r#""superclass": "flash.display.Sprite""#))
+    .stdout(predicate::str::contains(r#""class": "com.example.Missing","#));
+
+  Ok(())
+}
+
+#[test]
+fn decompile() -> Result<(), Box<dyn std::error::Error>> {
+  let expected = std::fs::read_to_string("./tests/data/avm2/classes.as.txt")?;
+
+  let mut cmd = Command::cargo_bin("ofl")?;
+  cmd.arg("decompile").arg("--avm2").arg("./tests/data/avm2/classes.swf");
+  cmd
+    .assert()
+    .success()
+    .stdout(expected.clone())
+    .stderr(predicate::str::contains("ABC files: 2, source files: 4"));
+
+  // Closures, activation slots and `catch` scopes
+  let mut cmd = Command::cargo_bin("ofl")?;
+  cmd
+    .arg("decompile")
+    .arg("--avm2")
+    .arg("./tests/data/avm2/functions.swf");
+  cmd
+    .assert()
+    .success()
+    .stdout(std::fs::read_to_string("./tests/data/avm2/functions.as.txt")?);
+
+  // `dump` writes one file per class, under the directory of its package
+  let root_dir = TempDir::new()?;
+  let output_dir = root_dir.path().join("classes");
+  let mut cmd = Command::cargo_bin("ofl")?;
+  cmd.arg("dump").arg("./tests/data/avm2/classes.swf").arg(&output_dir);
+  cmd.assert().success();
+  let main = std::fs::read_to_string(output_dir.join("as3/com/example/Main.as"))?;
+  assert!(expected.contains(&main));
+  assert!(main.contains("public class Main extends Sprite implements IResettable {"));
+  assert!(output_dir.join("as3/com/example/util/Counter.as").is_file());
+  assert!(output_dir.join("as3/com/example/api/IResettable.as").is_file());
+
+  Ok(())
+}
+
+#[test]
+fn path_collisions() -> Result<(), Box<dyn std::error::Error>> {
+  let root_dir = TempDir::new()?;
+  let output_dir = root_dir.path().join("collisions");
+
+  // The classes `A:B` and `A?B` both sanitize to `A_B.as`
+  let mut cmd = Command::cargo_bin("ofl")?;
+  cmd.arg("dump").arg("./tests/data/avm2/collisions.swf").arg(&output_dir);
+  cmd
+    .assert()
+    .success()
+    .stderr(predicate::str::contains("Skipping a source file of tag 0: "));
+  let source = std::fs::read_to_string(output_dir.join("as3/A_B.as"))?;
+  assert!(source.contains("public class A:B {"));
+
+  Ok(())
+}
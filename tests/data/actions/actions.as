@@ -0,0 +1,39 @@
+// do-action:1
+if (a > 1) {
+  trace("big");
+} else {
+  trace("small");
+}
+var i = 0;
+while (i < 10) {
+  i = i + 1;
+  if (i == 5) {
+    break;
+  }
+}
+x = c ? 1 : 2;
+do {
+  n = n + 1;
+} while (n < 3);
+function square(x) {
+  return x * x;
+}
+trace(square(3));
+identity = function (y) {
+  return y;
+};
+try {
+  trace("ok");
+} catch (e) {
+  trace(e);
+} finally {
+  trace("done");
+}
+with (o) {
+  x = 1;
+}
+o.m();
+stop();
+
+// do-action:0/0
+trace("sprite");
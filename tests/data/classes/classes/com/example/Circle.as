@@ -0,0 +1,17 @@
+// do-init-action:2
+class com.example.Circle extends com.example.Shape implements com.example.IDrawable {
+  static var count = 0;
+  var radius = 1;
+
+  function Circle(radius) {
+  }
+
+  static function create(radius) {
+  }
+
+  function area() {
+  }
+
+  function scale(factor) {
+  }
+}
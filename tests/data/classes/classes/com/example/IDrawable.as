@@ -0,0 +1,3 @@
+// do-init-action:0
+interface com.example.IDrawable {
+}
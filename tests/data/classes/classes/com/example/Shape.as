@@ -0,0 +1,10 @@
+// do-init-action:1
+class com.example.Shape {
+  var name = "shape";
+
+  function Shape() {
+  }
+
+  function draw(target) {
+  }
+}
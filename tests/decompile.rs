@@ -0,0 +1,12 @@
+use assert_cmd::prelude::*;
+use std::process::Command;
+
+#[test]
+fn decompile() -> Result<(), Box<dyn std::error::Error>> {
+  let mut cmd = Command::cargo_bin("ofl")?;
+  cmd.arg("decompile").arg("./tests/data/actions/actions.swf");
+  let expected = std::fs::read_to_string("./tests/data/actions/actions.as")?;
+  cmd.assert().success().stdout(expected);
+
+  Ok(())
+}
@@ -38,3 +38,85 @@ fn file_doesnt_exist() -> Result<(), Box<dyn std::error::Error>> {
 
   Ok(())
 }
+
+#[test]
+fn abc() -> Result<(), Box<dyn std::error::Error>> {
+  let root_dir = TempDir::new()?;
+  let output_dir = root_dir.path().join("abc");
+
+  let mut cmd = Command::cargo_bin("ofl")?;
+  cmd.arg("dump").arg("./tests/data/abc/abc.swf").arg(&output_dir);
+  cmd
+    .assert()
+    .success()
+    .stderr(predicate::str::contains("ABC files found: 1"));
+
+  let swf = std::fs::read("./tests/data/abc/abc.swf")?;
+  let abc = std::fs::read(output_dir.join("0").join("main.abc"))?;
+  assert!(swf.windows(abc.len()).any(|window| window == abc.as_slice()));
+  let expected = std::fs::read_to_string("./tests/data/abc/main.abc.json")?;
+  let actual = std::fs::read_to_string(output_dir.join("0").join("main.abc.json"))?;
+  assert_eq!(actual, expected);
+
+  Ok(())
+}
+
+#[test]
+fn clip_actions() -> Result<(), Box<dyn std::error::Error>> {
+  let root_dir = TempDir::new()?;
+  let output_dir = root_dir.path().join("clip-actions");
+
+  let mut cmd = Command::cargo_bin("ofl")?;
+  cmd
+    .arg("dump")
+    .arg("./tests/data/clip-actions/clip-actions.swf")
+    .arg(&output_dir);
+  cmd
+    .assert()
+    .success()
+    .stderr(predicate::str::contains("AVM1 buffers found: 3"));
+
+  // `onClipEvent` handlers of a `PlaceObject2` tag in the root timeline and of a `PlaceObject3` tag in a sprite
+  let expected_dir = Path::new("./tests/data/clip-actions/dump");
+  for dir in &[
+    "1/clip-actions/0-load-enterFrame",
+    "1/clip-actions/1-keyPress",
+    "0/0/clip-actions/0-enterFrame",
+  ] {
+    for file in &["main.avm1", "main.cfg.json"] {
+      let expected = std::fs::read(expected_dir.join(dir).join(file))?;
+      let actual = std::fs::read(output_dir.join(dir).join(file))?;
+      assert_eq!(actual, expected, "{}/{}", dir, file);
+    }
+  }
+
+  Ok(())
+}
+
+#[test]
+fn button_actions() -> Result<(), Box<dyn std::error::Error>> {
+  let root_dir = TempDir::new()?;
+  let output_dir = root_dir.path().join("button-actions");
+
+  let mut cmd = Command::cargo_bin("ofl")?;
+  cmd
+    .arg("dump")
+    .arg("./tests/data/button-actions/button-actions.swf")
+    .arg(&output_dir);
+  cmd
+    .assert()
+    .success()
+    .stderr(predicate::str::contains("AVM1 buffers found: 3"));
+
+  // The action of a `DefineButton` tag and the condition actions of a `DefineButton2` tag
+  let expected_dir = Path::new("./tests/data/button-actions/dump");
+  for dir in &["0/actions/0", "1/actions/0", "1/actions/1"] {
+    for file in &["main.avm1", "main.cfg.json"] {
+      let expected = std::fs::read(expected_dir.join(dir).join(file))?;
+      let actual = std::fs::read(output_dir.join(dir).join(file))?;
+      assert_eq!(actual, expected, "{}/{}", dir, file);
+    }
+  }
+
+  Ok(())
+}
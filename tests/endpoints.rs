@@ -0,0 +1,29 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::process::Command;
+
+#[test]
+fn endpoints() -> Result<(), Box<dyn std::error::Error>> {
+  let expected = std::fs::read_to_string("./tests/data/endpoints/endpoints.txt")?;
+
+  let mut cmd = Command::cargo_bin("ofl")?;
+  cmd.arg("endpoints").arg("./tests/data/endpoints/endpoints.swf");
+  cmd
+    .assert()
+    .success()
+    .stdout(expected)
+    .stderr(predicate::str::contains("ABC files: 1, endpoints: 9"));
+
+  let mut cmd = Command::cargo_bin("ofl")?;
+  cmd
+    .arg("--json")
+    .arg("endpoints")
+    .arg("./tests/data/endpoints/endpoints.swf");
+  cmd
+    .assert()
+    .success()
+    .stdout(predicate::str::contains(r#""source": "concatenation""#))
+    .stdout(predicate::str::contains(r#""location": "do-abc:1""#));
+
+  Ok(())
+}
@@ -0,0 +1,37 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::process::Command;
+use tempfile::TempDir;
+
+#[test]
+fn ttf() -> Result<(), Box<dyn std::error::Error>> {
+  let root_dir = TempDir::new()?;
+  let output_dir = root_dir.path().join("fonts");
+
+  let mut cmd = Command::cargo_bin("ofl")?;
+  cmd.arg("dump").arg("./tests/data/fonts/fonts.swf").arg(&output_dir);
+  cmd
+    .assert()
+    .success()
+    .stderr(predicate::str::contains("Fonts found: 4").and(predicate::str::contains(
+      "Failed to export the font of tag 3: No glyph outlines",
+    )));
+
+  let expected = std::fs::read_to_string("./tests/data/fonts/fonts.json")?;
+  let actual = std::fs::read_to_string(output_dir.join("fonts.json"))?;
+  assert_eq!(actual, expected);
+
+  // `DefineFont3` with layout, kerning and a `DefineFontName` tag, with a glyph hole drawn in the same direction as
+  // its outline, and `DefineFont2` without layout
+  for id in 1..=2 {
+    let expected = std::fs::read(format!("./tests/data/fonts/{}.ttf", id))?;
+    let actual = std::fs::read(output_dir.join("fonts").join(format!("{}.ttf", id)))?;
+    assert!(actual == expected, "TrueType font {}", id);
+  }
+
+  // `DefineFont4`: the CFF font is written as-is
+  let actual = std::fs::read(output_dir.join("fonts").join("4.otf"))?;
+  assert_eq!(actual, b"OTTO\x00\x01\x00\x00not a real font".to_vec());
+
+  Ok(())
+}
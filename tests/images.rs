@@ -0,0 +1,93 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::process::Command;
+use tempfile::TempDir;
+
+#[test]
+fn jpeg() -> Result<(), Box<dyn std::error::Error>> {
+  let root_dir = TempDir::new()?;
+  let output_dir = root_dir.path().join("images");
+
+  let mut cmd = Command::cargo_bin("ofl")?;
+  cmd.arg("dump").arg("./tests/data/images/images.swf").arg(&output_dir);
+  cmd
+    .assert()
+    .success()
+    .stderr(predicate::str::contains("Images found: 12"));
+
+  let expected = std::fs::read_to_string("./tests/data/images/images.json")?;
+  let actual = std::fs::read_to_string(output_dir.join("images.json"))?;
+  assert_eq!(actual, expected);
+
+  // `DefineBitsJpeg2` images are written as-is
+  let swf = std::fs::read("./tests/data/images/images.swf")?;
+  let jpeg2 = std::fs::read(output_dir.join("images").join("2.jpg"))?;
+  assert!(swf.windows(jpeg2.len()).any(|window| window == jpeg2.as_slice()));
+
+  // `DefineBits` images are merged with the JPEG tables, without the erroneous header
+  let jpeg = std::fs::read(output_dir.join("images").join("1.jpg"))?;
+  assert!(jpeg.starts_with(&[0xff, 0xd8, 0xff, 0xdb]));
+  assert!(jpeg.ends_with(&[0xff, 0xd9]));
+  assert!(!jpeg.windows(4).any(|window| window == [0xff, 0xd9, 0xff, 0xd8]));
+  assert_eq!(jpeg.windows(2).filter(|window| *window == [0xff, 0xd8]).count(), 1);
+
+  // `DefineBitsJpeg3` images are combined with their alpha channel
+  let decoder = png::Decoder::new(std::fs::File::open(output_dir.join("images").join("3.png"))?);
+  let (info, mut reader) = decoder.read_info()?;
+  assert_eq!(
+    (info.width, info.height, info.color_type),
+    (16, 16, png::ColorType::RGBA)
+  );
+  let mut pixels = vec![0; info.buffer_size()];
+  reader.next_frame(&mut pixels)?;
+  let alpha: Vec<u8> = pixels.chunks(4).map(|pixel| pixel[3]).collect();
+  let expected_alpha: Vec<u8> = (0..16 * 16).map(|i| (i % 16) as u8 * 16).collect();
+  assert_eq!(alpha, expected_alpha);
+  assert_eq!(&pixels[0..4], &[0, 0, 0, 0]);
+
+  Ok(())
+}
+
+#[test]
+fn lossless() -> Result<(), Box<dyn std::error::Error>> {
+  let root_dir = TempDir::new()?;
+  let output_dir = root_dir.path().join("images");
+
+  let mut cmd = Command::cargo_bin("ofl")?;
+  cmd.arg("dump").arg("./tests/data/images/images.swf").arg(&output_dir);
+  cmd
+    .assert()
+    .success()
+    .stderr(predicate::str::contains(
+      "Failed to export the image of tag 11: Empty bitmap: 0x2",
+    ))
+    .stderr(predicate::str::contains(
+      "Failed to export the image of tag 12: Empty bitmap: 0x1",
+    ));
+
+  // Colormapped, 15-bit and 24-bit `DefineBitsLossless` images, colormapped and 32-bit `DefineBitsLossless2` images
+  for id in 6..=10 {
+    let decoder = png::Decoder::new(std::fs::File::open(
+      output_dir.join("images").join(format!("{}.png", id)),
+    )?);
+    let (info, mut reader) = decoder.read_info()?;
+    let mut pixels = vec![0; info.buffer_size()];
+    reader.next_frame(&mut pixels)?;
+    let rgba: Vec<u8> = match info.color_type {
+      png::ColorType::RGBA => pixels,
+      png::ColorType::RGB => pixels
+        .chunks(3)
+        .flat_map(|pixel| vec![pixel[0], pixel[1], pixel[2], 255])
+        .collect(),
+      color_type => panic!("Unexpected color type: {:?}", color_type),
+    };
+    let expected = std::fs::read(format!("./tests/data/images/{}.rgba", id))?;
+    assert_eq!(rgba, expected, "pixels of image {}", id);
+  }
+
+  // Zero-width colormapped and 15-bit images are skipped
+  assert!(!output_dir.join("images").join("11.png").exists());
+  assert!(!output_dir.join("images").join("12.png").exists());
+
+  Ok(())
+}
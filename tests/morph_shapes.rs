@@ -0,0 +1,55 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::process::Command;
+use tempfile::TempDir;
+
+#[test]
+fn svg() -> Result<(), Box<dyn std::error::Error>> {
+  let root_dir = TempDir::new()?;
+  let output_dir = root_dir.path().join("morph");
+
+  let mut cmd = Command::cargo_bin("ofl")?;
+  cmd
+    .arg("dump")
+    .arg("./tests/data/morph-shapes/morph.swf")
+    .arg(&output_dir)
+    .arg("--morph-ratio")
+    .arg("0.5")
+    .arg("--morph-animation");
+  cmd
+    .assert()
+    .success()
+    .stderr(predicate::str::contains("Morph shapes found: 2"));
+
+  // `DefineMorphShape`: solid fill and line style changes, straight edge paired with a curved edge
+  // `DefineMorphShape2`: gradient stops and matrix changes, `LineStyle2` caps
+  for id in 1..=2 {
+    for suffix in &["start", "end", "0.5", "animated"] {
+      let name = format!("{}-{}.svg", id, suffix);
+      let expected = std::fs::read_to_string(format!("./tests/data/morph-shapes/{}", name))?;
+      let actual = std::fs::read_to_string(output_dir.join("morph-shapes").join(&name))?;
+      assert_eq!(actual, expected, "SVG {}", name);
+    }
+  }
+
+  Ok(())
+}
+
+#[test]
+fn invalid_ratio() -> Result<(), Box<dyn std::error::Error>> {
+  let root_dir = TempDir::new()?;
+
+  let mut cmd = Command::cargo_bin("ofl")?;
+  cmd
+    .arg("dump")
+    .arg("./tests/data/morph-shapes/morph.swf")
+    .arg(root_dir.path().join("morph"))
+    .arg("--morph-ratio")
+    .arg("1.5");
+  cmd
+    .assert()
+    .failure()
+    .stderr(predicate::str::contains("Invalid morph ratio"));
+
+  Ok(())
+}
@@ -0,0 +1,28 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::process::Command;
+use tempfile::TempDir;
+
+#[test]
+fn svg() -> Result<(), Box<dyn std::error::Error>> {
+  let root_dir = TempDir::new()?;
+  let output_dir = root_dir.path().join("shapes");
+
+  let mut cmd = Command::cargo_bin("ofl")?;
+  cmd.arg("dump").arg("./tests/data/shapes/shapes.swf").arg(&output_dir);
+  cmd
+    .assert()
+    .success()
+    .stderr(predicate::str::contains("Shapes found: 4"));
+
+  // `DefineShape` to `DefineShape4`: solid fills and lines with curves, bitmap fills, gradients with new styles in
+  // the middle of the shape, focal gradients and `LineStyle2` caps and joins
+  for id in 2..=5 {
+    let expected = std::fs::read_to_string(format!("./tests/data/shapes/{}.svg", id))?;
+    let actual = std::fs::read_to_string(output_dir.join("shapes").join(format!("{}.svg", id)))?;
+    assert_eq!(actual, expected, "SVG of shape {}", id);
+  }
+  assert!(output_dir.join("images").join("1.png").is_file());
+
+  Ok(())
+}
@@ -0,0 +1,45 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::process::Command;
+use tempfile::TempDir;
+
+#[test]
+fn sounds() -> Result<(), Box<dyn std::error::Error>> {
+  let root_dir = TempDir::new()?;
+  let output_dir = root_dir.path().join("sounds");
+
+  let mut cmd = Command::cargo_bin("ofl")?;
+  cmd.arg("dump").arg("./tests/data/sounds/sounds.swf").arg(&output_dir);
+  cmd
+    .assert()
+    .success()
+    .stderr(predicate::str::contains("Sounds found: 6"));
+
+  let expected = std::fs::read_to_string("./tests/data/sounds/sounds.json")?;
+  let actual = std::fs::read_to_string(output_dir.join("sounds.json"))?;
+  assert_eq!(actual, expected);
+
+  // MP3 without the seek samples prefix, 16-bit stereo little-endian PCM and 8-bit mono native-endian PCM (with an odd
+  // size) as WAV files
+  for name in &["1.mp3", "2.wav", "3.wav"] {
+    let expected = std::fs::read(format!("./tests/data/sounds/{}", name))?;
+    let actual = std::fs::read(output_dir.join("sounds").join(name))?;
+    assert!(actual == expected, "Sound {}", name);
+  }
+
+  // ADPCM, Nellymoser and Speex data is written as-is
+  assert_eq!(
+    std::fs::read(output_dir.join("sounds").join("4.adpcm"))?,
+    (0..64).collect::<Vec<u8>>()
+  );
+  assert_eq!(
+    std::fs::read(output_dir.join("sounds").join("5.nellymoser"))?,
+    vec![0; 64]
+  );
+  assert_eq!(
+    std::fs::read(output_dir.join("sounds").join("6.speex"))?,
+    b"speex frame".to_vec()
+  );
+
+  Ok(())
+}
@@ -0,0 +1,26 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::process::Command;
+use tempfile::TempDir;
+
+#[test]
+fn texts() -> Result<(), Box<dyn std::error::Error>> {
+  let root_dir = TempDir::new()?;
+  let output_dir = root_dir.path().join("texts");
+
+  let mut cmd = Command::cargo_bin("ofl")?;
+  cmd.arg("dump").arg("./tests/data/texts/texts.swf").arg(&output_dir);
+  cmd
+    .assert()
+    .success()
+    .stderr(predicate::str::contains("Texts found: 5"));
+
+  // `DefineText` with style changes in the middle of a line, a `DefineFont` font with its `DefineFontInfo` code table
+  // and an unknown glyph, `DefineText2` with a translucent color, and `DefineEditText` with plain text, HTML text (with
+  // a relative font size too large for twips) and no text
+  let expected = std::fs::read_to_string("./tests/data/texts/texts.json")?;
+  let actual = std::fs::read_to_string(output_dir.join("texts.json"))?;
+  assert_eq!(actual, expected);
+
+  Ok(())
+}